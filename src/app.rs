@@ -3,20 +3,25 @@
 use std::io;
 
 use crate::event::{EventHandler, TerminalEvent};
+use crate::network::chat::ChatHistory;
+use crate::network::ghosts::GhostTeams;
 use crate::network::handler::NetworkHandler;
-use crate::store::{get_world_size, reset, save_world};
+use crate::network::network_callback::NetworkCallbackPreset;
+use crate::network::protocol::NetworkEvent;
+use crate::network::results::{GameResult, GameResults};
+use crate::store::{
+    get_world_size, load_or_generate_keypair, reset, save_world, PERSISTED_KEYPAIR_FILENAME,
+};
 use crate::tui::Tui;
-use crate::types::{AppResult, SystemTimeTick, Tick};
+use crate::types::{AppResult, SystemTimeTick, TeamId, Tick};
 use crate::ui::ui::Ui;
 use crate::ui::utils::SwarmPanelEvent;
 use crate::world::world::World;
 use crossterm::event::{KeyCode, KeyModifiers};
 use futures::StreamExt;
-use libp2p::{gossipsub, swarm::SwarmEvent};
 use ratatui::backend::CrosstermBackend;
 use ratatui::Terminal;
 use tokio::select;
-use void::Void;
 
 pub struct App {
     pub world: World,
@@ -24,6 +29,10 @@ pub struct App {
     pub ui: Ui,
     generate_local_world: bool,
     pub network_handler: Option<NetworkHandler>,
+    // Set when the network could not start, so that we do not retry every loop.
+    network_failed: bool,
+    pub ghosts: GhostTeams,
+    pub game_results: GameResults,
     seed_ip: Option<String>,
 }
 
@@ -47,6 +56,9 @@ impl App {
             ui,
             generate_local_world,
             network_handler: None,
+            network_failed: false,
+            ghosts: GhostTeams::default(),
+            game_results: GameResults::default(),
             seed_ip,
         }
     }
@@ -59,7 +71,7 @@ impl App {
         let mut ratatui = Tui::new(terminal, events);
         ratatui.init()?;
         while self.running {
-            if self.network_handler.is_none() && (self.world.has_own_team()) {
+            if self.network_handler.is_none() && !self.network_failed && self.world.has_own_team() {
                 self.initialize_network_handler(self.seed_ip.clone());
             }
             //FIXME consolidate this into a single select! macro
@@ -96,11 +108,22 @@ impl App {
     }
 
     pub fn initialize_network_handler(&mut self, seed_ip: Option<String>) {
-        let handler = NetworkHandler::new(seed_ip);
-        if handler.is_err() {
-            eprintln!("Failed to initialize network handler");
-        } else {
-            self.network_handler = Some(handler.unwrap());
+        let handler = load_or_generate_keypair(PERSISTED_KEYPAIR_FILENAME)
+            .map_err(|e| format!("Failed to load network keypair: {}", e))
+            .and_then(|keypair| {
+                NetworkHandler::new(seed_ip, keypair)
+                    .map_err(|e| format!("Failed to initialize network handler: {}", e))
+            });
+        match handler {
+            Ok(handler) => self.network_handler = Some(handler),
+            Err(text) => {
+                self.network_failed = true;
+                self.ui.swarm_panel.push_log_event(SwarmPanelEvent {
+                    timestamp: Tick::now(),
+                    peer_id: None,
+                    text,
+                });
+            }
         }
     }
 
@@ -136,6 +159,17 @@ impl App {
             }
         }
         self.world.serialized_size = get_world_size().expect("Failed to get world size");
+
+        if let Ok(chat) = ChatHistory::load() {
+            self.ui.swarm_panel.set_chat_history(chat);
+        }
+        if let Ok(ghosts) = GhostTeams::load() {
+            self.ghosts = ghosts;
+            self.ui.swarm_panel.set_ghost_teams(self.ghosts.names());
+        }
+        if let Ok(game_results) = GameResults::load() {
+            self.game_results = game_results;
+        }
     }
 
     /// Set running to false to quit the application.
@@ -144,6 +178,9 @@ impl App {
         // save world and backup
         if self.world.has_own_team() {
             save_world(&self.world, true)?;
+            self.ui.swarm_panel.save_chat_history()?;
+            self.ghosts.save()?;
+            self.game_results.save()?;
         }
         Ok(())
     }
@@ -201,6 +238,104 @@ impl App {
             });
         }
 
+        // Only written when there are new messages.
+        if self.world.has_own_team() {
+            if let Err(e) = self.ui.swarm_panel.save_chat_history() {
+                self.ui.swarm_panel.push_log_event(SwarmPanelEvent {
+                    timestamp: Tick::now(),
+                    peer_id: None,
+                    text: format!("Failed to save chat: {}", e),
+                });
+            }
+            if let Err(e) = self.ghosts.save() {
+                self.ui.swarm_panel.push_log_event(SwarmPanelEvent {
+                    timestamp: Tick::now(),
+                    peer_id: None,
+                    text: format!("Failed to save ghost teams: {}", e),
+                });
+            }
+            if let Err(e) = self.game_results.save() {
+                self.ui.swarm_panel.push_log_event(SwarmPanelEvent {
+                    timestamp: Tick::now(),
+                    peer_id: None,
+                    text: format!("Failed to save game results: {}", e),
+                });
+            }
+        }
+
+        self.handle_network_tick(current_timestamp);
+
+        Ok(())
+    }
+
+    // Network upkeep that must run at every tick, even without a terminal.
+    pub fn handle_network_tick(&mut self, current_timestamp: Tick) {
+        // Retransmit and expire pending challenges.
+        if let Some(network_handler) = self.network_handler.as_mut() {
+            let events = network_handler.challenges.tick(current_timestamp);
+            if let Err(e) = NetworkCallbackPreset::handle_challenge_events(self, events) {
+                self.ui.set_popup(crate::ui::ui::PopupMessage::Error(
+                    format!("Challenge error\n{}", e),
+                    Tick::now(),
+                ));
+            }
+        }
+
+        // Games against ghosts end in the world tick, their results wait for the ghost owners.
+        for peer_id in self.ghosts.collect_results(&self.world) {
+            if let Err(e) = NetworkCallbackPreset::finish_ghost_game(self, peer_id) {
+                self.ui.swarm_panel.push_log_event(SwarmPanelEvent {
+                    timestamp: Tick::now(),
+                    peer_id: Some(peer_id),
+                    text: format!("Failed to send ghost game result: {}", e),
+                });
+            }
+        }
+
+        // Games between peers are signed by both teams once they end.
+        if let (Some(network_handler), Ok(own_team)) =
+            (self.network_handler.as_ref(), self.world.get_own_team())
+        {
+            if let Some(game) = own_team
+                .current_game
+                .and_then(|game_id| self.world.get_game(game_id))
+                .filter(|game| !self.ghosts.is_ghost_game(&game.id))
+            {
+                if let Some(result) =
+                    GameResult::from_game(game, own_team.id, *network_handler.swarm.local_peer_id())
+                {
+                    self.game_results.track(result);
+                }
+            }
+        }
+        // Ended games wait for the network handler to be signed.
+        if self.network_handler.is_some() {
+            for result in self.game_results.finished(&self.world) {
+                if let Err(e) = NetworkCallbackPreset::finish_network_game(self, result) {
+                    self.ui.swarm_panel.push_log_event(SwarmPanelEvent {
+                        timestamp: Tick::now(),
+                        peer_id: None,
+                        text: format!("Failed to sign game result: {}", e),
+                    });
+                }
+            }
+        }
+        // Our own team is bound to us, the others to the peers that sent them.
+        if let Some(network_handler) = self.network_handler.as_ref() {
+            let own_team_id = self.world.own_team_id;
+            let local_peer_id = *network_handler.swarm.local_peer_id();
+            let team_peer_id = |team_id: &TeamId| {
+                if *team_id == own_team_id {
+                    Some(local_peer_id)
+                } else {
+                    network_handler.validator.team_peer_id(team_id)
+                }
+            };
+            if let Some(ladder) = self.game_results.take_ladder(team_peer_id) {
+                self.ui.team_panel.set_ladder(ladder);
+            }
+        }
+
         // Send own team to peers if dirty
         if self.world.dirty_network && self.world.has_own_team() {
             self.world.dirty_network = false;
@@ -220,8 +355,6 @@ impl App {
                 }
             }
         }
-
-        Ok(())
     }
 
     pub fn handle_key_events(&mut self, key_event: crossterm::event::KeyEvent) -> AppResult<()> {
@@ -278,10 +411,7 @@ impl App {
         Ok(())
     }
 
-    pub fn handle_network_events(
-        &mut self,
-        network_event: SwarmEvent<gossipsub::Event, Void>,
-    ) -> AppResult<()> {
+    pub fn handle_network_events(&mut self, network_event: NetworkEvent) -> AppResult<()> {
         if let Some(network_handler) = &mut self.network_handler {
             if let Some(callback) = network_handler.handle_network_events(network_event) {
                 match callback.call(self) {
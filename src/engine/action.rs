@@ -1,5 +1,6 @@
 use super::{
     end_of_quarter::EndOfQuarter,
+    free_throw::FreeThrows,
     game::Game,
     isolation::Isolation,
     jump_ball::JumpBall,
@@ -11,6 +12,7 @@ use super::{
     start_of_quarter::StartOfQuarter,
     substitution::Substitution,
     timer::Timer,
+    transition::{FastBreak, OutletPass, SecondaryBreak},
     types::{GameStatsMap, Possession},
 };
 use core::fmt::Debug;
@@ -42,6 +44,7 @@ pub enum ActionSituation {
     CloseShot,
     MediumShot,
     LongShot,
+    Transition,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
@@ -59,12 +62,25 @@ pub struct ActionOutput {
     pub defense_stats_update: Option<GameStatsMap>,
     pub foul_from: Option<usize>,
     pub foul_on: Option<usize>,
+    #[serde(default)]
+    pub free_throws: u8,
+    #[serde(default)]
+    pub dunk: bool,
     pub home_score: u16,
     pub away_score: u16,
     pub score_change: u8,
     pub possession: Possession,
 }
 
+impl ActionOutput {
+    pub fn has_injury(&self) -> bool {
+        [&self.attack_stats_update, &self.defense_stats_update]
+            .iter()
+            .filter_map(|update| update.as_ref())
+            .any(|update| update.values().any(|stats| stats.injury.is_some()))
+    }
+}
+
 #[derive(Debug, Serialize_repr, Deserialize_repr, Clone, Default)]
 #[repr(u8)]
 pub enum Action {
@@ -81,6 +97,10 @@ pub enum Action {
     MediumShot,
     LongShot,
     Substitution,
+    FreeThrows,
+    OutletPass,
+    FastBreak,
+    SecondaryBreak,
 }
 
 impl Action {
@@ -98,6 +118,10 @@ impl Action {
             Action::MediumShot => "Medium Shot".into(),
             Action::LongShot => "Long Shot".into(),
             Action::Substitution => "Substitution".into(),
+            Action::FreeThrows => "Free Throws".into(),
+            Action::OutletPass => "Outlet Pass".into(),
+            Action::FastBreak => "Fast Break".into(),
+            Action::SecondaryBreak => "Secondary Break".into(),
         }
     }
     pub fn execute(
@@ -119,6 +143,10 @@ impl Action {
             Action::MediumShot => MediumShot.execute(input, game, rng),
             Action::LongShot => LongShot.execute(input, game, rng),
             Action::Substitution => Substitution.execute(input, game, rng),
+            Action::FreeThrows => FreeThrows.execute(input, game, rng),
+            Action::OutletPass => OutletPass.execute(input, game, rng),
+            Action::FastBreak => FastBreak.execute(input, game, rng),
+            Action::SecondaryBreak => SecondaryBreak.execute(input, game, rng),
         };
         if output.is_some() {
             output.as_mut().unwrap().random_seed = rng.get_seed();
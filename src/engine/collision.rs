@@ -0,0 +1,65 @@
+use super::{
+    constants::MAX_TIREDNESS,
+    types::{GameStats, GameStatsMap},
+};
+use crate::world::{
+    injury::{Injury, InjuryKind},
+    player::Player,
+    skill::MAX_SKILL,
+};
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+pub const POST_INJURIES: [InjuryKind; 4] = [
+    InjuryKind::Bruise,
+    InjuryKind::BrokenNose,
+    InjuryKind::Concussion,
+    InjuryKind::HamstringStrain,
+];
+pub const REBOUND_INJURIES: [InjuryKind; 4] = [
+    InjuryKind::Bruise,
+    InjuryKind::BrokenNose,
+    InjuryKind::AnkleSprain,
+    InjuryKind::KneeSprain,
+];
+pub const SHOT_INJURIES: [InjuryKind; 4] = [
+    InjuryKind::Bruise,
+    InjuryKind::AnkleSprain,
+    InjuryKind::HamstringStrain,
+    InjuryKind::KneeSprain,
+];
+
+// Players colliding might get injured. Tired players get hurt more easily,
+// strong players resist better.
+pub fn collision_injury(
+    rng: &mut ChaCha8Rng,
+    player: &Player,
+    player_stats: &GameStats,
+    chance: f32,
+    kinds: &[InjuryKind],
+) -> Option<Injury> {
+    let tiredness_factor = 1.0 + player_stats.tiredness / MAX_TIREDNESS;
+    let strength_factor = 1.0 - player.athleticism.strength / (2.0 * MAX_SKILL);
+    if rng.gen_range(0.0..100.0) < chance * tiredness_factor * strength_factor {
+        return Injury::random(rng, kinds);
+    }
+    None
+}
+
+// Adds the injury to the player update, merging with any update already there.
+// The player is knocked out and has to leave the game.
+pub fn add_injury(
+    stats_update: &mut GameStatsMap,
+    player: &Player,
+    injury: Injury,
+    opponent: &Player,
+) -> String {
+    let update = stats_update.entry(player.id).or_default();
+    update.injury = Some(injury);
+    update.knocked_out_by = Some(opponent.id);
+    format!(
+        " {} is hurt ({}) and has to leave the game.",
+        player.info.last_name,
+        injury.to_string().to_lowercase()
+    )
+}
@@ -0,0 +1,72 @@
+use super::tactic::{DefenseTactic, OffenseTactic};
+use crate::types::{GameId, PlayerId, TeamId};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum GameCommandKind {
+    SetOffenseTactic {
+        tactic: OffenseTactic,
+    },
+    SetDefenseTactic {
+        tactic: DefenseTactic,
+    },
+    Timeout,
+    Substitution {
+        player_in: PlayerId,
+        player_out: PlayerId,
+    },
+}
+
+impl GameCommandKind {
+    // Timeouts and substitutions wait for the ball to be dead,
+    // tactic changes are applied right away.
+    pub fn needs_stoppage(&self) -> bool {
+        matches!(self, Self::Timeout | Self::Substitution { .. })
+    }
+}
+
+impl Display for GameCommandKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SetOffenseTactic { tactic } => write!(f, "Offense tactic set to {}", tactic),
+            Self::SetDefenseTactic { tactic } => write!(f, "Defense tactic set to {}", tactic),
+            Self::Timeout => write!(f, "Timeout requested"),
+            Self::Substitution { .. } => write!(f, "Substitution requested"),
+        }
+    }
+}
+
+// A coaching command issued while the game is running. Commands are scheduled
+// at a future timer value, so that every peer simulating the game applies them
+// at the same moment.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GameCommand {
+    pub game_id: GameId,
+    pub team_id: TeamId,
+    // Orders commands of the same team scheduled at the same time.
+    pub sequence: u16,
+    pub apply_at: u16,
+    pub kind: GameCommandKind,
+    #[serde(default)]
+    pub applied: bool,
+}
+
+impl GameCommand {
+    pub fn new(
+        game_id: GameId,
+        team_id: TeamId,
+        sequence: u16,
+        apply_at: u16,
+        kind: GameCommandKind,
+    ) -> Self {
+        Self {
+            game_id,
+            team_id,
+            sequence,
+            apply_at,
+            kind,
+            applied: false,
+        }
+    }
+}
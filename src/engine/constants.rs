@@ -1,3 +1,7 @@
+// Bump whenever a change makes games play out differently,
+// peers only play each other with the same engine version.
+pub const ENGINE_VERSION: u16 = 2;
+
 pub const ADV_ATTACK_LIMIT: i16 = 15;
 pub const ADV_NEUTRAL_LIMIT: i16 = 0;
 pub const ADV_DEFENSE_LIMIT: i16 = -20;
@@ -17,8 +21,76 @@ impl TirednessCost {
     pub const HIGH: f32 = 2.5;
 }
 
+// Base chance (in percent) that the defender commits a foul.
+pub struct FoulChance;
+impl FoulChance {
+    pub const ISOLATION: f32 = 8.0;
+    pub const POST: f32 = 12.0;
+    pub const CLOSE_SHOT: f32 = 10.0;
+    pub const MEDIUM_SHOT: f32 = 5.0;
+    pub const LONG_SHOT: f32 = 3.0;
+    pub const FAST_BREAK: f32 = 6.0;
+}
+
+// Base chance (in percent) that the offense runs in transition instead of setting up.
+pub struct TransitionChance;
+impl TransitionChance {
+    pub const AFTER_REBOUND: f32 = 30.0;
+    pub const AFTER_TURNOVER: f32 = 50.0;
+}
+
+// Relative weights of pushing the break after an outlet pass or waiting for the trailer.
+pub struct TransitionWeight;
+impl TransitionWeight {
+    pub const FAST_BREAK: u32 = 3;
+    pub const SECONDARY_BREAK: u32 = 2;
+}
+
+// Base chance (in percent) that a player gets injured in a collision.
+pub struct InjuryChance;
+impl InjuryChance {
+    pub const POST: f32 = 0.06;
+    pub const REBOUND: f32 = 0.04;
+    pub const CLOSE_SHOT: f32 = 0.05;
+}
+
 pub const MAX_TIREDNESS: f32 = 100.0;
 pub const RECOVERING_TIREDNESS_PER_SHORT_TICK: f32 = 0.05;
 pub const MIN_TIREDNESS_FOR_SUB: f32 = 50.0;
 
+// Recovery slows down the more tired a player is.
+pub struct RecoveryPhase;
+impl RecoveryPhase {
+    pub const FRESH: f32 = 1.5; // below 50% tiredness
+    pub const TIRED: f32 = 0.75; // below 75% tiredness
+    pub const EXHAUSTED: f32 = 0.4; // up to the limit
+}
+
+// Momentum gained (and lost by the other team) on swing events.
+pub struct MomentumSwing;
+impl MomentumSwing {
+    pub const SCORE: u8 = 1;
+    pub const DUNK: u8 = 1;
+    pub const RUN: u8 = 2;
+    pub const BLOCK: u8 = 1;
+    pub const STEAL: u8 = 2;
+}
+
+pub const MAX_MOMENTUM: u8 = 10;
+// Unanswered points after which each basket gives extra momentum.
+pub const MOMENTUM_RUN_POINTS: u16 = 8;
+
+pub const MAX_MORALE: f32 = 20.0;
+pub const DEFAULT_MORALE: f32 = 10.0;
+
+pub const MAX_TIMEOUTS: u8 = 3;
+pub const TIMEOUT_TIREDNESS_RECOVERY: f32 = 5.0;
+// Game seconds between issuing a coaching command and applying it,
+// so that it can reach the other peers in time.
+pub const COMMAND_DELAY: u16 = 10;
+
+pub const MAX_PERSONAL_FOULS: u8 = 5;
+pub const TEAM_FOULS_FOR_BONUS: u8 = 4;
+pub const FREE_THROW_DIFFICULTY: i16 = 22;
+
 pub const BASE_ATTENDANCE: u32 = 60;
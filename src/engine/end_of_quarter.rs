@@ -21,7 +21,7 @@ impl EndOfQuarter {
             Period::Q1 => format!("It's the end of the first quarter.",),
             Period::Q2 => format!("It's the end of the second quarter. Halftime!",),
             Period::Q3 => format!("It's the end of the third quarter.",),
-            Period::Q4 => match input.home_score as  i16 - input.away_score as i16 {
+            Period::Q4 | Period::Overtime(_) => match input.home_score as i16 - input.away_score as i16 {
                 x if x > 0 => format!(
                     "It's the end of the game. {} won this nice game over {}. The final score is {} {}-{} {}.",
                     game.home_team_in_game.name,
@@ -41,13 +41,11 @@ impl EndOfQuarter {
                     game.away_team_in_game.name,
                 ),
                 x if x == 0 => format!(
-                    "It's a tie between {} and {}. The final score is {} {}-{} {}.",
+                    "It's a tie between {} and {} at {}-{}. We are going to overtime!",
                     game.home_team_in_game.name,
                     game.away_team_in_game.name,
-                    game.home_team_in_game.name,
                     input.home_score,
                     input.away_score,
-                    game.away_team_in_game.name,
                 ),
                 _ => panic!("Invalid score"),
             },
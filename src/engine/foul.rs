@@ -0,0 +1,133 @@
+use super::{
+    action::{ActionOutput, ActionSituation},
+    constants::{MAX_PERSONAL_FOULS, MAX_TIREDNESS, TEAM_FOULS_FOR_BONUS},
+    game::Game,
+    types::{GameStats, GameStatsMap},
+};
+use crate::world::{
+    player::Player,
+    skill::{GameSkill, MAX_SKILL},
+};
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+// Returns true if the defender commits a foul.
+// The base chance (in percent) grows with tiredness and shrinks with
+// the defender's positioning and the defensive skill relevant to the play.
+pub fn is_foul(
+    rng: &mut ChaCha8Rng,
+    defender: &Player,
+    defender_stats: &GameStats,
+    defense_skill: u8,
+    base_chance: f32,
+) -> bool {
+    let discipline = (defender.mental.positioning.value() + defense_skill) as f32 / 2.0;
+    let chance = base_chance
+        * (1.0 + defender_stats.tiredness / MAX_TIREDNESS)
+        * (1.5 - discipline / MAX_SKILL);
+    rng.gen_range(0.0..100.0) < chance
+}
+
+// Commentary to append when a foul sends the defender out of the game.
+pub fn foul_out_description(defender: &Player, defender_stats: &GameStats) -> Option<String> {
+    if defender_stats.fouls + defender_stats.technical_fouls + 1 >= MAX_PERSONAL_FOULS {
+        Some(format!(
+            " That's {} {}th foul, {} has fouled out!",
+            defender.info.pronouns.as_possessive(),
+            MAX_PERSONAL_FOULS,
+            defender.info.last_name
+        ))
+    } else {
+        None
+    }
+}
+
+// Adds a personal foul to the defender update, merging with any update already there.
+pub fn add_foul(defense_stats_update: &mut GameStatsMap, defender: &Player) {
+    defense_stats_update.entry(defender.id).or_default().fouls += 1;
+}
+
+// Builds the output of a foul committed away from a shot.
+// If the defending team is in the bonus, the attacker goes to the line for two free throws,
+// otherwise the ball is inbounded from the side.
+pub fn non_shooting_foul(
+    input: &ActionOutput,
+    game: &Game,
+    attacker_idx: usize,
+    defender_idx: usize,
+    attacker: &Player,
+    defender: &Player,
+    defender_stats: &GameStats,
+) -> ActionOutput {
+    let in_bonus = game.defending_team_fouls() + 1 > TEAM_FOULS_FOR_BONUS;
+    let mut description = format!(
+        "{} fouls {} on the way to the basket.",
+        defender.info.last_name, attacker.info.last_name
+    );
+    if let Some(foul_out) = foul_out_description(defender, defender_stats) {
+        description.push_str(foul_out.as_str());
+    }
+
+    let (situation, attackers, free_throws) = if in_bonus {
+        description.push_str(
+            format!(
+                " The team is in the bonus, {} goes to the line.",
+                attacker.info.last_name
+            )
+            .as_str(),
+        );
+        (ActionSituation::FreeThrow, vec![attacker_idx], 2)
+    } else {
+        (ActionSituation::BallInMidcourt, vec![], 0)
+    };
+
+    ActionOutput {
+        possession: input.possession.clone(),
+        attackers,
+        situation,
+        free_throws,
+        foul_from: Some(defender_idx),
+        foul_on: Some(attacker_idx),
+        description,
+        start_at: input.end_at,
+        end_at: input.end_at.plus(2),
+        home_score: input.home_score,
+        away_score: input.away_score,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_foul;
+    use crate::{
+        engine::types::GameStats,
+        types::{IdSystem, PlayerId},
+        world::{constants::DEFAULT_PLANET_ID, player::Player, world::World},
+    };
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn test_tired_defenders_foul_more() {
+        let world = World::new(None);
+        let rng = &mut ChaCha8Rng::seed_from_u64(0);
+        let planet = world.get_planet(DEFAULT_PLANET_ID.clone()).unwrap();
+        let mut defender = Player::random(rng, PlayerId::new(), Some(0), planet, 0.0);
+        defender.mental.positioning = 10.0;
+
+        let rested = GameStats::default();
+        let mut tired = GameStats::default();
+        tired.tiredness = 90.0;
+
+        let count = |stats: &GameStats, rng: &mut ChaCha8Rng| {
+            (0..1000)
+                .filter(|_| is_foul(rng, &defender, stats, 10, 10.0))
+                .count()
+        };
+        let rested_fouls = count(&rested, rng);
+        let tired_fouls = count(&tired, rng);
+        assert!(rested_fouls > 0);
+        assert!(tired_fouls > rested_fouls);
+    }
+}
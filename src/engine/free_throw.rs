@@ -0,0 +1,112 @@
+use super::{
+    action::{ActionOutput, ActionSituation, Advantage},
+    constants::{TirednessCost, FREE_THROW_DIFFICULTY},
+    game::Game,
+    types::{GameStats, Possession},
+    utils::roll,
+};
+use crate::world::skill::GameSkill;
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use std::collections::HashMap;
+
+#[derive(Debug, Default)]
+pub struct FreeThrows;
+
+impl FreeThrows {
+    pub fn execute(
+        &self,
+        input: &ActionOutput,
+        game: &Game,
+        rng: &mut ChaCha8Rng,
+    ) -> Option<ActionOutput> {
+        let attacking_players = game.attacking_players();
+        let attacking_stats = game.attacking_stats();
+
+        assert!(input.attackers.len() == 1);
+        let shooter_idx = input.attackers[0];
+        let shooter = attacking_players[shooter_idx];
+        let shooter_stats = attacking_stats.get(&shooter.id)?;
+
+        let attempted = input.free_throws.max(1);
+        let mut made = 0;
+        let mut last_made = false;
+        for _ in 0..attempted {
+            let result = (roll(rng, shooter_stats.tiredness) + shooter.offense.medium_range.value())
+                as i16
+                - FREE_THROW_DIFFICULTY;
+            last_made = result > 0;
+            if last_made {
+                made += 1;
+            }
+        }
+
+        let description = match (attempted, made) {
+            (1, 1) => format!("{} converts the free throw.", shooter.info.last_name),
+            (1, 0) => format!("{} misses the free throw.", shooter.info.last_name),
+            (x, y) if x == y => format!(
+                "{} makes all {} free throws.",
+                shooter.info.last_name, attempted
+            ),
+            (_, 0) => format!(
+                "{} misses all {} free throws.",
+                shooter.info.last_name, attempted
+            ),
+            _ => format!(
+                "{} makes {} of {} free throws.",
+                shooter.info.last_name, made, attempted
+            ),
+        };
+
+        let home_score = match input.possession {
+            Possession::Home => input.home_score + made as u16,
+            Possession::Away => input.home_score,
+        };
+        let away_score = match input.possession {
+            Possession::Home => input.away_score,
+            Possession::Away => input.away_score + made as u16,
+        };
+
+        let mut result = if last_made {
+            ActionOutput {
+                score_change: made,
+                possession: !input.possession.clone(),
+                situation: ActionSituation::BallInBackcourt,
+                description,
+                start_at: input.end_at,
+                end_at: input.end_at.plus(4 + rng.gen_range(0..=4)),
+                home_score,
+                away_score,
+                ..Default::default()
+            }
+        } else {
+            // The defense has the inside position on a missed free throw.
+            ActionOutput {
+                score_change: made,
+                advantage: Advantage::Defense,
+                possession: input.possession.clone(),
+                attackers: vec![shooter_idx],
+                situation: ActionSituation::MissedShot,
+                description,
+                start_at: input.end_at,
+                end_at: input.end_at.plus(1),
+                home_score,
+                away_score,
+                ..Default::default()
+            }
+        };
+
+        let mut attack_stats_update = HashMap::new();
+        let mut shooter_update = GameStats {
+            attempted_ft: attempted,
+            made_ft: made,
+            points: made,
+            ..Default::default()
+        };
+        shooter_update.add_tiredness(TirednessCost::LOW, shooter.athleticism.stamina);
+        attack_stats_update.insert(shooter.id, shooter_update);
+        result.attack_stats_update = Some(attack_stats_update);
+
+        Some(result)
+    }
+}
@@ -1,13 +1,16 @@
 use super::{
     action::{Action, ActionOutput, ActionSituation},
+    command::{GameCommand, GameCommandKind},
     constants::*,
     end_of_quarter::EndOfQuarter,
-    substitution::Substitution,
+    substitution::{requested_substitution, Substitution},
+    tactic::DefenseTactic,
     timer::Timer,
     types::{GameStatsMap, Possession, TeamInGame},
+    utils::tiredness_recovery,
 };
 use crate::{
-    types::{GameId, PlanetId, SortablePlayerMap, TeamId, Tick, SECONDS},
+    types::{AppResult, GameId, PlanetId, SortablePlayerMap, TeamId, Tick, SECONDS},
     world::{planet::Planet, player::Player, position::MAX_POSITION},
 };
 use rand::{Rng, SeedableRng};
@@ -15,7 +18,7 @@ use rand_chacha::ChaCha8Rng;
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GameSummary {
     pub id: GameId,
     pub home_team_id: TeamId,
@@ -26,6 +29,8 @@ pub struct GameSummary {
     pub away_score: u16,
     pub location: PlanetId,
     pub attendance: u32,
+    #[serde(default)]
+    pub overtimes: u8,
 }
 
 impl GameSummary {
@@ -51,6 +56,7 @@ impl GameSummary {
             away_score,
             location: game.location,
             attendance: game.attendance,
+            overtimes: game.timer.overtimes(),
         }
     }
 }
@@ -70,6 +76,8 @@ pub struct Game {
     pub timer: Timer,
     pub next_step: u16,
     pub current_action: Action,
+    #[serde(default)]
+    pub commands: Vec<GameCommand>,
 }
 
 impl<'game> Game {
@@ -104,6 +112,7 @@ impl<'game> Game {
             timer: Timer::default(),
             next_step: 0,
             current_action: Action::JumpBall,
+            commands: vec![],
         };
         let seed = game.get_rng_seed();
         let mut rng = ChaCha8Rng::from_seed(seed);
@@ -120,6 +129,37 @@ impl<'game> Game {
         game
     }
 
+    // Two random teams on the default planet playing each other.
+    #[cfg(test)]
+    pub fn test_game(world: &mut crate::world::world::World) -> Self {
+        use crate::types::{IdSystem, SystemTimeTick};
+        use crate::world::constants::DEFAULT_PLANET_ID;
+        let rng = &mut ChaCha8Rng::seed_from_u64(world.seed);
+        let home_id = world
+            .generate_random_team(
+                rng,
+                *DEFAULT_PLANET_ID,
+                "Testen".to_string(),
+                "Tosten".to_string(),
+            )
+            .unwrap();
+        let away_id = world
+            .generate_random_team(
+                rng,
+                *DEFAULT_PLANET_ID,
+                "Holalo".to_string(),
+                "Halley".to_string(),
+            )
+            .unwrap();
+        Self::new(
+            GameId::new(),
+            TeamInGame::from_team_id(home_id, &world.teams, &world.players).unwrap(),
+            TeamInGame::from_team_id(away_id, &world.teams, &world.players).unwrap(),
+            Tick::now(),
+            world.get_planet(*DEFAULT_PLANET_ID).unwrap(),
+        )
+    }
+
     fn pick_action(&self, rng: &mut ChaCha8Rng) -> Action {
         //FIXME: Actions should be picked based on the team tactic/players
         let situation = self.action_results[self.action_results.len() - 1]
@@ -133,22 +173,41 @@ impl<'game> Game {
             ActionSituation::MediumShot => Action::MediumShot,
             ActionSituation::LongShot => Action::LongShot,
             ActionSituation::MissedShot => Action::Rebound,
+            ActionSituation::FreeThrow => Action::FreeThrows,
             ActionSituation::EndOfQuarter => Action::StartOfQuarter,
+            // After an outlet pass, either push all the way or wait for the trailer.
+            ActionSituation::Transition => {
+                if rng
+                    .gen_range(0..TransitionWeight::FAST_BREAK + TransitionWeight::SECONDARY_BREAK)
+                    < TransitionWeight::FAST_BREAK
+                {
+                    Action::FastBreak
+                } else {
+                    Action::SecondaryBreak
+                }
+            }
             ActionSituation::BallInBackcourt
             | ActionSituation::BallInMidcourt
             | ActionSituation::AfterDefensiveRebound
-            | ActionSituation::Turnover => match self.possession {
-                Possession::Home => self
-                    .home_team_in_game
-                    .offense_tactic
-                    .pick_action(rng)
-                    .unwrap_or(Action::Isolation),
-                Possession::Away => self
-                    .away_team_in_game
-                    .offense_tactic
-                    .pick_action(rng)
-                    .unwrap_or(Action::Isolation),
-            },
+            | ActionSituation::Turnover => {
+                let offense_tactic = match self.possession {
+                    Possession::Home => self.home_team_in_game.offense_tactic,
+                    Possession::Away => self.away_team_in_game.offense_tactic,
+                };
+                let transition_chance = match situation {
+                    ActionSituation::AfterDefensiveRebound => TransitionChance::AFTER_REBOUND,
+                    ActionSituation::Turnover => TransitionChance::AFTER_TURNOVER,
+                    _ => 0.0,
+                } * offense_tactic.transition_factor();
+                if rng.gen_range(0.0..100.0) < transition_chance {
+                    match situation {
+                        ActionSituation::AfterDefensiveRebound => Action::OutletPass,
+                        _ => Action::FastBreak,
+                    }
+                } else {
+                    offense_tactic.pick_action(rng).unwrap_or(Action::Isolation)
+                }
+            }
             _ => panic!("Unknown situation: {:?}", situation),
         }
     }
@@ -208,17 +267,29 @@ impl<'game> Game {
     }
 
     fn apply_tiredness_recovery(&mut self) {
-        for team in [&mut self.home_team_in_game, &mut self.away_team_in_game] {
+        for (team, is_defending) in [
+            (
+                &mut self.home_team_in_game,
+                self.possession == Possession::Away,
+            ),
+            (
+                &mut self.away_team_in_game,
+                self.possession == Possession::Home,
+            ),
+        ] {
+            let tiredness_cost = if is_defending {
+                TirednessCost::LOW * team.defense_tactic.tiredness_factor()
+            } else {
+                TirednessCost::LOW
+            };
             for (id, stats) in team.stats.iter_mut() {
                 if stats.is_playing() && !stats.is_knocked_out() && !self.timer.is_break() {
                     stats.seconds_played += 1;
                     stats.experience_at_position[stats.position.unwrap() as usize] += 1;
                     let stamina = team.players.get(&id).unwrap().athleticism.stamina;
-                    stats.add_tiredness(TirednessCost::LOW, stamina);
-                } else if stats.tiredness > RECOVERING_TIREDNESS_PER_SHORT_TICK
-                    && !stats.is_knocked_out()
-                {
-                    stats.tiredness -= RECOVERING_TIREDNESS_PER_SHORT_TICK;
+                    stats.add_tiredness(tiredness_cost, stamina);
+                } else if stats.tiredness > 0.0 && !stats.is_knocked_out() {
+                    stats.tiredness = tiredness_recovery(stats.tiredness, 1.0);
                 }
             }
         }
@@ -280,7 +351,273 @@ impl<'game> Game {
         }
     }
 
-    fn get_rng_seed(&self) -> [u8; 32] {
+    pub fn defense_tactic(&self) -> DefenseTactic {
+        match self.possession {
+            Possession::Home => self.away_team_in_game.defense_tactic,
+            Possession::Away => self.home_team_in_game.defense_tactic,
+        }
+    }
+
+    // Positive when the attacking team is riding a wave, negative when the defense is.
+    pub fn momentum_modifier(&self) -> i16 {
+        let (attacking_team, defending_team) = match self.possession {
+            Possession::Home => (&self.home_team_in_game, &self.away_team_in_game),
+            Possession::Away => (&self.away_team_in_game, &self.home_team_in_game),
+        };
+        (attacking_team.offensive_momentum as i16 - defending_team.defensive_momentum as i16) / 2
+    }
+
+    // Points scored in a row by the team, without the opponent scoring.
+    fn scoring_run(&self, team: Possession) -> u16 {
+        let team_and_opponent_score = |home_score: u16, away_score: u16| match team {
+            Possession::Home => (home_score, away_score),
+            Possession::Away => (away_score, home_score),
+        };
+        let (home_score, away_score) = self.get_score();
+        let (team_score, opponent_score) = team_and_opponent_score(home_score, away_score);
+        let team_score_at_run_start = self
+            .action_results
+            .iter()
+            .rev()
+            .map(|result| team_and_opponent_score(result.home_score, result.away_score))
+            .find(|(_, opponent)| *opponent < opponent_score)
+            .map(|(team, _)| team)
+            .unwrap_or(0);
+        team_score - team_score_at_run_start
+    }
+
+    // Scores, dunks and runs swing momentum towards the attacking team,
+    // steals and blocks towards the defending one.
+    fn apply_momentum_update(&mut self, attacking: Possession) {
+        let result = &self.action_results[self.action_results.len() - 1];
+        let mut offense_swing = 0;
+        if result.score_change > 0 {
+            offense_swing += MomentumSwing::SCORE;
+            if result.dunk {
+                offense_swing += MomentumSwing::DUNK;
+            }
+            if self.scoring_run(attacking.clone()) >= MOMENTUM_RUN_POINTS {
+                offense_swing += MomentumSwing::RUN;
+            }
+        }
+        let mut defense_swing = 0;
+        if let Some(updates) = result.defense_stats_update.as_ref() {
+            for stats in updates.values() {
+                defense_swing +=
+                    stats.steals * MomentumSwing::STEAL + stats.blocks * MomentumSwing::BLOCK;
+            }
+        }
+
+        let (attacking_team, defending_team) = match attacking {
+            Possession::Home => (&mut self.home_team_in_game, &mut self.away_team_in_game),
+            Possession::Away => (&mut self.away_team_in_game, &mut self.home_team_in_game),
+        };
+        attacking_team.offensive_momentum = (attacking_team.offensive_momentum + offense_swing)
+            .min(MAX_MOMENTUM)
+            .saturating_sub(defense_swing);
+        defending_team.defensive_momentum = (defending_team.defensive_momentum + defense_swing)
+            .min(MAX_MOMENTUM)
+            .saturating_sub(offense_swing);
+        defending_team.offensive_momentum = defending_team
+            .offensive_momentum
+            .saturating_sub(offense_swing);
+    }
+
+    // Breaks cool teams down.
+    fn halve_momentum(&mut self) {
+        for team in [&mut self.home_team_in_game, &mut self.away_team_in_game] {
+            team.offensive_momentum /= 2;
+            team.defensive_momentum /= 2;
+        }
+    }
+
+    // Number of fouls committed by the defending team in the current period.
+    pub fn defending_team_fouls(&self) -> u8 {
+        let period = self.timer.period();
+        self.action_results
+            .iter()
+            .filter(|result| {
+                result.foul_from.is_some()
+                    && result.start_at.period() == period
+                    && result.possession == self.possession
+            })
+            .count() as u8
+    }
+
+    fn team_in_game_mut(&mut self, team_id: TeamId) -> Option<&mut TeamInGame> {
+        if self.home_team_in_game.team_id == team_id {
+            Some(&mut self.home_team_in_game)
+        } else if self.away_team_in_game.team_id == team_id {
+            Some(&mut self.away_team_in_game)
+        } else {
+            None
+        }
+    }
+
+    // Timer value at which a command issued now would be applied.
+    pub fn next_command_timer(&self) -> u16 {
+        self.timer.value + COMMAND_DELAY
+    }
+
+    pub fn next_command_sequence(&self, team_id: TeamId) -> u16 {
+        self.commands
+            .iter()
+            .filter(|command| command.team_id == team_id)
+            .count() as u16
+    }
+
+    pub fn has_command(&self, command: &GameCommand) -> bool {
+        self.commands
+            .iter()
+            .any(|c| c.team_id == command.team_id && c.sequence == command.sequence)
+    }
+
+    // A command we never got that should already have been applied means
+    // our simulation diverged from the one of the peer that issued it.
+    pub fn misses_command(&self, command: &GameCommand) -> bool {
+        command.apply_at <= self.timer.value && !self.has_command(command)
+    }
+
+    pub fn add_command(&mut self, command: GameCommand) -> AppResult<()> {
+        if command.game_id != self.id {
+            return Err("Command is for another game".into());
+        }
+        if command.team_id != self.home_team_in_game.team_id
+            && command.team_id != self.away_team_in_game.team_id
+        {
+            return Err("Command is from a team not in the game".into());
+        }
+        if self.has_command(&command) {
+            return Ok(());
+        }
+        // Applying a command in the past would break determinism between peers.
+        if command.apply_at <= self.timer.value || self.has_ended() {
+            return Err("Command arrived too late to be applied".into());
+        }
+        self.commands.push(command);
+        self.commands
+            .sort_by_key(|c| (c.apply_at, c.team_id, c.sequence));
+        Ok(())
+    }
+
+    fn apply_tactic_commands(&mut self) {
+        let timer = self.timer.value;
+        let mut tactic_changes = vec![];
+        for command in self.commands.iter_mut() {
+            if command.applied || command.apply_at > timer || command.kind.needs_stoppage() {
+                continue;
+            }
+            command.applied = true;
+            tactic_changes.push((command.team_id, command.kind));
+        }
+        for (team_id, kind) in tactic_changes {
+            if let Some(team) = self.team_in_game_mut(team_id) {
+                team.starting_tactics
+                    .get_or_insert((team.offense_tactic, team.defense_tactic));
+                match kind {
+                    GameCommandKind::SetOffenseTactic { tactic } => team.offense_tactic = tactic,
+                    GameCommandKind::SetDefenseTactic { tactic } => team.defense_tactic = tactic,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    // Timeouts and requested substitutions are applied when the ball is dead.
+    fn apply_stoppage_commands(&mut self) -> Option<ActionOutput> {
+        let timer = self.timer.value;
+        let mut pending = vec![];
+        for command in self.commands.iter_mut() {
+            if command.applied || command.apply_at > timer || !command.kind.needs_stoppage() {
+                continue;
+            }
+            command.applied = true;
+            pending.push((command.team_id, command.kind));
+        }
+        if pending.is_empty() {
+            return None;
+        }
+
+        let input = &self.action_results[self.action_results.len() - 1];
+        let mut result = ActionOutput {
+            advantage: input.advantage,
+            possession: input.possession.clone(),
+            attackers: input.attackers.clone(),
+            defenders: input.defenders.clone(),
+            situation: input.situation.clone(),
+            assist_from: input.assist_from,
+            free_throws: input.free_throws,
+            foul_on: input.foul_on,
+            start_at: input.start_at,
+            end_at: input.end_at,
+            home_score: input.home_score,
+            away_score: input.away_score,
+            ..Default::default()
+        };
+
+        for (team_id, kind) in pending {
+            let is_home = self.home_team_in_game.team_id == team_id;
+            let (team, opponent) = if is_home {
+                (&mut self.home_team_in_game, &mut self.away_team_in_game)
+            } else {
+                (&mut self.away_team_in_game, &mut self.home_team_in_game)
+            };
+            match kind {
+                GameCommandKind::Timeout => {
+                    if team.timeouts_used >= MAX_TIMEOUTS {
+                        result
+                            .description
+                            .push_str(format!("{} has no timeouts left. ", team.name).as_str());
+                        continue;
+                    }
+                    team.timeouts_used += 1;
+                    // A timeout lets players catch their breath and cools the opponent down.
+                    for stats in team.stats.values_mut() {
+                        if !stats.is_knocked_out() {
+                            stats.tiredness =
+                                (stats.tiredness - TIMEOUT_TIREDNESS_RECOVERY).max(0.0);
+                        }
+                    }
+                    opponent.offensive_momentum = 0;
+                    opponent.defensive_momentum = 0;
+                    result.description.push_str(
+                        format!(
+                            "Timeout {}. ({} left) ",
+                            team.name,
+                            MAX_TIMEOUTS - team.timeouts_used
+                        )
+                        .as_str(),
+                    );
+                }
+                GameCommandKind::Substitution {
+                    player_in,
+                    player_out,
+                } => {
+                    let players = team.players.by_position(&team.stats);
+                    if let Some((description, stats_update)) =
+                        requested_substitution(players, &team.stats, player_in, player_out)
+                    {
+                        for (id, update) in stats_update.iter() {
+                            if let Some(stats) = team.stats.get_mut(id) {
+                                stats.position = update.position;
+                            }
+                        }
+                        result.description.push_str(
+                            format!("Substitution for {}. {}", team.name, description).as_str(),
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if result.description.is_empty() {
+            return None;
+        }
+        Some(result)
+    }
+
+    pub fn get_rng_seed(&self) -> [u8; 32] {
         let mut seed = [0; 32];
         seed[0..16].copy_from_slice(self.id.as_bytes());
         seed[16..32].copy_from_slice(self.starting_at.to_be_bytes().as_ref());
@@ -298,19 +635,29 @@ impl<'game> Game {
         }
     }
 
+    pub fn has_ended(&self) -> bool {
+        self.ended_at.is_some()
+    }
+
     pub fn tick(&mut self) {
-        if !self.timer.has_ended() {
-            self.timer.tick();
+        if self.has_ended() {
+            return;
         }
 
-        if self.timer.has_ended() {
-            if self.ended_at.is_none() {
+        self.timer.tick();
+
+        // There are no ties: if the score is tied at the end of regulation
+        // or of an overtime, we keep playing.
+        if self.timer.period().can_end_game() {
+            let (home_score, away_score) = self.get_score();
+            if home_score != away_score {
                 self.ended_at = Some(self.starting_at + self.timer.value as Tick * SECONDS);
+                return;
             }
-            return;
         }
 
         self.apply_tiredness_recovery();
+        self.apply_tactic_commands();
 
         if !self.timer.reached(self.next_step) {
             return;
@@ -356,6 +703,7 @@ impl<'game> Game {
                 );
             }
 
+            let attacking = self.possession.clone();
             self.possession = result.possession.clone();
 
             // If this was the first action (JumpBall),
@@ -367,15 +715,30 @@ impl<'game> Game {
             let end_at = result.end_at.clone();
 
             self.action_results.push(result);
+            self.apply_momentum_update(attacking);
 
             let action_input = &self.action_results[self.action_results.len() - 1];
             if end_at.is_break() {
+                self.halve_momentum();
+                let action_input = &self.action_results[self.action_results.len() - 1];
                 if let Some(eoq) = EndOfQuarter.execute(action_input, self, rng) {
                     self.next_step = eoq.end_at.value;
                     self.action_results.push(eoq);
                 }
-            } else if action_input.situation == ActionSituation::BallInBackcourt {
-                // Check if teams make substitutions. Only if ball is out
+            } else if action_input.situation == ActionSituation::BallInBackcourt
+                || (action_input.foul_from.is_some()
+                    && action_input.situation != ActionSituation::FreeThrow)
+                || action_input.has_injury()
+            {
+                // Coaches' timeouts and substitutions come first.
+                if let Some(output) = self.apply_stoppage_commands() {
+                    self.next_step = output.end_at.value;
+                    self.action_results.push(output);
+                }
+
+                // Check if teams make substitutions. Only if ball is out,
+                // or if the game was stopped for an injury.
+                let action_input = &self.action_results[self.action_results.len() - 1];
                 if let Some(sub) = Substitution.execute(action_input, self, rng) {
                     self.apply_sub_update(
                         sub.attack_stats_update.clone(),
@@ -392,10 +755,13 @@ impl<'game> Game {
 #[cfg(test)]
 mod tests {
     use super::Game;
+    use crate::engine::action::{Action, ActionOutput, ActionSituation};
+    use crate::engine::command::{GameCommand, GameCommandKind};
+    use crate::engine::tactic::OffenseTactic;
     use crate::engine::types::TeamInGame;
     use crate::types::{GameId, IdSystem};
     use crate::types::{SystemTimeTick, Tick};
-    use crate::world::constants::DEFAULT_PLANET_ID;
+    use crate::world::constants::{TickInterval, DEFAULT_PLANET_ID};
     use crate::world::world::World;
     use rand::SeedableRng;
     use rand_chacha::ChaCha8Rng;
@@ -469,8 +835,11 @@ mod tests {
         println!("{:?}", game.home_team_in_game.players.len());
 
         world.games.insert(game.id, game);
+        // Play the game through instead of waiting for it in real time.
+        let mut now = Tick::now();
         while world.games.len() > 0 {
-            let _ = world.handle_tick_events(Tick::now(), false);
+            now += TickInterval::SHORT;
+            let _ = world.handle_tick_events(now, true);
         }
         let quickness_after = world
             .get_player(checked_player_id)
@@ -480,4 +849,115 @@ mod tests {
             .clone();
         println!("{} {}", quickness_before, quickness_after);
     }
+
+    #[test]
+    fn test_coaching_commands_are_deterministic() {
+        let mut world = World::new(None);
+        let mut game = Game::test_game(&mut world);
+        let id0 = game.home_team_in_game.team_id;
+        // Both peers start from the same game.
+        let mut other_game = game.clone();
+
+        let starting_tactic = game.home_team_in_game.offense_tactic;
+        let player_out = game.home_team_in_game.initial_positions[0];
+        let player_in = game.home_team_in_game.initial_positions[5];
+        let commands = vec![
+            GameCommand::new(
+                game.id,
+                id0,
+                0,
+                20,
+                GameCommandKind::SetOffenseTactic {
+                    tactic: starting_tactic.next(),
+                },
+            ),
+            GameCommand::new(game.id, id0, 1, 30, GameCommandKind::Timeout),
+            GameCommand::new(
+                game.id,
+                id0,
+                2,
+                40,
+                GameCommandKind::Substitution {
+                    player_in,
+                    player_out,
+                },
+            ),
+        ];
+        for command in commands.iter() {
+            game.add_command(command.clone()).unwrap();
+        }
+        // The other peer receives the commands in a different order, during the game.
+        while other_game.timer.value < 15 {
+            other_game.tick();
+        }
+        for command in commands.iter().rev() {
+            other_game.add_command(command.clone()).unwrap();
+        }
+
+        while !game.has_ended() {
+            game.tick();
+        }
+        while !other_game.has_ended() {
+            other_game.tick();
+        }
+
+        assert!(game.commands.iter().all(|command| command.applied));
+        assert_eq!(
+            game.home_team_in_game.offense_tactic,
+            starting_tactic.next()
+        );
+        assert_eq!(game.home_team_in_game.timeouts_used, 1);
+        assert_eq!(game.get_score(), other_game.get_score());
+        let descriptions = |game: &Game| {
+            game.action_results
+                .iter()
+                .map(|result| result.description.clone())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(descriptions(&game), descriptions(&other_game));
+        assert!(descriptions(&game)
+            .iter()
+            .any(|description| description.contains("Coach's call")));
+
+        // Commands for moments already simulated are rejected.
+        let late_command = GameCommand::new(game.id, id0, 3, 50, GameCommandKind::Timeout);
+        assert!(game.add_command(late_command).is_err());
+    }
+
+    #[test]
+    fn test_transition_trigger_chance() {
+        let mut world = World::new(Some(0));
+        let mut game = Game::test_game(&mut world);
+        let rng = &mut ChaCha8Rng::seed_from_u64(world.seed);
+
+        let mut count =
+            |game: &mut Game, situation: ActionSituation, action: fn(&Action) -> bool| {
+                game.action_results.push(ActionOutput {
+                    situation,
+                    ..Default::default()
+                });
+                (0..1000).filter(|_| action(&game.pick_action(rng))).count()
+            };
+
+        // After an outlet pass the break is pushed FAST_BREAK times out of the total weight.
+        let fast_breaks = count(&mut game, ActionSituation::Transition, |action| {
+            matches!(action, Action::FastBreak)
+        });
+        assert!((540..660).contains(&fast_breaks));
+
+        // Small ball runs more than big pirates.
+        let mut outlet_passes = |tactic: OffenseTactic| {
+            game.home_team_in_game.offense_tactic = tactic;
+            count(
+                &mut game,
+                ActionSituation::AfterDefensiveRebound,
+                |action| matches!(action, Action::OutletPass),
+            )
+        };
+        let balanced = outlet_passes(OffenseTactic::Balanced);
+        let small_ball = outlet_passes(OffenseTactic::SmallBall);
+        let big_pirates = outlet_passes(OffenseTactic::BigPirates);
+        assert!((240..360).contains(&balanced));
+        assert!(small_ball > balanced && balanced > big_pirates);
+    }
 }
@@ -1,14 +1,16 @@
 use super::{
-    action::{ActionOutput, ActionSituation, Advantage},
-    constants::{TirednessCost, ADV_ATTACK_LIMIT, ADV_DEFENSE_LIMIT, ADV_NEUTRAL_LIMIT},
+    action::{Action, ActionOutput, ActionSituation, Advantage},
+    constants::{
+        FoulChance, TirednessCost, ADV_ATTACK_LIMIT, ADV_DEFENSE_LIMIT, ADV_NEUTRAL_LIMIT,
+    },
+    foul::{is_foul, non_shooting_foul},
     game::Game,
     types::GameStats,
-    utils::roll,
+    utils::{pick_player_idx, roll},
 };
 use crate::world::{player::Player, skill::GameSkill};
 use rand::Rng;
 use rand_chacha::ChaCha8Rng;
-use rand_distr::{Distribution, WeightedIndex};
 use std::collections::HashMap;
 
 #[derive(Debug, Default)]
@@ -29,7 +31,7 @@ impl Isolation {
         let iso_idx: usize;
         match input.attackers.len() {
             0 => {
-                iso_idx = WeightedIndex::new(&[2, 3, 2, 1, 0]).ok()?.sample(rng);
+                iso_idx = pick_player_idx(rng, [2, 3, 2, 1, 0], attacking_players.len(), None)?;
             }
             _ => {
                 iso_idx = input.attackers[0];
@@ -38,7 +40,10 @@ impl Isolation {
 
         let iso: &Player = attacking_players[iso_idx];
         let iso_stats = attacking_stats.get(&iso.id)?;
-        let defender = defending_players[iso_idx];
+        let defense_tactic = game.defense_tactic();
+        let defender_idx =
+            defense_tactic.defender_idx(iso_idx, &attacking_players, &defending_players);
+        let defender = defending_players[defender_idx];
         let defender_stats = defending_stats.get(&defender.id)?;
 
         let timer_increase = 2 + rng.gen_range(0..=3);
@@ -61,12 +66,17 @@ impl Isolation {
             + defender.defense.perimeter_defense.value()
             + defender.athleticism.quickness.value();
 
-        let mut result = match atk_result as i16 - def_result as i16 {
+        let mut result = match atk_result as i16 - def_result as i16
+            + defense_tactic.action_modifier(&Action::Isolation)
+            + game.momentum_modifier()
+            + iso_stats.morale_modifier()
+            - defender_stats.morale_modifier()
+        {
             x if x > ADV_ATTACK_LIMIT => ActionOutput {
                 possession: input.possession.clone(),
                 advantage: Advantage::Attack,
                 attackers: vec![iso_idx],
-                defenders: vec![iso_idx],
+                defenders: vec![defender_idx],
                 situation: ActionSituation::CloseShot,
                 description: format!(
                     "{} breaks {}'s ankles and is now at the basket.",
@@ -82,7 +92,7 @@ impl Isolation {
                 possession: input.possession.clone(),
                 advantage: Advantage::Neutral,
                 attackers: vec![iso_idx],
-                defenders: vec![iso_idx], //got the switch
+                defenders: vec![defender_idx], //got the switch
                 situation: ActionSituation::CloseShot,
                 description: format!(
                     "{} gets through {} and gathers the ball to shoot.",
@@ -98,7 +108,7 @@ impl Isolation {
                 possession: input.possession.clone(),
                 advantage: Advantage::Defense,
                 attackers: vec![iso_idx],
-                defenders: vec![iso_idx], //no switch
+                defenders: vec![defender_idx], //no switch
                 situation: ActionSituation::MediumShot,
                 description: format!(
                     "{} tries to dribble past {} but {} is all over him.",
@@ -129,6 +139,28 @@ impl Isolation {
                 }
             }
         };
+
+        // A beaten defender might try to stop the attacker with a foul.
+        if result.situation == ActionSituation::CloseShot
+            && is_foul(
+                rng,
+                defender,
+                defender_stats,
+                defender.defense.perimeter_defense.value(),
+                FoulChance::ISOLATION,
+            )
+        {
+            defender_update.fouls = 1;
+            result = non_shooting_foul(
+                input,
+                game,
+                iso_idx,
+                defender_idx,
+                iso,
+                defender,
+                defender_stats,
+            );
+        }
         attack_stats_update.insert(iso.id, iso_update);
         defense_stats_update.insert(defender.id, defender_update);
         result.attack_stats_update = Some(attack_stats_update);
@@ -1,6 +1,10 @@
 pub mod action;
+mod collision;
+pub mod command;
 pub mod constants;
 mod end_of_quarter;
+mod foul;
+mod free_throw;
 pub mod game;
 mod isolation;
 mod jump_ball;
@@ -8,10 +12,12 @@ mod off_the_screen;
 mod pick_and_roll;
 mod post;
 mod rebound;
+pub mod replay;
 mod shot;
 mod start_of_quarter;
 mod substitution;
 pub mod tactic;
 pub mod timer;
+mod transition;
 pub mod types;
-mod utils;
+pub mod utils;
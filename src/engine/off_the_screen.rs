@@ -1,14 +1,13 @@
 use super::{
-    action::{ActionOutput, ActionSituation, Advantage},
+    action::{Action, ActionOutput, ActionSituation, Advantage},
     constants::{TirednessCost, ADV_ATTACK_LIMIT, ADV_DEFENSE_LIMIT},
     game::Game,
     types::{GameStats, GameStatsMap},
-    utils::roll,
+    utils::{pick_player_idx, roll},
 };
 use crate::world::{player::Player, skill::GameSkill};
 use rand::Rng;
 use rand_chacha::ChaCha8Rng;
-use rand_distr::{Distribution, WeightedIndex};
 use std::collections::HashMap;
 
 #[derive(Debug, Default)]
@@ -30,14 +29,17 @@ impl OffTheScreen {
         let target_idx: usize;
         match input.attackers.len() {
             0 => {
-                play_idx = WeightedIndex::new(&[6, 1, 2, 0, 0]).ok()?.sample(rng);
-                target_idx = WeightedIndex::new(&[1, 2, 3, 3, 2]).ok()?.sample(rng);
+                play_idx = pick_player_idx(rng, [6, 1, 2, 0, 0], attacking_players.len(), None)?;
+                target_idx = pick_player_idx(rng, [1, 2, 3, 3, 2], attacking_players.len(), None)?;
             }
             1 => {
                 play_idx = input.attackers[0];
-                let mut weights = [1, 2, 3, 3, 2];
-                weights[play_idx] = 0;
-                target_idx = WeightedIndex::new(&weights).ok()?.sample(rng);
+                target_idx = pick_player_idx(
+                    rng,
+                    [1, 2, 3, 3, 2],
+                    attacking_players.len(),
+                    Some(play_idx),
+                )?;
             }
             _ => {
                 play_idx = input.attackers[0];
@@ -46,12 +48,17 @@ impl OffTheScreen {
         }
 
         let playmaker: &Player = attacking_players[play_idx];
-        let playmaker_defender = defending_players[play_idx];
+        let defense_tactic = game.defense_tactic();
+        let playmaker_defender_idx =
+            defense_tactic.defender_idx(play_idx, &attacking_players, &defending_players);
+        let playmaker_defender = defending_players[playmaker_defender_idx];
         let play_stats = attacking_stats.get(&playmaker.id)?;
         let defender_stats = defending_stats.get(&playmaker_defender.id)?;
 
         let target = attacking_players[target_idx];
-        let target_defender = defending_players[target_idx];
+        let target_defender_idx =
+            defense_tactic.defender_idx(target_idx, &attacking_players, &defending_players);
+        let target_defender = defending_players[target_defender_idx];
 
         let mut attack_stats_update: GameStatsMap = HashMap::new();
         let mut playmaker_update = GameStats::default();
@@ -74,12 +81,17 @@ impl OffTheScreen {
             + target_defender.defense.perimeter_defense.value()
             + target_defender.athleticism.quickness.value();
 
-        result = match atk_result as i16 - def_result as i16 {
+        result = match atk_result as i16 - def_result as i16
+            + defense_tactic.action_modifier(&Action::OffTheScreen)
+            + game.momentum_modifier()
+            + play_stats.morale_modifier()
+            - defender_stats.morale_modifier()
+        {
             x if x > ADV_ATTACK_LIMIT => ActionOutput {
                 possession: input.possession.clone(),
                 advantage: Advantage::Attack,
                 attackers: vec![target_idx],
-                defenders: vec![target_idx],
+                defenders: vec![target_defender_idx],
                 situation: ActionSituation::LongShot,
                 description: format!(
                     "{} gets the pass from {} and is now open for the shot.",
@@ -89,14 +101,14 @@ impl OffTheScreen {
                 start_at: input.end_at,
                 end_at: input.end_at.plus(timer_increase),
                 home_score: input.home_score,
-                    away_score: input.away_score,
+                away_score: input.away_score,
                 ..Default::default()
             },
             x if x > 0 => ActionOutput {
                 possession: input.possession.clone(),
                 advantage: Advantage::Neutral,
                 attackers: vec![target_idx],
-                defenders: vec![target_idx],
+                defenders: vec![target_defender_idx],
                 situation: ActionSituation::LongShot,
                 description: format!(
                     "{} passes to {} after the screen.",
@@ -106,14 +118,14 @@ impl OffTheScreen {
                 start_at: input.end_at,
                 end_at: input.end_at.plus(timer_increase),
                 home_score: input.home_score,
-                    away_score: input.away_score,
+                away_score: input.away_score,
                 ..Default::default()
             },
             x if x > ADV_DEFENSE_LIMIT => ActionOutput {
                 possession: input.possession.clone(),
                 advantage: Advantage::Defense,
                 attackers: vec![target_idx],
-                defenders: vec![target_idx],
+                defenders: vec![target_defender_idx],
                 situation: ActionSituation::MediumShot,
                 description: format!(
                     "{} passes to {} who tried to get free using the screen, but {} is all over him.",
@@ -123,7 +135,7 @@ impl OffTheScreen {
                 start_at: input.end_at,
                 end_at: input.end_at.plus(timer_increase),
                 home_score: input.home_score,
-                    away_score: input.away_score,
+                away_score: input.away_score,
                 ..Default::default()
             },
             _ => {
@@ -138,8 +150,8 @@ impl OffTheScreen {
                         playmaker.info.last_name, target.info.last_name, target_defender.info.last_name
                     ),
                     start_at: input.end_at,
-                end_at: input.end_at.plus(2),
-                home_score: input.home_score,
+                    end_at: input.end_at.plus(2),
+                    home_score: input.home_score,
                     away_score: input.away_score,
                     ..Default::default()
                 }
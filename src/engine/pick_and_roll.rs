@@ -1,14 +1,13 @@
 use super::{
-    action::{ActionOutput, ActionSituation, Advantage},
+    action::{Action, ActionOutput, ActionSituation, Advantage},
     constants::{TirednessCost, ADV_ATTACK_LIMIT, ADV_DEFENSE_LIMIT, ADV_NEUTRAL_LIMIT},
     game::Game,
     types::{GameStats, GameStatsMap},
-    utils::roll,
+    utils::{pick_player_idx, roll},
 };
 use crate::world::{player::Player, skill::GameSkill};
 use rand::Rng;
 use rand_chacha::ChaCha8Rng;
-use rand_distr::{Distribution, WeightedIndex};
 use std::collections::HashMap;
 
 #[derive(Debug, Default)]
@@ -30,12 +29,12 @@ impl PickAndRoll {
         let target_idx: usize;
         match input.attackers.len() {
             0 => {
-                play_idx = WeightedIndex::new(&[6, 1, 2, 0, 0]).ok()?.sample(rng);
-                target_idx = WeightedIndex::new(&[1, 2, 3, 3, 2]).ok()?.sample(rng);
+                play_idx = pick_player_idx(rng, [6, 1, 2, 0, 0], attacking_players.len(), None)?;
+                target_idx = pick_player_idx(rng, [1, 2, 3, 3, 2], attacking_players.len(), None)?;
             }
             1 => {
                 play_idx = input.attackers[0];
-                target_idx = WeightedIndex::new(&[1, 2, 3, 3, 2]).ok()?.sample(rng);
+                target_idx = pick_player_idx(rng, [1, 2, 3, 3, 2], attacking_players.len(), None)?;
             }
             _ => {
                 play_idx = input.attackers[0];
@@ -44,12 +43,18 @@ impl PickAndRoll {
         }
 
         let playmaker: &Player = attacking_players[play_idx];
-        let playmaker_defender = defending_players[play_idx];
+        let defense_tactic = game.defense_tactic();
+        let playmaker_defender_idx =
+            defense_tactic.defender_idx(play_idx, &attacking_players, &defending_players);
+        let playmaker_defender = defending_players[playmaker_defender_idx];
         let play_stats = attacking_stats.get(&playmaker.id)?;
         let defender_stats = defending_stats.get(&playmaker_defender.id)?;
 
         let target = attacking_players[target_idx];
-        let target_defender = defending_players[target_idx];
+        let target_defender_idx =
+            defense_tactic.defender_idx(target_idx, &attacking_players, &defending_players);
+        let target_defender = defending_players[target_defender_idx];
+        let target_defender_stats = defending_stats.get(&target_defender.id)?;
 
         let mut attack_stats_update: GameStatsMap = HashMap::new();
         let mut playmaker_update = GameStats::default();
@@ -79,20 +84,25 @@ impl PickAndRoll {
                 + playmaker_defender.defense.perimeter_defense.value()
                 + playmaker_defender.mental.vision.value();
 
-            result = match atk_result as i16 - def_result as i16 {
+            result = match atk_result as i16 - def_result as i16
+            + defense_tactic.action_modifier(&Action::PickAndRoll)
+            + game.momentum_modifier()
+            + play_stats.morale_modifier()
+            - defender_stats.morale_modifier()
+        {
                 x if x > ADV_ATTACK_LIMIT => ActionOutput {
                     possession: input.possession.clone(),
                     advantage: Advantage::Attack,
                     attackers: vec![play_idx],
-                    defenders: vec![play_idx],
+                    defenders: vec![playmaker_defender_idx],
                     situation: ActionSituation::LongShot,
                     description: format!(
                         "{} uses the screen perfectly and is now open for the shot.",
                         playmaker.info.last_name
                     ),
                     start_at: input.end_at,
-                        end_at: input.end_at.plus(timer_increase),
-                        home_score: input.home_score,
+                    end_at: input.end_at.plus(timer_increase),
+                    home_score: input.home_score,
                     away_score: input.away_score,
                     ..Default::default()
                 },
@@ -100,15 +110,15 @@ impl PickAndRoll {
                     possession: input.possession.clone(),
                     advantage: Advantage::Neutral,
                     attackers: vec![play_idx],
-                    defenders: vec![play_idx],
+                    defenders: vec![playmaker_defender_idx],
                     situation: ActionSituation::LongShot,
                     description: format!(
                         "They go for the pick'n'roll. {} goes through the screen and manages to get a bit of space to shot.",
                         playmaker.info.last_name,
                     ),
                     start_at: input.end_at,
-                        end_at: input.end_at.plus(timer_increase),
-                        home_score: input.home_score,
+                    end_at: input.end_at.plus(timer_increase),
+                    home_score: input.home_score,
                     away_score: input.away_score,
                     ..Default::default()
                 },
@@ -116,15 +126,15 @@ impl PickAndRoll {
                     possession: input.possession.clone(),
                     advantage: Advantage::Defense,
                     attackers: vec![play_idx],
-                    defenders: vec![play_idx],
+                    defenders: vec![playmaker_defender_idx],
                     situation: ActionSituation::LongShot,
                     description: format!(
                         "{} tries to use the screen but {} slides nicely to cover.",
                         playmaker.info.last_name, target_defender.info.last_name
                     ),
                     start_at: input.end_at,
-                        end_at: input.end_at.plus(timer_increase),
-                        home_score: input.home_score,
+                    end_at: input.end_at.plus(timer_increase),
+                    home_score: input.home_score,
                     away_score: input.away_score,
                     ..Default::default()
                 },
@@ -139,9 +149,9 @@ impl PickAndRoll {
                             playmaker.info.last_name, target_defender.info.last_name, playmaker.info.pronouns.as_possessive()
                         ),
                         start_at: input.end_at,
-                end_at: input.end_at.plus(2),
-                home_score: input.home_score,
-                    away_score: input.away_score,
+                        end_at: input.end_at.plus(2),
+                        home_score: input.home_score,
+                        away_score: input.away_score,
                         ..Default::default()
                     }
                 }
@@ -156,12 +166,17 @@ impl PickAndRoll {
                 + playmaker_defender.defense.perimeter_defense.value()
                 + target_defender.athleticism.quickness.value();
 
-            result = match atk_result as i16 - def_result as i16 {
+            result = match atk_result as i16 - def_result as i16
+            + defense_tactic.action_modifier(&Action::PickAndRoll)
+            + game.momentum_modifier()
+            + play_stats.morale_modifier()
+            - target_defender_stats.morale_modifier()
+        {
             x if x > ADV_ATTACK_LIMIT => ActionOutput {
                 possession: input.possession.clone(),
                 advantage: Advantage::Attack,
                 attackers: vec![target_idx],
-                defenders: vec![play_idx], //got the switch
+                defenders: vec![playmaker_defender_idx], //got the switch
                 situation: ActionSituation::CloseShot,
                 description: format!(
                     "{} and {} execute the pick'n'roll perfectly! {} is now open for the shot.",
@@ -169,16 +184,16 @@ impl PickAndRoll {
                 ),
                 assist_from: Some(play_idx),
                 start_at: input.end_at,
-                        end_at: input.end_at.plus(timer_increase),
-                        home_score: input.home_score,
-                    away_score: input.away_score,
+                end_at: input.end_at.plus(timer_increase),
+                home_score: input.home_score,
+                away_score: input.away_score,
                 ..Default::default()
             },
             x if x > 0 => ActionOutput {
                 possession: input.possession.clone(),
                 advantage: Advantage::Neutral,
                 attackers: vec![target_idx],
-                defenders: vec![play_idx], //got the switch
+                defenders: vec![playmaker_defender_idx], //got the switch
                 situation: ActionSituation::CloseShot,
                 description:format!(
                     "They go for the pick'n'roll, nice move. {} passes to {} and is now ready to shoot.",
@@ -186,16 +201,16 @@ impl PickAndRoll {
                 ),
                 assist_from: Some(play_idx),
                 start_at: input.end_at,
-                        end_at: input.end_at.plus(timer_increase),
-                        home_score: input.home_score,
-                    away_score: input.away_score,
+                end_at: input.end_at.plus(timer_increase),
+                home_score: input.home_score,
+                away_score: input.away_score,
                 ..Default::default()
             },
             x if x > ADV_DEFENSE_LIMIT => ActionOutput {
                 possession: input.possession.clone(),
                 advantage: Advantage::Defense,
                 attackers: vec![target_idx],
-                defenders: vec![target_idx], //no switch
+                defenders: vec![target_defender_idx], //no switch
                 situation: ActionSituation::MediumShot,
                 description:format!(
                     "They go for the pick'n'roll. {} passes to {} but {} is all over him.",
@@ -203,9 +218,9 @@ impl PickAndRoll {
                 ),
                 assist_from: Some(play_idx),
                 start_at: input.end_at,
-                        end_at: input.end_at.plus(timer_increase),
-                        home_score: input.home_score,
-                    away_score: input.away_score,
+                end_at: input.end_at.plus(timer_increase),
+                home_score: input.home_score,
+                away_score: input.away_score,
                 ..Default::default()
             },
             _ => {
@@ -220,8 +235,8 @@ impl PickAndRoll {
                         playmaker.info.last_name, target.info.last_name, playmaker_defender.info.last_name
                     ),
                     start_at: input.end_at,
-                end_at: input.end_at.plus(2),
-                home_score: input.home_score,
+                    end_at: input.end_at.plus(2),
+                    home_score: input.home_score,
                     away_score: input.away_score,
                     ..Default::default()
                 }
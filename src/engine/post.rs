@@ -1,14 +1,18 @@
 use super::{
-    action::{ActionOutput, ActionSituation, Advantage},
-    constants::{TirednessCost, ADV_ATTACK_LIMIT, ADV_DEFENSE_LIMIT, ADV_NEUTRAL_LIMIT},
+    action::{Action, ActionOutput, ActionSituation, Advantage},
+    collision::{add_injury, collision_injury, POST_INJURIES},
+    constants::{
+        FoulChance, InjuryChance, TirednessCost, ADV_ATTACK_LIMIT, ADV_DEFENSE_LIMIT,
+        ADV_NEUTRAL_LIMIT,
+    },
+    foul::{is_foul, non_shooting_foul},
     game::Game,
     types::GameStats,
-    utils::roll,
+    utils::{pick_player_idx, roll},
 };
 use crate::world::{player::Player, skill::GameSkill};
 use rand::Rng;
 use rand_chacha::ChaCha8Rng;
-use rand_distr::{Distribution, WeightedIndex};
 use std::collections::HashMap;
 
 #[derive(Debug, Default)]
@@ -29,7 +33,7 @@ impl Post {
         let post_idx: usize;
         match input.attackers.len() {
             0 => {
-                post_idx = WeightedIndex::new(&[0, 0, 1, 2, 3]).ok()?.sample(rng);
+                post_idx = pick_player_idx(rng, [0, 0, 1, 2, 3], attacking_players.len(), None)?;
             }
             _ => {
                 post_idx = input.attackers[0];
@@ -38,7 +42,10 @@ impl Post {
 
         let poster: &Player = attacking_players[post_idx];
         let post_stats = attacking_stats.get(&poster.id)?;
-        let defender = defending_players[post_idx];
+        let defense_tactic = game.defense_tactic();
+        let defender_idx =
+            defense_tactic.defender_idx(post_idx, &attacking_players, &defending_players);
+        let defender = defending_players[defender_idx];
         let defender_stats = defending_stats.get(&defender.id)?;
 
         let timer_increase = 4 + rng.gen_range(0..=5);
@@ -61,12 +68,17 @@ impl Post {
             + defender.defense.interior_defense.value()
             + defender.athleticism.strength.value();
 
-        let mut result = match atk_result as i16 - def_result as i16 {
+        let mut result = match atk_result as i16 - def_result as i16
+            + defense_tactic.action_modifier(&Action::Post)
+            + game.momentum_modifier()
+            + post_stats.morale_modifier()
+            - defender_stats.morale_modifier()
+        {
             x if x > ADV_ATTACK_LIMIT => ActionOutput {
                 possession: input.possession.clone(),
                 advantage: Advantage::Attack,
                 attackers: vec![post_idx],
-                defenders: vec![post_idx],
+                defenders: vec![defender_idx],
                 situation: ActionSituation::CloseShot,
                 description: format!(
                     "{} worked {}'s perfectly and got to the basket.",
@@ -82,7 +94,7 @@ impl Post {
                 possession: input.possession.clone(),
                 advantage: Advantage::Neutral,
                 attackers: vec![post_idx],
-                defenders: vec![post_idx],
+                defenders: vec![defender_idx],
                 situation: ActionSituation::CloseShot,
                 description: format!(
                     "{} bumped on {} and gathers the ball to shoot.",
@@ -96,9 +108,12 @@ impl Post {
             },
             x if x > ADV_DEFENSE_LIMIT => {
                 if poster.mental.vision as i16 + x > ADV_NEUTRAL_LIMIT {
-                    let mut weights = [3, 3, 2, 2, 1];
-                    weights[post_idx] = 0;
-                    let target_idx = WeightedIndex::new(&weights).ok()?.sample(rng);
+                    let target_idx = pick_player_idx(
+                        rng,
+                        [3, 3, 2, 2, 1],
+                        attacking_players.len(),
+                        Some(post_idx),
+                    )?;
                     let target: &Player = attacking_players[target_idx];
                     ActionOutput {
                         possession: input.possession.clone(),
@@ -121,7 +136,7 @@ impl Post {
                         possession: input.possession.clone(),
                         advantage: Advantage::Defense,
                         attackers: vec![post_idx],
-                        defenders: vec![post_idx],
+                        defenders: vec![defender_idx],
                         situation: ActionSituation::MediumShot,
                         description: format!(
                         "{} tries to make the post moves work against {} but {} is all over him.",
@@ -154,8 +169,48 @@ impl Post {
                 }
             }
         };
+
+        // A beaten defender might try to stop the attacker with a foul.
+        if result.situation == ActionSituation::CloseShot
+            && is_foul(
+                rng,
+                defender,
+                defender_stats,
+                defender.defense.interior_defense.value(),
+                FoulChance::POST,
+            )
+        {
+            defender_update.fouls = 1;
+            result = non_shooting_foul(
+                input,
+                game,
+                post_idx,
+                defender_idx,
+                poster,
+                defender,
+                defender_stats,
+            );
+        }
         attack_stats_update.insert(poster.id, post_update);
         defense_stats_update.insert(defender.id, defender_update);
+
+        // Bodies collide on the post.
+        if let Some(injury) =
+            collision_injury(rng, poster, post_stats, InjuryChance::POST, &POST_INJURIES)
+        {
+            let description = add_injury(&mut attack_stats_update, poster, injury, defender);
+            result.description.push_str(description.as_str());
+        }
+        if let Some(injury) = collision_injury(
+            rng,
+            defender,
+            defender_stats,
+            InjuryChance::POST,
+            &POST_INJURIES,
+        ) {
+            let description = add_injury(&mut defense_stats_update, defender, injury, poster);
+            result.description.push_str(description.as_str());
+        }
         result.attack_stats_update = Some(attack_stats_update);
         result.defense_stats_update = Some(defense_stats_update);
         Some(result)
@@ -1,5 +1,7 @@
 use super::{
     action::{ActionOutput, ActionSituation},
+    collision::{add_injury, collision_injury, REBOUND_INJURIES},
+    constants::InjuryChance,
     game::Game,
     types::GameStats,
     utils::roll,
@@ -85,7 +87,7 @@ impl Rebound {
         let defence_rebounder = defending_players[defence_rebounder_idx];
 
         //FIXME: add more random situations
-        let result = match attack_result as i16 - defence_result as i16 {
+        let mut result = match attack_result as i16 - defence_result as i16 {
             x if x > 0 => {
                 let mut attack_stats_update: GameStatsMap = HashMap::new();
                 let mut rebounder_update = GameStats::default();
@@ -139,6 +141,38 @@ impl Rebound {
                 }
             }
         };
+
+        // The rebounders might crash into each other while fighting for the ball.
+        if let Some(injury) = collision_injury(
+            rng,
+            attack_rebounder,
+            attacking_stats.get(&attack_rebounder.id)?,
+            InjuryChance::REBOUND,
+            &REBOUND_INJURIES,
+        ) {
+            let description = add_injury(
+                result.attack_stats_update.get_or_insert_with(HashMap::new),
+                attack_rebounder,
+                injury,
+                defence_rebounder,
+            );
+            result.description.push_str(description.as_str());
+        }
+        if let Some(injury) = collision_injury(
+            rng,
+            defence_rebounder,
+            defending_stats.get(&defence_rebounder.id)?,
+            InjuryChance::REBOUND,
+            &REBOUND_INJURIES,
+        ) {
+            let description = add_injury(
+                result.defense_stats_update.get_or_insert_with(HashMap::new),
+                defence_rebounder,
+                injury,
+                attack_rebounder,
+            );
+            result.description.push_str(description.as_str());
+        }
         Some(result)
     }
 }
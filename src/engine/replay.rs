@@ -0,0 +1,229 @@
+use super::{
+    action::ActionOutput, command::GameCommand, constants::ENGINE_VERSION, game::Game,
+    types::TeamInGame,
+};
+use crate::{
+    types::{AppResult, GameId, Tick},
+    world::planet::Planet,
+};
+use serde::{Deserialize, Serialize};
+
+// FNV-1a, so that hashes do not depend on the std hasher implementation.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+// Hash of the canonical JSON of the action output. Going through a serde_json::Value
+// sorts the keys of the stats maps, which are serialized in random order otherwise.
+pub fn action_hash(output: &ActionOutput) -> AppResult<u64> {
+    let value = serde_json::to_value(output)?;
+    Ok(fnv1a(value.to_string().as_bytes()))
+}
+
+// The inputs of a game together with the hash of every action it produced.
+// Replaying the inputs must give the same hashes, otherwise peers running
+// different builds would compute different games.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameReplay {
+    pub engine_version: u16,
+    pub id: GameId,
+    pub starting_at: Tick,
+    pub planet: Planet,
+    pub home_team_in_game: TeamInGame,
+    pub away_team_in_game: TeamInGame,
+    #[serde(default)]
+    pub commands: Vec<GameCommand>,
+    pub action_hashes: Vec<u64>,
+}
+
+impl GameReplay {
+    fn simulate(&self) -> AppResult<Vec<u64>> {
+        let mut game = Game::new(
+            self.id,
+            self.home_team_in_game.clone(),
+            self.away_team_in_game.clone(),
+            self.starting_at,
+            &self.planet,
+        );
+        for command in self.commands.iter() {
+            game.add_command(command.clone())?;
+        }
+        while !game.has_ended() {
+            game.tick();
+        }
+        game.action_results.iter().map(action_hash).collect()
+    }
+
+    pub fn record(
+        id: GameId,
+        home_team_in_game: TeamInGame,
+        away_team_in_game: TeamInGame,
+        starting_at: Tick,
+        planet: Planet,
+        commands: Vec<GameCommand>,
+    ) -> AppResult<Self> {
+        let mut replay = Self {
+            engine_version: ENGINE_VERSION,
+            id,
+            starting_at,
+            planet,
+            home_team_in_game,
+            away_team_in_game,
+            commands,
+            action_hashes: vec![],
+        };
+        replay.action_hashes = replay.simulate()?;
+        Ok(replay)
+    }
+
+    // Returns an error describing the first action that differs from the recording.
+    pub fn verify(&self) -> AppResult<()> {
+        if self.engine_version != ENGINE_VERSION {
+            return Err(format!(
+                "Replay was recorded with engine version {}, current version is {}",
+                self.engine_version, ENGINE_VERSION
+            )
+            .into());
+        }
+
+        let action_hashes = self.simulate()?;
+        if let Some(idx) = self
+            .action_hashes
+            .iter()
+            .zip(action_hashes.iter())
+            .position(|(expected, actual)| expected != actual)
+        {
+            return Err(format!("Replay diverged at action {}", idx).into());
+        }
+        if self.action_hashes.len() != action_hashes.len() {
+            return Err(format!(
+                "Replay has {} actions, expected {}",
+                action_hashes.len(),
+                self.action_hashes.len()
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GameReplay;
+    use crate::engine::command::{GameCommand, GameCommandKind};
+    use crate::engine::constants::MAX_MORALE;
+    use crate::engine::game::Game;
+    use crate::engine::tactic::{DefenseTactic, OffenseTactic};
+    use crate::types::{GameId, IdSystem, SystemTimeTick, Tick};
+    use crate::world::constants::DEFAULT_PLANET_ID;
+    use crate::world::world::World;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+    use std::path::PathBuf;
+
+    fn replays_dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/engine/replays")
+    }
+
+    fn record_replay(seed: u64, commands: bool, random_morale: bool) -> GameReplay {
+        let mut world = World::new(Some(seed));
+        let game = Game::test_game(&mut world);
+        let mut home_team_in_game = game.home_team_in_game;
+        home_team_in_game.offense_tactic = OffenseTactic::SmallBall;
+        let mut away_team_in_game = game.away_team_in_game;
+        away_team_in_game.defense_tactic = DefenseTactic::FullCourtPress;
+        if random_morale {
+            let rng = &mut ChaCha8Rng::seed_from_u64(seed);
+            for stats in home_team_in_game
+                .stats
+                .values_mut()
+                .chain(away_team_in_game.stats.values_mut())
+            {
+                stats.morale = rng.gen_range(0..MAX_MORALE as u8);
+            }
+        }
+        let (id0, id1) = (home_team_in_game.team_id, away_team_in_game.team_id);
+
+        let id = GameId::new();
+        let commands = if commands {
+            vec![
+                GameCommand::new(id, id0, 0, 300, GameCommandKind::Timeout),
+                GameCommand::new(
+                    id,
+                    id1,
+                    0,
+                    600,
+                    GameCommandKind::SetDefenseTactic {
+                        tactic: DefenseTactic::Zone,
+                    },
+                ),
+            ]
+        } else {
+            vec![]
+        };
+
+        GameReplay::record(
+            id,
+            home_team_in_game,
+            away_team_in_game,
+            Tick::now(),
+            world.get_planet(DEFAULT_PLANET_ID.clone()).unwrap().clone(),
+            commands,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_golden_replays() {
+        // Set UPDATE_REPLAYS after an intentional engine change (and a version bump).
+        let update = std::env::var("UPDATE_REPLAYS").is_ok();
+        let mut failures = vec![];
+        for entry in std::fs::read_dir(replays_dir()).unwrap() {
+            let path = entry.unwrap().path();
+            let data = std::fs::read_to_string(&path).unwrap();
+            let replay: GameReplay = serde_json::from_str(&data).unwrap();
+            if update {
+                let replay = GameReplay::record(
+                    replay.id,
+                    replay.home_team_in_game,
+                    replay.away_team_in_game,
+                    replay.starting_at,
+                    replay.planet,
+                    replay.commands,
+                )
+                .unwrap();
+                std::fs::write(&path, serde_json::to_string(&replay).unwrap()).unwrap();
+            } else if let Err(err) = replay.verify() {
+                failures.push(format!("{}: {}", path.display(), err));
+            }
+        }
+        assert!(
+            failures.is_empty(),
+            "Engine results changed. If intended, bump ENGINE_VERSION and run with UPDATE_REPLAYS=1.\n{}",
+            failures.join("\n")
+        );
+    }
+
+    #[test]
+    fn test_replay_detects_divergence() {
+        let mut replay = record_replay(0, true, false);
+        assert!(replay.verify().is_ok());
+        replay.action_hashes[10] = replay.action_hashes[10].wrapping_add(1);
+        assert!(replay.verify().is_err());
+    }
+
+    #[test]
+    #[ignore]
+    fn generate_golden_replays() {
+        let dir = replays_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        // The last replay makes sure that morale still plays its part.
+        for seed in 0..4 {
+            let replay = record_replay(seed, seed > 0, seed > 2);
+            let path = dir.join(format!("replay_{}.json", seed));
+            std::fs::write(path, serde_json::to_string(&replay).unwrap()).unwrap();
+        }
+    }
+}
@@ -1,11 +1,16 @@
 use crate::{
-    engine::{constants::TirednessCost, types::*},
+    engine::{
+        collision::{add_injury, collision_injury, SHOT_INJURIES},
+        constants::{FoulChance, InjuryChance, TirednessCost},
+        types::*,
+    },
     world::{player::Player, skill::GameSkill},
 };
 
 use super::{
     action::{ActionOutput, ActionSituation, Advantage},
     constants::ShotDifficulty,
+    foul::{add_foul, foul_out_description, is_foul},
     game::Game,
     types::GameStats,
     utils::roll,
@@ -164,6 +169,23 @@ fn description(
     Some(description)
 }
 
+fn dunk_description(
+    rng: &mut ChaCha8Rng,
+    shooter: &Player,
+    assist: Option<&Player>,
+) -> Option<String> {
+    let text = [
+        format!("{} slams it home!", shooter.info.last_name),
+        format!("{} throws it down with authority!", shooter.info.last_name),
+        format!("{} rises up and dunks it!", shooter.info.last_name),
+    ];
+    let mut description = text.choose(rng)?.to_string();
+    if let Some(passer) = assist {
+        description.push_str(format!(" Assist from {}.", passer.info.last_name).as_str());
+    };
+    Some(description)
+}
+
 fn execute_shot(
     input: &ActionOutput,
     game: &Game,
@@ -202,21 +224,61 @@ fn execute_shot(
         })
         .sum::<u8>();
 
-    let roll = match input.advantage {
-        Advantage::Attack => {
-            (roll(rng, shooter_stats.tiredness) + atk_skill) as i16 - (shot as u8) as i16
-        }
-        Advantage::Neutral => {
-            (roll(rng, shooter_stats.tiredness) + atk_skill) as i16
-                - (shot as u8 + def_skill / 2) as i16
-        }
-        Advantage::Defense => {
-            (roll(rng, shooter_stats.tiredness) + atk_skill) as i16
-                - (shot as u8 + def_skill) as i16
-        }
-    };
+    let difficulty = shot as i16 + game.defense_tactic().shot_difficulty_modifier(shot);
+    let roll = shooter_stats.morale_modifier()
+        + match input.advantage {
+            Advantage::Attack => {
+                (roll(rng, shooter_stats.tiredness) + atk_skill) as i16 - difficulty
+            }
+            Advantage::Neutral => {
+                (roll(rng, shooter_stats.tiredness) + atk_skill) as i16
+                    - difficulty
+                    - (def_skill / 2) as i16
+            }
+            Advantage::Defense => {
+                (roll(rng, shooter_stats.tiredness) + atk_skill) as i16
+                    - difficulty
+                    - def_skill as i16
+            }
+        };
 
     let success = roll > 0;
+    let is_dunk = success
+        && shot == ShotDifficulty::Close
+        && input.advantage != Advantage::Defense
+        && shooter.offense.dunk.value() + shooter.athleticism.vertical.value()
+            > rng.gen_range(20..=40);
+
+    // The shooter might get fouled on the shot, more likely when the shot is contested.
+    let fouler_idx = match input.defenders.first() {
+        Some(&idx) => idx,
+        None => {
+            game.defense_tactic()
+                .defender_idx(shooter_idx, &attacking_players, &defending_players)
+        }
+    };
+    let fouler = defending_players[fouler_idx];
+    let fouler_stats = defending_stats.get(&fouler.id)?;
+    let (foul_chance, foul_skill) = match shot {
+        ShotDifficulty::Close => (
+            FoulChance::CLOSE_SHOT,
+            fouler.defense.interior_defense.value(),
+        ),
+        ShotDifficulty::Medium => (
+            FoulChance::MEDIUM_SHOT,
+            fouler.defense.perimeter_defense.value(),
+        ),
+        ShotDifficulty::Long => (
+            FoulChance::LONG_SHOT,
+            fouler.defense.perimeter_defense.value(),
+        ),
+    };
+    let contest = match input.advantage {
+        Advantage::Attack => 0.5,
+        Advantage::Neutral => 1.0,
+        Advantage::Defense => 1.5,
+    };
+    let is_shooting_foul = is_foul(rng, fouler, fouler_stats, foul_skill, foul_chance * contest);
     let mut result = match success {
         false => {
             // Attackers and defenders will get a malus in the rebound action.
@@ -269,15 +331,20 @@ fn execute_shot(
                 },
                 possession: !input.possession.clone(),
                 situation: ActionSituation::BallInBackcourt,
-                description: description(
-                    rng,
-                    shooter,
-                    assist,
-                    defenders.clone(),
-                    shot,
-                    input.advantage,
-                    success,
-                )?,
+                description: if is_dunk {
+                    dunk_description(rng, shooter, assist)?
+                } else {
+                    description(
+                        rng,
+                        shooter,
+                        assist,
+                        defenders.clone(),
+                        shot,
+                        input.advantage,
+                        success,
+                    )?
+                },
+                dunk: is_dunk,
                 start_at: input.end_at,
                 end_at: input.end_at.plus(10 + rng.gen_range(0..=8)),
                 ..Default::default()
@@ -285,6 +352,41 @@ fn execute_shot(
         }
     };
 
+    if is_shooting_foul {
+        let free_throws = match (success, shot) {
+            (true, _) => 1,
+            (false, ShotDifficulty::Long) => 3,
+            (false, _) => 2,
+        };
+        if success {
+            result.description.push_str(
+                format!(
+                    " And one! {} got fouled by {}.",
+                    shooter.info.last_name, fouler.info.last_name
+                )
+                .as_str(),
+            );
+        } else {
+            result.description.push_str(
+                format!(
+                    " {} fouled {} on the shot, {} free throws.",
+                    fouler.info.last_name, shooter.info.last_name, free_throws
+                )
+                .as_str(),
+            );
+        }
+        if let Some(foul_out) = foul_out_description(fouler, fouler_stats) {
+            result.description.push_str(foul_out.as_str());
+        }
+        result.possession = input.possession.clone();
+        result.situation = ActionSituation::FreeThrow;
+        result.attackers = vec![shooter_idx];
+        result.free_throws = free_throws;
+        result.foul_from = Some(fouler_idx);
+        result.foul_on = Some(shooter_idx);
+        result.end_at = input.end_at.plus(rng.gen_range(1..=2));
+    }
+
     // Update stats
     let mut attack_stats_update = HashMap::new();
     let mut shooter_update = GameStats::default();
@@ -364,6 +466,13 @@ fn execute_shot(
         }
     }
 
+    // A missed shot on a shooting foul does not count as an attempt.
+    if is_shooting_foul && !success {
+        shooter_update.attempted_2pt = 0;
+        shooter_update.attempted_3pt = 0;
+        shooter_update.shot_positions = vec![];
+    }
+
     attack_stats_update.insert(shooter.id, shooter_update);
 
     for (idx, defender) in defenders.iter().enumerate() {
@@ -372,7 +481,7 @@ fn execute_shot(
             Advantage::Defense => {
                 defender_update.add_tiredness(TirednessCost::MEDIUM, defender.athleticism.stamina);
                 // Only the first defender gets the block
-                if !success && idx == 0 {
+                if !success && !is_shooting_foul && idx == 0 {
                     defender_update.blocks = 1;
                 }
             }
@@ -383,6 +492,33 @@ fn execute_shot(
         }
         defense_stats_update.insert(defender.id, defender_update);
     }
+    if is_shooting_foul {
+        add_foul(&mut defense_stats_update, fouler);
+    }
+
+    // Contact at the rim can end badly.
+    if shot == ShotDifficulty::Close {
+        if let Some(injury) = collision_injury(
+            rng,
+            shooter,
+            shooter_stats,
+            InjuryChance::CLOSE_SHOT,
+            &SHOT_INJURIES,
+        ) {
+            let description = add_injury(&mut attack_stats_update, shooter, injury, fouler);
+            result.description.push_str(description.as_str());
+        }
+        if let Some(injury) = collision_injury(
+            rng,
+            fouler,
+            fouler_stats,
+            InjuryChance::CLOSE_SHOT,
+            &SHOT_INJURIES,
+        ) {
+            let description = add_injury(&mut defense_stats_update, fouler, injury, shooter);
+            result.description.push_str(description.as_str());
+        }
+    }
     result.attack_stats_update = Some(attack_stats_update);
     result.defense_stats_update = Some(defense_stats_update);
     return Some(result);
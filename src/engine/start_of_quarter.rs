@@ -2,6 +2,7 @@ use super::{
     action::{ActionOutput, ActionSituation},
     game::Game,
     timer::Period,
+    types::Possession,
 };
 use rand::Rng;
 use rand_chacha::ChaCha8Rng;
@@ -21,8 +22,23 @@ impl StartOfQuarter {
             Period::Q2 => format!("It's the start of the second quarter.",),
             Period::Q3 => format!("It's the start of the third quarter.",),
             Period::Q4 => format!("It's the start of the last period.",),
+            Period::Overtime(1) => "It's the start of overtime.".to_string(),
+            Period::Overtime(n) => format!("It's the start of overtime number {}.", n),
             _ => panic!("Invalid period {}", input.end_at.period()),
         };
+        // Overtimes start with a jump ball.
+        if input.end_at.period().is_overtime() {
+            return Some(ActionOutput {
+                situation: ActionSituation::JumpBall,
+                description,
+                start_at: input.end_at,
+                end_at: input.end_at.plus(timer_increase),
+                possession: Possession::Home,
+                home_score: input.home_score,
+                away_score: input.away_score,
+                ..Default::default()
+            });
+        }
         let possession = match input.end_at.period() {
             // Q2: Assign possession to team that did not win the jump ball
             Period::Q2 => !game.won_jump_ball.clone(),
@@ -30,8 +46,7 @@ impl StartOfQuarter {
             Period::Q3 => !game.won_jump_ball.clone(),
             // Q4: Assign possession to team that won the jump ball
             Period::Q4 => game.won_jump_ball.clone(),
-            // OT: FIXME: for the moment we just switch, but in reality OT are not handled atm
-            _ => !game.won_jump_ball.clone(),
+            _ => panic!("Invalid period {}", input.end_at.period()),
         };
 
         let result = ActionOutput {
@@ -6,7 +6,7 @@ use crate::{
         constants::MAX_TIREDNESS,
         types::{GameStats, Possession},
     },
-    types::SortablePlayerMap,
+    types::{PlayerId, SortablePlayerMap},
     world::{player::Player, position::Position, team::Team, types::Pronoun},
 };
 use itertools::Itertools;
@@ -21,6 +21,13 @@ fn get_subs<'a>(players: Vec<&'a Player>, team_stats: &GameStatsMap) -> Vec<&'a
         return vec![];
     }
 
+    // Players who fouled out or got knocked out must leave the court,
+    // even if nobody on the bench is fully rested.
+    let forced_sub = players.iter().take(5).any(|&p| {
+        let stats = team_stats.get(&p.id).unwrap();
+        stats.is_playing() && (stats.is_fouled_out() || stats.is_knocked_out())
+    });
+
     let bench: Vec<&Player> = players
         .iter()
         .skip(5)
@@ -28,7 +35,8 @@ fn get_subs<'a>(players: Vec<&'a Player>, team_stats: &GameStatsMap) -> Vec<&'a
             let stats = team_stats.get(&p.id).unwrap();
             return stats.is_playing() == false
                 && !stats.is_knocked_out()
-                && stats.tiredness <= MIN_TIREDNESS_FOR_SUB;
+                && !stats.is_fouled_out()
+                && (forced_sub || stats.tiredness <= MIN_TIREDNESS_FOR_SUB);
         })
         //Sort from most to less skilled*tired
         .sorted_by(|&a, &b| {
@@ -50,17 +58,22 @@ fn get_subs<'a>(players: Vec<&'a Player>, team_stats: &GameStatsMap) -> Vec<&'a
         .take(5)
         .filter(|&p| {
             let stats = team_stats.get(&p.id).unwrap();
-            return stats.is_playing() == true && stats.tiredness > MIN_TIREDNESS_FOR_SUB;
+            return stats.is_playing() == true
+                && (stats.tiredness > MIN_TIREDNESS_FOR_SUB
+                    || stats.is_fouled_out()
+                    || stats.is_knocked_out());
         })
         //Sort from less to most skilled*tired
         .sorted_by(|&a, &b| {
-            let v1 = if team_stats.get(&a.id).unwrap().is_knocked_out() {
+            let a_stats = team_stats.get(&a.id).unwrap();
+            let v1 = if a_stats.is_knocked_out() || a_stats.is_fouled_out() {
                 0
             } else {
                 let t1 = team_stats.get(&a.id).unwrap().tiredness;
                 a.total_skills() * (MAX_TIREDNESS - t1 / 2.0) as u16
             };
-            let v2 = if team_stats.get(&b.id).unwrap().is_knocked_out() {
+            let b_stats = team_stats.get(&b.id).unwrap();
+            let v2 = if b_stats.is_knocked_out() || b_stats.is_fouled_out() {
                 0
             } else {
                 let t2 = team_stats.get(&b.id).unwrap().tiredness;
@@ -86,8 +99,38 @@ fn make_substitution(
     if subs.len() == 0 {
         return None;
     }
-    let player_in = subs[0];
-    let player_out = subs[1];
+    substitute(players, stats, subs[0], subs[1])
+}
+
+// Substitution requested by the coach during the game.
+// It is skipped if it is not possible anymore when the ball is dead.
+pub fn requested_substitution(
+    players: Vec<&Player>,
+    stats: &GameStatsMap,
+    player_in_id: PlayerId,
+    player_out_id: PlayerId,
+) -> Option<(String, GameStatsMap)> {
+    let player_in = *players.iter().find(|p| p.id == player_in_id)?;
+    let player_out = *players.iter().find(|p| p.id == player_out_id)?;
+    let in_stats = stats.get(&player_in.id)?;
+    let out_stats = stats.get(&player_out.id)?;
+    if in_stats.is_playing()
+        || in_stats.is_fouled_out()
+        || in_stats.is_knocked_out()
+        || !out_stats.is_playing()
+    {
+        return None;
+    }
+    let (description, stats_update) = substitute(players.clone(), stats, player_in, player_out)?;
+    Some((format!("Coach's call. {}", description), stats_update))
+}
+
+fn substitute(
+    players: Vec<&Player>,
+    stats: &GameStatsMap,
+    player_in: &Player,
+    player_out: &Player,
+) -> Option<(String, GameStatsMap)> {
     let tiredness = stats.get(&player_out.id)?.tiredness;
     let position = stats.get(&player_out.id)?.position?;
 
@@ -96,7 +139,18 @@ fn make_substitution(
         player_out.info.last_name, player_in.info.last_name
     );
 
-    if tiredness > MIN_TIREDNESS_FOR_SUB {
+    if stats.get(&player_out.id)?.is_fouled_out() {
+        description
+            .push_str(format!("{} fouled out. ", player_out.info.pronouns.as_subject()).as_str());
+    } else if stats.get(&player_out.id)?.is_knocked_out() {
+        description.push_str(
+            format!(
+                "{} cannot continue after the injury. ",
+                player_out.info.pronouns.as_subject()
+            )
+            .as_str(),
+        );
+    } else if tiredness > MIN_TIREDNESS_FOR_SUB {
         description.push_str(
             format!(
                 "{} looked very tired. ",
@@ -175,6 +229,8 @@ impl Substitution {
             defenders: input.defenders.clone(),
             situation: input.situation.clone(),
             assist_from: input.assist_from,
+            free_throws: input.free_throws,
+            foul_on: input.foul_on,
             start_at: input.start_at,
             end_at: input.end_at,
             home_score: input.home_score,
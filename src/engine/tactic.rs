@@ -1,6 +1,7 @@
 use std::fmt::Display;
 
-use super::action::Action;
+use super::{action::Action, constants::ShotDifficulty};
+use crate::world::player::Player;
 use rand::seq::IteratorRandom;
 use rand_chacha::ChaCha8Rng;
 use rand_distr::{Distribution, WeightedIndex};
@@ -14,6 +15,8 @@ pub enum DefenseTactic {
     #[default]
     PirateToPirate,
     Zone,
+    BoxAndOne,
+    FullCourtPress,
 }
 
 impl Display for DefenseTactic {
@@ -21,6 +24,8 @@ impl Display for DefenseTactic {
         match self {
             DefenseTactic::PirateToPirate => write!(f, "Pirate-to-pirate"),
             DefenseTactic::Zone => write!(f, "Zone"),
+            DefenseTactic::BoxAndOne => write!(f, "Box-and-one"),
+            DefenseTactic::FullCourtPress => write!(f, "Full-court press"),
         }
     }
 }
@@ -30,6 +35,139 @@ impl DefenseTactic {
         let mut rng = rand::thread_rng();
         Self::iter().choose(&mut rng).unwrap()
     }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Self::PirateToPirate => Self::Zone,
+            Self::Zone => Self::BoxAndOne,
+            Self::BoxAndOne => Self::FullCourtPress,
+            Self::FullCourtPress => Self::PirateToPirate,
+        }
+    }
+
+    // Added to the attack-minus-defense result of a half-court action.
+    // Positive values favour the attack.
+    //  - PirateToPirate chases shooters and fronts the post, but can be hunted in isolation.
+    //  - Zone packs the paint and helps on drives, but leaves shooters open off screens.
+    //  - BoxAndOne shadows the best scorer, the other four zone up.
+    //  - FullCourtPress traps ball handlers, but bigs get easy looks once it is broken.
+    pub fn action_modifier(&self, action: &Action) -> i16 {
+        let modifiers = match self {
+            Self::PirateToPirate => [2, -2, 2, -2],
+            Self::Zone => [-4, 5, 1, -6],
+            Self::BoxAndOne => [-2, 3, 2, -4],
+            Self::FullCourtPress => [-4, 2, -3, 4],
+        };
+        match action {
+            Action::Isolation => modifiers[0],
+            Action::OffTheScreen => modifiers[1],
+            Action::PickAndRoll => modifiers[2],
+            Action::Post => modifiers[3],
+            _ => 0,
+        }
+    }
+
+    // Added to the attack-minus-defense result of a transition action.
+    // The press is the last to get back once it is beaten, the zone sprints back to the paint.
+    pub fn transition_modifier(&self) -> i16 {
+        match self {
+            Self::PirateToPirate => 0,
+            Self::Zone => -3,
+            Self::BoxAndOne => -1,
+            Self::FullCourtPress => 4,
+        }
+    }
+
+    // Added to the shot difficulty. Positive values make the shot harder.
+    pub fn shot_difficulty_modifier(&self, shot: ShotDifficulty) -> i16 {
+        match (self, shot) {
+            (Self::PirateToPirate, ShotDifficulty::Close) => -1,
+            (Self::PirateToPirate, ShotDifficulty::Medium) => 0,
+            (Self::PirateToPirate, ShotDifficulty::Long) => 2,
+            (Self::Zone, ShotDifficulty::Close) => 3,
+            (Self::Zone, ShotDifficulty::Medium) => 0,
+            (Self::Zone, ShotDifficulty::Long) => -3,
+            (Self::BoxAndOne, ShotDifficulty::Close) => 2,
+            (Self::BoxAndOne, ShotDifficulty::Medium) => 0,
+            (Self::BoxAndOne, ShotDifficulty::Long) => -2,
+            (Self::FullCourtPress, ShotDifficulty::Close) => -3,
+            (Self::FullCourtPress, ShotDifficulty::Medium) => 0,
+            (Self::FullCourtPress, ShotDifficulty::Long) => 1,
+        }
+    }
+
+    // Multiplier for the tiredness the defending players accumulate on court.
+    pub fn tiredness_factor(&self) -> f32 {
+        match self {
+            Self::FullCourtPress => 1.5,
+            _ => 1.0,
+        }
+    }
+
+    // Returns the index of the defender guarding the attacker at attacker_idx.
+    pub fn defender_idx(
+        &self,
+        attacker_idx: usize,
+        attackers: &[&Player],
+        defenders: &[&Player],
+    ) -> usize {
+        // A short-handed defense has fewer players than the attack.
+        let idx = match self {
+            Self::PirateToPirate | Self::FullCourtPress => attacker_idx,
+            Self::Zone => Self::zone_defender_idx(attacker_idx, defenders, None),
+            Self::BoxAndOne => {
+                let star_idx = best_idx(attackers, 0..attackers.len(), |p| {
+                    p.offense.close_range + p.offense.medium_range + p.offense.long_range
+                });
+                let chaser_idx = best_idx(defenders, 0..defenders.len(), |p| {
+                    p.defense.perimeter_defense
+                });
+                match (star_idx, chaser_idx) {
+                    (Some(star), Some(chaser)) if star == attacker_idx => chaser,
+                    _ => Self::zone_defender_idx(attacker_idx, defenders, chaser_idx),
+                }
+            }
+        };
+        idx.min(defenders.len().saturating_sub(1))
+    }
+
+    // In a zone, the ball is picked up by the best defender in the area of the court
+    // the attacker plays in: guards and small forwards on the perimeter, bigs in the paint.
+    fn zone_defender_idx(
+        attacker_idx: usize,
+        defenders: &[&Player],
+        excluded: Option<usize>,
+    ) -> usize {
+        let best = if attacker_idx <= 2 {
+            best_idx(
+                defenders,
+                (0..3).filter(|&idx| Some(idx) != excluded),
+                |p| p.defense.perimeter_defense,
+            )
+        } else {
+            best_idx(
+                defenders,
+                (3..5).filter(|&idx| Some(idx) != excluded),
+                |p| p.defense.interior_defense,
+            )
+        };
+        best.unwrap_or(attacker_idx)
+    }
+}
+
+fn best_idx(
+    players: &[&Player],
+    indexes: impl Iterator<Item = usize>,
+    skill: impl Fn(&Player) -> f32,
+) -> Option<usize> {
+    let mut best: Option<(usize, f32)> = None;
+    for idx in indexes.filter(|&idx| idx < players.len()) {
+        let value = skill(players[idx]);
+        if best.is_none() || value > best.unwrap().1 {
+            best = Some((idx, value));
+        }
+    }
+    best.map(|(idx, _)| idx)
 }
 
 #[derive(Debug, Clone, Copy, Default, Serialize_repr, Deserialize_repr, PartialEq, EnumIter)]
@@ -65,13 +203,26 @@ impl OffenseTactic {
         }
     }
 
-    pub fn pick_action(&self, rng: &mut ChaCha8Rng) -> Option<Action> {
-        let weights = match self {
+    // Multiplier for the chance of running in transition.
+    pub fn transition_factor(&self) -> f32 {
+        match self {
+            Self::Balanced => 1.0,
+            Self::BigPirates => 0.6,
+            Self::SmallBall => 1.5,
+        }
+    }
+
+    fn action_weights(&self) -> [u8; 4] {
+        match self {
             Self::Balanced => [2, 2, 3, 2],
             Self::BigPirates => [2, 1, 1, 3],
             Self::SmallBall => [2, 3, 3, 1],
-        };
-        let idx = WeightedIndex::new(&weights).ok()?.sample(rng);
+        }
+    }
+
+    pub fn pick_action(&self, rng: &mut ChaCha8Rng) -> Option<Action> {
+        let weights = self.action_weights();
+        let idx = WeightedIndex::new(weights).ok()?.sample(rng);
         match idx {
             0 => Some(Action::Isolation),
             1 => Some(Action::OffTheScreen),
@@ -81,3 +232,45 @@ impl OffenseTactic {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{DefenseTactic, OffenseTactic};
+    use crate::engine::action::Action;
+    use strum::IntoEnumIterator;
+
+    #[test]
+    fn test_defense_tactics_have_strengths_and_weaknesses() {
+        let actions = [
+            Action::Isolation,
+            Action::OffTheScreen,
+            Action::PickAndRoll,
+            Action::Post,
+        ];
+        for defense in DefenseTactic::iter() {
+            // Expected modifier against each offense, weighted by how often it runs each action.
+            let expected = OffenseTactic::iter()
+                .map(|offense| {
+                    let weights = offense.action_weights();
+                    let total = weights.iter().map(|&w| w as i16).sum::<i16>();
+                    let modifier = actions
+                        .iter()
+                        .zip(weights.iter())
+                        .map(|(action, &w)| defense.action_modifier(action) * w as i16)
+                        .sum::<i16>();
+                    modifier as f32 / total as f32
+                })
+                .collect::<Vec<f32>>();
+            assert!(
+                expected.iter().any(|&m| m > 0.0),
+                "{} has no weakness",
+                defense
+            );
+            assert!(
+                expected.iter().any(|&m| m < 0.0),
+                "{} has no strength",
+                defense
+            );
+        }
+    }
+}
@@ -1,14 +1,16 @@
 use serde::{Deserialize, Serialize};
-use strum::Display;
+use std::fmt::Display;
 
 const MINUTES_PER_QUARTER: u16 = 10;
+const MINUTES_PER_OVERTIME: u16 = 5;
 const MINUTES_PER_BREAK: u16 = 2;
 // const HALFTIME_BREAK_DURATION: u16 = 10;
 // const QUARTERS: u16 = 4;
 const SECONDS_PER_MINUTE: u16 = 60;
-const MAX_TIME: u16 = SECONDS_PER_MINUTE * (MINUTES_PER_QUARTER * 4 + MINUTES_PER_BREAK * 3);
+const REGULATION_TIME: u16 = SECONDS_PER_MINUTE * (MINUTES_PER_QUARTER * 4 + MINUTES_PER_BREAK * 3);
+const OVERTIME_CYCLE: u16 = SECONDS_PER_MINUTE * (MINUTES_PER_OVERTIME + MINUTES_PER_BREAK);
 
-#[derive(Debug, Display, Default, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Default, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum Period {
     #[default]
     NotStarted,
@@ -20,6 +22,27 @@ pub enum Period {
     B3,
     Q4,
     B4,
+    // Overtime periods are numbered from 1, each followed by its own break.
+    Overtime(u8),
+    OvertimeBreak(u8),
+}
+
+impl Display for Period {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotStarted => f.pad("NotStarted"),
+            Self::Q1 => f.pad("Q1"),
+            Self::B1 => f.pad("B1"),
+            Self::Q2 => f.pad("Q2"),
+            Self::B2 => f.pad("B2"),
+            Self::Q3 => f.pad("Q3"),
+            Self::B3 => f.pad("B3"),
+            Self::Q4 => f.pad("Q4"),
+            Self::B4 => f.pad("B4"),
+            Self::Overtime(n) => f.pad(format!("OT{}", n).as_str()),
+            Self::OvertimeBreak(n) => f.pad(format!("B{}", n + 4).as_str()),
+        }
+    }
 }
 
 impl Period {
@@ -33,7 +56,9 @@ impl Period {
             Self::Q3 => Self::B3,
             Self::B3 => Self::Q4,
             Self::Q4 => Self::B4,
-            Self::B4 => Self::B4,
+            Self::B4 => Self::Overtime(1),
+            Self::Overtime(n) => Self::OvertimeBreak(*n),
+            Self::OvertimeBreak(n) => Self::Overtime(n + 1),
         }
     }
 
@@ -48,6 +73,9 @@ impl Period {
             Self::B3 => Self::Q3,
             Self::Q4 => Self::B3,
             Self::B4 => Self::Q4,
+            Self::Overtime(1) => Self::B4,
+            Self::Overtime(n) => Self::OvertimeBreak(n - 1),
+            Self::OvertimeBreak(n) => Self::Overtime(*n),
         }
     }
     pub fn start(&self) -> u16 {
@@ -61,6 +89,14 @@ impl Period {
             Self::B3 => &self.previous().start() + SECONDS_PER_MINUTE * MINUTES_PER_QUARTER,
             Self::Q4 => &self.previous().start() + SECONDS_PER_MINUTE * MINUTES_PER_BREAK,
             Self::B4 => &self.previous().start() + SECONDS_PER_MINUTE * MINUTES_PER_QUARTER,
+            Self::Overtime(n) => {
+                REGULATION_TIME
+                    + SECONDS_PER_MINUTE * MINUTES_PER_BREAK
+                    + (*n as u16 - 1) * OVERTIME_CYCLE
+            }
+            Self::OvertimeBreak(_) => {
+                &self.previous().start() + SECONDS_PER_MINUTE * MINUTES_PER_OVERTIME
+            }
         }
     }
 
@@ -73,10 +109,22 @@ impl Period {
             Self::B2 => &self.previous().end() + SECONDS_PER_MINUTE * MINUTES_PER_BREAK,
             Self::Q3 => &self.previous().end() + SECONDS_PER_MINUTE * MINUTES_PER_QUARTER,
             Self::B3 => &self.previous().end() + SECONDS_PER_MINUTE * MINUTES_PER_BREAK,
-            Self::Q4 => &self.previous().end() + SECONDS_PER_MINUTE * MINUTES_PER_QUARTER,
-            Self::B4 => MAX_TIME,
+            Self::Q4 => REGULATION_TIME,
+            Self::B4 => REGULATION_TIME + SECONDS_PER_MINUTE * MINUTES_PER_BREAK,
+            Self::Overtime(_) => self.start() + SECONDS_PER_MINUTE * MINUTES_PER_OVERTIME,
+            Self::OvertimeBreak(_) => self.start() + SECONDS_PER_MINUTE * MINUTES_PER_BREAK,
         }
     }
+
+    pub fn is_overtime(&self) -> bool {
+        matches!(self, Self::Overtime(_))
+    }
+
+    // The game can only end in the break after the fourth quarter or after an overtime,
+    // as long as the score is not tied.
+    pub fn can_end_game(&self) -> bool {
+        matches!(self, Self::B4 | Self::OvertimeBreak(_))
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Clone, Copy, Serialize, Deserialize)]
@@ -118,10 +166,25 @@ impl Timer {
             x if x < SECONDS_PER_MINUTE * (MINUTES_PER_QUARTER * 3 + MINUTES_PER_BREAK * 3) => {
                 Period::B3
             }
-            x if x < SECONDS_PER_MINUTE * (MINUTES_PER_QUARTER * 4 + MINUTES_PER_BREAK * 3) => {
-                Period::Q4
+            x if x < REGULATION_TIME => Period::Q4,
+            x if x < Period::B4.end() => Period::B4,
+            x => {
+                let overtime_time = x - Period::B4.end();
+                let overtime = (overtime_time / OVERTIME_CYCLE + 1) as u8;
+                if overtime_time % OVERTIME_CYCLE < SECONDS_PER_MINUTE * MINUTES_PER_OVERTIME {
+                    Period::Overtime(overtime)
+                } else {
+                    Period::OvertimeBreak(overtime)
+                }
             }
-            _ => Period::B4,
+        }
+    }
+
+    // Number of overtime periods that have been started.
+    pub fn overtimes(&self) -> u8 {
+        match self.period() {
+            Period::Overtime(n) | Period::OvertimeBreak(n) => n,
+            _ => 0,
         }
     }
 
@@ -131,7 +194,7 @@ impl Timer {
 
     pub fn is_break(&self) -> bool {
         match self.period() {
-            Period::B1 | Period::B2 | Period::B3 | Period::B4 => true,
+            Period::B1 | Period::B2 | Period::B3 | Period::B4 | Period::OvertimeBreak(_) => true,
             _ => false,
         }
     }
@@ -147,17 +210,10 @@ impl Timer {
     }
 
     pub fn seconds(&self) -> u16 {
-        if self.value > MAX_TIME {
-            return 0;
-        }
-        (MAX_TIME - self.value) % SECONDS_PER_MINUTE
+        (self.period().end() - self.value) % SECONDS_PER_MINUTE
     }
 
     pub fn format(&self) -> String {
-        if self.has_ended() {
-            return "Q4 00:00".to_string();
-        }
-
         if !self.has_started() {
             return "Q1 10:00".to_string();
         }
@@ -171,9 +227,6 @@ impl Timer {
     }
 
     pub fn tick(&mut self) {
-        if self.has_ended() {
-            return;
-        }
         self.value += 1;
     }
 
@@ -184,10 +237,6 @@ impl Timer {
     pub fn has_started(&self) -> bool {
         self.value > 0
     }
-
-    pub fn has_ended(&self) -> bool {
-        self.period() == Period::B4
-    }
 }
 
 #[cfg(test)]
@@ -202,7 +251,7 @@ mod tests {
         let mut stdout = stdout();
         const BACKSPACE: char = 8u8 as char;
         timer.tick_by(60 * 7 + 55);
-        while !timer.has_ended() {
+        while timer.period() != timer::Period::B4 {
             print!("{}\r{}", BACKSPACE, timer.format());
             stdout.flush().unwrap();
             timer.tick_by(1);
@@ -237,9 +286,21 @@ mod tests {
         assert_eq!(timer.format(), "Q4 10:00");
         timer.tick();
         assert_eq!(timer.format(), "Q4 09:59");
-        timer.tick_by(60 * 10);
-        assert_eq!(timer.format(), "Q4 00:00");
-        assert_eq!(timer.has_ended(), true);
+        timer.tick_by(60 * 10 - 2);
+        assert_eq!(timer.format(), "Q4 00:01");
+        timer.tick();
+        assert_eq!(timer.format(), "B4 02:00");
+        assert_eq!(timer.period().can_end_game(), true);
+        timer.tick_by(60 * 2);
+        assert_eq!(timer.format(), "OT1 05:00");
+        timer.tick();
+        assert_eq!(timer.format(), "OT1 04:59");
+        timer.tick_by(60 * 5 - 1);
+        assert_eq!(timer.format(), "B5 02:00");
+        assert_eq!(timer.period().can_end_game(), true);
+        timer.tick_by(60 * 2 + 1);
+        assert_eq!(timer.format(), "OT2 04:59");
+        assert_eq!(timer.overtimes(), 2);
     }
 
     #[test]
@@ -307,13 +368,22 @@ mod tests {
         assert_eq!(timer.period(), super::Period::Q2);
         timer.tick_by(60 * 10);
         assert_eq!(timer.period(), super::Period::Q3);
-        assert_eq!(timer.has_ended(), false);
+        assert_eq!(timer.period().can_end_game(), false);
         timer.tick_by(60 * 10);
         assert_eq!(timer.period(), super::Period::Q4);
         timer.tick_by(60 * 5 - 1);
-        assert_eq!(timer.has_ended(), false);
+        assert_eq!(timer.period().can_end_game(), false);
         timer.tick_by(1);
         assert_eq!(timer.period(), super::Period::B4);
-        assert_eq!(timer.has_ended(), true);
+        assert_eq!(timer.period().can_end_game(), true);
+        timer.tick_by(60 * 2);
+        assert_eq!(timer.period(), super::Period::Overtime(1));
+        assert_eq!(timer.period().previous(), super::Period::B4);
+        timer.tick_by(60 * 5);
+        assert_eq!(timer.period(), super::Period::OvertimeBreak(1));
+        assert_eq!(timer.period().next(), super::Period::Overtime(2));
+        timer.tick_by(60 * 2);
+        assert_eq!(timer.period(), super::Period::Overtime(2));
+        assert_eq!(timer.period().start(), timer.value);
     }
 }
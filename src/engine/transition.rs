@@ -0,0 +1,572 @@
+use super::{
+    action::{ActionOutput, ActionSituation, Advantage},
+    constants::{
+        FoulChance, TirednessCost, ADV_ATTACK_LIMIT, ADV_DEFENSE_LIMIT, ADV_NEUTRAL_LIMIT,
+    },
+    foul::{is_foul, non_shooting_foul},
+    game::Game,
+    types::GameStats,
+    utils::{pick_player_idx, roll},
+};
+use crate::world::{player::Player, skill::GameSkill};
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use std::collections::HashMap;
+
+#[derive(Debug, Default)]
+pub struct OutletPass;
+
+#[derive(Debug, Default)]
+pub struct FastBreak;
+
+#[derive(Debug, Default)]
+pub struct SecondaryBreak;
+
+// The defender getting back first: quick players that read the play well.
+fn transition_defender_idx(defenders: &[&Player]) -> usize {
+    defenders
+        .iter()
+        .enumerate()
+        .max_by_key(|(_, p)| p.athleticism.quickness.value() + p.mental.positioning.value())
+        .map(|(idx, _)| idx)
+        .unwrap_or_default()
+}
+
+// A clean outlet pass gives the break a head start.
+fn head_start(advantage: Advantage) -> i16 {
+    match advantage {
+        Advantage::Attack => 5,
+        Advantage::Neutral => 0,
+        Advantage::Defense => -5,
+    }
+}
+
+fn turnover(input: &ActionOutput, description: String) -> ActionOutput {
+    ActionOutput {
+        situation: ActionSituation::Turnover,
+        possession: !input.possession.clone(),
+        description,
+        start_at: input.end_at,
+        end_at: input.end_at.plus(2),
+        home_score: input.home_score,
+        away_score: input.away_score,
+        ..Default::default()
+    }
+}
+
+impl OutletPass {
+    pub fn execute(
+        &self,
+        input: &ActionOutput,
+        game: &Game,
+        rng: &mut ChaCha8Rng,
+    ) -> Option<ActionOutput> {
+        let attacking_players = game.attacking_players();
+        let defending_players = game.defending_players();
+        let attacking_stats = game.attacking_stats();
+        let defending_stats = game.defending_stats();
+
+        // The rebounder starts the break, the update of the rebound tells us who it was.
+        let passer_idx = input
+            .defense_stats_update
+            .as_ref()
+            .and_then(|update| {
+                attacking_players
+                    .iter()
+                    .position(|p| update.get(&p.id).map(|s| s.defensive_rebounds) > Some(0))
+            })
+            .or_else(|| pick_player_idx(rng, [0u8, 0, 1, 2, 2], attacking_players.len(), None))?;
+        let target_idx = pick_player_idx(
+            rng,
+            [3u8, 2, 1, 0, 0],
+            attacking_players.len(),
+            Some(passer_idx),
+        )?;
+
+        let passer = attacking_players[passer_idx];
+        let passer_stats = attacking_stats.get(&passer.id)?;
+        let target = attacking_players[target_idx];
+        let defender_idx = transition_defender_idx(&defending_players);
+        let defender = defending_players[defender_idx];
+        let defender_stats = defending_stats.get(&defender.id)?;
+
+        let mut attack_stats_update = HashMap::new();
+        let mut passer_update = GameStats::default();
+        passer_update.add_tiredness(TirednessCost::LOW, passer.athleticism.stamina);
+
+        let mut defense_stats_update = HashMap::new();
+        let mut defender_update = GameStats::default();
+        defender_update.add_tiredness(TirednessCost::MEDIUM, defender.athleticism.stamina);
+
+        let atk_result = roll(rng, passer_stats.tiredness)
+            + passer.technical.passing.value()
+            + passer.mental.vision.value();
+
+        let def_result = roll(rng, defender_stats.tiredness)
+            + defender.athleticism.quickness.value()
+            + defender.defense.steal.value();
+
+        let timer_increase = 1 + rng.gen_range(0..=2);
+        let mut result = match atk_result as i16 - def_result as i16
+            + game.defense_tactic().transition_modifier()
+            + game.momentum_modifier()
+        {
+            x if x > ADV_ATTACK_LIMIT => ActionOutput {
+                possession: input.possession.clone(),
+                advantage: Advantage::Attack,
+                attackers: vec![target_idx],
+                situation: ActionSituation::Transition,
+                description: format!(
+                    "{} fires a long outlet pass to {}, the defense is caught napping.",
+                    passer.info.last_name, target.info.last_name
+                ),
+                start_at: input.end_at,
+                end_at: input.end_at.plus(timer_increase),
+                home_score: input.home_score,
+                away_score: input.away_score,
+                ..Default::default()
+            },
+            x if x > ADV_NEUTRAL_LIMIT => ActionOutput {
+                possession: input.possession.clone(),
+                advantage: Advantage::Neutral,
+                attackers: vec![target_idx],
+                situation: ActionSituation::Transition,
+                description: format!(
+                    "{} quickly outlets the ball to {}.",
+                    passer.info.last_name, target.info.last_name
+                ),
+                start_at: input.end_at,
+                end_at: input.end_at.plus(timer_increase),
+                home_score: input.home_score,
+                away_score: input.away_score,
+                ..Default::default()
+            },
+            x if x > ADV_DEFENSE_LIMIT => ActionOutput {
+                possession: input.possession.clone(),
+                advantage: Advantage::Defense,
+                situation: ActionSituation::BallInMidcourt,
+                description: format!(
+                    "{} looks for the outlet, but {} is already back. They slow it down.",
+                    passer.info.last_name, defender.info.last_name
+                ),
+                start_at: input.end_at,
+                end_at: input.end_at.plus(timer_increase + 2),
+                home_score: input.home_score,
+                away_score: input.away_score,
+                ..Default::default()
+            },
+            _ => {
+                passer_update.turnovers = 1;
+                defender_update.steals = 1;
+                turnover(
+                    input,
+                    format!(
+                        "{} rushes the outlet pass and {} picks it off.",
+                        passer.info.last_name, defender.info.last_name
+                    ),
+                )
+            }
+        };
+
+        attack_stats_update.insert(passer.id, passer_update);
+        defense_stats_update.insert(defender.id, defender_update);
+        result.attack_stats_update = Some(attack_stats_update);
+        result.defense_stats_update = Some(defense_stats_update);
+        Some(result)
+    }
+}
+
+impl FastBreak {
+    pub fn execute(
+        &self,
+        input: &ActionOutput,
+        game: &Game,
+        rng: &mut ChaCha8Rng,
+    ) -> Option<ActionOutput> {
+        let attacking_players = game.attacking_players();
+        let defending_players = game.defending_players();
+        let attacking_stats = game.attacking_stats();
+        let defending_stats = game.defending_stats();
+
+        let handler_idx = match input.attackers.len() {
+            0 => pick_player_idx(rng, [3u8, 3, 2, 1, 1], attacking_players.len(), None)?,
+            _ => input.attackers[0],
+        };
+        let handler = attacking_players[handler_idx];
+        let handler_stats = attacking_stats.get(&handler.id)?;
+        let defender_idx = transition_defender_idx(&defending_players);
+        let defender = defending_players[defender_idx];
+        let defender_stats = defending_stats.get(&defender.id)?;
+
+        let mut attack_stats_update = HashMap::new();
+        let mut handler_update = GameStats::default();
+        handler_update.add_tiredness(TirednessCost::HIGH, handler.athleticism.stamina);
+
+        let mut defense_stats_update = HashMap::new();
+        let mut defender_update = GameStats::default();
+        defender_update.add_tiredness(TirednessCost::HIGH, defender.athleticism.stamina);
+
+        let atk_result = roll(rng, handler_stats.tiredness)
+            + handler.athleticism.quickness.value()
+            + handler.mental.vision.value();
+
+        let def_result = roll(rng, defender_stats.tiredness)
+            + defender.athleticism.quickness.value()
+            + defender.mental.positioning.value();
+
+        let timer_increase = 2 + rng.gen_range(0..=2);
+        let mut result = match atk_result as i16 - def_result as i16
+            + head_start(input.advantage)
+            + game.defense_tactic().transition_modifier()
+            + game.momentum_modifier()
+            + handler_stats.morale_modifier()
+            - defender_stats.morale_modifier()
+        {
+            x if x > ADV_ATTACK_LIMIT => {
+                // With the defender beaten, a good passer finds the teammate running alongside.
+                let mate_idx = pick_player_idx(
+                    rng,
+                    [1u8, 2, 2, 2, 1],
+                    attacking_players.len(),
+                    Some(handler_idx),
+                )?;
+                let mate = attacking_players[mate_idx];
+                if rng.gen_range(0..=40) < handler.technical.passing.value() {
+                    ActionOutput {
+                        possession: input.possession.clone(),
+                        advantage: Advantage::Attack,
+                        attackers: vec![mate_idx],
+                        defenders: vec![defender_idx],
+                        assist_from: Some(handler_idx),
+                        situation: ActionSituation::CloseShot,
+                        description: format!(
+                            "{} pushes the break, draws {} and dishes to {} for the easy finish.",
+                            handler.info.last_name, defender.info.last_name, mate.info.last_name
+                        ),
+                        start_at: input.end_at,
+                        end_at: input.end_at.plus(timer_increase),
+                        home_score: input.home_score,
+                        away_score: input.away_score,
+                        ..Default::default()
+                    }
+                } else {
+                    ActionOutput {
+                        possession: input.possession.clone(),
+                        advantage: Advantage::Attack,
+                        attackers: vec![handler_idx],
+                        defenders: vec![defender_idx],
+                        situation: ActionSituation::CloseShot,
+                        description: format!(
+                            "{} is off to the races, nobody can catch up.",
+                            handler.info.last_name
+                        ),
+                        start_at: input.end_at,
+                        end_at: input.end_at.plus(timer_increase),
+                        home_score: input.home_score,
+                        away_score: input.away_score,
+                        ..Default::default()
+                    }
+                }
+            }
+            x if x > ADV_NEUTRAL_LIMIT => ActionOutput {
+                possession: input.possession.clone(),
+                advantage: Advantage::Neutral,
+                attackers: vec![handler_idx],
+                defenders: vec![defender_idx],
+                situation: ActionSituation::CloseShot,
+                description: format!(
+                    "{} races up the court with {} chasing.",
+                    handler.info.last_name, defender.info.last_name
+                ),
+                start_at: input.end_at,
+                end_at: input.end_at.plus(timer_increase),
+                home_score: input.home_score,
+                away_score: input.away_score,
+                ..Default::default()
+            },
+            x if x > ADV_DEFENSE_LIMIT => ActionOutput {
+                possession: input.possession.clone(),
+                advantage: Advantage::Defense,
+                situation: ActionSituation::BallInMidcourt,
+                description: format!(
+                    "{} sprints back and stops the break. {} pulls it out.",
+                    defender.info.last_name, handler.info.last_name
+                ),
+                start_at: input.end_at,
+                end_at: input.end_at.plus(timer_increase),
+                home_score: input.home_score,
+                away_score: input.away_score,
+                ..Default::default()
+            },
+            _ => {
+                handler_update.turnovers = 1;
+                defender_update.steals = 1;
+                turnover(
+                    input,
+                    format!(
+                        "{} runs into {} at full speed and loses the ball.",
+                        handler.info.last_name, defender.info.last_name
+                    ),
+                )
+            }
+        };
+
+        // A beaten defender might try to stop the break with a foul.
+        if result.situation == ActionSituation::CloseShot
+            && is_foul(
+                rng,
+                defender,
+                defender_stats,
+                defender.athleticism.quickness.value(),
+                FoulChance::FAST_BREAK,
+            )
+        {
+            defender_update.fouls = 1;
+            result = non_shooting_foul(
+                input,
+                game,
+                handler_idx,
+                defender_idx,
+                handler,
+                defender,
+                defender_stats,
+            );
+        }
+
+        attack_stats_update.insert(handler.id, handler_update);
+        defense_stats_update.insert(defender.id, defender_update);
+        result.attack_stats_update = Some(attack_stats_update);
+        result.defense_stats_update = Some(defense_stats_update);
+        Some(result)
+    }
+}
+
+impl SecondaryBreak {
+    pub fn execute(
+        &self,
+        input: &ActionOutput,
+        game: &Game,
+        rng: &mut ChaCha8Rng,
+    ) -> Option<ActionOutput> {
+        let attacking_players = game.attacking_players();
+        let defending_players = game.defending_players();
+        let attacking_stats = game.attacking_stats();
+        let defending_stats = game.defending_stats();
+
+        let handler_idx = match input.attackers.len() {
+            0 => pick_player_idx(rng, [3u8, 2, 1, 0, 0], attacking_players.len(), None)?,
+            _ => input.attackers[0],
+        };
+        // The trailer arrives late, while the defense is still matching up.
+        let trailer_idx = pick_player_idx(
+            rng,
+            [1u8, 2, 3, 2, 1],
+            attacking_players.len(),
+            Some(handler_idx),
+        )?;
+
+        let handler = attacking_players[handler_idx];
+        let handler_stats = attacking_stats.get(&handler.id)?;
+        let trailer = attacking_players[trailer_idx];
+        let trailer_stats = attacking_stats.get(&trailer.id)?;
+        let defense_tactic = game.defense_tactic();
+        let defender_idx =
+            defense_tactic.defender_idx(trailer_idx, &attacking_players, &defending_players);
+        let defender = defending_players[defender_idx];
+        let defender_stats = defending_stats.get(&defender.id)?;
+
+        let mut attack_stats_update = HashMap::new();
+        let mut handler_update = GameStats::default();
+        handler_update.add_tiredness(TirednessCost::MEDIUM, handler.athleticism.stamina);
+        let mut trailer_update = GameStats::default();
+        trailer_update.add_tiredness(TirednessCost::MEDIUM, trailer.athleticism.stamina);
+
+        let mut defense_stats_update = HashMap::new();
+        let mut defender_update = GameStats::default();
+        defender_update.add_tiredness(TirednessCost::MEDIUM, defender.athleticism.stamina);
+
+        let atk_result = roll(rng, handler_stats.tiredness)
+            + (handler.technical.passing.value() + handler.mental.vision.value()) / 2
+            + (trailer.athleticism.quickness.value() + trailer.mental.off_ball_movement.value())
+                / 2;
+
+        let def_result = roll(rng, defender_stats.tiredness)
+            + defender.athleticism.quickness.value()
+            + defender.defense.perimeter_defense.value();
+
+        // Trailers that can shoot it from deep get the three.
+        let open_shot = if trailer.offense.long_range > trailer.offense.medium_range {
+            ActionSituation::LongShot
+        } else {
+            ActionSituation::MediumShot
+        };
+
+        let timer_increase = 3 + rng.gen_range(0..=3);
+        let mut result = match atk_result as i16 - def_result as i16
+            + head_start(input.advantage)
+            + defense_tactic.transition_modifier()
+            + game.momentum_modifier()
+            + trailer_stats.morale_modifier()
+            - defender_stats.morale_modifier()
+        {
+            x if x > ADV_ATTACK_LIMIT => ActionOutput {
+                possession: input.possession.clone(),
+                advantage: Advantage::Attack,
+                attackers: vec![trailer_idx],
+                defenders: vec![defender_idx],
+                assist_from: Some(handler_idx),
+                situation: open_shot,
+                description: format!(
+                    "{} spots {} trailing the play, wide open.",
+                    handler.info.last_name, trailer.info.last_name
+                ),
+                start_at: input.end_at,
+                end_at: input.end_at.plus(timer_increase),
+                home_score: input.home_score,
+                away_score: input.away_score,
+                ..Default::default()
+            },
+            x if x > ADV_NEUTRAL_LIMIT => ActionOutput {
+                possession: input.possession.clone(),
+                advantage: Advantage::Neutral,
+                attackers: vec![trailer_idx],
+                defenders: vec![defender_idx],
+                assist_from: Some(handler_idx),
+                situation: ActionSituation::MediumShot,
+                description: format!(
+                    "{} swings it to the trailing {} before {} can match up.",
+                    handler.info.last_name, trailer.info.last_name, defender.info.last_name
+                ),
+                start_at: input.end_at,
+                end_at: input.end_at.plus(timer_increase),
+                home_score: input.home_score,
+                away_score: input.away_score,
+                ..Default::default()
+            },
+            x if x > ADV_DEFENSE_LIMIT => ActionOutput {
+                possession: input.possession.clone(),
+                advantage: Advantage::Defense,
+                situation: ActionSituation::BallInMidcourt,
+                description: format!(
+                    "{} picks up the trailer, {} has to set up the offense.",
+                    defender.info.last_name, handler.info.last_name
+                ),
+                start_at: input.end_at,
+                end_at: input.end_at.plus(timer_increase),
+                home_score: input.home_score,
+                away_score: input.away_score,
+                ..Default::default()
+            },
+            _ => {
+                handler_update.turnovers = 1;
+                defender_update.steals = 1;
+                turnover(
+                    input,
+                    format!(
+                        "{} reads the pass to {} and steals it.",
+                        defender.info.last_name, trailer.info.last_name
+                    ),
+                )
+            }
+        };
+
+        attack_stats_update.insert(handler.id, handler_update);
+        attack_stats_update.insert(trailer.id, trailer_update);
+        defense_stats_update.insert(defender.id, defender_update);
+        result.attack_stats_update = Some(attack_stats_update);
+        result.defense_stats_update = Some(defense_stats_update);
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FastBreak, OutletPass, SecondaryBreak};
+    use crate::engine::action::{ActionOutput, ActionSituation, Advantage};
+    use crate::engine::game::Game;
+    use crate::engine::types::GameStats;
+    use crate::world::world::World;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use std::collections::HashMap;
+
+    fn new_game() -> Game {
+        Game::test_game(&mut World::new(Some(0)))
+    }
+
+    #[test]
+    fn test_outlet_pass_starts_from_the_rebounder() {
+        let game = new_game();
+        let rebounder = game.attacking_players()[4];
+        let rebound = GameStats {
+            defensive_rebounds: 1,
+            ..Default::default()
+        };
+        let input = ActionOutput {
+            situation: ActionSituation::AfterDefensiveRebound,
+            defense_stats_update: Some(HashMap::from([(rebounder.id, rebound)])),
+            ..Default::default()
+        };
+
+        for seed in 0..200 {
+            let rng = &mut ChaCha8Rng::seed_from_u64(seed);
+            let result = OutletPass.execute(&input, &game, rng).unwrap();
+            assert!(result
+                .attack_stats_update
+                .unwrap()
+                .contains_key(&rebounder.id));
+            match result.situation {
+                ActionSituation::Transition => assert_ne!(result.attackers, vec![4]),
+                ActionSituation::BallInMidcourt | ActionSituation::Turnover => {}
+                situation => panic!("Unexpected situation {:?}", situation),
+            }
+        }
+    }
+
+    #[test]
+    fn test_fast_break_head_start() {
+        let game = new_game();
+        let count_shots = |advantage: Advantage| {
+            let input = ActionOutput {
+                situation: ActionSituation::Transition,
+                advantage,
+                attackers: vec![0],
+                ..Default::default()
+            };
+            (0..500)
+                .filter(|&seed| {
+                    let rng = &mut ChaCha8Rng::seed_from_u64(seed);
+                    FastBreak.execute(&input, &game, rng).unwrap().situation
+                        == ActionSituation::CloseShot
+                })
+                .count()
+        };
+        assert!(count_shots(Advantage::Attack) > count_shots(Advantage::Defense));
+    }
+
+    #[test]
+    fn test_secondary_break_finds_the_trailer() {
+        let game = new_game();
+        let input = ActionOutput {
+            situation: ActionSituation::Transition,
+            attackers: vec![0],
+            ..Default::default()
+        };
+
+        let mut shots = 0;
+        for seed in 0..200 {
+            let rng = &mut ChaCha8Rng::seed_from_u64(seed);
+            let result = SecondaryBreak.execute(&input, &game, rng).unwrap();
+            match result.situation {
+                ActionSituation::MediumShot | ActionSituation::LongShot => {
+                    shots += 1;
+                    assert_eq!(result.assist_from, Some(0));
+                    assert_ne!(result.attackers, vec![0]);
+                }
+                ActionSituation::BallInMidcourt | ActionSituation::Turnover => {}
+                situation => panic!("Unexpected situation {:?}", situation),
+            }
+        }
+        assert!(shots > 0);
+    }
+}
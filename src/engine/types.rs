@@ -1,11 +1,12 @@
 use super::{
-    constants::MAX_TIREDNESS,
+    constants::{DEFAULT_MORALE, MAX_PERSONAL_FOULS, MAX_TIREDNESS},
     tactic::{DefenseTactic, OffenseTactic},
 };
 use crate::{
     image::pitch::PitchStyle,
     types::{GameId, PlayerId, PlayerMap, TeamId, TeamMap},
     world::{
+        injury::Injury,
         player::{InfoStats, Player},
         position::{Position, MAX_POSITION},
         skill::{Athleticism, Defense, Mental, Offense, Technical},
@@ -95,6 +96,9 @@ pub struct GameStats {
     #[serde(skip_serializing_if = "is_default")]
     #[serde(default)]
     pub knocked_out_by: Option<PlayerId>,
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    pub injury: Option<Injury>,
 }
 
 impl GameStats {
@@ -124,7 +128,12 @@ impl GameStats {
         for (idx, exp) in stats.experience_at_position.iter().enumerate() {
             self.experience_at_position[idx] += exp;
         }
-        self.knocked_out_by = stats.knocked_out_by.clone();
+        if stats.knocked_out_by.is_some() {
+            self.knocked_out_by = stats.knocked_out_by;
+        }
+        if stats.injury.is_some() {
+            self.injury = stats.injury;
+        }
     }
 
     pub fn is_playing(&self) -> bool {
@@ -135,6 +144,15 @@ impl GameStats {
         self.knocked_out_by.is_some()
     }
 
+    pub fn is_fouled_out(&self) -> bool {
+        self.fouls + self.technical_fouls >= MAX_PERSONAL_FOULS
+    }
+
+    // Happy players perform better, unhappy ones worse.
+    pub fn morale_modifier(&self) -> i16 {
+        (self.morale as i16 - DEFAULT_MORALE as i16) / 4
+    }
+
     pub fn add_tiredness(&mut self, tiredness: f32, stamina: f32) {
         self.tiredness = (self.tiredness + tiredness / (1.0 + stamina / 20.0)).min(MAX_TIREDNESS);
     }
@@ -154,18 +172,49 @@ pub struct TeamInGame {
     pub defense_tactic: DefenseTactic,
     pub defensive_momentum: u8,
     pub offensive_momentum: u8,
+    #[serde(default)]
+    pub timeouts_used: u8,
+    // Tactics before the first coaching command changed them.
+    #[serde(default)]
+    pub starting_tactics: Option<(OffenseTactic, DefenseTactic)>,
 }
 
 impl<'game> TeamInGame {
     pub fn new(team: &Team, players: PlayerMap) -> Self {
+        // Injured starters are replaced by the first healthy players on the bench.
+        // Injured players sit out, unless nobody can replace them.
+        let is_injured = |id: &PlayerId| players[id].is_injured();
+        let mut player_ids = team.player_ids.clone();
+        for idx in 0..player_ids.len().min(MAX_POSITION as usize) {
+            if !is_injured(&player_ids[idx]) {
+                continue;
+            }
+            if let Some(bench_idx) = (MAX_POSITION as usize..player_ids.len())
+                .find(|&bench_idx| !is_injured(&player_ids[bench_idx]))
+            {
+                player_ids.swap(idx, bench_idx);
+            }
+        }
+        let player_ids: Vec<PlayerId> = player_ids
+            .iter()
+            .enumerate()
+            .filter(|(idx, id)| (*idx as Position) < MAX_POSITION || !is_injured(id))
+            .map(|(_, id)| *id)
+            .collect();
+        let players: PlayerMap = players
+            .into_iter()
+            .filter(|(id, _)| player_ids.contains(id))
+            .collect();
+
         let mut stats = HashMap::new();
-        for (idx, player_id) in team.player_ids.iter().enumerate() {
+        for (idx, player_id) in player_ids.iter().enumerate() {
             let mut player_stats = GameStats::default();
             if (idx as Position) < MAX_POSITION {
                 player_stats.position = Some(idx as Position);
             }
             player_stats.initial_tiredness = players[player_id].tiredness;
             player_stats.tiredness = players[player_id].tiredness;
+            player_stats.morale = players[player_id].morale.round() as u8;
             stats.insert(player_id.clone(), player_stats.clone());
         }
         Self {
@@ -173,7 +222,7 @@ impl<'game> TeamInGame {
             peer_id: team.peer_id,
             reputation: team.reputation,
             name: team.name.clone(),
-            initial_positions: team.player_ids.clone(),
+            initial_positions: player_ids,
             version: team.version,
             players,
             stats,
@@ -183,6 +232,23 @@ impl<'game> TeamInGame {
         }
     }
 
+    // Undo the changes made by coaching commands, so that the game
+    // can be simulated again from the start.
+    pub fn reset_coaching(&mut self) {
+        if let Some((offense_tactic, defense_tactic)) = self.starting_tactics.take() {
+            self.offense_tactic = offense_tactic;
+            self.defense_tactic = defense_tactic;
+        }
+        self.timeouts_used = 0;
+    }
+
+    pub fn set_peer_id(&mut self, peer_id: PeerId) {
+        self.peer_id = Some(peer_id);
+        for player in self.players.values_mut() {
+            player.peer_id = Some(peer_id);
+        }
+    }
+
     pub fn from_team_id(team_id: TeamId, teams: &TeamMap, players: &PlayerMap) -> Option<Self> {
         let team = teams.get(&team_id)?;
         let mut team_players = PlayerMap::new();
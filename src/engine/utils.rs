@@ -1,8 +1,11 @@
 use std::cmp::min;
 
-use super::constants::MAX_TIREDNESS;
+use super::constants::{RecoveryPhase, MAX_TIREDNESS, RECOVERING_TIREDNESS_PER_SHORT_TICK};
+use rand::distributions::uniform::SampleUniform;
 use rand::Rng;
 use rand_chacha::ChaCha8Rng;
+use rand_distr::{Distribution, WeightedIndex};
+use std::ops::AddAssign;
 
 pub fn roll(rng: &mut ChaCha8Rng, tiredness: f32) -> u8 {
     min(
@@ -11,9 +14,43 @@ pub fn roll(rng: &mut ChaCha8Rng, tiredness: f32) -> u8 {
     )
 }
 
+// Picks a player by position weights among the ones on court, skipping excluded_idx.
+// A short-handed team has nobody in the last positions, anyone else will do then.
+pub fn pick_player_idx<X>(
+    rng: &mut ChaCha8Rng,
+    weights: [X; 5],
+    players: usize,
+    excluded_idx: Option<usize>,
+) -> Option<usize>
+where
+    X: SampleUniform + PartialOrd + for<'a> AddAssign<&'a X> + Clone + Default + From<u8>,
+{
+    let mut weights = weights;
+    for (idx, weight) in weights.iter_mut().enumerate() {
+        if idx >= players || Some(idx) == excluded_idx {
+            *weight = X::default();
+        }
+    }
+    if weights.iter().all(|weight| *weight == X::default()) {
+        for idx in (0..players.min(weights.len())).filter(|&idx| Some(idx) != excluded_idx) {
+            weights[idx] = X::from(1);
+        }
+    }
+    Some(WeightedIndex::new(weights).ok()?.sample(rng))
+}
+
+pub fn tiredness_recovery(tiredness: f32, bonus: f32) -> f32 {
+    let phase = match tiredness {
+        x if x < MAX_TIREDNESS / 2.0 => RecoveryPhase::FRESH,
+        x if x < MAX_TIREDNESS * 3.0 / 4.0 => RecoveryPhase::TIRED,
+        _ => RecoveryPhase::EXHAUSTED,
+    };
+    (tiredness - bonus * phase * RECOVERING_TIREDNESS_PER_SHORT_TICK).max(0.0)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::roll;
+    use super::{roll, tiredness_recovery};
     use crate::engine::types::GameStats;
     use rand::SeedableRng;
     use rand_chacha::ChaCha8Rng;
@@ -33,4 +70,15 @@ mod tests {
             assert_eq!((r > 0 && r <= 25), true);
         }
     }
+
+    #[test]
+    fn test_tiredness_recovery_phases() {
+        let fresh = 40.0 - tiredness_recovery(40.0, 1.0);
+        let tired = 60.0 - tiredness_recovery(60.0, 1.0);
+        let exhausted = 90.0 - tiredness_recovery(90.0, 1.0);
+        assert!(fresh > tired);
+        assert!(tired > exhausted);
+        assert!(exhausted > 0.0);
+        assert_eq!(tiredness_recovery(0.01, 1.0), 0.0);
+    }
 }
@@ -5,6 +5,7 @@ pub mod event;
 pub mod image;
 pub mod network;
 pub mod relayer;
+pub mod simulator;
 pub mod store;
 pub mod tui;
 pub mod types;
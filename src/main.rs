@@ -1,7 +1,8 @@
 use clap::{ArgAction, Parser};
 use rebels::app::App;
 use rebels::relayer::Relayer;
-use rebels::types::AppResult;
+use rebels::simulator::Simulator;
+use rebels::types::{AppResult, TeamId};
 
 #[derive(Parser, Debug)]
 #[clap(name="B2Ball", about = "P(lanet)2P(lanet) basketball", author, version, long_about = None)]
@@ -16,17 +17,50 @@ struct Args {
     reset_world: bool,
     #[clap(long, short='f', action=ArgAction::SetFalse, help = "Disable generating local teams")]
     generate_local_world: bool,
-    #[clap(long, short='n', action=ArgAction::SetTrue, help = "Run in network relayer mode (no game)")]
+    #[clap(long, short='n', action=ArgAction::SetTrue, help = "Run in network relayer mode, also serving relay circuits (no game)")]
     relayer_mode: bool,
     #[clap(long, short = 'i', action=ArgAction::Set, help = "Set ip of seed node")]
     seed_ip: Option<String>,
+    #[clap(long, action=ArgAction::Set, help = "Set ip the relayer listens on")]
+    listen_ip: Option<String>,
+    #[clap(long, action=ArgAction::Set, help = "Set port the relayer listens on")]
+    listen_port: Option<u16>,
+    #[clap(long, action=ArgAction::Set, help = "Set message of the day sent by the relayer")]
+    motd: Option<String>,
+    #[clap(long, short = 'g', action=ArgAction::Set, help = "Simulate N games headlessly and print statistics (no game)")]
+    simulate: Option<u32>,
+    #[clap(long, action=ArgAction::Set, help = "Id of the home team in the saved world for the simulation")]
+    home_team: Option<TeamId>,
+    #[clap(long, action=ArgAction::Set, help = "Id of the away team in the saved world for the simulation")]
+    away_team: Option<TeamId>,
+    #[clap(long, short='j', action=ArgAction::SetTrue, help = "Print simulation statistics as JSON")]
+    json: bool,
 }
 
 #[tokio::main]
 async fn main() -> AppResult<()> {
     let args = Args::parse();
     if args.relayer_mode {
-        Relayer::new().run().await?;
+        Relayer::new(args.listen_ip, args.listen_port, args.motd)?
+            .run()
+            .await?;
+    } else if let Some(number_of_games) = args.simulate {
+        // Teams are taken from the saved world if both ids are given,
+        // otherwise they are generated from the seed.
+        let seed = args.seed.unwrap_or_else(rand::random);
+        let simulator = match (args.home_team, args.away_team) {
+            (Some(home_team_id), Some(away_team_id)) => {
+                Simulator::from_saved_world(seed, home_team_id, away_team_id)?
+            }
+            (None, None) => Simulator::from_seed(seed)?,
+            _ => return Err("Both --home-team and --away-team must be set".into()),
+        };
+        let report = simulator.run(number_of_games);
+        if args.json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            println!("{}", report.to_table());
+        }
     } else {
         App::new(
             args.seed,
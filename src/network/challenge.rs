@@ -0,0 +1,515 @@
+use super::constants::{CHALLENGE_GAME_DELAY, CHALLENGE_RETRY_INTERVAL, CHALLENGE_TIMEOUT};
+use super::types::{Challenge, ChallengeState};
+use crate::engine::constants::ENGINE_VERSION;
+use crate::engine::types::TeamInGame;
+use crate::types::{AppResult, ChallengeId, GameId, IdSystem, Tick};
+use libp2p::PeerId;
+use std::collections::HashMap;
+
+// The handshake is
+//   challenger -> Syn     -> challenged (user accepts or declines)
+//   challenger <- SynAck  <- challenged
+//   challenger -> Ack     -> challenged (both generate the game)
+// Either side can answer with Failed at any point. Messages are matched by
+// challenge id, so duplicates and retransmissions are harmless.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChallengeRole {
+    // Sends Syn and Ack, picks the game id and starting time.
+    Challenger,
+    // Receives Syn and answers with SynAck once the user accepts.
+    Challenged,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChallengeEvent {
+    // Publish the message on the challenge topic.
+    Send(Challenge),
+    // A new challenge is waiting for the user to accept or decline it.
+    Received(Challenge),
+    // The handshake completed. If confirm is set, the Ack must be sent after
+    // the game has been generated, otherwise the challenge must be failed.
+    GenerateGame { challenge: Challenge, confirm: bool },
+    // The challenge was declined, failed or expired.
+    Failed(Challenge),
+    // The game was generated, but the peer failed the challenge afterwards.
+    CancelGame(GameId),
+}
+
+#[derive(Debug, Clone)]
+struct PendingChallenge {
+    role: ChallengeRole,
+    // Latest version of the challenge, its state is our local state.
+    challenge: Challenge,
+    // Retransmitted until the peer moves the handshake forward.
+    outgoing: Option<Challenge>,
+    next_retry_at: Tick,
+}
+
+#[derive(Debug)]
+pub struct ChallengeManager {
+    local_peer_id: PeerId,
+    challenges: HashMap<ChallengeId, PendingChallenge>,
+    // Challenges we failed, kept until they expire to answer retransmissions.
+    failed: HashMap<ChallengeId, Challenge>,
+}
+
+impl ChallengeManager {
+    pub fn new(local_peer_id: PeerId) -> Self {
+        Self {
+            local_peer_id,
+            challenges: HashMap::new(),
+            failed: HashMap::new(),
+        }
+    }
+
+    // Challenges are sent directly to the peers, others never see them.
+    fn role(&self, challenge: &Challenge) -> Option<ChallengeRole> {
+        if challenge.home_peer_id == self.local_peer_id {
+            Some(ChallengeRole::Challenger)
+        } else if challenge.away_peer_id == self.local_peer_id {
+            Some(ChallengeRole::Challenged)
+        } else {
+            None
+        }
+    }
+
+    pub fn get(&self, challenge_id: &ChallengeId) -> Option<&Challenge> {
+        self.challenges
+            .get(challenge_id)
+            .map(|pending| &pending.challenge)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Challenge> {
+        self.challenges.values().map(|pending| &pending.challenge)
+    }
+
+    pub fn len(&self) -> usize {
+        self.challenges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.challenges.is_empty()
+    }
+
+    pub fn new_challenge(
+        &mut self,
+        away_peer_id: PeerId,
+        home_team: TeamInGame,
+        now: Tick,
+    ) -> Challenge {
+        let mut challenge = Challenge::new(self.local_peer_id, away_peer_id);
+        challenge.home_team = Some(home_team);
+        challenge.expires_at = now + CHALLENGE_TIMEOUT;
+        self.challenges.insert(
+            challenge.id,
+            PendingChallenge {
+                role: ChallengeRole::Challenger,
+                challenge: challenge.clone(),
+                outgoing: Some(challenge.clone()),
+                next_retry_at: now + CHALLENGE_RETRY_INTERVAL,
+            },
+        );
+        challenge
+    }
+
+    // Returns the SynAck to send to the challenger.
+    pub fn accept(
+        &mut self,
+        challenge_id: ChallengeId,
+        away_team: TeamInGame,
+        now: Tick,
+    ) -> AppResult<Challenge> {
+        let pending = self
+            .challenges
+            .get_mut(&challenge_id)
+            .ok_or("Challenge not found, it may have expired")?;
+        if pending.role != ChallengeRole::Challenged
+            || pending.challenge.state != ChallengeState::Syn
+        {
+            return Err("Challenge has already been answered".into());
+        }
+
+        let mut challenge = pending.challenge.clone();
+        challenge.away_team = Some(away_team);
+        challenge.state = ChallengeState::SynAck;
+        // Let the challenger check our engine version too.
+        challenge.engine_version = ENGINE_VERSION;
+        pending.challenge = challenge.clone();
+        pending.outgoing = Some(challenge.clone());
+        pending.next_retry_at = now + CHALLENGE_RETRY_INTERVAL;
+        Ok(challenge)
+    }
+
+    // Drops the challenge and returns the Failed message to send to the peer, if any.
+    pub fn fail(&mut self, challenge_id: ChallengeId, error_message: String) -> Option<Challenge> {
+        let pending = self.challenges.remove(&challenge_id)?;
+        let failed = pending.challenge.failed(error_message);
+        self.failed.insert(challenge_id, failed.clone());
+        Some(failed)
+    }
+
+    pub fn handle_message(&mut self, challenge: Challenge, now: Tick) -> Vec<ChallengeEvent> {
+        // The peer missed our Failed message and retransmitted.
+        if let Some(failed) = self.failed.get(&challenge.id) {
+            if challenge.state == ChallengeState::Failed {
+                return vec![];
+            }
+            return vec![ChallengeEvent::Send(failed.clone())];
+        }
+
+        if challenge.state == ChallengeState::Failed {
+            return self.handle_failed(challenge);
+        }
+
+        match (self.role(&challenge), challenge.state.clone()) {
+            (Some(ChallengeRole::Challenged), ChallengeState::Syn) => {
+                self.handle_syn(challenge, now)
+            }
+            (Some(ChallengeRole::Challenger), ChallengeState::SynAck) => {
+                self.handle_syn_ack(challenge, now)
+            }
+            (Some(ChallengeRole::Challenged), ChallengeState::Ack) => self.handle_ack(challenge),
+            // Messages for a role we do not have in this challenge.
+            _ => vec![],
+        }
+    }
+
+    fn handle_syn(&mut self, challenge: Challenge, now: Tick) -> Vec<ChallengeEvent> {
+        if let Some(pending) = self.challenges.get(&challenge.id) {
+            // The challenger did not get our SynAck yet.
+            if pending.challenge.state == ChallengeState::SynAck {
+                if let Some(outgoing) = pending.outgoing.clone() {
+                    return vec![ChallengeEvent::Send(outgoing)];
+                }
+            }
+            return vec![];
+        }
+
+        if let Err(err) = challenge.check_engine_version() {
+            let failed = challenge.failed(err.to_string());
+            return vec![
+                ChallengeEvent::Send(failed.clone()),
+                ChallengeEvent::Failed(failed),
+            ];
+        }
+
+        if challenge.expires_at <= now {
+            return vec![];
+        }
+
+        self.challenges.insert(
+            challenge.id,
+            PendingChallenge {
+                role: ChallengeRole::Challenged,
+                challenge: challenge.clone(),
+                outgoing: None,
+                next_retry_at: Tick::MAX,
+            },
+        );
+        vec![ChallengeEvent::Received(challenge)]
+    }
+
+    fn handle_syn_ack(&mut self, challenge: Challenge, now: Tick) -> Vec<ChallengeEvent> {
+        let pending = match self.challenges.get_mut(&challenge.id) {
+            Some(pending) => pending,
+            None => return vec![],
+        };
+
+        match pending.challenge.state {
+            ChallengeState::Syn => {
+                if let Err(err) = challenge.check_engine_version() {
+                    self.challenges.remove(&challenge.id);
+                    let failed = challenge.failed(err.to_string());
+                    self.failed.insert(failed.id, failed.clone());
+                    return vec![
+                        ChallengeEvent::Send(failed.clone()),
+                        ChallengeEvent::Failed(failed),
+                    ];
+                }
+
+                let mut ack = challenge;
+                ack.state = ChallengeState::Ack;
+                ack.game_id = Some(GameId::new());
+                ack.starting_at = Some(now + CHALLENGE_GAME_DELAY);
+                pending.challenge = ack.clone();
+                // The Ack is only resent when a duplicate SynAck shows it was lost.
+                pending.outgoing = None;
+                pending.next_retry_at = Tick::MAX;
+                vec![ChallengeEvent::GenerateGame {
+                    challenge: ack,
+                    confirm: true,
+                }]
+            }
+            ChallengeState::Ack => vec![ChallengeEvent::Send(pending.challenge.clone())],
+            _ => vec![],
+        }
+    }
+
+    fn handle_ack(&mut self, challenge: Challenge) -> Vec<ChallengeEvent> {
+        let pending = match self.challenges.get_mut(&challenge.id) {
+            Some(pending) => pending,
+            None => return vec![],
+        };
+        if pending.challenge.state != ChallengeState::SynAck {
+            return vec![];
+        }
+
+        pending.challenge = challenge.clone();
+        pending.outgoing = None;
+        pending.next_retry_at = Tick::MAX;
+        vec![ChallengeEvent::GenerateGame {
+            challenge,
+            confirm: false,
+        }]
+    }
+
+    fn handle_failed(&mut self, challenge: Challenge) -> Vec<ChallengeEvent> {
+        let pending = match self.challenges.remove(&challenge.id) {
+            Some(pending) => pending,
+            None => return vec![],
+        };
+
+        let mut events = vec![];
+        if pending.challenge.state == ChallengeState::Ack {
+            if let Some(game_id) = pending.challenge.game_id {
+                events.push(ChallengeEvent::CancelGame(game_id));
+            }
+        }
+        events.push(ChallengeEvent::Failed(challenge));
+        events
+    }
+
+    // Retransmits unanswered messages, expires stale challenges and forgets
+    // completed ones once their game has started.
+    pub fn tick(&mut self, now: Tick) -> Vec<ChallengeEvent> {
+        let mut events = vec![];
+        let mut expired = vec![];
+
+        for (id, pending) in self.challenges.iter_mut() {
+            if pending.challenge.state == ChallengeState::Ack {
+                if pending.challenge.starting_at.unwrap_or_default() <= now {
+                    expired.push(*id);
+                }
+                continue;
+            }
+
+            if pending.challenge.expires_at <= now {
+                expired.push(*id);
+                let failed = pending.challenge.failed("Challenge expired".to_string());
+                events.push(ChallengeEvent::Send(failed.clone()));
+                events.push(ChallengeEvent::Failed(failed));
+                continue;
+            }
+
+            if let Some(outgoing) = pending.outgoing.as_ref() {
+                if pending.next_retry_at <= now {
+                    events.push(ChallengeEvent::Send(outgoing.clone()));
+                    pending.next_retry_at = now + CHALLENGE_RETRY_INTERVAL;
+                }
+            }
+        }
+
+        for id in expired {
+            self.challenges.remove(&id);
+        }
+        self.failed.retain(|_, failed| failed.expires_at > now);
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChallengeEvent, ChallengeManager};
+    use crate::engine::types::TeamInGame;
+    use crate::network::constants::{CHALLENGE_RETRY_INTERVAL, CHALLENGE_TIMEOUT};
+    use crate::network::types::{Challenge, ChallengeState};
+    use libp2p::PeerId;
+
+    fn peers() -> (ChallengeManager, ChallengeManager) {
+        (
+            ChallengeManager::new(PeerId::random()),
+            ChallengeManager::new(PeerId::random()),
+        )
+    }
+
+    fn sent(events: &[ChallengeEvent]) -> Vec<Challenge> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                ChallengeEvent::Send(challenge) => Some(challenge.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn generated(events: &[ChallengeEvent]) -> Option<(Challenge, bool)> {
+        events.iter().find_map(|event| match event {
+            ChallengeEvent::GenerateGame { challenge, confirm } => {
+                Some((challenge.clone(), *confirm))
+            }
+            _ => None,
+        })
+    }
+
+    // Runs the handshake up to the Ack sent by the challenger.
+    fn handshake(
+        home: &mut ChallengeManager,
+        away: &mut ChallengeManager,
+    ) -> (Challenge, Challenge) {
+        let syn = home.new_challenge(away.local_peer_id, TeamInGame::default(), 0);
+        let events = away.handle_message(syn.clone(), 0);
+        assert_eq!(events, vec![ChallengeEvent::Received(syn.clone())]);
+
+        let syn_ack = away.accept(syn.id, TeamInGame::default(), 0).unwrap();
+        assert_eq!(syn_ack.state, ChallengeState::SynAck);
+
+        let events = home.handle_message(syn_ack.clone(), 0);
+        let (ack, confirm) = generated(&events).unwrap();
+        assert!(confirm);
+        assert_eq!(ack.state, ChallengeState::Ack);
+        assert!(ack.game_id.is_some());
+        (syn_ack, ack)
+    }
+
+    #[test]
+    fn test_challenge_happy_path() {
+        let (mut home, mut away) = peers();
+        let (_, ack) = handshake(&mut home, &mut away);
+
+        let events = away.handle_message(ack.clone(), 0);
+        let (generated_ack, confirm) = generated(&events).unwrap();
+        assert!(!confirm);
+        assert_eq!(generated_ack.game_id, ack.game_id);
+
+        // Other peers are not part of the challenge.
+        let mut other = ChallengeManager::new(PeerId::random());
+        assert!(other.handle_message(ack.clone(), 0).is_empty());
+        assert!(other.is_empty());
+
+        // Completed challenges are dropped once the game starts.
+        let starting_at = ack.starting_at.unwrap();
+        assert!(home.tick(starting_at).is_empty());
+        assert!(away.tick(starting_at).is_empty());
+        assert_eq!(home.len(), 0);
+        assert_eq!(away.len(), 0);
+    }
+
+    #[test]
+    fn test_challenge_lost_ack_is_retransmitted() {
+        let (mut home, mut away) = peers();
+        let (syn_ack, ack) = handshake(&mut home, &mut away);
+
+        // The Ack is lost, so the challenged peer keeps resending its SynAck.
+        assert!(sent(&away.tick(CHALLENGE_RETRY_INTERVAL - 1)).is_empty());
+        let resent = sent(&away.tick(CHALLENGE_RETRY_INTERVAL));
+        assert_eq!(resent, vec![syn_ack]);
+
+        // The challenger answers with the same Ack without generating a new game.
+        let events = home.handle_message(resent[0].clone(), CHALLENGE_RETRY_INTERVAL);
+        assert!(generated(&events).is_none());
+        assert_eq!(sent(&events), vec![ack.clone()]);
+
+        assert!(generated(&away.handle_message(ack.clone(), 0)).is_some());
+        assert!(away.handle_message(ack, 0).is_empty());
+    }
+
+    #[test]
+    fn test_challenge_duplicate_messages_are_ignored() {
+        let (mut home, mut away) = peers();
+        let syn = home.new_challenge(away.local_peer_id, TeamInGame::default(), 0);
+        assert_eq!(away.handle_message(syn.clone(), 0).len(), 1);
+        // The user is still deciding, a retransmitted Syn must not show up twice.
+        assert!(away.handle_message(syn.clone(), 0).is_empty());
+
+        let syn_ack = away.accept(syn.id, TeamInGame::default(), 0).unwrap();
+        assert!(away.accept(syn.id, TeamInGame::default(), 0).is_err());
+        // The SynAck got lost and the challenger resent the Syn.
+        assert_eq!(sent(&away.handle_message(syn, 0)), vec![syn_ack]);
+    }
+
+    #[test]
+    fn test_challenge_expires() {
+        let (mut home, mut away) = peers();
+        let syn = home.new_challenge(away.local_peer_id, TeamInGame::default(), 0);
+        away.handle_message(syn.clone(), 0);
+
+        // The Syn is retransmitted while waiting for an answer.
+        assert_eq!(
+            sent(&home.tick(CHALLENGE_RETRY_INTERVAL)),
+            vec![syn.clone()]
+        );
+
+        let events = away.tick(CHALLENGE_TIMEOUT);
+        let failed = sent(&events);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].state, ChallengeState::Failed);
+        assert!(events.contains(&ChallengeEvent::Failed(failed[0].clone())));
+        assert_eq!(away.len(), 0);
+        assert!(away.accept(syn.id, TeamInGame::default(), 0).is_err());
+
+        home.tick(CHALLENGE_TIMEOUT);
+        assert_eq!(home.len(), 0);
+        // A late Syn is not shown to the user.
+        assert!(away.handle_message(syn, CHALLENGE_TIMEOUT).is_empty());
+    }
+
+    #[test]
+    fn test_challenge_failed_after_ack_cancels_game() {
+        let (mut home, mut away) = peers();
+        let (_, ack) = handshake(&mut home, &mut away);
+        away.handle_message(ack.clone(), 0);
+
+        // The challenged peer could not generate the game.
+        let failed = away
+            .fail(ack.id, "Cannot generate game".to_string())
+            .unwrap();
+        let events = home.handle_message(failed.clone(), 0);
+        assert_eq!(
+            events,
+            vec![
+                ChallengeEvent::CancelGame(ack.game_id.unwrap()),
+                ChallengeEvent::Failed(failed.clone())
+            ]
+        );
+        assert!(home.handle_message(failed, 0).is_empty());
+    }
+
+    #[test]
+    fn test_challenge_declined_syn_is_failed_again() {
+        let (mut home, mut away) = peers();
+        let syn = home.new_challenge(away.local_peer_id, TeamInGame::default(), 0);
+        away.handle_message(syn.clone(), 0);
+
+        let failed = away.fail(syn.id, "Challenge declined".to_string()).unwrap();
+        assert_eq!(failed.state, ChallengeState::Failed);
+        assert_eq!(away.len(), 0);
+
+        // The Failed message got lost and the challenger retransmitted the Syn.
+        let events = away.handle_message(syn.clone(), CHALLENGE_RETRY_INTERVAL);
+        assert_eq!(events, vec![ChallengeEvent::Send(failed.clone())]);
+        assert!(away.accept(syn.id, TeamInGame::default(), 0).is_err());
+
+        let events = home.handle_message(failed.clone(), CHALLENGE_RETRY_INTERVAL);
+        assert_eq!(events, vec![ChallengeEvent::Failed(failed.clone())]);
+        assert!(away
+            .handle_message(failed, CHALLENGE_RETRY_INTERVAL)
+            .is_empty());
+
+        // The tombstone is dropped once the challenge expires.
+        away.tick(CHALLENGE_TIMEOUT);
+        assert!(away.handle_message(syn, CHALLENGE_TIMEOUT).is_empty());
+    }
+
+    #[test]
+    fn test_challenge_engine_version_mismatch() {
+        let (mut home, mut away) = peers();
+        let mut syn = home.new_challenge(away.local_peer_id, TeamInGame::default(), 0);
+        syn.engine_version += 1;
+        let failed = sent(&away.handle_message(syn, 0));
+        assert_eq!(failed[0].state, ChallengeState::Failed);
+        assert_eq!(away.len(), 0);
+
+        home.handle_message(failed[0].clone(), 0);
+        assert_eq!(home.len(), 0);
+    }
+}
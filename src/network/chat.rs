@@ -0,0 +1,212 @@
+use super::constants::MAX_CHAT_HISTORY;
+use crate::store::{load_from_json, save_to_json, PERSISTED_CHAT_FILENAME};
+use crate::types::{AppResult, Tick};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+// Teams belong to a single peer, so a team channel is the conversation with its peer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Conversation {
+    #[default]
+    Global,
+    Peer(PeerId),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChatAuthor {
+    Own,
+    Peer(PeerId),
+    // Command output and delivery errors.
+    System,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub timestamp: Tick,
+    pub author: ChatAuthor,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ConversationLog {
+    conversation: Conversation,
+    messages: Vec<ChatMessage>,
+    unread: usize,
+}
+
+// Conversations are persisted separately from the world, which is shared
+// with other peers while the chat is not.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ChatHistory {
+    conversations: Vec<ConversationLog>,
+    muted: HashSet<PeerId>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl ChatHistory {
+    pub fn load() -> AppResult<Self> {
+        load_from_json(PERSISTED_CHAT_FILENAME)
+    }
+
+    // A failed save is retried with the next message only.
+    pub fn save(&mut self) -> AppResult<()> {
+        if self.dirty {
+            self.dirty = false;
+            save_to_json(PERSISTED_CHAT_FILENAME, self)?;
+        }
+        Ok(())
+    }
+
+    fn log_mut(&mut self, conversation: Conversation) -> &mut ConversationLog {
+        let idx = match self
+            .conversations
+            .iter()
+            .position(|log| log.conversation == conversation)
+        {
+            Some(idx) => idx,
+            None => {
+                self.conversations.push(ConversationLog {
+                    conversation,
+                    ..Default::default()
+                });
+                self.conversations.len() - 1
+            }
+        };
+        &mut self.conversations[idx]
+    }
+
+    // Returns false if the message was dropped because its author is muted.
+    pub fn push(&mut self, conversation: Conversation, message: ChatMessage) -> bool {
+        if let ChatAuthor::Peer(peer_id) = message.author {
+            if self.muted.contains(&peer_id) {
+                return false;
+            }
+        }
+        let is_unread = matches!(message.author, ChatAuthor::Peer(_));
+        let log = self.log_mut(conversation);
+        log.messages.push(message);
+        if log.messages.len() > MAX_CHAT_HISTORY {
+            log.messages.remove(0);
+        }
+        if is_unread {
+            log.unread += 1;
+        }
+        self.dirty = true;
+        true
+    }
+
+    pub fn messages(&self, conversation: Conversation) -> &[ChatMessage] {
+        self.conversations
+            .iter()
+            .find(|log| log.conversation == conversation)
+            .map(|log| log.messages.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn clear(&mut self, conversation: Conversation) {
+        self.conversations
+            .retain(|log| log.conversation != conversation);
+        self.dirty = true;
+    }
+
+    pub fn unread(&self, conversation: Conversation) -> usize {
+        self.conversations
+            .iter()
+            .find(|log| log.conversation == conversation)
+            .map(|log| log.unread)
+            .unwrap_or_default()
+    }
+
+    pub fn total_unread(&self) -> usize {
+        self.conversations.iter().map(|log| log.unread).sum()
+    }
+
+    pub fn mark_read(&mut self, conversation: Conversation) {
+        if let Some(log) = self
+            .conversations
+            .iter_mut()
+            .find(|log| log.conversation == conversation && log.unread > 0)
+        {
+            log.unread = 0;
+            self.dirty = true;
+        }
+    }
+
+    pub fn is_muted(&self, peer_id: &PeerId) -> bool {
+        self.muted.contains(peer_id)
+    }
+
+    // Returns true if the peer is now muted.
+    pub fn toggle_mute(&mut self, peer_id: PeerId) -> bool {
+        self.dirty = true;
+        if self.muted.remove(&peer_id) {
+            return false;
+        }
+        self.muted.insert(peer_id);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChatAuthor, ChatHistory, ChatMessage, Conversation};
+    use crate::network::constants::MAX_CHAT_HISTORY;
+    use libp2p::PeerId;
+
+    fn message(author: ChatAuthor, text: &str) -> ChatMessage {
+        ChatMessage {
+            timestamp: 0,
+            author,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_conversations() {
+        let peer_id = PeerId::random();
+        let direct = Conversation::Peer(peer_id);
+        let mut chat = ChatHistory::default();
+
+        chat.push(Conversation::Global, message(ChatAuthor::Own, "Hello"));
+        chat.push(direct, message(ChatAuthor::Peer(peer_id), "Ahoy"));
+        chat.push(direct, message(ChatAuthor::Peer(peer_id), "Matey"));
+        assert_eq!(chat.messages(Conversation::Global).len(), 1);
+        assert_eq!(chat.messages(direct).len(), 2);
+        // Own messages are never unread.
+        assert_eq!(chat.unread(Conversation::Global), 0);
+        assert_eq!(chat.unread(direct), 2);
+        assert_eq!(chat.total_unread(), 2);
+
+        chat.mark_read(direct);
+        assert_eq!(chat.total_unread(), 0);
+
+        for _ in 0..MAX_CHAT_HISTORY {
+            chat.push(direct, message(ChatAuthor::Own, "Spam"));
+        }
+        assert_eq!(chat.messages(direct).len(), MAX_CHAT_HISTORY);
+
+        let chat: ChatHistory =
+            serde_json::from_str(&serde_json::to_string(&chat).unwrap()).unwrap();
+        assert_eq!(chat.messages(direct).len(), MAX_CHAT_HISTORY);
+    }
+
+    #[test]
+    fn test_muted_peers_are_dropped() {
+        let peer_id = PeerId::random();
+        let mut chat = ChatHistory::default();
+        assert!(chat.toggle_mute(peer_id));
+        assert!(!chat.push(
+            Conversation::Global,
+            message(ChatAuthor::Peer(peer_id), "Spam")
+        ));
+        assert!(chat.messages(Conversation::Global).is_empty());
+
+        assert!(!chat.toggle_mute(peer_id));
+        assert!(chat.push(
+            Conversation::Global,
+            message(ChatAuthor::Peer(peer_id), "Sorry")
+        ));
+    }
+}
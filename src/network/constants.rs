@@ -1,13 +1,54 @@
+use crate::types::{Tick, MINUTES, SECONDS};
+use std::time::Duration;
+
 pub struct SubscriptionTopic {}
 
 impl SubscriptionTopic {
     pub const TEAM: &'static str = "rebels-b2b-team";
-    pub const CHALLENGE: &'static str = "rebels-b2b-challenge";
     pub const MSG: &'static str = "rebels-b2b-msg";
     pub const GAME: &'static str = "rebels-b2b-game";
     pub const SEED_INFO: &'static str = "rebels-b2b-seed";
+    pub const RESULT: &'static str = "rebels-b2b-result";
 }
 
+pub const PROTOCOL_NAME: &str = "/rebels/b2b/3";
+pub const IDENTIFY_PROTOCOL_NAME: &str = "/rebels/id/1";
+
 pub const DEFAULT_PORT: u16 = 37202;
-pub const DEFAULT_PORT_BACKUP: u16 = 37203;
 pub const SEED_ADDRESS: &'static str = "/ip4/85.214.130.204/tcp/37202";
+// The seed is dialed over QUIC first, SEED_ADDRESS is the fallback when UDP is blocked.
+pub const SEED_QUIC_ADDRESS: &str = "/ip4/85.214.130.204/udp/37202/quic-v1";
+
+// Pending challenges are dropped if the handshake does not complete in time.
+pub const CHALLENGE_TIMEOUT: Tick = 2 * MINUTES;
+// Unanswered handshake messages are retransmitted at this interval.
+pub const CHALLENGE_RETRY_INTERVAL: Tick = 10 * SECONDS;
+// Delay between the challenge being accepted and the game starting.
+pub const CHALLENGE_GAME_DELAY: Tick = 2 * MINUTES;
+
+// Received teams can bump their version by this much, plus one per second since the last update.
+pub const MAX_TEAM_VERSION_JUMP: u64 = 100;
+// Upper bound on what a team can earn between two updates, for each hour in between.
+pub const MAX_INCOME_PER_HOUR: u32 = 100_000;
+// Peers sending this many invalid teams are banned until restart.
+pub const MAX_PEER_OFFENCES: usize = 3;
+
+// Teams not announced again within this time are dropped from the seed directory.
+pub const DIRECTORY_ENTRY_TTL: Tick = 10 * MINUTES;
+// The seed sends at most this many teams, most recently seen first.
+pub const MAX_DIRECTORY_ENTRIES: usize = 64;
+// Interval between two dumps of the relayer stats.
+pub const RELAYER_STATS_INTERVAL: Tick = MINUTES;
+// Relayed connections only carry messages until DCUtR upgrades them, but a
+// game between two peers that cannot hole punch goes through the relay.
+pub const RELAY_MAX_CIRCUIT_DURATION: Duration = Duration::from_secs(60 * 60);
+pub const RELAY_MAX_CIRCUIT_BYTES: u64 = 1 << 26;
+
+// Messages kept for each chat conversation.
+pub const MAX_CHAT_HISTORY: usize = 200;
+
+// Snapshots of peer teams kept on disk to be played while their peer is offline.
+pub const MAX_GHOST_TEAMS: usize = 64;
+
+// Co-signed game results kept on disk, the oldest games are dropped first.
+pub const MAX_GAME_RESULTS: usize = 4096;
@@ -0,0 +1,284 @@
+use super::constants::MAX_GHOST_TEAMS;
+use super::types::NetworkTeam;
+use crate::engine::game::GameSummary;
+use crate::store::{load_from_json, save_to_json, PERSISTED_GHOSTS_FILENAME};
+use crate::types::{AppResult, GameId, TeamId, Tick};
+use crate::world::world::World;
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// The last team received from a peer. While the peer is offline, the team
+// can still be challenged and plays with the tactics it had then.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GhostTeam {
+    pub network_team: NetworkTeam,
+    pub saved_at: Tick,
+}
+
+impl GhostTeam {
+    pub fn peer_id(&self) -> Option<PeerId> {
+        self.network_team.team.peer_id
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GhostTeams {
+    teams: HashMap<TeamId, GhostTeam>,
+    // Games being played against ghosts, with the peer owning the ghost.
+    games: HashMap<GameId, PeerId>,
+    // Results waiting for the owner of the ghost to come online.
+    results: Vec<(PeerId, GameSummary)>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl GhostTeams {
+    pub fn load() -> AppResult<Self> {
+        load_from_json(PERSISTED_GHOSTS_FILENAME)
+    }
+
+    pub fn save(&mut self) -> AppResult<()> {
+        if self.dirty {
+            self.dirty = false;
+            save_to_json(PERSISTED_GHOSTS_FILENAME, self)?;
+        }
+        Ok(())
+    }
+
+    // Keeps the snapshot, dropping the oldest one when full.
+    pub fn record(&mut self, network_team: NetworkTeam, now: Tick) {
+        if network_team.team.peer_id.is_none() {
+            return;
+        }
+        self.teams.insert(
+            network_team.team.id,
+            GhostTeam {
+                network_team,
+                saved_at: now,
+            },
+        );
+        if self.teams.len() > MAX_GHOST_TEAMS {
+            if let Some(oldest) = self
+                .teams
+                .values()
+                .min_by_key(|ghost| ghost.saved_at)
+                .map(|ghost| ghost.network_team.team.id)
+            {
+                self.teams.remove(&oldest);
+            }
+        }
+        self.dirty = true;
+    }
+
+    pub fn get(&self, team_id: &TeamId) -> Option<&GhostTeam> {
+        self.teams.get(team_id)
+    }
+
+    pub fn names(&self) -> HashMap<TeamId, String> {
+        self.teams
+            .iter()
+            .map(|(team_id, ghost)| (*team_id, ghost.network_team.team.name.clone()))
+            .collect()
+    }
+
+    pub fn start_game(&mut self, game_id: GameId, peer_id: PeerId) {
+        self.games.insert(game_id, peer_id);
+        self.dirty = true;
+    }
+
+    pub fn is_ghost_game(&self, game_id: &GameId) -> bool {
+        self.games.contains_key(game_id)
+    }
+
+    pub fn is_playing(&self, peer_id: &PeerId) -> bool {
+        self.games.values().any(|id| id == peer_id)
+    }
+
+    // Games end in the world, which keeps the summary of our past games.
+    // Returns the owners of the ghosts that finished playing.
+    pub fn collect_results(&mut self, world: &World) -> Vec<PeerId> {
+        let finished: Vec<(GameId, PeerId)> = self
+            .games
+            .iter()
+            .filter(|(game_id, _)| !world.games.contains_key(game_id))
+            .map(|(game_id, peer_id)| (*game_id, *peer_id))
+            .collect();
+        for (game_id, peer_id) in finished.iter() {
+            self.games.remove(game_id);
+            if let Some(summary) = world.past_games.get(game_id) {
+                self.results.push((*peer_id, summary.clone()));
+            }
+            self.dirty = true;
+        }
+        finished.into_iter().map(|(_, peer_id)| peer_id).collect()
+    }
+
+    pub fn has_results(&self, peer_id: &PeerId) -> bool {
+        self.results.iter().any(|(id, _)| id == peer_id)
+    }
+
+    pub fn take_results(&mut self, peer_id: &PeerId) -> Vec<GameSummary> {
+        let (taken, kept) = self.results.drain(..).partition(|(id, _)| id == peer_id);
+        self.results = kept;
+        if !taken.is_empty() {
+            self.dirty = true;
+        }
+        taken.into_iter().map(|(_, summary)| summary).collect()
+    }
+
+    // Results that could not be delivered are retried on the next connection.
+    pub fn push_result(&mut self, peer_id: PeerId, summary: GameSummary) {
+        self.results.push((peer_id, summary));
+        self.dirty = true;
+    }
+}
+
+// A result from the owner of a ghost counts only if it was a game between our
+// team and a team of the sender, like signed results only count for their peers.
+pub fn validate_result(world: &World, peer_id: PeerId, summary: &GameSummary) -> AppResult<()> {
+    let own_team_id = world.own_team_id;
+    let other_team_id = if summary.home_team_id == own_team_id {
+        summary.away_team_id
+    } else if summary.away_team_id == own_team_id {
+        summary.home_team_id
+    } else {
+        return Err("Game was not against our team".into());
+    };
+    let other_team = world.get_team_or_err(other_team_id)?;
+    if other_team_id == own_team_id || other_team.peer_id != Some(peer_id) {
+        return Err(format!("Game was not played by a team of {}", peer_id).into());
+    }
+
+    let home_team = world.get_team_or_err(summary.home_team_id)?;
+    let away_team = world.get_team_or_err(summary.away_team_id)?;
+    if summary.home_team_name != home_team.name || summary.away_team_name != away_team.name {
+        return Err("Game result does not match the teams".into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate_result, GhostTeams};
+    use crate::engine::game::GameSummary;
+    use crate::network::constants::MAX_GHOST_TEAMS;
+    use crate::network::types::NetworkTeam;
+    use crate::types::{GameId, IdSystem, TeamId};
+    use crate::world::constants::DEFAULT_PLANET_ID;
+    use crate::world::world::World;
+    use libp2p::PeerId;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn test_ghost_teams() {
+        let mut network_team = NetworkTeam::test_team(None);
+        let team_id = network_team.team.id;
+
+        let mut ghosts = GhostTeams::default();
+        // Own teams are not ghosts.
+        ghosts.record(network_team.clone(), 0);
+        assert!(ghosts.get(&team_id).is_none());
+
+        let peer_id = PeerId::random();
+        network_team.set_peer_id(peer_id);
+        ghosts.record(network_team.clone(), 0);
+        assert_eq!(ghosts.get(&team_id).unwrap().peer_id(), Some(peer_id));
+
+        for idx in 1..=MAX_GHOST_TEAMS {
+            let mut other = network_team.clone();
+            other.team.id = TeamId::new();
+            ghosts.record(other, idx as u128);
+        }
+        // The oldest snapshot made room.
+        assert_eq!(ghosts.names().len(), MAX_GHOST_TEAMS);
+        assert!(ghosts.get(&team_id).is_none());
+
+        let ghosts: GhostTeams =
+            serde_json::from_str(&serde_json::to_string(&ghosts).unwrap()).unwrap();
+        assert_eq!(ghosts.names().len(), MAX_GHOST_TEAMS);
+    }
+
+    #[test]
+    fn test_ghost_results() {
+        let mut world = World::new(None);
+        let peer_id = PeerId::random();
+        let game_id = GameId::new();
+        let mut ghosts = GhostTeams::default();
+        ghosts.start_game(game_id, peer_id);
+        assert!(ghosts.is_playing(&peer_id));
+
+        world.past_games.insert(
+            game_id,
+            GameSummary {
+                id: game_id,
+                home_team_id: TeamId::new(),
+                away_team_id: TeamId::new(),
+                home_team_name: "Home".to_string(),
+                away_team_name: "Ghost".to_string(),
+                home_score: 3,
+                away_score: 1,
+                location: world.planets.keys().next().unwrap().clone(),
+                attendance: 0,
+                overtimes: 0,
+            },
+        );
+        assert_eq!(ghosts.collect_results(&world), vec![peer_id]);
+        assert!(!ghosts.is_playing(&peer_id));
+        assert!(ghosts.has_results(&peer_id));
+
+        assert!(ghosts.take_results(&PeerId::random()).is_empty());
+        let results = ghosts.take_results(&peer_id);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, game_id);
+        assert!(!ghosts.has_results(&peer_id));
+    }
+
+    #[test]
+    fn test_validate_ghost_result() {
+        let mut world = World::new(None);
+        let rng = &mut ChaCha8Rng::seed_from_u64(0);
+        let own_team_id = world
+            .generate_random_team(
+                rng,
+                DEFAULT_PLANET_ID.clone(),
+                "Own".to_string(),
+                "Own ship".to_string(),
+            )
+            .unwrap();
+        world.own_team_id = own_team_id;
+        let peer_id = PeerId::random();
+        let mut network_team = NetworkTeam::test_team(Some(peer_id));
+        world.add_network_team(network_team.clone()).unwrap();
+
+        let summary = GameSummary {
+            id: GameId::new(),
+            home_team_id: own_team_id,
+            away_team_id: network_team.team.id,
+            home_team_name: "Own".to_string(),
+            away_team_name: network_team.team.name.clone(),
+            home_score: 3,
+            away_score: 1,
+            location: DEFAULT_PLANET_ID.clone(),
+            attendance: 0,
+            overtimes: 0,
+        };
+        assert!(validate_result(&world, peer_id, &summary).is_ok());
+        // Only the owner of the other team can send it.
+        assert!(validate_result(&world, PeerId::random(), &summary).is_err());
+
+        let mut forged = summary.clone();
+        forged.away_team_id = own_team_id;
+        forged.away_team_name = "Own".to_string();
+        assert!(validate_result(&world, peer_id, &forged).is_err());
+
+        let mut forged = summary.clone();
+        forged.home_team_id = TeamId::new();
+        assert!(validate_result(&world, peer_id, &forged).is_err());
+
+        let mut forged = summary;
+        forged.away_team_name = "Someone else".to_string();
+        assert!(validate_result(&world, peer_id, &forged).is_err());
+    }
+}
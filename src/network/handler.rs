@@ -1,29 +1,69 @@
+use super::challenge::ChallengeManager;
 use super::constants::*;
 use super::network_callback::NetworkCallbackPreset;
-use super::types::{Challenge, ChallengeState, NetworkGame, NetworkTeam, SeedInfo};
+use super::protocol::{
+    NetworkEvent, PeerRequest, PeerResponse, RebelsBehaviour, RebelsBehaviourEvent,
+};
+use super::results::{GameResult, ResultSignature, SignedGameResult};
+use super::types::{
+    Challenge, NetworkTeam, PeerTransport, SeedInfo, TeamAnnouncement, TeamTopicMessage,
+};
+use super::validation::PeerValidator;
+use super::wire::{ChallengeDto, Envelope, MessageKind};
+use crate::engine::command::GameCommand;
 use crate::engine::types::TeamInGame;
+use crate::types::AppResult;
+use crate::types::ChallengeId;
 use crate::types::TeamId;
-use crate::types::{AppResult, GameId};
 use crate::types::{SystemTimeTick, Tick};
 use crate::world::world::World;
+use futures::future::Either;
+use libp2p::core::muxing::StreamMuxerBox;
+use libp2p::core::transport::{ListenerId, OrTransport};
 use libp2p::core::upgrade::Version;
 use libp2p::gossipsub::{self, IdentTopic, MessageId};
-use libp2p::swarm::{Config, SwarmEvent};
-use libp2p::{identity, noise, tcp, yamux, PeerId, Transport};
-use libp2p::{Multiaddr, Swarm};
+use libp2p::multiaddr::Protocol;
+use libp2p::request_response::{self, ProtocolSupport, RequestId, ResponseChannel};
+use libp2p::swarm::behaviour::toggle::Toggle;
+use libp2p::swarm::dial_opts::{DialOpts, PeerCondition};
+use libp2p::swarm::{Config, ConnectionId, SwarmEvent};
+use libp2p::{
+    autonat, dcutr, identify, identity, mdns, noise, quic, relay, tcp, yamux, PeerId, Transport,
+};
+use libp2p::{Multiaddr, StreamProtocol, Swarm};
+use serde::Serialize;
 use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt::Debug;
 use std::hash::{Hash, Hasher};
 use std::time::Duration;
-use void::Void;
+
+// To content-address message, we can take the hash of message and use it as an ID.
+fn message_id(data: &[u8]) -> MessageId {
+    let mut s = DefaultHasher::new();
+    data.hash(&mut s);
+    MessageId::from(s.finish().to_string())
+}
 
 pub struct NetworkHandler {
-    pub swarm: Swarm<gossipsub::Behaviour>,
+    pub swarm: Swarm<RebelsBehaviour>,
     pub address: Multiaddr,
-    challenges: HashMap<PeerId, Challenge>,
-    pub seed_address: Multiaddr,
+    pub challenges: ChallengeManager,
+    pub validator: PeerValidator,
+    pub seed_addresses: Vec<Multiaddr>,
+    // Pending dial to the seed, with the index of the address being tried.
+    seed_dial: Option<(ConnectionId, usize)>,
+    // Kept to tell what was lost when a request fails.
+    outbound_requests: HashMap<RequestId, (PeerId, PeerRequest)>,
+    inbound_channels: HashMap<RequestId, ResponseChannel<Envelope>>,
+    // The seed and the address we are connected to it at.
+    seed_peer: Option<(PeerId, Multiaddr)>,
+    // Set once the seed is known to serve relay circuits.
+    relay_peer_id: Option<PeerId>,
+    relay_listener: Option<ListenerId>,
+    // Signs the results of our network games.
+    local_key: identity::Keypair,
 }
 
 impl Debug for NetworkHandler {
@@ -36,44 +76,68 @@ impl Debug for NetworkHandler {
 }
 
 impl NetworkHandler {
-    pub fn new(seed_ip: Option<String>) -> Result<Self, Box<dyn Error>> {
-        env_logger::init();
-        let local_key = identity::Keypair::generate_ed25519();
+    pub fn new(
+        seed_ip: Option<String>,
+        local_key: identity::Keypair,
+    ) -> Result<Self, Box<dyn Error>> {
+        let seed_addresses = match seed_ip {
+            Some(ip) => vec![
+                format!("/ip4/{ip}/udp/{DEFAULT_PORT}/quic-v1")
+                    .parse()
+                    .expect("Invalid provided seed ip."),
+                format!("/ip4/{ip}/tcp/{DEFAULT_PORT}")
+                    .parse()
+                    .expect("Invalid provided seed ip."),
+            ],
+            None => vec![SEED_QUIC_ADDRESS.parse()?, SEED_ADDRESS.parse()?],
+        };
+        Self::with_addresses(local_key, seed_addresses, vec![], true, false)
+    }
+
+    // Listens on the default ports unless listen addresses are given.
+    // Seed addresses are tried in order until one can be dialed.
+    pub fn with_addresses(
+        local_key: identity::Keypair,
+        seed_addresses: Vec<Multiaddr>,
+        listen_addresses: Vec<Multiaddr>,
+        enable_mdns: bool,
+        enable_relay_server: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        // The handler is rebuilt when the identity is rotated.
+        let _ = env_logger::try_init();
         let local_peer_id = PeerId::from(local_key.public());
 
-        let tcp_transport = tcp::tokio::Transport::default()
+        // Hole punching needs outgoing connections to use the listening port.
+        let (relay_transport, relay_client) = relay::client::new(local_peer_id);
+        let tcp_transport = relay_transport
+            .or_transport(tcp::tokio::Transport::new(
+                tcp::Config::default().port_reuse(true),
+            ))
             .upgrade(Version::V1Lazy)
             .authenticate(noise::Config::new(&local_key)?)
             .multiplex(yamux::Config::default())
             .timeout(std::time::Duration::from_secs(20))
             .boxed();
 
-        // let quic_transport = quic::tokio::Transport::new(quic::Config::new(&local_key));
-        // let transport = OrTransport::new(quic_transport, tcp_transport)
-        //     .map(|either_output, _| match either_output {
-        //         Either::Left((peer_id, muxer)) => (peer_id, StreamMuxerBox::new(muxer)),
-        //         Either::Right((peer_id, muxer)) => (peer_id, StreamMuxerBox::new(muxer)),
-        //     })
-        //     .boxed();
-
-        // To content-address message, we can take the hash of message and use it as an ID.
-        let message_id_fn = |message: &gossipsub::Message| {
-            let mut s = DefaultHasher::new();
-            message.data.hash(&mut s);
-            gossipsub::MessageId::from(s.finish().to_string())
-        };
+        let quic_transport = quic::tokio::Transport::new(quic::Config::new(&local_key));
+        let transport = OrTransport::new(quic_transport, tcp_transport)
+            .map(|either_output, _| match either_output {
+                Either::Left((peer_id, muxer)) => (peer_id, StreamMuxerBox::new(muxer)),
+                Either::Right((peer_id, muxer)) => (peer_id, StreamMuxerBox::new(muxer)),
+            })
+            .boxed();
 
         // Set a custom gossipsub configuration
         let gossipsub_config = gossipsub::ConfigBuilder::default()
             .heartbeat_interval(Duration::from_secs(1)) // This is set to aid debugging by not cluttering the log space
             .validation_mode(gossipsub::ValidationMode::Strict) // This sets the kind of message validation. The default is Strict (enforce message signing)
-            .message_id_fn(message_id_fn) // content-address messages. No two messages of the same content will be propagated.
+            .message_id_fn(|message| message_id(&message.data)) // content-address messages. No two messages of the same content will be propagated.
             .build()
             .expect("Valid config");
 
         // build a gossipsub network behaviour
         let mut gossipsub = gossipsub::Behaviour::new(
-            gossipsub::MessageAuthenticity::Signed(local_key),
+            gossipsub::MessageAuthenticity::Signed(local_key.clone()),
             gossipsub_config,
         )
         .expect("Correct configuration");
@@ -82,50 +146,108 @@ impl NetworkHandler {
         gossipsub.subscribe(&IdentTopic::new(SubscriptionTopic::TEAM))?;
         gossipsub.subscribe(&IdentTopic::new(SubscriptionTopic::MSG))?;
         gossipsub.subscribe(&IdentTopic::new(SubscriptionTopic::GAME))?;
-        gossipsub.subscribe(&IdentTopic::new(SubscriptionTopic::CHALLENGE))?;
+        gossipsub.subscribe(&IdentTopic::new(SubscriptionTopic::RESULT))?;
+
+        let request_response = request_response::cbor::Behaviour::new(
+            [(StreamProtocol::new(PROTOCOL_NAME), ProtocolSupport::Full)],
+            request_response::Config::default(),
+        );
+
+        // Peers on the same LAN find each other without the seed.
+        let mdns = if enable_mdns {
+            Some(mdns::tokio::Behaviour::new(
+                mdns::Config::default(),
+                local_peer_id,
+            )?)
+        } else {
+            None
+        };
+
+        let identify = identify::Behaviour::new(identify::Config::new(
+            IDENTIFY_PROTOCOL_NAME.to_string(),
+            local_key.public(),
+        ));
+
+        let relay = if enable_relay_server {
+            Some(relay::Behaviour::new(
+                local_peer_id,
+                relay::Config {
+                    max_circuit_duration: RELAY_MAX_CIRCUIT_DURATION,
+                    max_circuit_bytes: RELAY_MAX_CIRCUIT_BYTES,
+                    ..Default::default()
+                },
+            ))
+        } else {
+            None
+        };
 
         let mut swarm = Swarm::new(
-            tcp_transport,
-            gossipsub,
+            transport,
+            RebelsBehaviour {
+                gossipsub,
+                request_response,
+                mdns: Toggle::from(mdns),
+                identify,
+                autonat: autonat::Behaviour::new(local_peer_id, autonat::Config::default()),
+                relay_client,
+                dcutr: dcutr::Behaviour::new(local_peer_id),
+                relay: Toggle::from(relay),
+            },
             local_peer_id,
             Config::with_tokio_executor(),
         );
 
-        // swarm.listen_on("/ip4/0.0.0.0/udp/0/quic-v1".parse()?)?;
-        if swarm
-            .listen_on(format!("/ip4/0.0.0.0/tcp/{DEFAULT_PORT}").parse()?)
-            .is_err()
-        {
-            swarm.listen_on(format!("/ip4/0.0.0.0/tcp/{DEFAULT_PORT_BACKUP}").parse()?)?;
+        if !listen_addresses.is_empty() {
+            for address in listen_addresses {
+                swarm.listen_on(address)?;
+            }
+        } else {
+            // With port reuse, binding a port in use by another instance does not fail,
+            // so peers listen on ports picked by the system. Only the seed has a fixed one.
+            swarm.listen_on("/ip4/0.0.0.0/tcp/0".parse()?)?;
+            // QUIC is optional, without UDP we still get outbound QUIC or plain TCP.
+            let _ = swarm.listen_on("/ip4/0.0.0.0/udp/0/quic-v1".parse()?);
         }
 
-        let seed_address = match seed_ip {
-            Some(ip) => format!("/ip4/{ip}/tcp/{DEFAULT_PORT}")
-                .parse()
-                .expect("Invalid provided seed ip."),
-            None => SEED_ADDRESS.parse()?,
-        };
-
         Ok(Self {
             swarm,
             address: Multiaddr::empty(),
-            challenges: HashMap::new(),
-            seed_address,
+            challenges: ChallengeManager::new(local_peer_id),
+            validator: PeerValidator::default(),
+            seed_addresses,
+            seed_dial: None,
+            outbound_requests: HashMap::new(),
+            inbound_channels: HashMap::new(),
+            seed_peer: None,
+            relay_peer_id: None,
+            relay_listener: None,
+            local_key,
         })
     }
 
     fn _send(&mut self, data: Vec<u8>, topic: &str) -> AppResult<MessageId> {
         let timestamp = Tick::now().to_le_bytes().to_vec();
-        let msg_id = self
+        let data = [timestamp, data].concat();
+        match self
             .swarm
             .behaviour_mut()
-            .publish(IdentTopic::new(topic), [timestamp, data].concat());
-        Ok(msg_id?)
+            .gossipsub
+            .publish(IdentTopic::new(topic), data.clone())
+        {
+            // The same message was already published within this millisecond.
+            Err(gossipsub::PublishError::Duplicate) => Ok(message_id(&data)),
+            result => Ok(result?),
+        }
     }
 
-    pub fn add_challenge(&mut self, challenge: Challenge) {
-        self.challenges
-            .insert(challenge.home_peer_id.clone(), challenge);
+    fn _send_envelope<T: Serialize>(
+        &mut self,
+        kind: MessageKind,
+        payload: &T,
+        topic: &str,
+    ) -> AppResult<MessageId> {
+        let data = Envelope::new(kind, payload)?.to_vec()?;
+        self._send(data, topic)
     }
 
     pub fn dial(&mut self, address: Multiaddr) -> AppResult<()> {
@@ -135,64 +257,192 @@ impl NetworkHandler {
         Ok(())
     }
 
+    pub fn is_connected_to_seed(&self) -> bool {
+        self.seed_peer
+            .as_ref()
+            .is_some_and(|(peer_id, _)| self.swarm.is_connected(peer_id))
+    }
+
+    // Dials the first seed address, the others are tried if it fails.
+    pub fn dial_seed(&mut self) -> AppResult<()> {
+        if self.seed_dial.is_some() || self.is_connected_to_seed() {
+            return Ok(());
+        }
+        self.dial_seed_address(0)
+    }
+
+    fn dial_seed_address(&mut self, idx: usize) -> AppResult<()> {
+        let address = self
+            .seed_addresses
+            .get(idx)
+            .ok_or("No seed address")?
+            .clone();
+        let opts = DialOpts::unknown_peer_id().address(address).build();
+        let connection_id = opts.connection_id();
+        self.swarm.dial(opts)?;
+        self.seed_dial = Some((connection_id, idx));
+        Ok(())
+    }
+
+    // Dials a peer found on the LAN, unless we are already connected to it.
+    pub fn dial_lan_peer(&mut self, peer_id: PeerId, addresses: Vec<Multiaddr>) -> AppResult<()> {
+        if peer_id == *self.swarm.local_peer_id() || self.validator.is_banned(&peer_id) {
+            return Ok(());
+        }
+        self.add_peer_addresses(&peer_id, addresses.clone());
+        self.swarm.dial(
+            DialOpts::peer_id(peer_id)
+                .addresses(addresses)
+                .condition(PeerCondition::Disconnected)
+                .build(),
+        )?;
+        Ok(())
+    }
+
     pub fn send_msg(&mut self, msg: String) -> AppResult<MessageId> {
-        self._send(msg.as_bytes().to_vec(), SubscriptionTopic::MSG)
+        self._send_envelope(MessageKind::Chat, &msg, SubscriptionTopic::MSG)
     }
 
+    // Not wrapped in an envelope, so that peers on any version learn about updates.
     pub fn send_seed_info(&mut self, info: SeedInfo) -> AppResult<MessageId> {
         let serialized_info = serde_json::to_string(&info)?.as_bytes().to_vec();
         self._send(serialized_info, SubscriptionTopic::SEED_INFO)
     }
 
     pub fn send_own_team(&mut self, world: &World) -> AppResult<MessageId> {
-        let message_id = if world.has_own_team() {
-            self.send_team(world, world.own_team_id)?
-        } else {
+        if !world.has_own_team() {
             return Err("No own team".into());
+        }
+        let own_team = world.get_own_team()?;
+
+        let mut addresses: Vec<Multiaddr> = self.swarm.external_addresses().cloned().collect();
+        addresses.extend(self.swarm.listeners().cloned());
+        let announcement = TeamAnnouncement {
+            peer_id: *self.swarm.local_peer_id(),
+            team_id: own_team.id,
+            version: own_team.version,
+            current_game: own_team.current_game,
+            addresses,
         };
+        self.send_team_topic_message(&TeamTopicMessage::Announcement(announcement))
+    }
 
-        //If own team is playing with network peer, send the game.
-        if let Some(game_id) = world.get_own_team()?.current_game {
-            let game = world.get_game_or_err(game_id)?;
-            if game.home_team_in_game.peer_id.is_some() && game.away_team_in_game.peer_id.is_some()
-            {
-                return self.send_game(world, game_id);
-            }
-        }
+    pub fn send_team_topic_message(&mut self, message: &TeamTopicMessage) -> AppResult<MessageId> {
+        self._send_envelope(MessageKind::Team, message, SubscriptionTopic::TEAM)
+    }
+
+    pub fn send_game_command(&mut self, command: &GameCommand) -> AppResult<MessageId> {
+        self._send_envelope(MessageKind::GameCommand, command, SubscriptionTopic::GAME)
+    }
 
-        Ok(message_id)
+    pub fn sign_result(&self, result: &GameResult) -> AppResult<ResultSignature> {
+        ResultSignature::new(&self.local_key, result)
     }
 
-    fn send_game(&mut self, world: &World, game_id: GameId) -> AppResult<MessageId> {
-        let network_game = NetworkGame::from_game_id(&world, game_id)?;
-        let serialized_game = serde_json::to_string(&network_game)?.as_bytes().to_vec();
-        self._send(serialized_game, SubscriptionTopic::GAME)
+    pub fn publish_game_result(&mut self, result: &SignedGameResult) -> AppResult<MessageId> {
+        self._send_envelope(MessageKind::GameResult, result, SubscriptionTopic::RESULT)
     }
 
-    fn send_team(&mut self, world: &World, team_id: TeamId) -> AppResult<MessageId> {
-        let mut network_team = NetworkTeam::from_team_id(world, &team_id)?;
+    pub fn own_network_team(&self, world: &World) -> AppResult<NetworkTeam> {
+        let mut network_team = NetworkTeam::from_team_id(world, &world.own_team_id)?;
         // Set the peer_id for team we are sending out
         // This means that the team can be challenged online and it will not be stored.
-        network_team.set_peer_id(self.swarm.local_peer_id().clone());
+        network_team.set_peer_id(*self.swarm.local_peer_id());
+        Ok(network_team)
+    }
 
-        let serialized_team = serde_json::to_string(&network_team)?.as_bytes().to_vec();
-        self._send(serialized_team, SubscriptionTopic::TEAM)
+    pub fn add_peer_addresses(&mut self, peer_id: &PeerId, addresses: Vec<Multiaddr>) {
+        if peer_id == self.swarm.local_peer_id() {
+            return;
+        }
+        for address in addresses {
+            self.swarm
+                .behaviour_mut()
+                .request_response
+                .add_address(peer_id, address);
+        }
+    }
+
+    pub fn send_request(&mut self, peer_id: PeerId, request: PeerRequest) -> AppResult<RequestId> {
+        let envelope = Envelope::new(MessageKind::Request, &request)?;
+        let request_id = self
+            .swarm
+            .behaviour_mut()
+            .request_response
+            .send_request(&peer_id, envelope);
+        self.outbound_requests
+            .insert(request_id, (peer_id, request));
+        Ok(request_id)
+    }
+
+    // Asks for the players that changed since the versions we have.
+    pub fn request_team(
+        &mut self,
+        world: &World,
+        peer_id: PeerId,
+        team_id: TeamId,
+    ) -> AppResult<RequestId> {
+        let known_players = world
+            .get_team(team_id)
+            .filter(|team| team.peer_id == Some(peer_id))
+            .map(|team| {
+                team.player_ids
+                    .iter()
+                    .filter_map(|id| world.get_player(*id))
+                    .map(|player| (player.id, player.version))
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.send_request(peer_id, PeerRequest::Team { known_players })
+    }
+
+    pub fn send_response(
+        &mut self,
+        request_id: RequestId,
+        response: PeerResponse,
+    ) -> AppResult<()> {
+        let channel = self
+            .inbound_channels
+            .remove(&request_id)
+            .ok_or("Response channel not found")?;
+        let envelope = Envelope::new(MessageKind::Response, &response)?;
+        self.swarm
+            .behaviour_mut()
+            .request_response
+            .send_response(channel, envelope)
+            .map_err(|_| "Failed to send response, connection closed".to_string())?;
+        Ok(())
+    }
+
+    // Challenges go directly to the other peer of the challenge.
+    pub fn send_challenge(&mut self, challenge: &Challenge) -> AppResult<()> {
+        let self_peer_id = *self.swarm.local_peer_id();
+        let peer_id = if challenge.home_peer_id == self_peer_id {
+            challenge.away_peer_id
+        } else {
+            challenge.home_peer_id
+        };
+        self.send_request(
+            peer_id,
+            PeerRequest::Challenge(Box::new(ChallengeDto::from(challenge))),
+        )?;
+        Ok(())
     }
 
-    pub fn send_challenge(&mut self, challenge: &Challenge) -> AppResult<MessageId> {
-        let serialized_challenge = serde_json::to_vec(challenge)?;
-        self._send(serialized_challenge, SubscriptionTopic::CHALLENGE)
+    // Returns the request that failed, if we sent it.
+    pub fn take_failed_request(&mut self, request_id: &RequestId) -> Option<(PeerId, PeerRequest)> {
+        self.outbound_requests.remove(request_id)
     }
 
     pub fn can_handle_challenge(world: &World) -> AppResult<()> {
         if !world.has_own_team() {
-            return Err(format!("No own team, declining challenge").into());
+            return Err("No own team, declining challenge".into());
         }
 
         let own_team = world.get_own_team()?;
 
         if own_team.current_game.is_some() {
-            return Err(format!("Already in a game, declining challenge").into());
+            return Err("Already in a game, declining challenge".into());
         }
 
         Ok(())
@@ -201,99 +451,319 @@ impl NetworkHandler {
     pub fn send_new_challenge(&mut self, world: &World, peer_id: PeerId) -> AppResult<()> {
         self.send_own_team(world)?;
 
-        let mut challenge = Challenge::new(self.swarm.local_peer_id().clone(), peer_id);
         let mut home_team_in_game =
             TeamInGame::from_team_id(world.own_team_id, &world.teams, &world.players)
                 .ok_or("Cannot generate team in game")?;
-        home_team_in_game.peer_id = Some(self.swarm.local_peer_id().clone());
-        challenge.home_team = Some(home_team_in_game);
+        home_team_in_game.set_peer_id(*self.swarm.local_peer_id());
+        let challenge = self
+            .challenges
+            .new_challenge(peer_id, home_team_in_game, Tick::now());
 
         self.send_challenge(&challenge)?;
         Ok(())
     }
 
-    pub fn accept_challenge(&mut self, world: &World, challenge: Challenge) -> AppResult<()> {
+    pub fn accept_challenge(&mut self, world: &World, challenge_id: ChallengeId) -> AppResult<()> {
         let mut handle_syn = || -> AppResult<()> {
             Self::can_handle_challenge(world)?;
 
-            let away_team = world.get_own_team()?;
-            if away_team.current_game.is_some() {
-                return Err("Cannot accept challenge, already in a game".into());
-            }
-
-            let try_away_team_in_game =
-                TeamInGame::from_team_id(world.own_team_id, &world.teams, &world.players);
-
-            if try_away_team_in_game.is_none() {
-                return Err("Cannot generate team in game for challenge")?;
-            }
+            let mut away_team_in_game =
+                TeamInGame::from_team_id(world.own_team_id, &world.teams, &world.players)
+                    .ok_or("Cannot generate team in game for challenge")?;
+            away_team_in_game.set_peer_id(*self.swarm.local_peer_id());
 
-            let mut away_team_in_game = try_away_team_in_game.unwrap();
-            away_team_in_game.peer_id = Some(self.swarm.local_peer_id().clone());
-
-            let mut challenge = challenge.clone();
-            challenge.away_team = Some(away_team_in_game);
-            challenge.state = ChallengeState::SynAck;
+            let challenge = self
+                .challenges
+                .accept(challenge_id, away_team_in_game, Tick::now())?;
             self.send_challenge(&challenge)?;
             Ok(())
         };
 
         if let Err(err) = handle_syn() {
-            let mut challenge = Challenge::new(challenge.home_peer_id, challenge.away_peer_id);
-            challenge.state = ChallengeState::Failed;
-            challenge.error_message = Some(err.to_string());
-            self.send_challenge(&challenge)?;
+            self.fail_challenge(challenge_id, err.to_string())?;
             return Err(err.to_string())?;
         }
         Ok(())
     }
 
-    pub fn decline_challenge(&mut self, challenge: Challenge) -> AppResult<()> {
-        let mut challenge = challenge.clone();
-        challenge.state = ChallengeState::Failed;
-        challenge.error_message = Some("Declined".to_string());
-        self.send_challenge(&challenge)?;
-        self.challenges.remove(&challenge.home_peer_id);
+    pub fn fail_challenge(&mut self, challenge_id: ChallengeId, reason: String) -> AppResult<()> {
+        if let Some(challenge) = self.challenges.fail(challenge_id, reason) {
+            self.send_challenge(&challenge)?;
+        }
         Ok(())
     }
 
-    pub fn handle_network_events(
-        &mut self,
-        event: SwarmEvent<gossipsub::Event, Void>,
-    ) -> Option<NetworkCallbackPreset> {
-        // self.swarm.connected_peers()
+    pub fn decline_challenge(&mut self, challenge_id: ChallengeId) -> AppResult<()> {
+        self.fail_challenge(challenge_id, "Declined".to_string())
+    }
+
+    pub fn disconnect_peer(&mut self, peer_id: PeerId) {
+        let _ = self.swarm.disconnect_peer_id(peer_id);
+    }
+
+    pub fn nat_status(&self) -> autonat::NatStatus {
+        self.swarm.behaviour().autonat.nat_status()
+    }
+
+    // The circuit address other peers can reach us at, once the reservation is accepted.
+    pub fn relay_address(&self) -> Option<&Multiaddr> {
+        self.swarm
+            .listeners()
+            .find(|address| address.iter().any(|p| p == Protocol::P2pCircuit))
+    }
+
+    // Keeps a circuit reservation on the relay unless we are publicly reachable.
+    // Returns what changed, to be logged.
+    fn update_relay_listener(&mut self) -> Option<String> {
+        let relay_peer_id = self.relay_peer_id?;
+        let (_, seed_address) = self.seed_peer.as_ref()?;
+        let is_public = self.nat_status().is_public();
+
+        let text = match self.relay_listener {
+            Some(listener_id) if is_public => {
+                self.swarm.remove_listener(listener_id);
+                self.relay_listener = None;
+                "Publicly reachable, dropping the relay circuit".to_string()
+            }
+            None if !is_public => {
+                let mut address = seed_address.clone();
+                if !matches!(address.iter().last(), Some(Protocol::P2p(_))) {
+                    address.push(Protocol::P2p(relay_peer_id));
+                }
+                address.push(Protocol::P2pCircuit);
+                match self.swarm.listen_on(address.clone()) {
+                    Ok(listener_id) => {
+                        self.relay_listener = Some(listener_id);
+                        format!("Requesting a relay circuit at {}", address)
+                    }
+                    Err(err) => format!("Cannot listen on relay {}: {}", address, err),
+                }
+            }
+            _ => return None,
+        };
+        Some(text)
+    }
+
+    pub fn handle_network_events(&mut self, event: NetworkEvent) -> Option<NetworkCallbackPreset> {
+        // Drop everything coming from banned peers.
+        let banned_source = match &event {
+            SwarmEvent::Behaviour(RebelsBehaviourEvent::Gossipsub(gossipsub::Event::Message {
+                message,
+                ..
+            })) => message.source,
+            SwarmEvent::Behaviour(RebelsBehaviourEvent::RequestResponse(
+                request_response::Event::Message { peer, .. },
+            ))
+            | SwarmEvent::ConnectionEstablished { peer_id: peer, .. } => Some(*peer),
+            _ => None,
+        }
+        .filter(|peer_id| self.validator.is_banned(peer_id));
+        if let Some(peer_id) = banned_source {
+            self.disconnect_peer(peer_id);
+            return None;
+        }
+
         match event {
+            // Circuit addresses are announced with the team, but are not bound.
+            SwarmEvent::NewListenAddr { address, .. }
+                if address.iter().any(|p| p == Protocol::P2pCircuit) =>
+            {
+                Some(NetworkCallbackPreset::PushSwarmPanelLog {
+                    timestamp: Tick::now(),
+                    text: format!("Reachable through relay at {}", address),
+                })
+            }
             SwarmEvent::NewListenAddr { address, .. } => {
                 Some(NetworkCallbackPreset::BindAddress { address })
             }
-            SwarmEvent::Behaviour(gossipsub::Event::Message {
+            SwarmEvent::ListenerClosed { listener_id, .. }
+                if Some(listener_id) == self.relay_listener =>
+            {
+                self.relay_listener = None;
+                Some(NetworkCallbackPreset::PushSwarmPanelLog {
+                    timestamp: Tick::now(),
+                    text: "Relay circuit closed".to_string(),
+                })
+            }
+            SwarmEvent::Behaviour(RebelsBehaviourEvent::Identify(identify::Event::Received {
+                peer_id,
+                info,
+            })) => {
+                if self.seed_peer.as_ref().map(|(id, _)| *id) == Some(peer_id)
+                    && info.protocols.contains(&relay::HOP_PROTOCOL_NAME)
+                {
+                    self.relay_peer_id = Some(peer_id);
+                    if let Some(text) = self.update_relay_listener() {
+                        return Some(NetworkCallbackPreset::PushSwarmPanelLog {
+                            timestamp: Tick::now(),
+                            text,
+                        });
+                    }
+                }
+                None
+            }
+            SwarmEvent::Behaviour(RebelsBehaviourEvent::Autonat(
+                autonat::Event::StatusChanged { old, new },
+            )) => {
+                let mut text = format!("NAT status changed from {:?} to {:?}", old, new);
+                if let Some(change) = self.update_relay_listener() {
+                    text = format!("{}. {}", text, change);
+                }
+                Some(NetworkCallbackPreset::PushSwarmPanelLog {
+                    timestamp: Tick::now(),
+                    text,
+                })
+            }
+            SwarmEvent::Behaviour(RebelsBehaviourEvent::RelayClient(event)) => {
+                let text = match event {
+                    relay::client::Event::ReservationReqAccepted {
+                        relay_peer_id,
+                        renewal: false,
+                        ..
+                    } => format!("Relay circuit reserved on {}", relay_peer_id),
+                    relay::client::Event::ReservationReqFailed {
+                        relay_peer_id,
+                        error,
+                        ..
+                    } => format!("Relay reservation on {} failed: {}", relay_peer_id, error),
+                    relay::client::Event::InboundCircuitEstablished { src_peer_id, .. } => {
+                        format!("Relayed connection from {}", src_peer_id)
+                    }
+                    relay::client::Event::OutboundCircuitEstablished { relay_peer_id, .. } => {
+                        format!("Relayed connection through {}", relay_peer_id)
+                    }
+                    _ => return None,
+                };
+                Some(NetworkCallbackPreset::PushSwarmPanelLog {
+                    timestamp: Tick::now(),
+                    text,
+                })
+            }
+            SwarmEvent::Behaviour(RebelsBehaviourEvent::Dcutr(event)) => {
+                let text = match event {
+                    dcutr::Event::DirectConnectionUpgradeSucceeded { remote_peer_id } => {
+                        format!("Hole punched to {}", remote_peer_id)
+                    }
+                    dcutr::Event::DirectConnectionUpgradeFailed {
+                        remote_peer_id,
+                        error,
+                    } => format!("Hole punching to {} failed: {}", remote_peer_id, error),
+                    _ => return None,
+                };
+                Some(NetworkCallbackPreset::PushSwarmPanelLog {
+                    timestamp: Tick::now(),
+                    text,
+                })
+            }
+            SwarmEvent::Behaviour(RebelsBehaviourEvent::Identify(_))
+            | SwarmEvent::Behaviour(RebelsBehaviourEvent::Autonat(_))
+            | SwarmEvent::Behaviour(RebelsBehaviourEvent::Relay(_)) => None,
+            SwarmEvent::Behaviour(RebelsBehaviourEvent::Gossipsub(gossipsub::Event::Message {
                 propagation_source: _,
                 message_id: _,
                 message,
-            }) => match message.topic.clone() {
+            })) => match message.topic.clone() {
                 x if x == IdentTopic::new(SubscriptionTopic::TEAM).hash() => {
                     Some(NetworkCallbackPreset::HandleTeamTopic { message })
                 }
                 x if x == IdentTopic::new(SubscriptionTopic::MSG).hash() => {
                     Some(NetworkCallbackPreset::HandleMsgTopic { message })
                 }
-                x if x == IdentTopic::new(SubscriptionTopic::CHALLENGE).hash() => {
-                    Some(NetworkCallbackPreset::HandleChallengeTopic { message })
-                }
                 x if x == IdentTopic::new(SubscriptionTopic::GAME).hash() => {
                     Some(NetworkCallbackPreset::HandleGameTopic { message })
                 }
                 x if x == IdentTopic::new(SubscriptionTopic::SEED_INFO).hash() => {
                     Some(NetworkCallbackPreset::HandleSeedTopic { message })
                 }
+                x if x == IdentTopic::new(SubscriptionTopic::RESULT).hash() => {
+                    Some(NetworkCallbackPreset::HandleResultTopic { message })
+                }
                 _ => None,
             },
-            SwarmEvent::Behaviour(gossipsub::Event::Subscribed { peer_id, topic }) => {
-                Some(NetworkCallbackPreset::Subscribe { peer_id, topic })
+            SwarmEvent::Behaviour(RebelsBehaviourEvent::Gossipsub(
+                gossipsub::Event::Subscribed { peer_id, topic },
+            )) => Some(NetworkCallbackPreset::Subscribe { peer_id, topic }),
+            SwarmEvent::Behaviour(RebelsBehaviourEvent::Mdns(mdns::Event::Discovered(peers))) => {
+                Some(NetworkCallbackPreset::DiscoverLanPeers { peers })
+            }
+            SwarmEvent::Behaviour(RebelsBehaviourEvent::Mdns(mdns::Event::Expired(peers))) => {
+                Some(NetworkCallbackPreset::ExpireLanPeers { peers })
             }
 
-            SwarmEvent::Behaviour(gossipsub::Event::Unsubscribed { peer_id, topic }) => {
-                Some(NetworkCallbackPreset::Unsubscribe { peer_id, topic })
+            SwarmEvent::Behaviour(RebelsBehaviourEvent::Gossipsub(
+                gossipsub::Event::Unsubscribed { peer_id, topic },
+            )) => Some(NetworkCallbackPreset::Unsubscribe { peer_id, topic }),
+            SwarmEvent::Behaviour(RebelsBehaviourEvent::RequestResponse(
+                request_response::Event::Message { peer, message },
+            )) => match message {
+                request_response::Message::Request {
+                    request_id,
+                    request,
+                    channel,
+                } => {
+                    self.inbound_channels.insert(request_id, channel);
+                    let (version, is_compatible) = (request.version, request.is_compatible());
+                    match request.open::<PeerRequest>(MessageKind::Request) {
+                        Ok(request) => Some(NetworkCallbackPreset::HandleRequest {
+                            peer_id: peer,
+                            request_id,
+                            request,
+                        }),
+                        Err(err) => {
+                            let _ = self
+                                .send_response(request_id, PeerResponse::Error(err.to_string()));
+                            Some(NetworkCallbackPreset::RejectMessage {
+                                peer_id: peer,
+                                version,
+                                is_compatible,
+                                error: err.to_string(),
+                            })
+                        }
+                    }
+                }
+                request_response::Message::Response {
+                    request_id,
+                    response,
+                } => {
+                    self.outbound_requests.remove(&request_id);
+                    let (version, is_compatible) = (response.version, response.is_compatible());
+                    match response.open::<PeerResponse>(MessageKind::Response) {
+                        Ok(response) => Some(NetworkCallbackPreset::HandleResponse {
+                            peer_id: peer,
+                            response,
+                        }),
+                        Err(err) => Some(NetworkCallbackPreset::RejectMessage {
+                            peer_id: peer,
+                            version,
+                            is_compatible,
+                            error: err.to_string(),
+                        }),
+                    }
+                }
+            },
+            SwarmEvent::Behaviour(RebelsBehaviourEvent::RequestResponse(
+                request_response::Event::OutboundFailure {
+                    peer,
+                    request_id,
+                    error,
+                },
+            )) => Some(NetworkCallbackPreset::HandleRequestFailure {
+                peer_id: peer,
+                request_id,
+                error: error.to_string(),
+            }),
+            SwarmEvent::Behaviour(RebelsBehaviourEvent::RequestResponse(
+                request_response::Event::InboundFailure {
+                    peer,
+                    request_id,
+                    error,
+                },
+            )) => {
+                self.inbound_channels.remove(&request_id);
+                Some(NetworkCallbackPreset::PushSwarmPanelLog {
+                    timestamp: Tick::now(),
+                    text: format!("Request from {} failed: {}", peer, error),
+                })
             }
             SwarmEvent::ExpiredListenAddr {
                 listener_id: _,
@@ -302,8 +772,45 @@ impl NetworkHandler {
                 timestamp: Tick::now(),
                 text: format!("Expired listen address: {}", address),
             }),
-            SwarmEvent::ConnectionEstablished { peer_id, .. } => {
-                Some(NetworkCallbackPreset::HandleConnectionEstablished { peer_id })
+            SwarmEvent::ConnectionEstablished {
+                peer_id,
+                connection_id,
+                endpoint,
+                num_established,
+                ..
+            } => {
+                let address = endpoint.get_remote_address();
+                if self.seed_dial.is_some_and(|(id, _)| id == connection_id) {
+                    self.seed_dial = None;
+                    self.seed_peer = Some((peer_id, address.clone()));
+                }
+                let transport = PeerTransport::from_address(address);
+                // AutoNAT probes and hole punching open more connections to known peers.
+                if num_established.get() > 1 {
+                    return Some(NetworkCallbackPreset::UpdatePeerTransport { peer_id, transport });
+                }
+                Some(NetworkCallbackPreset::HandleConnectionEstablished { peer_id, transport })
+            }
+            SwarmEvent::OutgoingConnectionError {
+                connection_id,
+                error,
+                ..
+            } if self.seed_dial.is_some_and(|(id, _)| id == connection_id) => {
+                let (_, idx) = self.seed_dial.take()?;
+                let mut text = format!(
+                    "Cannot reach the seed at {}: {}",
+                    self.seed_addresses[idx], error
+                );
+                if idx + 1 < self.seed_addresses.len() {
+                    text = match self.dial_seed_address(idx + 1) {
+                        Ok(_) => format!("{}. Trying {}", text, self.seed_addresses[idx + 1]),
+                        Err(err) => format!("{}. {}", text, err),
+                    };
+                }
+                Some(NetworkCallbackPreset::PushSwarmPanelLog {
+                    timestamp: Tick::now(),
+                    text,
+                })
             }
             SwarmEvent::ConnectionClosed { peer_id, .. } => {
                 Some(NetworkCallbackPreset::CloseConnection { peer_id })
@@ -319,22 +826,29 @@ impl NetworkHandler {
 #[cfg(test)]
 mod tests {
     use crate::{
-        network::types::NetworkTeam,
+        network::{
+            types::{NetworkTeam, TeamTopicMessage},
+            wire::{Envelope, MessageKind, NetworkTeamDto},
+        },
         types::{SystemTimeTick, Tick},
         world::world::World,
     };
     use rand::SeedableRng;
     use rand_chacha::ChaCha8Rng;
 
-    #[test]
-    fn test_send_own_team() {
+    fn own_network_team() -> NetworkTeam {
         let mut world = World::new(None);
         let rng = &mut ChaCha8Rng::from_entropy();
         let home_planet = world.planets.keys().next().unwrap().clone();
         let team_name = "Testen".to_string();
         let ship_name = "Tosten".to_string();
         let own_team_id = world.generate_random_team(rng, home_planet, team_name, ship_name);
-        let network_team = NetworkTeam::from_team_id(&world, &own_team_id.unwrap()).unwrap();
+        NetworkTeam::from_team_id(&world, &own_team_id.unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_send_own_team() {
+        let network_team = own_network_team();
 
         let timestamp = Tick::now().as_secs().to_le_bytes().to_vec();
         let serialized_team = serde_json::to_string(&network_team)
@@ -353,4 +867,40 @@ mod tests {
         assert_eq!(deserialized_team.players.len(), network_team.players.len());
         assert_eq!(deserialized_team.players[0], network_team.players[0]);
     }
+
+    // CBOR is smaller than JSON, deflate halves it and deltas are smaller still.
+    #[test]
+    fn test_own_team_payloads_shrink() {
+        let network_team = NetworkTeam::test_team(None);
+
+        let json = serde_json::to_vec(&network_team).unwrap().len();
+        let cbor = cbor4ii::serde::to_vec(vec![], &NetworkTeamDto::from(&network_team))
+            .unwrap()
+            .len();
+        let message = TeamTopicMessage::Team(Box::new(NetworkTeamDto::from(&network_team)));
+        let envelope = Envelope::new(MessageKind::Team, &message)
+            .unwrap()
+            .to_vec()
+            .unwrap()
+            .len();
+
+        // A single player changed since the version the peer has.
+        let mut updated_team = network_team.clone();
+        updated_team.players[0].version += 1;
+        let known_players: Vec<_> = network_team
+            .players
+            .iter()
+            .map(|player| (player.id, player.version))
+            .collect();
+        let delta = NetworkTeamDto::delta(&updated_team, &known_players);
+        let delta = Envelope::new(MessageKind::Response, &delta)
+            .unwrap()
+            .to_vec()
+            .unwrap()
+            .len();
+
+        assert!(cbor < json);
+        assert!(envelope * 2 < json);
+        assert!(delta < envelope);
+    }
 }
@@ -0,0 +1,615 @@
+// In-process P2P harness: a local relayer stands in for SEED_ADDRESS and
+// several apps connect to it over localhost. Scenarios drive the swarms until
+// a condition on the apps holds, then assert on their worlds. mDNS is off, so
+// that harnesses running in parallel do not discover each other.
+use super::handler::NetworkHandler;
+use super::protocol::NetworkEvent;
+use super::types::PeerTransport;
+use crate::app::App;
+use crate::relayer::Relayer;
+use crate::types::{AppResult, SystemTimeTick, TeamId, Tick};
+use crate::world::constants::DEFAULT_PLANET_ID;
+use futures::StreamExt;
+use libp2p::{identity, Multiaddr, PeerId};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::future::poll_fn;
+use std::task::Poll;
+use std::time::Duration;
+
+const LOCALHOST: &str = "/ip4/127.0.0.1/tcp/0";
+const LOCALHOST_QUIC: &str = "/ip4/127.0.0.1/udp/0/quic-v1";
+const SCENARIO_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct Node {
+    pub app: App,
+    pub team_id: TeamId,
+}
+
+impl Node {
+    pub fn peer_id(&self) -> PeerId {
+        *self
+            .app
+            .network_handler
+            .as_ref()
+            .expect("Node has no network handler")
+            .swarm
+            .local_peer_id()
+    }
+}
+
+pub struct Harness {
+    pub relayer: Relayer,
+    pub nodes: Vec<Node>,
+    seed_addresses: Vec<Multiaddr>,
+}
+
+impl Harness {
+    pub async fn new(nodes: usize) -> AppResult<Self> {
+        let handler = NetworkHandler::with_addresses(
+            identity::Keypair::generate_ed25519(),
+            vec![],
+            vec![LOCALHOST_QUIC.parse()?, LOCALHOST.parse()?],
+            false,
+            true,
+        )
+        .map_err(|e| e.to_string())?;
+        let mut relayer = Relayer::with_network_handler(handler);
+
+        // Wait for the relayer to be bound, its addresses are the seed addresses of the nodes.
+        let mut seed_addresses = tokio::time::timeout(SCENARIO_TIMEOUT, async {
+            let mut addresses = vec![];
+            while addresses.len() < 2 {
+                if let NetworkEvent::NewListenAddr { address, .. } =
+                    relayer.network_handler.swarm.select_next_some().await
+                {
+                    addresses.push(address);
+                }
+            }
+            addresses
+        })
+        .await?;
+        // QUIC first, like the default seed addresses.
+        seed_addresses
+            .sort_by_key(|address| PeerTransport::from_address(address) != PeerTransport::Quic);
+        // Needed for relay reservations.
+        for address in seed_addresses.iter() {
+            relayer
+                .network_handler
+                .swarm
+                .add_external_address(address.clone());
+        }
+
+        let mut harness = Self {
+            relayer,
+            nodes: vec![],
+            seed_addresses,
+        };
+        for idx in 0..nodes {
+            harness.add_node(idx as u64)?;
+        }
+        Ok(harness)
+    }
+
+    pub fn add_node(&mut self, seed: u64) -> AppResult<usize> {
+        let mut app = App::new(Some(seed), false, true, false, false, None);
+        let rng = &mut ChaCha8Rng::seed_from_u64(seed);
+        let team_id = app.world.generate_random_team(
+            rng,
+            DEFAULT_PLANET_ID.clone(),
+            format!("Team {}", seed),
+            format!("Ship {}", seed),
+        )?;
+        app.world.own_team_id = team_id;
+
+        let handler = NetworkHandler::with_addresses(
+            identity::Keypair::generate_ed25519(),
+            self.seed_addresses.clone(),
+            vec![LOCALHOST_QUIC.parse()?, LOCALHOST.parse()?],
+            false,
+            false,
+        )
+        .map_err(|e| e.to_string())?;
+        app.network_handler = Some(handler);
+
+        self.nodes.push(Node { app, team_id });
+        Ok(self.nodes.len() - 1)
+    }
+
+    // Dropping the node closes its swarm and all its connections.
+    pub fn remove_node(&mut self, idx: usize) -> Node {
+        self.nodes.remove(idx)
+    }
+
+    // Polls the relayer and every node, returns the first ready event.
+    async fn next_event(&mut self) -> (Option<usize>, NetworkEvent) {
+        poll_fn(|cx| {
+            if let Poll::Ready(Some(event)) = self.relayer.network_handler.swarm.poll_next_unpin(cx)
+            {
+                return Poll::Ready((None, event));
+            }
+            for (idx, node) in self.nodes.iter_mut().enumerate() {
+                let handler = node.app.network_handler.as_mut().unwrap();
+                if let Poll::Ready(Some(event)) = handler.swarm.poll_next_unpin(cx) {
+                    return Poll::Ready((Some(idx), event));
+                }
+            }
+            Poll::Pending
+        })
+        .await
+    }
+
+    async fn step(&mut self) -> AppResult<()> {
+        let tick = tokio::time::timeout(Duration::from_millis(100), self.next_event()).await;
+        if let Ok((source, event)) = tick {
+            match source {
+                None => self.relayer.handle_network_events(event)?,
+                Some(idx) => self.nodes[idx].app.handle_network_events(event)?,
+            }
+        }
+        for node in self.nodes.iter_mut() {
+            node.app.handle_network_tick(Tick::now());
+        }
+        Ok(())
+    }
+
+    pub async fn run_until<F>(&mut self, description: &str, condition: F) -> AppResult<()>
+    where
+        F: Fn(&Harness) -> bool,
+    {
+        let deadline = tokio::time::Instant::now() + SCENARIO_TIMEOUT;
+        while !condition(self) {
+            if tokio::time::Instant::now() > deadline {
+                return Err(format!("Timed out waiting for: {}", description).into());
+            }
+            self.step().await?;
+        }
+        Ok(())
+    }
+
+    pub fn knows_team(&self, node: usize, team_id: TeamId) -> bool {
+        self.nodes[node].app.world.get_team(team_id).is_some()
+    }
+
+    pub async fn exchange_teams(&mut self) -> AppResult<()> {
+        self.run_until("teams to be exchanged", |h| {
+            h.nodes
+                .iter()
+                .enumerate()
+                .all(|(idx, _)| h.nodes.iter().all(|other| h.knows_team(idx, other.team_id)))
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Harness;
+    use crate::network::chat::{ChatAuthor, Conversation};
+    use crate::network::network_callback::NetworkCallbackPreset;
+    use crate::network::results::GameResult;
+    use crate::network::types::PeerTransport;
+    use crate::types::{GameId, IdSystem, SystemTimeTick, Tick};
+    use crate::ui::ui_callback::UiCallbackPreset;
+    use libp2p::swarm::dial_opts::{DialOpts, PeerCondition};
+
+    #[tokio::test]
+    async fn test_team_broadcast() {
+        let mut harness = Harness::new(3).await.unwrap();
+        harness.exchange_teams().await.unwrap();
+
+        for node in harness.nodes.iter() {
+            for other in harness.nodes.iter() {
+                let team = node.app.world.get_team_or_err(other.team_id).unwrap();
+                let players = node.app.world.get_players_by_team(team).unwrap();
+                assert_eq!(players.len(), team.player_ids.len());
+                if other.team_id == node.team_id {
+                    assert!(team.peer_id.is_none());
+                } else {
+                    assert_eq!(team.peer_id, Some(other.peer_id()));
+                    assert!(players.iter().all(|p| p.peer_id == team.peer_id));
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_challenge_acceptance() {
+        let mut harness = Harness::new(2).await.unwrap();
+        harness.exchange_teams().await.unwrap();
+
+        let away_team_id = harness.nodes[1].team_id;
+        UiCallbackPreset::ChallengeTeam {
+            team_id: away_team_id,
+        }
+        .call(&mut harness.nodes[0].app)
+        .unwrap();
+
+        harness
+            .run_until("challenge to be received", |h| {
+                let handler = h.nodes[1].app.network_handler.as_ref().unwrap();
+                handler.challenges.iter().next().is_some()
+            })
+            .await
+            .unwrap();
+
+        let challenge = harness.nodes[1]
+            .app
+            .network_handler
+            .as_ref()
+            .unwrap()
+            .challenges
+            .iter()
+            .next()
+            .unwrap()
+            .clone();
+        UiCallbackPreset::AcceptChallenge { challenge }
+            .call(&mut harness.nodes[1].app)
+            .unwrap();
+
+        harness
+            .run_until("game to be generated on both peers", |h| {
+                h.nodes.iter().all(|node| {
+                    node.app
+                        .world
+                        .get_own_team()
+                        .unwrap()
+                        .current_game
+                        .is_some()
+                })
+            })
+            .await
+            .unwrap();
+
+        let home = &harness.nodes[0].app.world;
+        let away = &harness.nodes[1].app.world;
+        let game_id = home.get_own_team().unwrap().current_game.unwrap();
+        assert_eq!(away.get_own_team().unwrap().current_game, Some(game_id));
+        let home_game = home.get_game_or_err(game_id).unwrap();
+        let away_game = away.get_game_or_err(game_id).unwrap();
+        assert_eq!(home_game.starting_at, away_game.starting_at);
+        assert_eq!(
+            home_game.home_team_in_game.team_id,
+            away_game.home_team_in_game.team_id
+        );
+        assert_eq!(
+            home_game.away_team_in_game.team_id,
+            away_game.away_team_in_game.team_id
+        );
+    }
+
+    #[tokio::test]
+    async fn test_peer_disconnect_filters_peer_data() {
+        let mut harness = Harness::new(3).await.unwrap();
+        harness.exchange_teams().await.unwrap();
+
+        let gone = harness.remove_node(2);
+        let gone_peer_id = gone.peer_id();
+        drop(gone);
+
+        harness
+            .run_until("peer data to be filtered", |h| {
+                h.nodes.iter().all(|node| {
+                    !node
+                        .app
+                        .world
+                        .teams
+                        .values()
+                        .any(|team| team.peer_id == Some(gone_peer_id))
+                })
+            })
+            .await
+            .unwrap();
+
+        for node in harness.nodes.iter() {
+            let world = &node.app.world;
+            assert!(world
+                .players
+                .values()
+                .all(|player| player.peer_id != Some(gone_peer_id)));
+            // Own team and the remaining peer are untouched.
+            for other in harness.nodes.iter() {
+                assert!(world.get_team(other.team_id).is_some());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ghost_challenge() {
+        let mut harness = Harness::new(2).await.unwrap();
+        harness.exchange_teams().await.unwrap();
+
+        let ghost_team_id = harness.nodes[1].team_id;
+        let gone = harness.remove_node(1);
+        let gone_peer_id = gone.peer_id();
+        drop(gone);
+
+        harness
+            .run_until("ghost team to leave the world", |h| {
+                h.nodes[0].app.world.get_team(ghost_team_id).is_none()
+            })
+            .await
+            .unwrap();
+
+        let app = &mut harness.nodes[0].app;
+        assert!(app.ghosts.get(&ghost_team_id).is_some());
+        UiCallbackPreset::ChallengeGhost {
+            team_id: ghost_team_id,
+        }
+        .call(app)
+        .unwrap();
+
+        let game_id = app.world.get_own_team().unwrap().current_game.unwrap();
+        let game = app.world.get_game_or_err(game_id).unwrap();
+        assert!(
+            game.home_team_in_game.team_id == ghost_team_id
+                || game.away_team_in_game.team_id == ghost_team_id
+        );
+        assert!(app.ghosts.is_playing(&gone_peer_id));
+    }
+
+    #[tokio::test]
+    async fn test_signed_game_result() {
+        let mut harness = Harness::new(3).await.unwrap();
+        harness.exchange_teams().await.unwrap();
+
+        let result = GameResult {
+            game_id: GameId::new(),
+            starting_at: Tick::now(),
+            home_team_id: harness.nodes[0].team_id,
+            away_team_id: harness.nodes[1].team_id,
+            home_team_name: "Home".to_string(),
+            away_team_name: "Away".to_string(),
+            home_peer_id: harness.nodes[0].peer_id(),
+            away_peer_id: harness.nodes[1].peer_id(),
+            home_team_version: 0,
+            away_team_version: 0,
+            home_score: 12,
+            away_score: 10,
+        };
+        // Both players saw the game end, each signs its own copy of the result.
+        for node in harness.nodes[..2].iter_mut() {
+            node.app.game_results.track(result.clone());
+            NetworkCallbackPreset::finish_network_game(&mut node.app, result.clone()).unwrap();
+        }
+
+        harness
+            .run_until("co-signed result to reach every peer", |h| {
+                h.nodes
+                    .iter()
+                    .all(|node| node.app.game_results.get(&result.game_id).is_some())
+            })
+            .await
+            .unwrap();
+
+        for node in harness.nodes.iter() {
+            let signed = node.app.game_results.get(&result.game_id).unwrap();
+            assert_eq!(signed.result, result);
+            signed.verify().unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_signed_result_reaches_late_peer() {
+        let mut harness = Harness::new(2).await.unwrap();
+        harness.exchange_teams().await.unwrap();
+
+        let result = GameResult {
+            game_id: GameId::new(),
+            starting_at: Tick::now(),
+            home_team_id: harness.nodes[0].team_id,
+            away_team_id: harness.nodes[1].team_id,
+            home_team_name: "Home".to_string(),
+            away_team_name: "Away".to_string(),
+            home_peer_id: harness.nodes[0].peer_id(),
+            away_peer_id: harness.nodes[1].peer_id(),
+            home_team_version: 0,
+            away_team_version: 0,
+            home_score: 12,
+            away_score: 10,
+        };
+        for node in harness.nodes.iter_mut() {
+            node.app.game_results.track(result.clone());
+            NetworkCallbackPreset::finish_network_game(&mut node.app, result.clone()).unwrap();
+        }
+        harness
+            .run_until("result to be co-signed", |h| {
+                h.nodes
+                    .iter()
+                    .all(|node| node.app.game_results.get(&result.game_id).is_some())
+            })
+            .await
+            .unwrap();
+
+        // The result was published before this peer joined.
+        let late = harness.add_node(2).unwrap();
+        harness
+            .run_until("result to be announced again", |h| {
+                h.nodes[late]
+                    .app
+                    .game_results
+                    .get(&result.game_id)
+                    .is_some()
+            })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_relayer_directory() {
+        let mut harness = Harness::new(2).await.unwrap();
+        harness.exchange_teams().await.unwrap();
+        harness
+            .run_until("teams to be in the directory", |h| {
+                h.relayer.directory().len() == 2
+            })
+            .await
+            .unwrap();
+
+        let stats = harness.relayer.stats();
+        assert_eq!(stats.connected_peers, 2);
+        for node in harness.nodes.iter() {
+            assert!(stats
+                .online_teams
+                .iter()
+                .any(|team| team.peer_id == node.peer_id() && team.team_id == node.team_id));
+        }
+
+        let gone = harness.remove_node(1);
+        drop(gone);
+        harness
+            .run_until("team to leave the directory", |h| {
+                h.relayer.directory().len() == 1
+            })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_direct_message() {
+        let mut harness = Harness::new(3).await.unwrap();
+        harness.exchange_teams().await.unwrap();
+
+        let sender = harness.nodes[0].peer_id();
+        UiCallbackPreset::SendDirectMessage {
+            peer_id: harness.nodes[1].peer_id(),
+            message: "Ahoy".to_string(),
+        }
+        .call(&mut harness.nodes[0].app)
+        .unwrap();
+
+        harness
+            .run_until("direct message to be delivered", |h| {
+                h.nodes[1]
+                    .app
+                    .ui
+                    .swarm_panel
+                    .chat()
+                    .unread(Conversation::Peer(sender))
+                    == 1
+            })
+            .await
+            .unwrap();
+
+        let chat = harness.nodes[1].app.ui.swarm_panel.chat();
+        let messages = chat.messages(Conversation::Peer(sender));
+        assert_eq!(messages[0].text, "Ahoy");
+        assert_eq!(messages[0].author, ChatAuthor::Peer(sender));
+        assert!(chat.messages(Conversation::Global).is_empty());
+        // Only the recipient gets it.
+        assert_eq!(harness.nodes[2].app.ui.swarm_panel.unread_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_relay_circuit() {
+        let mut harness = Harness::new(2).await.unwrap();
+        harness
+            .run_until("relay circuit to be reserved", |h| {
+                let handler = h.nodes[0].app.network_handler.as_ref().unwrap();
+                handler.relay_address().is_some()
+            })
+            .await
+            .unwrap();
+        assert_eq!(harness.relayer.stats().relay_reservations, 2);
+
+        let address = harness.nodes[0]
+            .app
+            .network_handler
+            .as_ref()
+            .unwrap()
+            .relay_address()
+            .unwrap()
+            .clone();
+        // The nodes may already be connected directly.
+        let dial = DialOpts::peer_id(harness.nodes[0].peer_id())
+            .addresses(vec![address])
+            .condition(PeerCondition::Always)
+            .build();
+        harness.nodes[1]
+            .app
+            .network_handler
+            .as_mut()
+            .unwrap()
+            .swarm
+            .dial(dial)
+            .unwrap();
+
+        harness
+            .run_until("connection to be relayed", |h| {
+                h.relayer.stats().relayed_circuits == 1
+            })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_peer_transports() {
+        let mut harness = Harness::new(2).await.unwrap();
+        harness.exchange_teams().await.unwrap();
+
+        let relayer_peer_id = *harness.relayer.network_handler.swarm.local_peer_id();
+        for (node, other) in [(0, 1), (1, 0)] {
+            let handler = harness.nodes[node].app.network_handler.as_ref().unwrap();
+            assert!(handler.is_connected_to_seed());
+            let swarm_panel = &harness.nodes[node].app.ui.swarm_panel;
+            assert_eq!(
+                swarm_panel.peer_transport(&relayer_peer_id),
+                Some(PeerTransport::Quic)
+            );
+            assert!(swarm_panel
+                .peer_transport(&harness.nodes[other].peer_id())
+                .is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_discover_connected_lan_peer() {
+        let mut harness = Harness::new(2).await.unwrap();
+        harness.exchange_teams().await.unwrap();
+
+        // Dialing the connected peer fails, the unknown one must still be handled.
+        let connected = harness.nodes[1].peer_id();
+        let unknown = libp2p::PeerId::random();
+        let address: libp2p::Multiaddr = "/ip4/127.0.0.1/udp/9/quic-v1".parse().unwrap();
+        NetworkCallbackPreset::DiscoverLanPeers {
+            peers: vec![(connected, address.clone()), (unknown, address)],
+        }
+        .call(&mut harness.nodes[0].app)
+        .unwrap();
+
+        let swarm_panel = &harness.nodes[0].app.ui.swarm_panel;
+        assert!(swarm_panel.is_lan_peer(&connected));
+        assert!(swarm_panel.is_lan_peer(&unknown));
+        let handler = harness.nodes[0].app.network_handler.as_ref().unwrap();
+        assert!(handler.swarm.is_connected(&connected));
+    }
+
+    #[tokio::test]
+    async fn test_seed_tcp_fallback() {
+        let mut harness = Harness::new(0).await.unwrap();
+        // A UDP socket that never answers, as if UDP was blocked.
+        let blackhole = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = blackhole.local_addr().unwrap().port();
+        harness.seed_addresses[0] = format!("/ip4/127.0.0.1/udp/{port}/quic-v1")
+            .parse()
+            .unwrap();
+        harness.add_node(0).unwrap();
+
+        harness
+            .run_until("node to reach the seed over TCP", |h| {
+                let handler = h.nodes[0].app.network_handler.as_ref().unwrap();
+                handler.is_connected_to_seed()
+            })
+            .await
+            .unwrap();
+        let relayer_peer_id = *harness.relayer.network_handler.swarm.local_peer_id();
+        assert_eq!(
+            harness.nodes[0]
+                .app
+                .ui
+                .swarm_panel
+                .peer_transport(&relayer_peer_id),
+            Some(PeerTransport::Tcp)
+        );
+    }
+}
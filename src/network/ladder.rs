@@ -0,0 +1,276 @@
+use super::results::SignedGameResult;
+use crate::types::TeamId;
+use libp2p::PeerId;
+use std::collections::{HashMap, HashSet};
+
+// Elo ratings: every team starts at the same rating and a win against an
+// equal opponent is worth half of the K factor.
+pub const INITIAL_RATING: f64 = 1500.0;
+const K_FACTOR: f64 = 32.0;
+// Games between two teams that have both met fewer opponents than this count
+// for less, so that fresh peers cannot farm rating by playing each other.
+const ESTABLISHED_OPPONENTS: usize = 3;
+const PROVISIONAL_WEIGHT: f64 = 0.25;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LadderEntry {
+    pub team_id: TeamId,
+    pub team_name: String,
+    pub peer_id: PeerId,
+    pub rating: f64,
+    pub games: u32,
+    pub wins: u32,
+    pub losses: u32,
+    // Positive for consecutive wins, negative for consecutive losses.
+    pub streak: i32,
+    pub best_streak: u32,
+    opponents: HashSet<TeamId>,
+}
+
+impl LadderEntry {
+    fn new(team_id: TeamId, team_name: String, peer_id: PeerId) -> Self {
+        Self {
+            team_id,
+            team_name,
+            peer_id,
+            rating: INITIAL_RATING,
+            games: 0,
+            wins: 0,
+            losses: 0,
+            streak: 0,
+            best_streak: 0,
+            opponents: HashSet::new(),
+        }
+    }
+
+    fn is_established(&self) -> bool {
+        self.opponents.len() >= ESTABLISHED_OPPONENTS
+    }
+
+    fn expected_score(&self, opponent_rating: f64) -> f64 {
+        1.0 / (1.0 + 10f64.powf((opponent_rating - self.rating) / 400.0))
+    }
+
+    fn record(&mut self, score: f64, rating_change: f64) {
+        self.rating += rating_change;
+        self.games += 1;
+        if score > 0.5 {
+            self.wins += 1;
+            self.streak = self.streak.max(0) + 1;
+            self.best_streak = self.best_streak.max(self.streak as u32);
+        } else if score < 0.5 {
+            self.losses += 1;
+            self.streak = self.streak.min(0) - 1;
+        } else {
+            self.streak = 0;
+        }
+    }
+}
+
+// Results are replayed in the order the games were played, not the order
+// they were received, so every peer with the same results gets the same ladder.
+// Peers that missed results, or already evicted the oldest ones, see a different one.
+// Teams are bound to the peer that sent them, given by team_peer_id: games signed
+// by another peer, or with teams we never received, are not rated.
+pub fn compute_ladder<'a>(
+    results: impl Iterator<Item = &'a SignedGameResult>,
+    team_peer_id: impl Fn(&TeamId) -> Option<PeerId>,
+) -> Vec<LadderEntry> {
+    let mut results: Vec<&SignedGameResult> = results.collect();
+    results.sort_by_key(|signed| (signed.result.starting_at, signed.result.game_id));
+
+    let mut entries: HashMap<TeamId, LadderEntry> = HashMap::new();
+    for signed in results {
+        let result = &signed.result;
+        if result.home_team_id == result.away_team_id {
+            continue;
+        }
+        let is_bound = |team_id: &TeamId, peer_id: &PeerId| team_peer_id(team_id) == Some(*peer_id);
+        if !is_bound(&result.home_team_id, &result.home_peer_id)
+            || !is_bound(&result.away_team_id, &result.away_peer_id)
+        {
+            continue;
+        }
+        // Teams are renamed, the latest game tells the current name.
+        for (team_id, team_name, peer_id) in [
+            (
+                result.home_team_id,
+                &result.home_team_name,
+                result.home_peer_id,
+            ),
+            (
+                result.away_team_id,
+                &result.away_team_name,
+                result.away_peer_id,
+            ),
+        ] {
+            let entry = entries
+                .entry(team_id)
+                .or_insert_with(|| LadderEntry::new(team_id, team_name.clone(), peer_id));
+            entry.team_name = team_name.clone();
+        }
+
+        let home_score = match result.home_score.cmp(&result.away_score) {
+            std::cmp::Ordering::Greater => 1.0,
+            std::cmp::Ordering::Less => 0.0,
+            std::cmp::Ordering::Equal => 0.5,
+        };
+        let home = &entries[&result.home_team_id];
+        let away = &entries[&result.away_team_id];
+        let weight = if home.is_established() || away.is_established() {
+            1.0
+        } else {
+            PROVISIONAL_WEIGHT
+        };
+        let rating_change = weight * K_FACTOR * (home_score - home.expected_score(away.rating));
+        if let Some(home) = entries.get_mut(&result.home_team_id) {
+            home.record(home_score, rating_change);
+            home.opponents.insert(result.away_team_id);
+        }
+        if let Some(away) = entries.get_mut(&result.away_team_id) {
+            away.record(1.0 - home_score, -rating_change);
+            away.opponents.insert(result.home_team_id);
+        }
+    }
+
+    let mut ladder: Vec<LadderEntry> = entries.into_values().collect();
+    ladder.sort_by(|a, b| {
+        b.rating
+            .total_cmp(&a.rating)
+            .then(b.games.cmp(&a.games))
+            .then(a.team_id.cmp(&b.team_id))
+    });
+    ladder
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compute_ladder, LadderEntry, INITIAL_RATING};
+    use crate::network::results::{GameResult, ResultSignature, SignedGameResult};
+    use crate::types::{GameId, IdSystem, TeamId};
+    use libp2p::PeerId;
+    use std::collections::HashMap;
+
+    // Every team is bound to the peer of its first game.
+    fn ladder(results: &[SignedGameResult]) -> Vec<LadderEntry> {
+        let mut peers: HashMap<TeamId, PeerId> = HashMap::new();
+        for signed in results.iter() {
+            let result = &signed.result;
+            peers
+                .entry(result.home_team_id)
+                .or_insert(result.home_peer_id);
+            peers
+                .entry(result.away_team_id)
+                .or_insert(result.away_peer_id);
+        }
+        compute_ladder(results.iter(), |team_id| peers.get(team_id).copied())
+    }
+
+    fn signed_result(
+        starting_at: u128,
+        home: (TeamId, PeerId),
+        away: (TeamId, PeerId),
+        home_score: u16,
+        away_score: u16,
+    ) -> SignedGameResult {
+        let signature = ResultSignature {
+            public_key: vec![],
+            signature: vec![],
+        };
+        SignedGameResult {
+            result: GameResult {
+                game_id: GameId::new(),
+                starting_at,
+                home_team_id: home.0,
+                away_team_id: away.0,
+                home_team_name: "Home".to_string(),
+                away_team_name: "Away".to_string(),
+                home_peer_id: home.1,
+                away_peer_id: away.1,
+                home_team_version: 0,
+                away_team_version: 0,
+                home_score,
+                away_score,
+            },
+            home_signature: signature.clone(),
+            away_signature: signature,
+        }
+    }
+
+    #[test]
+    fn test_ladder() {
+        let a = (TeamId::new(), PeerId::random());
+        let b = (TeamId::new(), PeerId::random());
+        let c = (TeamId::new(), PeerId::random());
+        let results = vec![
+            signed_result(1, a, b, 20, 10),
+            signed_result(2, b, c, 15, 18),
+            signed_result(3, c, a, 12, 30),
+            signed_result(4, a, c, 22, 22),
+        ];
+
+        let ladder = ladder(&results);
+        assert_eq!(ladder.len(), 3);
+        assert_eq!(ladder[0].team_id, a.0);
+        assert_eq!(ladder[0].wins, 2);
+        assert_eq!(ladder[0].games, 3);
+        assert_eq!(ladder[0].streak, 0);
+        assert_eq!(ladder[0].best_streak, 2);
+        assert_eq!(ladder[2].team_id, b.0);
+        assert_eq!(ladder[2].streak, -2);
+        // Elo only moves points between teams.
+        let total: f64 = ladder.iter().map(|entry| entry.rating).sum();
+        assert!((total - 3.0 * INITIAL_RATING).abs() < 1e-6);
+
+        // The order results are received in does not matter.
+        let peers = |team_id: &TeamId| [a, b, c].iter().find(|t| t.0 == *team_id).map(|t| t.1);
+        let reversed = compute_ladder(results.iter().rev(), peers);
+        assert_eq!(ladder, reversed);
+    }
+
+    #[test]
+    fn test_ladder_binds_teams_to_peers() {
+        let a = (TeamId::new(), PeerId::random());
+        let b = (TeamId::new(), PeerId::random());
+        // Someone else signs games for team a, even before its first one.
+        let forged_a = (a.0, PeerId::random());
+        let results = vec![
+            signed_result(0, forged_a, b, 0, 40),
+            signed_result(1, a, b, 20, 10),
+            signed_result(2, forged_a, b, 0, 40),
+            signed_result(3, b, forged_a, 40, 0),
+        ];
+
+        let peers = |team_id: &TeamId| [a, b].iter().find(|t| t.0 == *team_id).map(|t| t.1);
+        let ladder = compute_ladder(results.iter(), peers);
+        let entry = ladder.iter().find(|entry| entry.team_id == a.0).unwrap();
+        assert_eq!(entry.peer_id, a.1);
+        assert_eq!(entry.games, 1);
+        assert_eq!(entry.wins, 1);
+    }
+
+    #[test]
+    fn test_ladder_discounts_fresh_teams() {
+        let fresh = (TeamId::new(), PeerId::random());
+        let sybil = (TeamId::new(), PeerId::random());
+        let fresh_games = vec![signed_result(1, fresh, sybil, 20, 10)];
+        let fresh_rating = ladder(&fresh_games)[0].rating;
+
+        // The same win against an established team is worth the full K factor.
+        let established = (TeamId::new(), PeerId::random());
+        let mut games = vec![];
+        for starting_at in 1..=3 {
+            let opponent = (TeamId::new(), PeerId::random());
+            games.push(signed_result(starting_at, established, opponent, 10, 10));
+        }
+        games.push(signed_result(4, fresh, established, 20, 10));
+        let ladder = ladder(&games);
+        let rating = ladder
+            .iter()
+            .find(|entry| entry.team_id == fresh.0)
+            .unwrap()
+            .rating;
+        assert!((fresh_rating - INITIAL_RATING - 4.0).abs() < 1e-6);
+        assert!((rating - INITIAL_RATING - 16.0).abs() < 1e-6);
+    }
+}
@@ -1,4 +1,14 @@
+pub mod challenge;
+pub mod chat;
 pub mod constants;
+pub mod ghosts;
 pub mod handler;
+#[cfg(test)]
+mod harness;
+pub mod ladder;
 pub mod network_callback;
+pub mod protocol;
+pub mod results;
 pub mod types;
+pub mod validation;
+pub mod wire;
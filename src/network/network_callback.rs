@@ -1,12 +1,27 @@
+use super::challenge::ChallengeEvent;
+use super::chat::ChatAuthor;
 use super::constants::*;
+use super::ghosts;
 use super::handler::NetworkHandler;
-use super::types::{Challenge, ChallengeState, NetworkGame, NetworkTeam, SeedInfo};
-use crate::types::{AppResult, SystemTimeTick, Tick, MINUTES};
-use crate::types::{GameId, IdSystem};
+use super::protocol::{PeerRequest, PeerResponse};
+use super::results::{GameResult, SignedGameResult};
+use super::types::{
+    Challenge, NetworkGame, NetworkTeam, PeerTransport, SeedInfo, TeamAnnouncement,
+    TeamTopicMessage,
+};
+use super::wire::{
+    Envelope, MessageKind, NetworkGameDto, NetworkTeamDto, GHOST_RESULT_WIRE_VERSION,
+    LEGACY_WIRE_VERSION, SIGNED_RESULT_WIRE_VERSION, WIRE_VERSION,
+};
+use crate::engine::command::GameCommand;
+use crate::types::{AppResult, SystemTimeTick, Tick};
 use crate::ui::utils::SwarmPanelEvent;
 use crate::{app::App, types::AppCallback};
 use libp2p::gossipsub::{IdentTopic, TopicHash};
+use libp2p::request_response::RequestId;
 use libp2p::{gossipsub::Message, Multiaddr, PeerId};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
 pub enum NetworkCallbackPreset {
@@ -35,6 +50,17 @@ pub enum NetworkCallbackPreset {
     },
     HandleConnectionEstablished {
         peer_id: PeerId,
+        transport: PeerTransport,
+    },
+    UpdatePeerTransport {
+        peer_id: PeerId,
+        transport: PeerTransport,
+    },
+    DiscoverLanPeers {
+        peers: Vec<(PeerId, Multiaddr)>,
+    },
+    ExpireLanPeers {
+        peers: Vec<(PeerId, Multiaddr)>,
     },
     HandleTeamTopic {
         message: Message,
@@ -42,15 +68,35 @@ pub enum NetworkCallbackPreset {
     HandleMsgTopic {
         message: Message,
     },
-    HandleChallengeTopic {
-        message: Message,
-    },
     HandleGameTopic {
         message: Message,
     },
     HandleSeedTopic {
         message: Message,
     },
+    HandleResultTopic {
+        message: Message,
+    },
+    HandleRequest {
+        peer_id: PeerId,
+        request_id: RequestId,
+        request: PeerRequest,
+    },
+    HandleResponse {
+        peer_id: PeerId,
+        response: PeerResponse,
+    },
+    HandleRequestFailure {
+        peer_id: PeerId,
+        request_id: RequestId,
+        error: String,
+    },
+    RejectMessage {
+        peer_id: PeerId,
+        version: u16,
+        is_compatible: bool,
+        error: String,
+    },
 }
 impl NetworkCallbackPreset {
     fn push_swarm_panel_message(timestamp: Tick, peer_id: PeerId, text: String) -> AppCallback {
@@ -86,9 +132,7 @@ impl NetworkCallbackPreset {
             };
             app.ui.swarm_panel.push_log_event(event);
             app.network_handler.as_mut().unwrap().address = address.clone();
-
-            let multiaddr = app.network_handler.as_ref().unwrap().seed_address.clone();
-            app.network_handler.as_mut().unwrap().dial(multiaddr)?;
+            app.network_handler.as_mut().unwrap().dial_seed()?;
             Ok(None)
         })
     }
@@ -106,6 +150,8 @@ impl NetworkCallbackPreset {
                 if app.world.has_own_team() {
                     app.world.dirty_network = true;
                 }
+            } else if topic == IdentTopic::new(SubscriptionTopic::RESULT).hash() {
+                Self::announce_own_results(app)?;
             }
             Ok(None)
         })
@@ -140,6 +186,7 @@ impl NetworkCallbackPreset {
                     text: format!("Closing connection: {}", peer_id),
                 };
                 app.ui.swarm_panel.push_log_event(event);
+                app.ui.swarm_panel.remove_peer_transport(&peer_id);
                 // app.ui.swarm_panel.remove_peer_id(&peer_id);
                 app.world.filter_peer_data(Some(peer_id));
             }
@@ -147,44 +194,565 @@ impl NetworkCallbackPreset {
         })
     }
 
-    fn handle_team_topic(message: Message) -> AppCallback {
+    fn discover_lan_peers(peers: Vec<(PeerId, Multiaddr)>) -> AppCallback {
         Box::new(move |app: &mut App| {
-            let (timestamp, data) = split_message(&message);
-            let peer_id = message.source.clone();
-            let event = SwarmPanelEvent {
+            // A peer is reported once for each of its addresses.
+            let mut addresses: HashMap<PeerId, Vec<Multiaddr>> = HashMap::new();
+            for (peer_id, address) in peers.iter() {
+                addresses.entry(*peer_id).or_default().push(address.clone());
+            }
+
+            let network_handler = app.network_handler.as_mut().unwrap();
+            for (peer_id, addresses) in addresses {
+                if peer_id == *network_handler.swarm.local_peer_id() {
+                    continue;
+                }
+                let event = SwarmPanelEvent {
+                    timestamp: Tick::now(),
+                    peer_id: Some(peer_id),
+                    text: format!("Discovered LAN peer at {}", addresses[0]),
+                };
+                app.ui.swarm_panel.push_log_event(event);
+                app.ui.swarm_panel.add_lan_peer(peer_id);
+                // A failed dial must not stop the other peers from being dialed.
+                if let Err(err) = network_handler.dial_lan_peer(peer_id, addresses) {
+                    let event = SwarmPanelEvent {
+                        timestamp: Tick::now(),
+                        peer_id: Some(peer_id),
+                        text: format!("Cannot dial LAN peer: {}", err),
+                    };
+                    app.ui.swarm_panel.push_log_event(event);
+                }
+            }
+            Ok(None)
+        })
+    }
+
+    // The connection, if any, is closed separately.
+    fn expire_lan_peers(peers: Vec<(PeerId, Multiaddr)>) -> AppCallback {
+        Box::new(move |app: &mut App| {
+            for (peer_id, _) in peers.iter() {
+                app.ui.swarm_panel.remove_lan_peer(peer_id);
+            }
+            Ok(None)
+        })
+    }
+
+    // Records the wire version of the peer and tells whether we can read its messages.
+    // Incompatible peers are only logged the first time we see their version.
+    fn check_peer_version(
+        app: &mut App,
+        timestamp: Tick,
+        peer_id: Option<PeerId>,
+        version: u16,
+        is_compatible: bool,
+    ) -> bool {
+        let is_new_version = peer_id
+            .map(|id| app.ui.swarm_panel.set_peer_version(id, version))
+            .unwrap_or(false);
+        if !is_compatible && is_new_version {
+            app.ui.swarm_panel.push_log_event(SwarmPanelEvent {
                 timestamp,
                 peer_id,
-                text: format!("Got a team from peer: {:?}", peer_id),
+                text: format!(
+                    "Peer runs protocol version {}, we run {}: ignoring its messages",
+                    version, WIRE_VERSION
+                ),
+            });
+        }
+        is_compatible
+    }
+
+    // Returns None if the message comes from an incompatible peer.
+    fn open_envelope(
+        app: &mut App,
+        timestamp: Tick,
+        peer_id: Option<PeerId>,
+        data: &[u8],
+    ) -> Option<Envelope> {
+        let envelope = Envelope::from_slice(data);
+        let (version, is_compatible) = envelope
+            .as_ref()
+            .map(|envelope| (envelope.version, envelope.is_compatible()))
+            .unwrap_or((LEGACY_WIRE_VERSION, false));
+        if !Self::check_peer_version(app, timestamp, peer_id, version, is_compatible) {
+            return None;
+        }
+        envelope
+    }
+
+    fn reject_message(
+        peer_id: PeerId,
+        version: u16,
+        is_compatible: bool,
+        error: String,
+    ) -> AppCallback {
+        Box::new(move |app: &mut App| {
+            if Self::check_peer_version(app, Tick::now(), Some(peer_id), version, is_compatible) {
+                return Err(format!("Invalid message from {}: {}", peer_id, error).into());
+            }
+            Ok(None)
+        })
+    }
+
+    fn handle_team_topic(message: Message) -> AppCallback {
+        Box::new(move |app: &mut App| {
+            let (timestamp, data) = split_message(&message)?;
+            let peer_id = message.source;
+
+            let envelope = match Self::open_envelope(app, timestamp, peer_id, data) {
+                Some(envelope) => envelope,
+                None => return Ok(None),
+            };
+            let team_message = match envelope.open::<TeamTopicMessage>(MessageKind::Team) {
+                Ok(team_message) => team_message,
+                Err(err) => {
+                    let text = format!("Failed to deserialize team message {}", err);
+                    let event = SwarmPanelEvent {
+                        timestamp,
+                        peer_id,
+                        text: text.clone(),
+                    };
+                    app.ui.swarm_panel.push_log_event(event);
+                    return Err(text)?;
+                }
+            };
+
+            match team_message {
+                TeamTopicMessage::Announcement(announcement) => {
+                    if peer_id != Some(announcement.peer_id) {
+                        return Err("Team announcement does not match its source".into());
+                    }
+                    Self::handle_team_announcement(app, announcement)?;
+                }
+                TeamTopicMessage::Team(network_team) => {
+                    Self::add_network_team(app, timestamp, peer_id, (*network_team).into())?;
+                }
+            }
+            Ok(None)
+        })
+    }
+
+    // Fetches the team (and its game) if ours is missing or outdated.
+    fn handle_team_announcement(app: &mut App, announcement: TeamAnnouncement) -> AppResult<()> {
+        let network_handler = app.network_handler.as_mut().unwrap();
+        app.ui
+            .swarm_panel
+            .add_peer_id(announcement.peer_id, announcement.team_id);
+        network_handler.add_peer_addresses(&announcement.peer_id, announcement.addresses);
+
+        let is_outdated = app
+            .world
+            .get_team(announcement.team_id)
+            .map(|team| team.version < announcement.version)
+            .unwrap_or(true);
+        if is_outdated {
+            network_handler.request_team(&app.world, announcement.peer_id, announcement.team_id)?;
+        }
+
+        if let Some(game_id) = announcement.current_game {
+            if app.world.get_game(game_id).is_none() {
+                network_handler
+                    .send_request(announcement.peer_id, PeerRequest::Game { game_id })?;
+            }
+        }
+
+        if app.ghosts.has_results(&announcement.peer_id) {
+            Self::send_ghost_results(app, announcement.peer_id)?;
+        }
+        Self::send_result_signatures(app, announcement.peer_id)?;
+        Ok(())
+    }
+
+    // Counts the invalid data against its peer, repeat offenders are banned.
+    fn reject_peer_data(app: &mut App, peer_id: PeerId, error: String) -> AppResult<()> {
+        let network_handler = app
+            .network_handler
+            .as_mut()
+            .ok_or("Network handler is not initialized")?;
+        if network_handler.validator.record_offence(peer_id) {
+            network_handler.disconnect_peer(peer_id);
+            app.world.filter_peer_data(Some(peer_id));
+            app.ui.swarm_panel.remove_peer_id(&peer_id);
+            app.ui.swarm_panel.push_log_event(SwarmPanelEvent {
+                timestamp: Tick::now(),
+                peer_id: Some(peer_id),
+                text: format!("Banned peer {} for sending invalid data", peer_id),
+            });
+        }
+        Err(error.into())
+    }
+
+    fn validate_challenge(app: &mut App, peer_id: PeerId, challenge: &Challenge) -> AppResult<()> {
+        let network_handler = app
+            .network_handler
+            .as_ref()
+            .ok_or("Network handler is not initialized")?;
+        let team_in_game = if challenge.home_peer_id == peer_id {
+            challenge.home_team.as_ref()
+        } else {
+            challenge.away_team.as_ref()
+        };
+        if let Some(team_in_game) = team_in_game {
+            if let Err(err) = network_handler
+                .validator
+                .validate_team_in_game(peer_id, team_in_game)
+            {
+                let error = format!("Rejected challenge from {}: {}", peer_id, err);
+                return Self::reject_peer_data(app, peer_id, error);
+            }
+        }
+        Ok(())
+    }
+
+    fn add_network_team(
+        app: &mut App,
+        timestamp: Tick,
+        peer_id: Option<PeerId>,
+        network_team: NetworkTeam,
+    ) -> AppResult<()> {
+        let peer_id = peer_id.ok_or("Team was not sent by its peer")?;
+        let network_handler = app
+            .network_handler
+            .as_mut()
+            .ok_or("Network handler is not initialized")?;
+        let name = network_team.team.name.clone();
+        if let Err(err) = network_handler
+            .validator
+            .validate_team(peer_id, &network_team, Tick::now())
+            .and_then(|_| app.world.add_network_team(network_team.clone()))
+        {
+            let error = format!("Rejected team {} from {}: {}", name, peer_id, err);
+            return Self::reject_peer_data(app, peer_id, error);
+        }
+        if network_handler
+            .validator
+            .record_team(peer_id, &network_team, Tick::now())
+        {
+            // Results of the team are rated now that we know who it belongs to.
+            app.game_results.refresh_ladder();
+        }
+
+        let peer_id = Some(peer_id);
+        let event = SwarmPanelEvent {
+            timestamp,
+            peer_id,
+            text: format!(
+                "Got team: {} {}",
+                network_team.team.name, network_team.team.version
+            ),
+        };
+        app.ui.swarm_panel.push_log_event(event);
+        if let Some(id) = peer_id {
+            app.ui.swarm_panel.add_peer_id(id, network_team.team.id);
+        }
+        app.ghosts.record(network_team, timestamp);
+        app.ui.swarm_panel.set_ghost_teams(app.ghosts.names());
+        Ok(())
+    }
+
+    // The ghost team leaves the world with the game, unless its peer came back.
+    pub fn finish_ghost_game(app: &mut App, peer_id: PeerId) -> AppResult<()> {
+        let is_connected = app
+            .network_handler
+            .as_ref()
+            .is_some_and(|handler| handler.swarm.is_connected(&peer_id));
+        if !is_connected && !app.ghosts.is_playing(&peer_id) {
+            app.world.filter_peer_data(Some(peer_id));
+        }
+        Self::send_ghost_results(app, peer_id)
+    }
+
+    // Signs the result of a network game that just ended, the opponent signs it too.
+    pub fn finish_network_game(app: &mut App, result: GameResult) -> AppResult<()> {
+        let network_handler = app
+            .network_handler
+            .as_ref()
+            .ok_or("Network handler is not initialized")?;
+        let local_peer_id = *network_handler.swarm.local_peer_id();
+        let signature = network_handler.sign_result(&result)?;
+        let opponent = result
+            .opponent(&local_peer_id)
+            .ok_or("Result is not from one of our games")?;
+        if let Some(signed) =
+            app.game_results
+                .add_own_signature(result, signature, local_peer_id)?
+        {
+            Self::publish_game_result(app, signed)?;
+        }
+        Self::send_result_signatures(app, opponent)
+    }
+
+    fn publish_game_result(app: &mut App, signed: SignedGameResult) -> AppResult<()> {
+        let result = &signed.result;
+        app.ui.swarm_panel.push_log_event(SwarmPanelEvent {
+            timestamp: Tick::now(),
+            peer_id: None,
+            text: format!(
+                "Signed result: {} {}-{} {}",
+                result.home_team_name, result.home_score, result.away_score, result.away_team_name
+            ),
+        });
+        let network_handler = app
+            .network_handler
+            .as_mut()
+            .ok_or("Network handler is not initialized")?;
+        // Without peers it is announced when the first one joins the topic.
+        if network_handler.swarm.connected_peers().count() > 0 {
+            network_handler.publish_game_result(&signed)?;
+        }
+        Ok(())
+    }
+
+    // Peers that were offline when our games ended get the results when they join.
+    fn announce_own_results(app: &mut App) -> AppResult<()> {
+        let network_handler = match app.network_handler.as_mut() {
+            Some(network_handler) => network_handler,
+            None => return Ok(()),
+        };
+        let local_peer_id = *network_handler.swarm.local_peer_id();
+        for signed in app.game_results.own_results(local_peer_id) {
+            network_handler.publish_game_result(&signed)?;
+        }
+        Ok(())
+    }
+
+    // Our signatures are sent again until the opponent has signed too.
+    fn send_result_signatures(app: &mut App, peer_id: PeerId) -> AppResult<()> {
+        let network_handler = match app.network_handler.as_mut() {
+            Some(network_handler) => network_handler,
+            None => return Ok(()),
+        };
+        let can_receive = app
+            .ui
+            .swarm_panel
+            .peer_version(&peer_id)
+            .is_some_and(|version| version >= SIGNED_RESULT_WIRE_VERSION);
+        if !can_receive || !network_handler.swarm.is_connected(&peer_id) {
+            return Ok(());
+        }
+        for (game_id, signature) in app.game_results.pending_signatures(&peer_id) {
+            network_handler
+                .send_request(peer_id, PeerRequest::ResultSignature { game_id, signature })?;
+        }
+        Ok(())
+    }
+
+    // Results are kept until their owner is online with a version that reads them.
+    fn send_ghost_results(app: &mut App, peer_id: PeerId) -> AppResult<()> {
+        let network_handler = match app.network_handler.as_mut() {
+            Some(network_handler) => network_handler,
+            None => return Ok(()),
+        };
+        let can_receive = app
+            .ui
+            .swarm_panel
+            .peer_version(&peer_id)
+            .is_some_and(|version| version >= GHOST_RESULT_WIRE_VERSION);
+        if !can_receive || !network_handler.swarm.is_connected(&peer_id) {
+            return Ok(());
+        }
+        for summary in app.ghosts.take_results(&peer_id) {
+            network_handler.send_request(peer_id, PeerRequest::GhostResult(Box::new(summary)))?;
+        }
+        Ok(())
+    }
+
+    fn handle_request(peer_id: PeerId, request_id: RequestId, request: PeerRequest) -> AppCallback {
+        Box::new(move |app: &mut App| {
+            let network_handler = app.network_handler.as_mut().unwrap();
+            match request.clone() {
+                PeerRequest::Challenge(challenge) => {
+                    let challenge = Challenge::from(*challenge);
+                    if challenge.home_peer_id != peer_id && challenge.away_peer_id != peer_id {
+                        network_handler.send_response(
+                            request_id,
+                            PeerResponse::Error("Challenge was not sent by its peer".to_string()),
+                        )?;
+                        return Ok(None);
+                    }
+                    if let Err(err) = Self::validate_challenge(app, peer_id, &challenge) {
+                        let network_handler = app.network_handler.as_mut().unwrap();
+                        network_handler
+                            .send_response(request_id, PeerResponse::Error(err.to_string()))?;
+                        return Err(err);
+                    }
+                    let network_handler = app.network_handler.as_mut().unwrap();
+                    network_handler.send_response(request_id, PeerResponse::Ack)?;
+
+                    let event = SwarmPanelEvent {
+                        timestamp: Tick::now(),
+                        peer_id: Some(peer_id),
+                        text: format!("\nChallenge: {}", challenge.format()),
+                    };
+                    app.ui.swarm_panel.push_log_event(event);
+                    let events = network_handler
+                        .challenges
+                        .handle_message(challenge, Tick::now());
+                    Self::handle_challenge_events(app, events)
+                }
+                PeerRequest::Team { known_players } => {
+                    let response = match network_handler.own_network_team(&app.world) {
+                        Ok(network_team) if known_players.is_empty() => {
+                            PeerResponse::Team(Box::new(NetworkTeamDto::from(&network_team)))
+                        }
+                        Ok(network_team) => PeerResponse::TeamDelta(Box::new(
+                            NetworkTeamDto::delta(&network_team, &known_players),
+                        )),
+                        Err(err) => PeerResponse::Error(err.to_string()),
+                    };
+                    network_handler.send_response(request_id, response)?;
+                    Ok(None)
+                }
+                PeerRequest::Game { game_id } => {
+                    let response = match NetworkGame::from_game_id(&app.world, game_id) {
+                        Ok(network_game) => {
+                            PeerResponse::Game(Box::new(NetworkGameDto::from(&network_game)))
+                        }
+                        Err(err) => PeerResponse::Error(err.to_string()),
+                    };
+                    network_handler.send_response(request_id, response)?;
+                    Ok(None)
+                }
+                // Muted peers are acknowledged too, they don't need to know.
+                PeerRequest::Chat { text } => {
+                    network_handler.send_response(request_id, PeerResponse::Ack)?;
+                    app.ui.swarm_panel.push_direct_message(
+                        peer_id,
+                        ChatAuthor::Peer(peer_id),
+                        text,
+                    );
+                    Ok(None)
+                }
+                PeerRequest::ResultSignature { game_id, signature } => {
+                    let local_peer_id = *network_handler.swarm.local_peer_id();
+                    match app.game_results.add_opponent_signature(
+                        game_id,
+                        peer_id,
+                        signature,
+                        local_peer_id,
+                    ) {
+                        Ok(signed) => {
+                            network_handler.send_response(request_id, PeerResponse::Ack)?;
+                            if let Some(signed) = signed {
+                                Self::publish_game_result(app, signed)?;
+                            }
+                        }
+                        Err(err) => {
+                            network_handler
+                                .send_response(request_id, PeerResponse::Error(err.to_string()))?;
+                        }
+                    }
+                    Ok(None)
+                }
+                PeerRequest::GhostResult(summary) => {
+                    if let Err(err) = ghosts::validate_result(&app.world, peer_id, &summary) {
+                        network_handler
+                            .send_response(request_id, PeerResponse::Error(err.to_string()))?;
+                        return Ok(None);
+                    }
+                    network_handler.send_response(request_id, PeerResponse::Ack)?;
+
+                    let text = format!(
+                        "Played against our ghost: {} {}-{} {}",
+                        summary.home_team_name,
+                        summary.home_score,
+                        summary.away_score,
+                        summary.away_team_name
+                    );
+                    app.ui.swarm_panel.push_log_event(SwarmPanelEvent {
+                        timestamp: Tick::now(),
+                        peer_id: Some(peer_id),
+                        text: text.clone(),
+                    });
+                    app.ui
+                        .swarm_panel
+                        .push_direct_message(peer_id, ChatAuthor::System, text);
+                    if let Entry::Vacant(entry) = app.world.past_games.entry(summary.id) {
+                        entry.insert(*summary);
+                        app.world.dirty = true;
+                        app.world.dirty_ui = true;
+                    }
+                    Ok(None)
+                }
+            }
+        })
+    }
+
+    fn handle_response(peer_id: PeerId, response: PeerResponse) -> AppCallback {
+        Box::new(move |app: &mut App| {
+            match response.clone() {
+                PeerResponse::Ack => {}
+                PeerResponse::Team(network_team) => {
+                    Self::add_network_team(
+                        app,
+                        Tick::now(),
+                        Some(peer_id),
+                        (*network_team).into(),
+                    )?;
+                }
+                PeerResponse::TeamDelta(delta) => match delta.merge(&app.world) {
+                    Ok(network_team) => {
+                        Self::add_network_team(app, Tick::now(), Some(peer_id), network_team)?;
+                    }
+                    // We lost some of the players in the meantime, ask for all of them.
+                    Err(_) => {
+                        let network_handler = app.network_handler.as_mut().unwrap();
+                        network_handler.send_request(
+                            peer_id,
+                            PeerRequest::Team {
+                                known_players: vec![],
+                            },
+                        )?;
+                    }
+                },
+                PeerResponse::Game(network_game) => {
+                    let event = SwarmPanelEvent {
+                        timestamp: Tick::now(),
+                        peer_id: Some(peer_id),
+                        text: format!("Got game: {}", network_game.id),
+                    };
+                    app.ui.swarm_panel.push_log_event(event);
+                    app.world
+                        .add_network_game((*network_game).into(), peer_id)?;
+                }
+                PeerResponse::Error(err) => {
+                    return Err(format!("Request to {} failed: {}", peer_id, err).into());
+                }
+            }
+            Ok(None)
+        })
+    }
+
+    fn handle_request_failure(
+        peer_id: PeerId,
+        request_id: RequestId,
+        error: String,
+    ) -> AppCallback {
+        Box::new(move |app: &mut App| {
+            let network_handler = app.network_handler.as_mut().unwrap();
+            let event = SwarmPanelEvent {
+                timestamp: Tick::now(),
+                peer_id: Some(peer_id),
+                text: format!("Request to {} failed: {}", peer_id, error),
             };
             app.ui.swarm_panel.push_log_event(event);
 
-            let try_deserialize_team = serde_json::from_slice::<NetworkTeam>(data);
-            if let Ok(network_team) = try_deserialize_team {
-                let event = SwarmPanelEvent {
-                    timestamp,
-                    peer_id,
-                    text: format!(
-                        "Deserialized team: {} {}",
-                        network_team.team.name, network_team.team.version
-                    ),
-                };
-                app.ui.swarm_panel.push_log_event(event);
-                if let Some(id) = peer_id {
-                    app.ui.swarm_panel.add_peer_id(id, network_team.team.id);
+            // Challenges are retransmitted by the challenge manager and teams are
+            // requested again on the next announcement, other requests need handling.
+            match network_handler.take_failed_request(&request_id) {
+                Some((_, PeerRequest::GhostResult(summary))) => {
+                    app.ghosts.push_result(peer_id, *summary);
                 }
-                app.world.add_network_team(network_team)?;
-            } else {
-                let text = format!(
-                    "Failed to deserialize network team {}",
-                    try_deserialize_team.unwrap_err()
-                );
-                let event = SwarmPanelEvent {
-                    timestamp,
-                    peer_id,
-                    text: text.clone(),
-                };
-                app.ui.swarm_panel.push_log_event(event);
-                return Err(text)?;
+                // Direct messages are not gossiped, they would not be private anymore.
+                Some((_, PeerRequest::Chat { .. })) => {
+                    app.ui.swarm_panel.push_direct_message(
+                        peer_id,
+                        ChatAuthor::System,
+                        format!("Not delivered: {}", error),
+                    );
+                }
+                _ => {}
             }
             Ok(None)
         })
@@ -192,8 +760,12 @@ impl NetworkCallbackPreset {
 
     fn handle_msg_topic(message: Message) -> AppCallback {
         Box::new(move |app: &mut App| {
-            let (timestamp, data) = split_message(&message);
-            let text = std::str::from_utf8(data)?.to_string();
+            let (timestamp, data) = split_message(&message)?;
+            let envelope = match Self::open_envelope(app, timestamp, message.source, data) {
+                Some(envelope) => envelope,
+                None => return Ok(None),
+            };
+            let text = envelope.open::<String>(MessageKind::Chat)?;
             let event = SwarmPanelEvent {
                 timestamp,
                 peer_id: message.source,
@@ -206,36 +778,97 @@ impl NetworkCallbackPreset {
 
     fn handle_game_topic(message: Message) -> AppCallback {
         Box::new(move |app: &mut App| {
-            let (timestamp, data) = split_message(&message);
-            let peer_id = message.source.clone();
-            let event = SwarmPanelEvent {
-                timestamp,
-                peer_id,
-                text: format!("Got a game from peer: {:?}", peer_id),
+            let (timestamp, data) = split_message(&message)?;
+            let peer_id = message.source;
+            let envelope = match Self::open_envelope(app, timestamp, peer_id, data) {
+                Some(envelope) => envelope,
+                None => return Ok(None),
             };
-            app.ui.swarm_panel.push_log_event(event);
 
-            let try_deserialize_game = serde_json::from_slice::<NetworkGame>(data);
-            if let Ok(game) = try_deserialize_game {
-                let event = SwarmPanelEvent {
-                    timestamp,
-                    peer_id,
-                    text: format!("Deserialized game: {}", game.id),
-                };
-                app.ui.swarm_panel.push_log_event(event);
-                app.world.add_network_game(game)?;
-            } else {
-                let text = format!(
-                    "Failed to deserialize game {}",
-                    try_deserialize_game.unwrap_err()
-                );
-                let event = SwarmPanelEvent {
-                    timestamp,
-                    peer_id,
-                    text: text.clone(),
-                };
-                app.ui.swarm_panel.push_log_event(event);
-                return Err(text)?;
+            match envelope.kind {
+                MessageKind::Game => {
+                    let game: NetworkGame =
+                        envelope.open::<NetworkGameDto>(MessageKind::Game)?.into();
+                    let event = SwarmPanelEvent {
+                        timestamp,
+                        peer_id,
+                        text: format!("Got game: {}", game.id),
+                    };
+                    app.ui.swarm_panel.push_log_event(event);
+                    let source = peer_id.ok_or("Game without source".to_string())?;
+                    app.world.add_network_game(game, source)?;
+                }
+                MessageKind::GameCommand => {
+                    let command = envelope.open::<GameCommand>(MessageKind::GameCommand)?;
+                    let event = SwarmPanelEvent {
+                        timestamp,
+                        peer_id,
+                        text: format!("Got game command: {}", command.kind),
+                    };
+                    app.ui.swarm_panel.push_log_event(event);
+                    let source = peer_id.ok_or("Game command without source".to_string())?;
+                    // We missed the moment to apply it, ask the sender for the whole game.
+                    if app
+                        .world
+                        .get_game(command.game_id)
+                        .map(|game| game.misses_command(&command))
+                        .unwrap_or_default()
+                    {
+                        let game_id = command.game_id;
+                        app.ui.swarm_panel.push_log_event(SwarmPanelEvent {
+                            timestamp,
+                            peer_id,
+                            text: format!("Game {} is out of sync, requesting it again", game_id),
+                        });
+                        app.network_handler
+                            .as_mut()
+                            .unwrap()
+                            .send_request(source, PeerRequest::Game { game_id })?;
+                        return Ok(None);
+                    }
+                    app.world.add_network_game_command(command, source)?;
+                }
+                kind => {
+                    return Err(format!("Unexpected {} message on the game topic", kind).into());
+                }
+            }
+            Ok(None)
+        })
+    }
+
+    // Anyone can forward a result, it only counts if both teams signed it.
+    fn handle_result_topic(message: Message) -> AppCallback {
+        Box::new(move |app: &mut App| {
+            let (timestamp, data) = split_message(&message)?;
+            let peer_id = message
+                .source
+                .ok_or("Game result without source".to_string())?;
+            let envelope = match Self::open_envelope(app, timestamp, Some(peer_id), data) {
+                Some(envelope) => envelope,
+                None => return Ok(None),
+            };
+            let signed = envelope.open::<SignedGameResult>(MessageKind::GameResult)?;
+            let text = format!(
+                "Got result: {} {}-{} {}",
+                signed.result.home_team_name,
+                signed.result.home_score,
+                signed.result.away_score,
+                signed.result.away_team_name
+            );
+            match app.game_results.insert(signed) {
+                Ok(true) => {
+                    app.ui.swarm_panel.push_log_event(SwarmPanelEvent {
+                        timestamp,
+                        peer_id: Some(peer_id),
+                        text,
+                    });
+                    app.world.dirty_ui = true;
+                }
+                Ok(false) => {}
+                Err(err) => {
+                    let error = format!("Rejected game result from {}: {}", peer_id, err);
+                    Self::reject_peer_data(app, peer_id, error)?;
+                }
             }
             Ok(None)
         })
@@ -243,7 +876,7 @@ impl NetworkCallbackPreset {
 
     pub fn handle_seed_topic(message: Message) -> AppCallback {
         Box::new(move |app: &mut App| {
-            let (timestamp, data) = split_message(&message);
+            let (timestamp, data) = split_message(&message)?;
             let info = serde_json::from_slice::<SeedInfo>(data)?;
 
             let event = SwarmPanelEvent {
@@ -253,11 +886,19 @@ impl NetworkCallbackPreset {
             };
             app.ui.swarm_panel.push_log_event(event);
 
-            if info.message.is_some() {
-                app.ui.set_popup(crate::ui::ui::PopupMessage::Ok(
-                    info.message.unwrap(),
-                    timestamp,
-                ));
+            let own_peer_id = app
+                .network_handler
+                .as_ref()
+                .map(|handler| *handler.swarm.local_peer_id());
+            for announcement in info.directory {
+                if Some(announcement.peer_id) != own_peer_id {
+                    Self::handle_team_announcement(app, announcement)?;
+                }
+            }
+
+            if let Some(message) = info.message {
+                app.ui
+                    .set_popup(crate::ui::ui::PopupMessage::Ok(message, timestamp));
             }
 
             let own_version_major = env!("CARGO_PKG_VERSION_MAJOR").parse()?;
@@ -282,157 +923,90 @@ impl NetworkCallbackPreset {
         })
     }
 
-    pub fn handle_challenge_topic(message: Message) -> AppCallback {
-        Box::new(move |app: &mut App| {
-            let network_handler = app.network_handler.as_mut().unwrap();
-            let self_peer_id = network_handler.swarm.local_peer_id().clone();
-            let (timestamp, data) = split_message(&message);
-
-            let mut challenge = serde_json::from_slice::<Challenge>(data)?;
-            let event = SwarmPanelEvent {
-                timestamp,
-                peer_id: message.source,
-                text: format!("\nChallenge: {}", challenge.format()),
-            };
-            app.ui.swarm_panel.push_log_event(event);
-
-            match challenge.state {
-                //FIXME: I think a single player is trying to handle more than one state. We should enforce the roles more clearly, i.e. who is the challenger who the challenged and only handle relevant states
-                ChallengeState::Syn => {
-                    if challenge.home_peer_id == self_peer_id {
-                        return Err(format!("Team is challenge sender (should be receiver)").into());
-                    }
+    // Applies the outcome of the challenge state machine to the world, the UI and the network.
+    pub fn handle_challenge_events(
+        app: &mut App,
+        events: Vec<ChallengeEvent>,
+    ) -> AppResult<Option<String>> {
+        let mut result = None;
+        for event in events {
+            let network_handler = app
+                .network_handler
+                .as_mut()
+                .ok_or("Network handler is not initialized")?;
+            let self_peer_id = *network_handler.swarm.local_peer_id();
 
-                    if challenge.away_peer_id != self_peer_id {
-                        return Err(format!("Team is not challenge receiver").into());
+            match event {
+                ChallengeEvent::Send(challenge) => {
+                    if let Err(err) = network_handler.send_challenge(&challenge) {
+                        app.ui.swarm_panel.push_log_event(SwarmPanelEvent {
+                            timestamp: Tick::now(),
+                            peer_id: None,
+                            text: format!("Failed to send challenge: {}", err),
+                        });
                     }
-
-                    network_handler.add_challenge(challenge.clone());
-
-                    app.ui
-                        .swarm_panel
-                        .add_challenge(challenge.home_peer_id.clone(), challenge.clone());
-
-                    return Ok(Some(
-                        "Challenge received.\nCheck the swarm panel".to_string(),
-                    ));
                 }
-
-                ChallengeState::SynAck => {
-                    if challenge.away_peer_id == self_peer_id {
-                        return Err(format!("Team is challenge receiver (should be sender)").into());
-                    }
-
-                    if challenge.home_peer_id != self_peer_id {
-                        return Err(format!("Team is not challenge sender").into());
-                    }
-
-                    let mut handle_syn_ack = || -> AppResult<()> {
-                        NetworkHandler::can_handle_challenge(&app.world)?;
-                        challenge.state = ChallengeState::Ack;
-                        challenge.game_id = Some(GameId::new());
-                        challenge.starting_at = Some(Tick::now() + 2 * MINUTES);
-
-                        let event = SwarmPanelEvent {
-                            timestamp,
-                            peer_id: message.source,
-                            text: format!("Challenge accepted, generating game"),
-                        };
-                        app.ui.swarm_panel.push_log_event(event);
-                        challenge.generate_game(&mut app.world)?;
-                        app.ui.set_popup(crate::ui::ui::PopupMessage::Ok(
-                            format!("Challenge accepted, game is starting."),
-                            Tick::now(),
-                        ));
-
-                        network_handler.send_challenge(&challenge)?;
-                        Ok(())
-                    };
-
-                    if let Err(err) = handle_syn_ack() {
-                        let mut challenge =
-                            Challenge::new(challenge.home_peer_id, challenge.away_peer_id);
-                        challenge.state = ChallengeState::Failed;
-                        challenge.error_message = Some(err.to_string());
-                        network_handler.send_challenge(&challenge)?;
-                        return Err(err.to_string())?;
-                    }
+                ChallengeEvent::Received(challenge) => {
+                    app.ui.swarm_panel.add_challenge(challenge);
+                    result = Some("Challenge received.\nCheck the swarm panel".to_string());
                 }
-
-                ChallengeState::Ack => {
-                    // Not team challenge, we just generate game to display it in UI.
+                ChallengeEvent::GenerateGame { challenge, confirm } => {
+                    // Not our challenge, we just generate the game to display it in the UI.
                     if challenge.home_peer_id != self_peer_id
                         && challenge.away_peer_id != self_peer_id
                     {
-                        let event = SwarmPanelEvent {
-                            timestamp,
-                            peer_id: message.source,
-                            text: format!("Adding challenge from network"),
+                        let text = match challenge.generate_game(&mut app.world) {
+                            Ok(_) => "Adding challenge from network".to_string(),
+                            Err(err) => format!("Cannot add challenge from network: {}", err),
                         };
-                        app.ui.swarm_panel.push_log_event(event);
-                        challenge.generate_game(&mut app.world)?;
-                        return Ok(None);
+                        app.ui.swarm_panel.push_log_event(SwarmPanelEvent {
+                            timestamp: Tick::now(),
+                            peer_id: None,
+                            text,
+                        });
+                        continue;
                     }
 
-                    if challenge.home_peer_id == self_peer_id {
-                        return Err(format!("Team is challenge sender (should be receiver)").into());
-                    }
-
-                    if challenge.away_peer_id != self_peer_id {
-                        return Err(format!("Team is not challenge receiver").into());
-                    }
-
-                    let mut handle_ack = || -> AppResult<()> {
+                    let mut generate = || -> AppResult<()> {
                         NetworkHandler::can_handle_challenge(&app.world)?;
-                        let event = SwarmPanelEvent {
-                            timestamp,
-                            peer_id: message.source,
-                            text: format!("Challenge accepted, generating game"),
-                        };
-                        app.ui.swarm_panel.push_log_event(event);
                         challenge.generate_game(&mut app.world)?;
-                        app.ui.set_popup(crate::ui::ui::PopupMessage::Ok(
-                            format!("Challenge accepted, game is starting."),
-                            Tick::now(),
-                        ));
                         Ok(())
                     };
 
-                    if let Err(err) = handle_ack() {
-                        let mut challenge =
-                            Challenge::new(challenge.home_peer_id, challenge.away_peer_id);
-                        challenge.state = ChallengeState::Failed;
-                        challenge.error_message = Some(err.to_string());
-                        network_handler.send_challenge(&challenge)?;
+                    if let Err(err) = generate() {
+                        network_handler.fail_challenge(challenge.id, err.to_string())?;
+                        app.ui.swarm_panel.remove_challenge(&challenge.id);
                         return Err(err.to_string())?;
                     }
-                }
 
-                ChallengeState::Failed => {
-                    assert!(challenge.error_message.is_some());
-                    if challenge.home_peer_id != self_peer_id
-                        && challenge.away_peer_id != self_peer_id
-                    {
-                        return Err("Challenge failed, but it's not our challenge.")?;
+                    if confirm {
+                        network_handler.send_challenge(&challenge)?;
                     }
-                    app.ui.swarm_panel.remove_challenge(&challenge.home_peer_id);
+                    app.ui.swarm_panel.push_log_event(SwarmPanelEvent {
+                        timestamp: Tick::now(),
+                        peer_id: None,
+                        text: "Challenge accepted, generating game".to_string(),
+                    });
+                    app.ui.swarm_panel.remove_challenge(&challenge.id);
+                    result = Some("Challenge accepted, game is starting.".to_string());
+                }
+                ChallengeEvent::Failed(challenge) => {
+                    app.ui.swarm_panel.remove_challenge(&challenge.id);
                     app.ui.set_popup(crate::ui::ui::PopupMessage::Error(
                         format!(
                             "Challenge failed: {}",
-                            challenge.error_message.clone().unwrap()
+                            challenge.error_message.clone().unwrap_or_default()
                         ),
                         Tick::now(),
                     ));
-
-                    return Err(format!(
-                        "Challenge failed. {}",
-                        challenge.error_message.unwrap()
-                    ))?;
+                }
+                ChallengeEvent::CancelGame(game_id) => {
+                    app.world.cancel_game(game_id)?;
                 }
             }
+        }
 
-            Ok(None)
-        })
+        Ok(result)
     }
 
     pub fn call(&self, app: &mut App) -> AppResult<Option<String>> {
@@ -441,42 +1015,158 @@ impl NetworkCallbackPreset {
                 timestamp,
                 peer_id,
                 text,
-            } => Self::push_swarm_panel_message(timestamp.clone(), peer_id.clone(), text.clone())(
-                app,
-            ),
+            } => Self::push_swarm_panel_message(*timestamp, *peer_id, text.clone())(app),
             Self::PushSwarmPanelLog { timestamp, text } => {
-                Self::push_swarm_panel_log(timestamp.clone(), text.clone())(app)
+                Self::push_swarm_panel_log(*timestamp, text.clone())(app)
             }
             Self::BindAddress { address } => Self::bind_address(address.clone())(app),
             Self::Subscribe { peer_id: _, topic } => Self::subscribe(topic.clone())(app),
-            Self::Unsubscribe { peer_id, topic } => {
-                Self::unsubscribe(peer_id.clone(), topic.clone())(app)
-            }
-            Self::CloseConnection { peer_id } => Self::close_connection(peer_id.clone())(app),
-            Self::HandleConnectionEstablished { peer_id } => {
-                let network_handler = app.network_handler.as_mut().unwrap();
-                network_handler.send_own_team(&app.world)?;
+            Self::Unsubscribe { peer_id, topic } => Self::unsubscribe(*peer_id, topic.clone())(app),
+            Self::CloseConnection { peer_id } => Self::close_connection(*peer_id)(app),
+            Self::HandleConnectionEstablished { peer_id, transport } => {
+                app.ui.swarm_panel.set_peer_transport(*peer_id, *transport);
                 let event = SwarmPanelEvent {
                     timestamp: Tick::now(),
-                    peer_id: Some(peer_id.clone()),
-                    text: format!("Connected to peer: {}", peer_id),
+                    peer_id: Some(*peer_id),
+                    text: format!("Connected to peer: {} over {}", peer_id, transport),
                 };
                 app.ui.swarm_panel.push_log_event(event);
+                let network_handler = app.network_handler.as_mut().unwrap();
+                network_handler.send_own_team(&app.world)?;
                 Ok(None)
             }
+            Self::UpdatePeerTransport { peer_id, transport } => {
+                if app.ui.swarm_panel.set_peer_transport(*peer_id, *transport) {
+                    app.ui.swarm_panel.push_log_event(SwarmPanelEvent {
+                        timestamp: Tick::now(),
+                        peer_id: Some(*peer_id),
+                        text: format!("Now connected to peer: {} over {}", peer_id, transport),
+                    });
+                }
+                Ok(None)
+            }
+            Self::DiscoverLanPeers { peers } => Self::discover_lan_peers(peers.clone())(app),
+            Self::ExpireLanPeers { peers } => Self::expire_lan_peers(peers.clone())(app),
             Self::HandleTeamTopic { message } => Self::handle_team_topic(message.clone())(app),
             Self::HandleMsgTopic { message } => Self::handle_msg_topic(message.clone())(app),
-            Self::HandleChallengeTopic { message } => {
-                Self::handle_challenge_topic(message.clone())(app)
-            }
             Self::HandleGameTopic { message } => Self::handle_game_topic(message.clone())(app),
             Self::HandleSeedTopic { message } => Self::handle_seed_topic(message.clone())(app),
+            Self::HandleResultTopic { message } => Self::handle_result_topic(message.clone())(app),
+            Self::HandleRequest {
+                peer_id,
+                request_id,
+                request,
+            } => Self::handle_request(*peer_id, *request_id, request.clone())(app),
+            Self::HandleResponse { peer_id, response } => {
+                Self::handle_response(*peer_id, response.clone())(app)
+            }
+            Self::HandleRequestFailure {
+                peer_id,
+                request_id,
+                error,
+            } => Self::handle_request_failure(*peer_id, *request_id, error.clone())(app),
+            Self::RejectMessage {
+                peer_id,
+                version,
+                is_compatible,
+                error,
+            } => Self::reject_message(*peer_id, *version, *is_compatible, error.clone())(app),
         }
     }
 }
 
-pub fn split_message(message: &Message) -> (Tick, &[u8]) {
-    // Deserialize the data as a team. first 16 bytes are the timestamp, so we slice them off
-    let timestamp = Tick::from_le_bytes(message.data[..16].try_into().unwrap());
-    (timestamp, &message.data[16..])
+pub fn split_message(message: &Message) -> AppResult<(Tick, &[u8])> {
+    // The first 16 bytes are the timestamp, the rest is the payload.
+    if message.data.len() < 16 {
+        return Err(format!("Message too short: {} bytes", message.data.len()).into());
+    }
+    let (timestamp, data) = message.data.split_at(16);
+    let timestamp = Tick::from_le_bytes(timestamp.try_into()?);
+    Ok((timestamp, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split_message, NetworkCallbackPreset};
+    use crate::app::App;
+    use crate::engine::command::{GameCommand, GameCommandKind};
+    use crate::engine::game::Game;
+    use crate::engine::tactic::DefenseTactic;
+    use crate::network::protocol::PeerResponse;
+    use crate::network::types::NetworkGame;
+    use crate::network::wire::NetworkGameDto;
+    use libp2p::gossipsub::{Message, TopicHash};
+    use libp2p::PeerId;
+
+    #[test]
+    fn test_own_game_resyncs_from_opponent() {
+        let mut app = App::new(Some(0), true, true, false, false, None);
+        let mut game = Game::test_game(&mut app.world);
+        let opponent = PeerId::random();
+        game.home_team_in_game.peer_id = Some(PeerId::random());
+        game.away_team_in_game.peer_id = Some(opponent);
+        let game_id = game.id;
+        let home_id = game.home_team_in_game.team_id;
+        let away_id = game.away_team_in_game.team_id;
+        app.world.own_team_id = home_id;
+        app.world.games.insert(game_id, game);
+
+        // The opponent coached its team, but we missed the command. It also
+        // tries to sneak in a command for our team.
+        let mut network_game = NetworkGame::from_game_id(&app.world, game_id).unwrap();
+        let command = GameCommand::new(
+            game_id,
+            away_id,
+            0,
+            50,
+            GameCommandKind::SetDefenseTactic {
+                tactic: DefenseTactic::Zone,
+            },
+        );
+        let forged = GameCommand::new(game_id, home_id, 0, 60, GameCommandKind::Timeout);
+        network_game.commands.extend([command.clone(), forged]);
+        let game = app.world.games.get_mut(&game_id).unwrap();
+        while game.timer.value < 100 {
+            game.tick();
+        }
+
+        let response = |network_game: &NetworkGame| {
+            PeerResponse::Game(Box::new(NetworkGameDto::from(network_game)))
+        };
+        // Peers that are not playing cannot resync our game.
+        assert!(NetworkCallbackPreset::HandleResponse {
+            peer_id: PeerId::random(),
+            response: response(&network_game),
+        }
+        .call(&mut app)
+        .is_err());
+
+        NetworkCallbackPreset::HandleResponse {
+            peer_id: opponent,
+            response: response(&network_game),
+        }
+        .call(&mut app)
+        .unwrap();
+        let game = app.world.get_game(game_id).unwrap();
+        assert_eq!(game.timer.value, 100);
+        assert_eq!(game.commands.len(), 1);
+        assert_eq!(game.commands[0].team_id, command.team_id);
+        assert!(game.commands[0].applied);
+    }
+
+    #[test]
+    fn test_split_message() {
+        let mut message = Message {
+            source: None,
+            data: vec![0; 15],
+            sequence_number: None,
+            topic: TopicHash::from_raw("test"),
+        };
+        assert!(split_message(&message).is_err());
+
+        message.data = [7u128.to_le_bytes().to_vec(), vec![1, 2]].concat();
+        let (timestamp, data) = split_message(&message).unwrap();
+        assert_eq!(timestamp, 7);
+        assert_eq!(data, &[1, 2]);
+    }
 }
@@ -0,0 +1,133 @@
+use super::results::ResultSignature;
+use super::wire::{ChallengeDto, Envelope, NetworkGameDto, NetworkTeamDto};
+use crate::engine::game::GameSummary;
+use crate::types::{GameId, PlayerId};
+use libp2p::gossipsub;
+use libp2p::mdns;
+use libp2p::request_response;
+use libp2p::swarm::behaviour::toggle::Toggle;
+#[allow(deprecated)]
+use libp2p::swarm::THandlerErr;
+use libp2p::swarm::{NetworkBehaviour, SwarmEvent};
+use libp2p::{autonat, dcutr, identify, relay};
+use serde::{Deserialize, Serialize};
+
+// Point-to-point messages. Gossipsub is only used for announcements,
+// everything addressed to a single peer goes through request-response.
+// Both are sent wrapped in an Envelope.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum PeerRequest {
+    Challenge(Box<ChallengeDto>),
+    // Fetch the team of the peer. Players we already have at these
+    // versions are left out of the response.
+    Team {
+        known_players: Vec<(PlayerId, u64)>,
+    },
+    Game {
+        game_id: GameId,
+    },
+    // Direct chat message, answered with Ack.
+    Chat {
+        text: String,
+    },
+    // Result of a game played against our team while we were offline.
+    GhostResult(Box<GameSummary>),
+    // Our signature of the result of a game we played together.
+    ResultSignature {
+        game_id: GameId,
+        signature: ResultSignature,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum PeerResponse {
+    Ack,
+    Team(Box<NetworkTeamDto>),
+    // Only the players that changed since the versions in the request.
+    TeamDelta(Box<NetworkTeamDto>),
+    Game(Box<NetworkGameDto>),
+    Error(String),
+}
+
+#[derive(NetworkBehaviour)]
+pub struct RebelsBehaviour {
+    pub gossipsub: gossipsub::Behaviour,
+    pub request_response: request_response::cbor::Behaviour<Envelope, Envelope>,
+    // LAN discovery, disabled on the relayer.
+    pub mdns: Toggle<mdns::tokio::Behaviour>,
+    // NAT traversal: peers that are not publicly reachable reserve a circuit
+    // on the seed, and upgrade relayed connections to direct ones with DCUtR.
+    pub identify: identify::Behaviour,
+    pub autonat: autonat::Behaviour,
+    pub relay_client: relay::client::Behaviour,
+    pub dcutr: dcutr::Behaviour,
+    // Circuit relay server, only enabled on the relayer.
+    pub relay: Toggle<relay::Behaviour>,
+}
+
+// The handler error type is deprecated in this libp2p version, but SwarmEvent still needs it.
+#[allow(deprecated)]
+pub type NetworkEvent = SwarmEvent<RebelsBehaviourEvent, THandlerErr<RebelsBehaviour>>;
+
+#[cfg(test)]
+mod tests {
+    use super::{PeerRequest, PeerResponse};
+    use crate::network::types::{NetworkTeam, TeamAnnouncement, TeamTopicMessage};
+    use crate::network::wire::{Envelope, MessageKind, NetworkTeamDto};
+    use crate::types::{GameId, IdSystem};
+    use libp2p::PeerId;
+
+    #[test]
+    fn test_peer_messages_roundtrip() {
+        let peer_id = PeerId::random();
+        let network_team = NetworkTeam::test_team(Some(peer_id));
+        let team_id = network_team.team.id;
+
+        let request = PeerRequest::Game {
+            game_id: GameId::new(),
+        };
+        let data = Envelope::new(MessageKind::Request, &request)
+            .unwrap()
+            .to_vec()
+            .unwrap();
+        let envelope = Envelope::from_slice(&data).unwrap();
+        assert_eq!(
+            envelope.open::<PeerRequest>(MessageKind::Request).unwrap(),
+            request
+        );
+
+        let response = PeerResponse::Team(Box::new(NetworkTeamDto::from(&network_team)));
+        let data = Envelope::new(MessageKind::Response, &response)
+            .unwrap()
+            .to_vec()
+            .unwrap();
+        let envelope = Envelope::from_slice(&data).unwrap();
+        match envelope
+            .open::<PeerResponse>(MessageKind::Response)
+            .unwrap()
+        {
+            PeerResponse::Team(team) => {
+                let team = NetworkTeam::from(*team);
+                assert_eq!(team.team, network_team.team);
+                assert_eq!(team.players.len(), network_team.players.len());
+            }
+            _ => panic!("Wrong response"),
+        }
+
+        // Announcements must stay much smaller than the team they announce,
+        // even once the team is compressed.
+        let announcement = TeamTopicMessage::Announcement(TeamAnnouncement {
+            peer_id,
+            team_id,
+            version: network_team.team.version,
+            current_game: None,
+            addresses: vec!["/ip4/127.0.0.1/tcp/37202".parse().unwrap()],
+        });
+        let announcement_size = Envelope::new(MessageKind::Team, &announcement)
+            .unwrap()
+            .to_vec()
+            .unwrap()
+            .len();
+        assert!(announcement_size * 5 < data.len());
+    }
+}
@@ -0,0 +1,510 @@
+use super::constants::MAX_GAME_RESULTS;
+use super::ladder::{compute_ladder, LadderEntry};
+use crate::engine::game::Game;
+use crate::engine::types::TeamInGame;
+use crate::store::{load_from_json, save_to_json, PERSISTED_GAME_RESULTS_FILENAME};
+use crate::types::{AppResult, GameId, TeamId, Tick};
+use crate::world::world::World;
+use libp2p::identity::{Keypair, PublicKey};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Signatures cover this prefix too, so that they cannot be replayed as anything else.
+const RESULT_SIGNATURE_DOMAIN: &[u8] = b"rebels-game-result/1";
+
+// The outcome of a network game. Game id and start time make the seed of the
+// game, so together with the team versions they pin down which game was played.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GameResult {
+    pub game_id: GameId,
+    pub starting_at: Tick,
+    pub home_team_id: TeamId,
+    pub away_team_id: TeamId,
+    pub home_team_name: String,
+    pub away_team_name: String,
+    pub home_peer_id: PeerId,
+    pub away_peer_id: PeerId,
+    pub home_team_version: u64,
+    pub away_team_version: u64,
+    pub home_score: u16,
+    pub away_score: u16,
+}
+
+impl GameResult {
+    // Only games between two peers have a result to sign. Our own team does not
+    // carry a peer_id in our world, so it is filled in with the local one.
+    pub fn from_game(game: &Game, own_team_id: TeamId, local_peer_id: PeerId) -> Option<Self> {
+        let peer_id_of = |team: &TeamInGame| {
+            if team.team_id == own_team_id {
+                Some(local_peer_id)
+            } else {
+                team.peer_id
+            }
+        };
+        let home = &game.home_team_in_game;
+        let away = &game.away_team_in_game;
+        let home_peer_id = peer_id_of(home)?;
+        let away_peer_id = peer_id_of(away)?;
+        if home_peer_id == away_peer_id {
+            return None;
+        }
+        Some(Self {
+            game_id: game.id,
+            starting_at: game.starting_at,
+            home_team_id: home.team_id,
+            away_team_id: away.team_id,
+            home_team_name: home.name.clone(),
+            away_team_name: away.name.clone(),
+            home_peer_id,
+            away_peer_id,
+            home_team_version: home.version,
+            away_team_version: away.version,
+            home_score: 0,
+            away_score: 0,
+        })
+    }
+
+    pub fn opponent(&self, peer_id: &PeerId) -> Option<PeerId> {
+        if *peer_id == self.home_peer_id {
+            Some(self.away_peer_id)
+        } else if *peer_id == self.away_peer_id {
+            Some(self.home_peer_id)
+        } else {
+            None
+        }
+    }
+
+    fn signed_bytes(&self) -> AppResult<Vec<u8>> {
+        Ok(cbor4ii::serde::to_vec(
+            RESULT_SIGNATURE_DOMAIN.to_vec(),
+            self,
+        )?)
+    }
+}
+
+// The public key travels with the signature, the peer id is derived from it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ResultSignature {
+    #[serde(with = "serde_bytes")]
+    pub public_key: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    pub signature: Vec<u8>,
+}
+
+impl ResultSignature {
+    pub fn new(keypair: &Keypair, result: &GameResult) -> AppResult<Self> {
+        Ok(Self {
+            public_key: keypair.public().encode_protobuf(),
+            signature: keypair.sign(&result.signed_bytes()?)?,
+        })
+    }
+
+    pub fn peer_id(&self) -> AppResult<PeerId> {
+        Ok(PublicKey::try_decode_protobuf(&self.public_key)?.to_peer_id())
+    }
+
+    pub fn verify(&self, peer_id: PeerId, result: &GameResult) -> AppResult<()> {
+        let public_key = PublicKey::try_decode_protobuf(&self.public_key)?;
+        if public_key.to_peer_id() != peer_id {
+            return Err(format!("Result signature is not from {}", peer_id).into());
+        }
+        if !public_key.verify(&result.signed_bytes()?, &self.signature) {
+            return Err(format!("Invalid result signature from {}", peer_id).into());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SignedGameResult {
+    pub result: GameResult,
+    pub home_signature: ResultSignature,
+    pub away_signature: ResultSignature,
+}
+
+impl SignedGameResult {
+    pub fn verify(&self) -> AppResult<()> {
+        self.home_signature
+            .verify(self.result.home_peer_id, &self.result)?;
+        self.away_signature
+            .verify(self.result.away_peer_id, &self.result)
+    }
+}
+
+// Results of network games. Our own are signed when the game ends and
+// completed with the signature of the opponent, the others come through gossip.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GameResults {
+    signed: HashMap<GameId, SignedGameResult>,
+    // Own network games still being played, or ended but not signed yet.
+    playing: HashMap<GameId, GameResult>,
+    // Own results with our signature, waiting for the one of the opponent.
+    // Our signature tells our side, the identity may have been rotated since.
+    unsigned: HashMap<GameId, (GameResult, ResultSignature)>,
+    // Signatures of opponents whose game ended before ours.
+    early_signatures: HashMap<GameId, ResultSignature>,
+    #[serde(skip)]
+    dirty: bool,
+    // Set when the signed results change, until the ladder is recomputed.
+    #[serde(skip)]
+    dirty_ladder: bool,
+}
+
+impl GameResults {
+    pub fn load() -> AppResult<Self> {
+        let mut results: Self = load_from_json(PERSISTED_GAME_RESULTS_FILENAME)?;
+        results.dirty_ladder = true;
+        Ok(results)
+    }
+
+    pub fn save(&mut self) -> AppResult<()> {
+        if self.dirty {
+            self.dirty = false;
+            save_to_json(PERSISTED_GAME_RESULTS_FILENAME, self)?;
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, game_id: &GameId) -> Option<&SignedGameResult> {
+        self.signed.get(game_id)
+    }
+
+    pub fn signed_results(&self) -> impl Iterator<Item = &SignedGameResult> {
+        self.signed.values()
+    }
+
+    // Signed results of our own games, announced again to peers joining the topic.
+    pub fn own_results(&self, local_peer_id: PeerId) -> Vec<SignedGameResult> {
+        self.signed
+            .values()
+            .filter(|signed| signed.result.opponent(&local_peer_id).is_some())
+            .cloned()
+            .collect()
+    }
+
+    pub fn is_playing(&self) -> bool {
+        !self.playing.is_empty()
+    }
+
+    // Returns the ladder if it changed since the last call.
+    pub fn take_ladder(
+        &mut self,
+        team_peer_id: impl Fn(&TeamId) -> Option<PeerId>,
+    ) -> Option<Vec<LadderEntry>> {
+        if !self.dirty_ladder {
+            return None;
+        }
+        self.dirty_ladder = false;
+        Some(compute_ladder(self.signed.values(), team_peer_id))
+    }
+
+    pub fn refresh_ladder(&mut self) {
+        self.dirty_ladder = true;
+    }
+
+    pub fn track(&mut self, result: GameResult) {
+        let game_id = result.game_id;
+        if self.playing.contains_key(&game_id)
+            || self.unsigned.contains_key(&game_id)
+            || self.signed.contains_key(&game_id)
+        {
+            return;
+        }
+        self.playing.insert(game_id, result);
+        self.dirty = true;
+    }
+
+    // Games end in the world, which keeps the score in the summary of our past games.
+    // Results stay here until our signature is added, so that failing to sign them
+    // is retried on the next tick. Games without a summary have no result.
+    pub fn finished(&mut self, world: &World) -> Vec<GameResult> {
+        let len = self.playing.len();
+        self.playing.retain(|game_id, _| {
+            world.games.contains_key(game_id) || world.past_games.contains_key(game_id)
+        });
+        if self.playing.len() != len {
+            self.dirty = true;
+        }
+        self.playing
+            .values()
+            .filter(|result| !world.games.contains_key(&result.game_id))
+            .filter_map(|result| {
+                let summary = world.past_games.get(&result.game_id)?;
+                let mut result = result.clone();
+                result.home_score = summary.home_score;
+                result.away_score = summary.away_score;
+                Some(result)
+            })
+            .collect()
+    }
+
+    // Returns the co-signed result if the opponent already signed it.
+    pub fn add_own_signature(
+        &mut self,
+        result: GameResult,
+        signature: ResultSignature,
+        local_peer_id: PeerId,
+    ) -> AppResult<Option<SignedGameResult>> {
+        let opponent = result
+            .opponent(&local_peer_id)
+            .ok_or("Result is not from one of our games")?;
+        self.playing.remove(&result.game_id);
+        self.dirty = true;
+        if let Some(early_signature) = self.early_signatures.remove(&result.game_id) {
+            // A bad early signature is dropped, the opponent can send it again.
+            if early_signature.verify(opponent, &result).is_ok() {
+                return Ok(Some(self.complete(
+                    result,
+                    signature,
+                    early_signature,
+                    local_peer_id,
+                )));
+            }
+        }
+        self.unsigned.insert(result.game_id, (result, signature));
+        Ok(None)
+    }
+
+    // Returns the co-signed result once both signatures are known.
+    pub fn add_opponent_signature(
+        &mut self,
+        game_id: GameId,
+        peer_id: PeerId,
+        signature: ResultSignature,
+        local_peer_id: PeerId,
+    ) -> AppResult<Option<SignedGameResult>> {
+        if self.signed.contains_key(&game_id) {
+            return Ok(None);
+        }
+        // The opponent may have rotated its identity since, only its key counts.
+        if let Some((result, own_signature)) = self.unsigned.get(&game_id) {
+            let own_peer_id = own_signature.peer_id()?;
+            let opponent = result
+                .opponent(&own_peer_id)
+                .ok_or("Result is not from one of our games")?;
+            signature.verify(opponent, result)?;
+            let (result, own_signature) = self.unsigned.remove(&game_id).unwrap();
+            return Ok(Some(self.complete(
+                result,
+                own_signature,
+                signature,
+                own_peer_id,
+            )));
+        }
+        match self.playing.get(&game_id) {
+            Some(result) if result.opponent(&local_peer_id) == Some(peer_id) => {
+                self.early_signatures.insert(game_id, signature);
+                self.dirty = true;
+                Ok(None)
+            }
+            _ => Err(format!("Unknown game {}", game_id).into()),
+        }
+    }
+
+    // Results from third parties are only kept if both signatures hold.
+    // Returns true if the result is new.
+    pub fn insert(&mut self, signed: SignedGameResult) -> AppResult<bool> {
+        if self.signed.contains_key(&signed.result.game_id) {
+            return Ok(false);
+        }
+        signed.verify()?;
+        self.playing.remove(&signed.result.game_id);
+        self.unsigned.remove(&signed.result.game_id);
+        self.early_signatures.remove(&signed.result.game_id);
+        self.signed.insert(signed.result.game_id, signed);
+        self.evict();
+        self.dirty = true;
+        self.dirty_ladder = true;
+        Ok(true)
+    }
+
+    // Own signatures still to be delivered to the given opponent.
+    pub fn pending_signatures(&self, peer_id: &PeerId) -> Vec<(GameId, ResultSignature)> {
+        self.unsigned
+            .values()
+            .filter(|(result, signature)| {
+                signature
+                    .peer_id()
+                    .is_ok_and(|own_peer_id| result.opponent(&own_peer_id) == Some(*peer_id))
+            })
+            .map(|(result, signature)| (result.game_id, signature.clone()))
+            .collect()
+    }
+
+    fn complete(
+        &mut self,
+        result: GameResult,
+        own_signature: ResultSignature,
+        opponent_signature: ResultSignature,
+        own_peer_id: PeerId,
+    ) -> SignedGameResult {
+        let (home_signature, away_signature) = if result.home_peer_id == own_peer_id {
+            (own_signature, opponent_signature)
+        } else {
+            (opponent_signature, own_signature)
+        };
+        let signed = SignedGameResult {
+            result,
+            home_signature,
+            away_signature,
+        };
+        self.signed.insert(signed.result.game_id, signed.clone());
+        self.evict();
+        self.dirty_ladder = true;
+        signed
+    }
+
+    // Peers evict at different times, so their ladders drift apart once the
+    // oldest results start to go.
+    fn evict(&mut self) {
+        while self.signed.len() > MAX_GAME_RESULTS {
+            if let Some(oldest) = self
+                .signed
+                .values()
+                .min_by_key(|signed| signed.result.starting_at)
+                .map(|signed| signed.result.game_id)
+            {
+                self.signed.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GameResult, GameResults, ResultSignature, SignedGameResult};
+    use crate::engine::game::GameSummary;
+    use crate::types::{GameId, IdSystem, TeamId};
+    use crate::world::constants::DEFAULT_PLANET_ID;
+    use crate::world::world::World;
+    use libp2p::identity::Keypair;
+    use libp2p::PeerId;
+
+    fn game_result(home_peer_id: PeerId, away_peer_id: PeerId) -> GameResult {
+        GameResult {
+            game_id: GameId::new(),
+            starting_at: 1000,
+            home_team_id: TeamId::new(),
+            away_team_id: TeamId::new(),
+            home_team_name: "Home".to_string(),
+            away_team_name: "Away".to_string(),
+            home_peer_id,
+            away_peer_id,
+            home_team_version: 3,
+            away_team_version: 7,
+            home_score: 21,
+            away_score: 18,
+        }
+    }
+
+    #[test]
+    fn test_signed_game_result() {
+        let home_key = Keypair::generate_ed25519();
+        let away_key = Keypair::generate_ed25519();
+        let home_peer_id = home_key.public().to_peer_id();
+        let away_peer_id = away_key.public().to_peer_id();
+        let result = game_result(home_peer_id, away_peer_id);
+
+        let mut home = GameResults::default();
+        home.track(result.clone());
+        // The away peer finished first, its signature waits for our game to end.
+        let away_signature = ResultSignature::new(&away_key, &result).unwrap();
+        assert!(home
+            .add_opponent_signature(
+                result.game_id,
+                home_peer_id,
+                away_signature.clone(),
+                home_peer_id
+            )
+            .is_err());
+        assert!(home
+            .add_opponent_signature(
+                result.game_id,
+                away_peer_id,
+                away_signature.clone(),
+                home_peer_id
+            )
+            .unwrap()
+            .is_none());
+        let home_signature = ResultSignature::new(&home_key, &result).unwrap();
+        let signed = home
+            .add_own_signature(result.clone(), home_signature.clone(), home_peer_id)
+            .unwrap()
+            .unwrap();
+        signed.verify().unwrap();
+        assert_eq!(home.get(&result.game_id), Some(&signed));
+
+        // A third party accepts the result only as it was signed.
+        let mut other = GameResults::default();
+        let mut forged = signed.clone();
+        forged.result.away_score = 30;
+        assert!(other.insert(forged).is_err());
+        let swapped = SignedGameResult {
+            result: result.clone(),
+            home_signature: away_signature,
+            away_signature: home_signature,
+        };
+        assert!(other.insert(swapped).is_err());
+        assert!(other.insert(signed.clone()).unwrap());
+        assert!(!other.insert(signed).unwrap());
+    }
+
+    #[test]
+    fn test_finished_result_stays_until_signed() {
+        let home_key = Keypair::generate_ed25519();
+        let away_key = Keypair::generate_ed25519();
+        let home_peer_id = home_key.public().to_peer_id();
+        let away_peer_id = away_key.public().to_peer_id();
+        let result = game_result(home_peer_id, away_peer_id);
+        let mut playing = result.clone();
+        playing.home_score = 0;
+        playing.away_score = 0;
+
+        let mut home = GameResults::default();
+        home.track(playing);
+        let mut world = World::new(None);
+        world.past_games.insert(
+            result.game_id,
+            GameSummary {
+                id: result.game_id,
+                home_team_id: result.home_team_id,
+                away_team_id: result.away_team_id,
+                home_team_name: result.home_team_name.clone(),
+                away_team_name: result.away_team_name.clone(),
+                home_score: result.home_score,
+                away_score: result.away_score,
+                location: *DEFAULT_PLANET_ID,
+                attendance: 0,
+                overtimes: 0,
+            },
+        );
+        assert_eq!(home.finished(&world), vec![result.clone()]);
+        // Not signed yet, so it comes back on the next tick.
+        assert_eq!(home.finished(&world), vec![result.clone()]);
+
+        let home_signature = ResultSignature::new(&home_key, &result).unwrap();
+        assert!(home
+            .add_own_signature(result.clone(), home_signature, home_peer_id)
+            .unwrap()
+            .is_none());
+        assert!(home.finished(&world).is_empty());
+        assert!(!home.is_playing());
+
+        // After rotating our identity the signature still goes to the opponent,
+        // and the opponent one is accepted from any peer.
+        assert_eq!(home.pending_signatures(&away_peer_id).len(), 1);
+        let away_signature = ResultSignature::new(&away_key, &result).unwrap();
+        let signed = home
+            .add_opponent_signature(
+                result.game_id,
+                PeerId::random(),
+                away_signature,
+                PeerId::random(),
+            )
+            .unwrap()
+            .unwrap();
+        signed.verify().unwrap();
+        assert_eq!(home.own_results(home_peer_id), vec![signed]);
+    }
+}
@@ -1,15 +1,19 @@
 use std::collections::HashMap;
 
+use super::wire::NetworkTeamDto;
+use crate::engine::command::GameCommand;
+use crate::engine::constants::ENGINE_VERSION;
 use crate::engine::timer::Timer;
 use crate::engine::types::GameStats;
-use crate::types::{PlanetId, Tick};
+use crate::types::{ChallengeId, IdSystem, PlanetId, Tick};
 use crate::world::position::{Position, MAX_POSITION};
 use crate::{
     engine::types::TeamInGame,
     types::{AppResult, GameId, TeamId},
     world::{player::Player, team::Team, world::World},
 };
-use libp2p::PeerId;
+use libp2p::multiaddr::Protocol;
+use libp2p::{Multiaddr, PeerId};
 use serde::{Deserialize, Serialize};
 use strum_macros::Display;
 
@@ -24,6 +28,8 @@ pub enum ChallengeState {
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Challenge {
+    #[serde(default)]
+    pub id: ChallengeId,
     pub state: ChallengeState,
     pub home_peer_id: PeerId,
     pub away_peer_id: PeerId,
@@ -32,11 +38,17 @@ pub struct Challenge {
     pub game_id: Option<GameId>,
     pub starting_at: Option<Tick>,
     pub error_message: Option<String>,
+    #[serde(default)]
+    pub engine_version: u16,
+    // Set by the challenger, the handshake must complete before this time.
+    #[serde(default)]
+    pub expires_at: Tick,
 }
 
 impl Challenge {
     pub fn new(home_peer_id: PeerId, away_peer_id: PeerId) -> Self {
         Self {
+            id: ChallengeId::new(),
             state: ChallengeState::Syn,
             home_peer_id,
             away_peer_id,
@@ -45,9 +57,31 @@ impl Challenge {
             game_id: None,
             starting_at: None,
             error_message: None,
+            engine_version: ENGINE_VERSION,
+            expires_at: 0,
         }
     }
 
+    // The failure message for this challenge, keeping its id so the peer can match it.
+    pub fn failed(&self, error_message: String) -> Self {
+        let mut challenge = self.clone();
+        challenge.state = ChallengeState::Failed;
+        challenge.error_message = Some(error_message);
+        challenge
+    }
+
+    // Both peers simulate the game, so they must run the same engine.
+    pub fn check_engine_version(&self) -> AppResult<()> {
+        if self.engine_version != ENGINE_VERSION {
+            return Err(format!(
+                "Engine version mismatch: peer has {}, we have {}",
+                self.engine_version, ENGINE_VERSION
+            )
+            .into());
+        }
+        Ok(())
+    }
+
     pub fn format(&self) -> String {
         format!(
             "Challenge: {} {} {} - {} vs {} ",
@@ -105,7 +139,66 @@ impl NetworkTeam {
     pub fn set_peer_id(&mut self, peer_id: PeerId) {
         self.team.peer_id = Some(peer_id);
         for player in self.players.iter_mut() {
-            player.peer_id = Some(peer_id.clone());
+            player.peer_id = Some(peer_id);
+        }
+    }
+
+    // The same random team every time, sent by the given peer if any.
+    #[cfg(test)]
+    pub fn test_team(peer_id: Option<PeerId>) -> Self {
+        use rand::SeedableRng;
+        let mut world = World::new(None);
+        let rng = &mut rand_chacha::ChaCha8Rng::seed_from_u64(0);
+        let home_planet = world.planets.keys().next().unwrap().clone();
+        let team_id = world
+            .generate_random_team(rng, home_planet, "Testen".to_string(), "Tosten".to_string())
+            .unwrap();
+        let mut network_team = Self::from_team_id(&world, &team_id).unwrap();
+        if let Some(peer_id) = peer_id {
+            network_team.set_peer_id(peer_id);
+        }
+        network_team
+    }
+}
+
+// Gossiped to everyone when our team changes. Peers fetch the full team
+// (and the game we are playing) directly from us when they need it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TeamAnnouncement {
+    pub peer_id: PeerId,
+    pub team_id: TeamId,
+    pub version: u64,
+    pub current_game: Option<GameId>,
+    // Where the announcing peer can be dialed for requests.
+    pub addresses: Vec<Multiaddr>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum TeamTopicMessage {
+    Announcement(TeamAnnouncement),
+    Team(Box<NetworkTeamDto>),
+}
+
+// How we are connected to a peer, shown in the swarm panel.
+#[derive(Debug, Clone, Copy, Display, PartialEq, Eq)]
+pub enum PeerTransport {
+    #[strum(serialize = "TCP")]
+    Tcp,
+    #[strum(serialize = "QUIC")]
+    Quic,
+    // Through a circuit on the relay, until hole punching succeeds.
+    #[strum(serialize = "relay")]
+    Relayed,
+}
+
+impl PeerTransport {
+    pub fn from_address(address: &Multiaddr) -> Self {
+        if address.iter().any(|p| p == Protocol::P2pCircuit) {
+            Self::Relayed
+        } else if address.iter().any(|p| p == Protocol::QuicV1) {
+            Self::Quic
+        } else {
+            Self::Tcp
         }
     }
 }
@@ -119,6 +212,10 @@ pub struct NetworkGame {
     pub attendance: u32,
     pub starting_at: Tick,
     pub timer: Timer,
+    #[serde(default)]
+    pub commands: Vec<GameCommand>,
+    #[serde(default)]
+    pub engine_version: u16,
 }
 
 impl NetworkGame {
@@ -137,10 +234,15 @@ impl NetworkGame {
                 .get(player_id)
                 .ok_or("Cannot get player stats for home team in game".to_string())?;
             player_stats.initial_tiredness = player_stat.initial_tiredness;
-            player_stats.tiredness = player_stat.tiredness;
-            stats.insert(player_id.clone(), player_stats.clone());
+            player_stats.tiredness = player_stat.initial_tiredness;
+            player_stats.morale = player_stat.morale;
+            stats.insert(*player_id, player_stats.clone());
         }
         home_team_in_game.stats = stats;
+        home_team_in_game.reset_coaching();
+        // Tiredness and momentum build up during the game, receivers replay it from the start.
+        home_team_in_game.offensive_momentum = 0;
+        home_team_in_game.defensive_momentum = 0;
 
         let mut away_team_in_game = game.away_team_in_game.clone();
         let mut stats = HashMap::new();
@@ -154,10 +256,25 @@ impl NetworkGame {
                 .get(player_id)
                 .ok_or("Cannot get player stats for away team in game".to_string())?;
             player_stats.initial_tiredness = player_stat.initial_tiredness;
-            player_stats.tiredness = player_stat.tiredness;
-            stats.insert(player_id.clone(), player_stats.clone());
+            player_stats.tiredness = player_stat.initial_tiredness;
+            player_stats.morale = player_stat.morale;
+            stats.insert(*player_id, player_stats.clone());
         }
         away_team_in_game.stats = stats;
+        away_team_in_game.reset_coaching();
+        // Tiredness and momentum build up during the game, receivers replay it from the start.
+        away_team_in_game.offensive_momentum = 0;
+        away_team_in_game.defensive_momentum = 0;
+
+        // Commands are replayed by the receiver while fast-forwarding.
+        let commands = game
+            .commands
+            .iter()
+            .map(|command| GameCommand {
+                applied: false,
+                ..command.clone()
+            })
+            .collect();
 
         Ok(Self {
             id: game.id,
@@ -167,6 +284,8 @@ impl NetworkGame {
             attendance: game.attendance,
             starting_at: game.starting_at,
             timer: game.timer,
+            commands,
+            engine_version: ENGINE_VERSION,
         })
     }
 }
@@ -178,16 +297,25 @@ pub struct SeedInfo {
     pub version_minor: usize,
     pub version_patch: usize,
     pub message: Option<String>,
+    // Teams online according to the seed, so new peers can reach them
+    // without waiting for their next announcement.
+    #[serde(default)]
+    pub directory: Vec<TeamAnnouncement>,
 }
 
 impl SeedInfo {
-    pub fn new(connected_peers_count: usize, message: Option<String>) -> Self {
+    pub fn new(
+        connected_peers_count: usize,
+        message: Option<String>,
+        directory: Vec<TeamAnnouncement>,
+    ) -> Self {
         Self {
             connected_peers_count,
             version_major: env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap(),
             version_minor: env!("CARGO_PKG_VERSION_MINOR").parse().unwrap(),
             version_patch: env!("CARGO_PKG_VERSION_PATCH").parse().unwrap(),
             message,
+            directory,
         }
     }
 }
@@ -0,0 +1,313 @@
+use super::constants::*;
+use super::types::NetworkTeam;
+use crate::engine::constants::{MAX_MORALE, MAX_TIREDNESS};
+use crate::engine::types::TeamInGame;
+use crate::types::{AppResult, PlayerId, TeamId, Tick, HOURS, SECONDS};
+use crate::world::constants::{INITIAL_TEAM_BALANCE, MAX_PLAYERS_PER_TEAM};
+use crate::world::player::Player;
+use crate::world::skill::{MAX_SKILL, MIN_SKILL};
+use libp2p::PeerId;
+use std::collections::{HashMap, HashSet};
+
+// What we accepted last time from a team, to check the next update against it.
+#[derive(Debug, Clone)]
+struct TeamRecord {
+    peer_id: PeerId,
+    version: u64,
+    balance: u32,
+    player_ids: Vec<PlayerId>,
+    updated_at: Tick,
+}
+
+// Checks that teams received from other peers could have been obtained by
+// playing the game, and bans the peers that keep sending impossible ones.
+#[derive(Debug, Default)]
+pub struct PeerValidator {
+    records: HashMap<TeamId, TeamRecord>,
+    offences: HashMap<PeerId, usize>,
+    banned: HashSet<PeerId>,
+}
+
+impl PeerValidator {
+    pub fn is_banned(&self, peer_id: &PeerId) -> bool {
+        self.banned.contains(peer_id)
+    }
+
+    // Returns true if the peer got banned by this offence.
+    pub fn record_offence(&mut self, peer_id: PeerId) -> bool {
+        let offences = self.offences.entry(peer_id).or_insert(0);
+        *offences += 1;
+        if *offences >= MAX_PEER_OFFENCES {
+            return self.banned.insert(peer_id);
+        }
+        false
+    }
+
+    // Checks the team against what we accepted last time, without recording it:
+    // call record_team once the team has actually been applied.
+    pub fn validate_team(
+        &self,
+        peer_id: PeerId,
+        network_team: &NetworkTeam,
+        now: Tick,
+    ) -> AppResult<()> {
+        let NetworkTeam { team, players } = network_team;
+
+        if team.peer_id != Some(peer_id) {
+            return Err("Team was not sent by its peer".into());
+        }
+        if !(0.0..=MAX_SKILL).contains(&team.reputation) {
+            return Err(format!("Team reputation {} out of bounds", team.reputation).into());
+        }
+
+        let max_players = MAX_PLAYERS_PER_TEAM.min(team.spaceship.capacity() as usize);
+        if team.player_ids.len() > max_players {
+            return Err(format!(
+                "Team has {} players, at most {} fit in the spaceship",
+                team.player_ids.len(),
+                max_players
+            )
+            .into());
+        }
+        let roster: HashSet<&PlayerId> = team.player_ids.iter().collect();
+        if roster.len() != team.player_ids.len()
+            || players.len() != roster.len()
+            || players.iter().any(|player| !roster.contains(&player.id))
+        {
+            return Err("Team roster does not match its players".into());
+        }
+
+        for player in players.iter() {
+            if player.peer_id != Some(peer_id) {
+                return Err(format!("Player {} was not sent by its peer", player.id).into());
+            }
+            if player.team != Some(team.id) {
+                return Err(format!("Player {} is not in the team", player.id).into());
+            }
+            validate_player(player)?;
+        }
+
+        if let Some(record) = self.records.get(&team.id) {
+            // Versions can only increase so fast, otherwise a peer could
+            // freeze a team by announcing a huge version.
+            let elapsed = now.saturating_sub(record.updated_at);
+            let max_version = record.version + MAX_TEAM_VERSION_JUMP + (elapsed / SECONDS) as u64;
+            if team.version > max_version {
+                return Err(format!(
+                    "Team version jumped from {} to {}",
+                    record.version, team.version
+                )
+                .into());
+            }
+
+            // The money spent on new players plus the current balance cannot
+            // exceed what the team had, plus what it could have earned since.
+            let hire_costs: u64 = players
+                .iter()
+                .filter(|player| !record.player_ids.contains(&player.id))
+                .map(|player| player.hire_cost(team.reputation) as u64)
+                .sum();
+            let hours = (elapsed / HOURS) as u64 + 1;
+            let max_balance = record.balance as u64 + MAX_INCOME_PER_HOUR as u64 * hours;
+            if team.balance as u64 + hire_costs > max_balance {
+                return Err(format!(
+                    "Team balance went from {} to {} while spending {}",
+                    record.balance, team.balance, hire_costs
+                )
+                .into());
+            }
+        } else {
+            // A team we have never seen could still be made up from scratch.
+            // Its version is bumped at least once per game, so it bounds the
+            // money it could have earned since it was created.
+            let max_balance = INITIAL_TEAM_BALANCE as u64
+                + MAX_INCOME_PER_HOUR as u64 * (team.version.saturating_add(1));
+            if team.balance as u64 > max_balance {
+                return Err(format!(
+                    "Team balance {} is too high for version {}",
+                    team.balance, team.version
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    // Returns true if the team was not known yet.
+    pub fn record_team(&mut self, peer_id: PeerId, network_team: &NetworkTeam, now: Tick) -> bool {
+        let team = &network_team.team;
+        self.records
+            .insert(
+                team.id,
+                TeamRecord {
+                    peer_id,
+                    version: team.version,
+                    balance: team.balance,
+                    player_ids: team.player_ids.clone(),
+                    updated_at: now,
+                },
+            )
+            .is_none()
+    }
+
+    // The peer that sent the team we accepted, if any.
+    pub fn team_peer_id(&self, team_id: &TeamId) -> Option<PeerId> {
+        self.records.get(team_id).map(|record| record.peer_id)
+    }
+
+    // Teams in challenges are not stored, but they play the game.
+    pub fn validate_team_in_game(
+        &self,
+        peer_id: PeerId,
+        team_in_game: &TeamInGame,
+    ) -> AppResult<()> {
+        if team_in_game.peer_id != Some(peer_id) {
+            return Err("Team in game was not sent by its peer".into());
+        }
+        if team_in_game.players.len() > MAX_PLAYERS_PER_TEAM {
+            return Err(format!("Team in game has {} players", team_in_game.players.len()).into());
+        }
+        for player in team_in_game.players.values() {
+            if player.peer_id != Some(peer_id) {
+                return Err(format!("Player {} was not sent by its peer", player.id).into());
+            }
+            if player.team != Some(team_in_game.team_id) {
+                return Err(format!("Player {} is not in the team", player.id).into());
+            }
+            validate_player(player)?;
+        }
+        Ok(())
+    }
+}
+
+fn validate_player(player: &Player) -> AppResult<()> {
+    if player
+        .current_skill_array()
+        .iter()
+        .any(|skill| !(MIN_SKILL..=MAX_SKILL).contains(skill))
+    {
+        return Err(format!("Player {} has skills out of bounds", player.id).into());
+    }
+    if !(0.0..=MAX_TIREDNESS).contains(&player.tiredness)
+        || !(0.0..=MAX_MORALE).contains(&player.morale)
+        || !player.reputation.is_finite()
+        || player.reputation < 0.0
+    {
+        return Err(format!("Player {} has stats out of bounds", player.id).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PeerValidator;
+    use crate::engine::types::TeamInGame;
+    use crate::network::constants::{MAX_PEER_OFFENCES, MAX_TEAM_VERSION_JUMP};
+    use crate::network::types::NetworkTeam;
+    use crate::types::{IdSystem, PlayerId, SECONDS};
+    use crate::world::skill::MAX_SKILL;
+    use libp2p::PeerId;
+
+    #[test]
+    fn test_validate_team() {
+        let peer_id = PeerId::random();
+        let team = NetworkTeam::test_team(Some(peer_id));
+        let validator = PeerValidator::default();
+        assert!(validator.validate_team(peer_id, &team, 0).is_ok());
+
+        // Forged peer binding.
+        assert!(validator.validate_team(PeerId::random(), &team, 0).is_err());
+
+        let mut cheat = team.clone();
+        cheat.players[0].athleticism.quickness = MAX_SKILL + 1.0;
+        assert!(validator.validate_team(peer_id, &cheat, 0).is_err());
+
+        let mut cheat = team.clone();
+        let mut extra = cheat.players[0].clone();
+        extra.id = PlayerId::new();
+        cheat.team.player_ids.push(extra.id);
+        cheat.players.push(extra);
+        while cheat.team.player_ids.len() <= cheat.team.spaceship.capacity() as usize {
+            let mut extra = cheat.players[0].clone();
+            extra.id = PlayerId::new();
+            cheat.team.player_ids.push(extra.id);
+            cheat.players.push(extra);
+        }
+        assert!(validator.validate_team(peer_id, &cheat, 0).is_err());
+
+        let mut cheat = team.clone();
+        cheat.players.pop();
+        assert!(validator.validate_team(peer_id, &cheat, 0).is_err());
+    }
+
+    #[test]
+    fn test_validate_team_updates() {
+        let peer_id = PeerId::random();
+        let team = NetworkTeam::test_team(Some(peer_id));
+        let mut validator = PeerValidator::default();
+        validator.validate_team(peer_id, &team, 0).unwrap();
+        validator.record_team(peer_id, &team, 0);
+
+        let mut update = team.clone();
+        update.team.version += MAX_TEAM_VERSION_JUMP + 10;
+        assert!(validator.validate_team(peer_id, &update, 0).is_err());
+        // Fine once enough time has passed.
+        assert!(validator
+            .validate_team(peer_id, &update, 10 * SECONDS)
+            .is_ok());
+        validator.record_team(peer_id, &update, 10 * SECONDS);
+
+        let mut update = update.clone();
+        update.team.balance = u32::MAX;
+        assert!(validator
+            .validate_team(peer_id, &update, 10 * SECONDS)
+            .is_err());
+    }
+
+    #[test]
+    fn test_validate_new_team_balance() {
+        let peer_id = PeerId::random();
+        let validator = PeerValidator::default();
+        let mut team = NetworkTeam::test_team(Some(peer_id));
+        team.team.version = 0;
+        team.team.balance = u32::MAX;
+        assert!(validator.validate_team(peer_id, &team, 0).is_err());
+
+        // The team is not recorded, so the version jump is not checked.
+        team.team.balance = 0;
+        team.team.version = 10 * MAX_TEAM_VERSION_JUMP;
+        assert!(validator.validate_team(peer_id, &team, 0).is_ok());
+    }
+
+    #[test]
+    fn test_validate_team_in_game() {
+        let peer_id = PeerId::random();
+        let NetworkTeam { team, players } = NetworkTeam::test_team(Some(peer_id));
+        let players = players.into_iter().map(|p| (p.id, p)).collect();
+        let team_in_game = TeamInGame::new(&team, players);
+        let validator = PeerValidator::default();
+        assert!(validator
+            .validate_team_in_game(peer_id, &team_in_game)
+            .is_ok());
+
+        let mut cheat = team_in_game.clone();
+        cheat.players.values_mut().next().unwrap().peer_id = Some(PeerId::random());
+        assert!(validator.validate_team_in_game(peer_id, &cheat).is_err());
+
+        let mut cheat = team_in_game.clone();
+        cheat.players.values_mut().next().unwrap().team = None;
+        assert!(validator.validate_team_in_game(peer_id, &cheat).is_err());
+    }
+
+    #[test]
+    fn test_repeat_offenders_are_banned() {
+        let peer_id = PeerId::random();
+        let mut validator = PeerValidator::default();
+        for _ in 1..MAX_PEER_OFFENCES {
+            assert!(!validator.record_offence(peer_id));
+        }
+        assert!(validator.record_offence(peer_id));
+        assert!(validator.is_banned(&peer_id));
+        assert!(!validator.record_offence(peer_id));
+    }
+}
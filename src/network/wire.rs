@@ -0,0 +1,680 @@
+// Everything we send to other peers is wrapped in an Envelope. Players, teams,
+// games and challenges go through the DTOs below, so their world types can be
+// restructured as long as the conversions in this file follow.
+// The DTOs still embed smaller world types as they are (skills, InfoStats,
+// PlayingStyle, PlayerImage, Jersey, Spaceship, CrewRoles, locations, TrainingFocus,
+// Injury, tactics, Timer, GameStatsMap, GameCommand): changing how any of those
+// serializes is a wire change and follows the rules below.
+//
+// Envelopes and payloads are CBOR, which keeps field names so the DTOs stay
+// self-describing. Large payloads are deflated on top of that.
+//
+// Compatibility rules for the DTOs:
+// - new fields must be #[serde(default)], so that older payloads still decode;
+// - unknown fields are ignored, so that newer payloads still decode;
+// - new request variants bump WIRE_VERSION only, and are not sent to older peers;
+// - anything else (renames, type changes, removals) bumps WIRE_VERSION and
+//   raises MIN_WIRE_VERSION to the first version we can no longer read.
+use super::types::{Challenge, ChallengeState, NetworkGame, NetworkTeam};
+use crate::engine::command::GameCommand;
+use crate::engine::tactic::{DefenseTactic, OffenseTactic};
+use crate::engine::timer::Timer;
+use crate::engine::types::{GameStatsMap, TeamInGame};
+use crate::image::player::PlayerImage;
+use crate::types::{AppResult, ChallengeId, GameId, PlanetId, PlayerId, TeamId, Tick};
+use crate::world::injury::Injury;
+use crate::world::jersey::Jersey;
+use crate::world::player::{InfoStats, Player};
+use crate::world::position::PlayingStyle;
+use crate::world::skill::{Athleticism, Defense, Mental, Offense, Skill, Technical};
+use crate::world::spaceship::Spaceship;
+use crate::world::team::{CrewRoles, Team};
+use crate::world::types::{PlayerLocation, TeamLocation, TrainingFocus};
+use crate::world::world::World;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use libp2p::PeerId;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use strum_macros::Display;
+
+// Version of the wire format we send.
+pub const WIRE_VERSION: u16 = 5;
+// Oldest wire format we can still read.
+pub const MIN_WIRE_VERSION: u16 = 2;
+// First version that accepts direct chat messages.
+pub const DIRECT_MESSAGE_WIRE_VERSION: u16 = 3;
+// First version that accepts the results of games against its ghost team.
+pub const GHOST_RESULT_WIRE_VERSION: u16 = 4;
+// First version that signs the results of network games.
+pub const SIGNED_RESULT_WIRE_VERSION: u16 = 5;
+// Peers from before the envelope send raw JSON of the world types.
+pub const LEGACY_WIRE_VERSION: u16 = 0;
+// Smaller payloads don't shrink enough to be worth deflating.
+const COMPRESSION_THRESHOLD: usize = 256;
+// Inflated payloads are capped, so that a small message cannot blow up in memory.
+// Full teams, the largest payloads we send, stay well below this.
+const MAX_PAYLOAD: u64 = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, Display, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum MessageKind {
+    Chat,
+    Team,
+    Challenge,
+    Game,
+    GameCommand,
+    Request,
+    Response,
+    GameResult,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Envelope {
+    pub version: u16,
+    // Oldest wire version that can read this payload.
+    pub min_version: u16,
+    pub kind: MessageKind,
+    #[serde(default)]
+    pub compressed: bool,
+    #[serde(with = "serde_bytes")]
+    pub payload: Vec<u8>,
+}
+
+// Version 1 envelopes were JSON, we only read their header to tell the peer version.
+#[derive(Deserialize)]
+struct JsonEnvelopeHeader {
+    version: u16,
+    min_version: u16,
+    kind: MessageKind,
+}
+
+impl Envelope {
+    pub fn new<T: Serialize>(kind: MessageKind, payload: &T) -> AppResult<Self> {
+        let mut payload = cbor4ii::serde::to_vec(vec![], payload)?;
+        let compressed = payload.len() > COMPRESSION_THRESHOLD;
+        if compressed {
+            let mut encoder = DeflateEncoder::new(vec![], Compression::default());
+            encoder.write_all(&payload)?;
+            payload = encoder.finish()?;
+        }
+        Ok(Self {
+            version: WIRE_VERSION,
+            min_version: MIN_WIRE_VERSION,
+            kind,
+            compressed,
+            payload,
+        })
+    }
+
+    pub fn to_vec(&self) -> AppResult<Vec<u8>> {
+        Ok(cbor4ii::serde::to_vec(vec![], self)?)
+    }
+
+    // Data that is not an envelope comes from a legacy peer.
+    pub fn from_slice(data: &[u8]) -> Option<Self> {
+        if let Ok(envelope) = cbor4ii::serde::from_slice(data) {
+            return Some(envelope);
+        }
+        serde_json::from_slice::<JsonEnvelopeHeader>(data)
+            .ok()
+            .map(|header| Self {
+                version: header.version,
+                min_version: header.min_version,
+                kind: header.kind,
+                compressed: false,
+                payload: vec![],
+            })
+    }
+
+    pub fn is_compatible(&self) -> bool {
+        self.version >= MIN_WIRE_VERSION && self.min_version <= WIRE_VERSION
+    }
+
+    pub fn check_compatibility(&self) -> AppResult<()> {
+        if self.version < MIN_WIRE_VERSION {
+            return Err(format!(
+                "Peer protocol version {} is too old, we need at least {}",
+                self.version, MIN_WIRE_VERSION
+            )
+            .into());
+        }
+        if self.min_version > WIRE_VERSION {
+            return Err(format!(
+                "Peer requires protocol version {}, we have {}. Please update the game",
+                self.min_version, WIRE_VERSION
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    pub fn open<T: DeserializeOwned>(self, kind: MessageKind) -> AppResult<T> {
+        self.check_compatibility()?;
+        if self.kind != kind {
+            return Err(format!("Expected a {} message, got {}", kind, self.kind).into());
+        }
+        let payload = if self.compressed {
+            let mut payload = vec![];
+            DeflateDecoder::new(self.payload.as_slice())
+                .take(MAX_PAYLOAD + 1)
+                .read_to_end(&mut payload)?;
+            if payload.len() as u64 > MAX_PAYLOAD {
+                return Err(format!("Payload is larger than {} bytes", MAX_PAYLOAD).into());
+            }
+            payload
+        } else {
+            self.payload
+        };
+        Ok(cbor4ii::serde::from_slice(&payload)?)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PlayerDto {
+    pub id: PlayerId,
+    pub peer_id: Option<PeerId>,
+    pub version: u64,
+    pub info: InfoStats,
+    pub team: Option<TeamId>,
+    pub jersey_number: Option<usize>,
+    pub reputation: f32,
+    pub playing_style: PlayingStyle,
+    pub image: PlayerImage,
+    pub current_location: PlayerLocation,
+    pub training_focus: Option<TrainingFocus>,
+    pub tiredness: f32,
+    pub morale: f32,
+    pub injury: Option<Injury>,
+    pub skills: [Skill; 20],
+}
+
+impl From<&Player> for PlayerDto {
+    fn from(player: &Player) -> Self {
+        Self {
+            id: player.id,
+            peer_id: player.peer_id,
+            version: player.version,
+            info: player.info.clone(),
+            team: player.team,
+            jersey_number: player.jersey_number,
+            reputation: player.reputation,
+            playing_style: player.playing_style,
+            image: player.image,
+            current_location: player.current_location,
+            training_focus: player.training_focus,
+            tiredness: player.tiredness,
+            morale: player.morale,
+            injury: player.injury,
+            skills: player.current_skill_array(),
+        }
+    }
+}
+
+impl From<PlayerDto> for Player {
+    fn from(dto: PlayerDto) -> Self {
+        let mut player = Player {
+            id: dto.id,
+            peer_id: dto.peer_id,
+            version: dto.version,
+            info: dto.info,
+            team: dto.team,
+            jersey_number: dto.jersey_number,
+            reputation: dto.reputation,
+            playing_style: dto.playing_style,
+            athleticism: Athleticism::default(),
+            offense: Offense::default(),
+            defense: Defense::default(),
+            technical: Technical::default(),
+            mental: Mental::default(),
+            image: dto.image,
+            current_location: dto.current_location,
+            // Only used to display recent changes, which we don't know for remote players.
+            previous_skills: dto.skills,
+            training_focus: dto.training_focus,
+            tiredness: dto.tiredness,
+            morale: dto.morale,
+            injury: dto.injury,
+        };
+        player.set_skill_array(dto.skills);
+        player
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TeamDto {
+    pub id: TeamId,
+    pub version: u64,
+    pub name: String,
+    pub reputation: f32,
+    pub player_ids: Vec<PlayerId>,
+    pub crew_roles: CrewRoles,
+    pub jersey: Jersey,
+    pub balance: u32,
+    pub max_jersey_number: u8,
+    pub spaceship: Spaceship,
+    pub home_planet: PlanetId,
+    pub current_location: TeamLocation,
+    pub peer_id: Option<PeerId>,
+    pub current_game: Option<GameId>,
+    pub offense_tactic: OffenseTactic,
+    pub defense_tactic: DefenseTactic,
+    #[serde(default)]
+    pub streak: i8,
+}
+
+impl From<&Team> for TeamDto {
+    fn from(team: &Team) -> Self {
+        Self {
+            id: team.id,
+            version: team.version,
+            name: team.name.clone(),
+            reputation: team.reputation,
+            player_ids: team.player_ids.clone(),
+            crew_roles: team.crew_roles.clone(),
+            jersey: team.jersey.clone(),
+            balance: team.balance,
+            max_jersey_number: team.max_jersey_number,
+            spaceship: team.spaceship.clone(),
+            home_planet: team.home_planet,
+            current_location: team.current_location,
+            peer_id: team.peer_id,
+            current_game: team.current_game,
+            offense_tactic: team.game_offense_tactic,
+            defense_tactic: team.game_defense_tactic,
+            streak: team.streak,
+        }
+    }
+}
+
+impl From<TeamDto> for Team {
+    fn from(dto: TeamDto) -> Self {
+        Self {
+            id: dto.id,
+            version: dto.version,
+            name: dto.name,
+            reputation: dto.reputation,
+            player_ids: dto.player_ids,
+            crew_roles: dto.crew_roles,
+            jersey: dto.jersey,
+            balance: dto.balance,
+            max_jersey_number: dto.max_jersey_number,
+            spaceship: dto.spaceship,
+            home_planet: dto.home_planet,
+            current_location: dto.current_location,
+            peer_id: dto.peer_id,
+            current_game: dto.current_game,
+            game_offense_tactic: dto.offense_tactic,
+            game_defense_tactic: dto.defense_tactic,
+            streak: dto.streak,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NetworkTeamDto {
+    pub team: TeamDto,
+    pub players: Vec<PlayerDto>,
+}
+
+impl From<&NetworkTeam> for NetworkTeamDto {
+    fn from(network_team: &NetworkTeam) -> Self {
+        Self {
+            team: TeamDto::from(&network_team.team),
+            players: network_team.players.iter().map(PlayerDto::from).collect(),
+        }
+    }
+}
+
+impl From<NetworkTeamDto> for NetworkTeam {
+    fn from(dto: NetworkTeamDto) -> Self {
+        NetworkTeam::new(
+            dto.team.into(),
+            dto.players.into_iter().map(Player::from).collect(),
+        )
+    }
+}
+
+impl NetworkTeamDto {
+    // Leaves out the players that the receiver already has at their current version.
+    pub fn delta(network_team: &NetworkTeam, known_players: &[(PlayerId, u64)]) -> Self {
+        let mut dto = Self::from(network_team);
+        dto.players.retain(|player| {
+            !known_players
+                .iter()
+                .any(|(id, version)| *id == player.id && *version >= player.version)
+        });
+        dto
+    }
+
+    // Fills in the players left out of a delta with the ones we already have.
+    pub fn merge(self, world: &World) -> AppResult<NetworkTeam> {
+        let mut network_team = NetworkTeam::from(self);
+        for player_id in network_team.team.player_ids.iter() {
+            if network_team
+                .players
+                .iter()
+                .any(|player| player.id == *player_id)
+            {
+                continue;
+            }
+            let player = world
+                .get_player(*player_id)
+                .filter(|player| player.peer_id == network_team.team.peer_id)
+                .ok_or(format!(
+                    "Player {} is missing from the team delta",
+                    player_id
+                ))?;
+            network_team.players.push(player.clone());
+        }
+        Ok(network_team)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TeamInGameDto {
+    pub team_id: TeamId,
+    pub peer_id: Option<PeerId>,
+    pub reputation: f32,
+    pub version: u64,
+    pub name: String,
+    pub initial_positions: Vec<PlayerId>,
+    pub players: Vec<PlayerDto>,
+    pub stats: GameStatsMap,
+    pub offense_tactic: OffenseTactic,
+    pub defense_tactic: DefenseTactic,
+    pub defensive_momentum: u8,
+    pub offensive_momentum: u8,
+    #[serde(default)]
+    pub timeouts_used: u8,
+    #[serde(default)]
+    pub starting_tactics: Option<(OffenseTactic, DefenseTactic)>,
+}
+
+impl From<&TeamInGame> for TeamInGameDto {
+    fn from(team_in_game: &TeamInGame) -> Self {
+        Self {
+            team_id: team_in_game.team_id,
+            peer_id: team_in_game.peer_id,
+            reputation: team_in_game.reputation,
+            version: team_in_game.version,
+            name: team_in_game.name.clone(),
+            initial_positions: team_in_game.initial_positions.clone(),
+            players: team_in_game.players.values().map(PlayerDto::from).collect(),
+            stats: team_in_game.stats.clone(),
+            offense_tactic: team_in_game.offense_tactic,
+            defense_tactic: team_in_game.defense_tactic,
+            defensive_momentum: team_in_game.defensive_momentum,
+            offensive_momentum: team_in_game.offensive_momentum,
+            timeouts_used: team_in_game.timeouts_used,
+            starting_tactics: team_in_game.starting_tactics,
+        }
+    }
+}
+
+impl From<TeamInGameDto> for TeamInGame {
+    fn from(dto: TeamInGameDto) -> Self {
+        Self {
+            team_id: dto.team_id,
+            peer_id: dto.peer_id,
+            reputation: dto.reputation,
+            version: dto.version,
+            name: dto.name,
+            initial_positions: dto.initial_positions,
+            players: dto
+                .players
+                .into_iter()
+                .map(|player| (player.id, Player::from(player)))
+                .collect(),
+            stats: dto.stats,
+            offense_tactic: dto.offense_tactic,
+            defense_tactic: dto.defense_tactic,
+            defensive_momentum: dto.defensive_momentum,
+            offensive_momentum: dto.offensive_momentum,
+            timeouts_used: dto.timeouts_used,
+            starting_tactics: dto.starting_tactics,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChallengeDto {
+    pub id: ChallengeId,
+    pub state: ChallengeState,
+    pub home_peer_id: PeerId,
+    pub away_peer_id: PeerId,
+    pub home_team: Option<TeamInGameDto>,
+    pub away_team: Option<TeamInGameDto>,
+    pub game_id: Option<GameId>,
+    pub starting_at: Option<Tick>,
+    pub error_message: Option<String>,
+    pub engine_version: u16,
+    pub expires_at: Tick,
+}
+
+impl From<&Challenge> for ChallengeDto {
+    fn from(challenge: &Challenge) -> Self {
+        Self {
+            id: challenge.id,
+            state: challenge.state.clone(),
+            home_peer_id: challenge.home_peer_id,
+            away_peer_id: challenge.away_peer_id,
+            home_team: challenge.home_team.as_ref().map(TeamInGameDto::from),
+            away_team: challenge.away_team.as_ref().map(TeamInGameDto::from),
+            game_id: challenge.game_id,
+            starting_at: challenge.starting_at,
+            error_message: challenge.error_message.clone(),
+            engine_version: challenge.engine_version,
+            expires_at: challenge.expires_at,
+        }
+    }
+}
+
+impl From<ChallengeDto> for Challenge {
+    fn from(dto: ChallengeDto) -> Self {
+        Self {
+            id: dto.id,
+            state: dto.state,
+            home_peer_id: dto.home_peer_id,
+            away_peer_id: dto.away_peer_id,
+            home_team: dto.home_team.map(TeamInGame::from),
+            away_team: dto.away_team.map(TeamInGame::from),
+            game_id: dto.game_id,
+            starting_at: dto.starting_at,
+            error_message: dto.error_message,
+            engine_version: dto.engine_version,
+            expires_at: dto.expires_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NetworkGameDto {
+    pub id: GameId,
+    pub home_team_in_game: TeamInGameDto,
+    pub away_team_in_game: TeamInGameDto,
+    pub location: PlanetId,
+    pub attendance: u32,
+    pub starting_at: Tick,
+    pub timer: Timer,
+    pub commands: Vec<GameCommand>,
+    pub engine_version: u16,
+}
+
+impl From<&NetworkGame> for NetworkGameDto {
+    fn from(game: &NetworkGame) -> Self {
+        Self {
+            id: game.id,
+            home_team_in_game: TeamInGameDto::from(&game.home_team_in_game),
+            away_team_in_game: TeamInGameDto::from(&game.away_team_in_game),
+            location: game.location,
+            attendance: game.attendance,
+            starting_at: game.starting_at,
+            timer: game.timer,
+            commands: game.commands.clone(),
+            engine_version: game.engine_version,
+        }
+    }
+}
+
+impl From<NetworkGameDto> for NetworkGame {
+    fn from(dto: NetworkGameDto) -> Self {
+        Self {
+            id: dto.id,
+            home_team_in_game: dto.home_team_in_game.into(),
+            away_team_in_game: dto.away_team_in_game.into(),
+            location: dto.location,
+            attendance: dto.attendance,
+            starting_at: dto.starting_at,
+            timer: dto.timer,
+            commands: dto.commands,
+            engine_version: dto.engine_version,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Envelope, MessageKind, NetworkTeamDto, MAX_PAYLOAD, MIN_WIRE_VERSION, WIRE_VERSION,
+    };
+    use crate::network::types::NetworkTeam;
+    use crate::world::world::World;
+    use libp2p::PeerId;
+    use serde::{Deserialize, Serialize};
+
+    #[test]
+    fn test_team_dto_roundtrip() {
+        let network_team = NetworkTeam::test_team(Some(PeerId::random()));
+        let envelope =
+            Envelope::new(MessageKind::Team, &NetworkTeamDto::from(&network_team)).unwrap();
+        let data = envelope.to_vec().unwrap();
+
+        let envelope = Envelope::from_slice(&data).unwrap();
+        assert_eq!(envelope.version, WIRE_VERSION);
+        let received: NetworkTeam = envelope
+            .open::<NetworkTeamDto>(MessageKind::Team)
+            .unwrap()
+            .into();
+        assert_eq!(received.team, network_team.team);
+        assert_eq!(received.players.len(), network_team.players.len());
+        for (received, sent) in received.players.iter().zip(network_team.players.iter()) {
+            assert_eq!(received.id, sent.id);
+            assert_eq!(received.peer_id, sent.peer_id);
+            assert_eq!(received.total_skills(), sent.total_skills());
+        }
+    }
+
+    #[test]
+    fn test_team_delta() {
+        let mut network_team = NetworkTeam::test_team(Some(PeerId::random()));
+        let mut receiver = World::new(None);
+        receiver.add_network_team(network_team.clone()).unwrap();
+
+        network_team.team.version += 1;
+        network_team.players[0].version += 1;
+        network_team.players[0].tiredness = 10.0;
+        let known_players: Vec<_> = network_team
+            .team
+            .player_ids
+            .iter()
+            .map(|id| (*id, receiver.get_player(*id).unwrap().version))
+            .collect();
+
+        let delta = NetworkTeamDto::delta(&network_team, &known_players);
+        assert_eq!(delta.players.len(), 1);
+        let full = NetworkTeamDto::delta(&network_team, &[]);
+        assert_eq!(full.players.len(), network_team.players.len());
+
+        let merged = delta.merge(&receiver).unwrap();
+        assert_eq!(merged.players.len(), network_team.players.len());
+        let updated = merged
+            .players
+            .iter()
+            .find(|player| player.id == network_team.players[0].id)
+            .unwrap();
+        assert_eq!(updated.tiredness, 10.0);
+
+        // Players we don't have must be sent in full.
+        let delta = NetworkTeamDto::delta(&network_team, &known_players);
+        assert!(delta.merge(&World::new(None)).is_err());
+    }
+
+    #[test]
+    fn test_incompatible_envelopes_are_rejected() {
+        let dto = NetworkTeamDto::from(&NetworkTeam::test_team(Some(PeerId::random())));
+        let envelope = Envelope::new(MessageKind::Team, &dto).unwrap();
+        assert!(envelope.compressed);
+        assert!(envelope.is_compatible());
+        assert!(envelope
+            .clone()
+            .open::<NetworkTeamDto>(MessageKind::Game)
+            .is_err());
+
+        let mut newer = envelope.clone();
+        newer.version = WIRE_VERSION + 1;
+        newer.min_version = WIRE_VERSION + 1;
+        assert!(!newer.is_compatible());
+        assert!(newer.open::<NetworkTeamDto>(MessageKind::Team).is_err());
+
+        // Newer peers that we can still read, extra fields are ignored.
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Message {
+            text: String,
+        }
+        #[derive(Serialize)]
+        struct NewerMessage {
+            text: String,
+            new_field: u32,
+        }
+        let mut newer = Envelope::new(
+            MessageKind::Chat,
+            &NewerMessage {
+                text: "Ahoy".to_string(),
+                new_field: 42,
+            },
+        )
+        .unwrap();
+        newer.version = WIRE_VERSION + 1;
+        assert_eq!(
+            newer.open::<Message>(MessageKind::Chat).unwrap(),
+            Message {
+                text: "Ahoy".to_string()
+            }
+        );
+
+        // Version 1 envelopes were JSON.
+        let json = serde_json::json!({
+            "version": 1,
+            "min_version": 1,
+            "kind": "Team",
+            "payload": serde_json::to_value(&dto).unwrap(),
+        });
+        let old = Envelope::from_slice(&serde_json::to_vec(&json).unwrap()).unwrap();
+        assert_eq!(old.version, 1);
+        assert!(!old.is_compatible());
+
+        let mut older = envelope;
+        older.version = MIN_WIRE_VERSION - 1;
+        assert!(older.open::<NetworkTeamDto>(MessageKind::Team).is_err());
+
+        // Legacy peers send the world types without envelope.
+        let legacy = serde_json::to_vec(&NetworkTeam::test_team(Some(PeerId::random()))).unwrap();
+        assert!(Envelope::from_slice(&legacy).is_none());
+    }
+
+    #[test]
+    fn test_oversized_payloads_are_rejected() {
+        let text = "a".repeat(MAX_PAYLOAD as usize);
+        let envelope = Envelope::new(MessageKind::Chat, &text).unwrap();
+        assert!(envelope.compressed);
+        // A few KBs on the wire, megabytes once inflated.
+        assert!(envelope.payload.len() < 64 * 1024);
+        assert!(envelope.open::<String>(MessageKind::Chat).is_err());
+
+        let text = "a".repeat(1024);
+        let envelope = Envelope::new(MessageKind::Chat, &text).unwrap();
+        assert_eq!(envelope.open::<String>(MessageKind::Chat).unwrap(), text);
+    }
+}
@@ -1,58 +1,398 @@
-use crate::network::constants::SubscriptionTopic;
+use crate::network::constants::*;
+use crate::network::network_callback::split_message;
+use crate::network::protocol::{NetworkEvent, RebelsBehaviourEvent};
+use crate::network::types::{TeamAnnouncement, TeamTopicMessage};
+use crate::network::wire::{Envelope, MessageKind};
 use crate::network::{handler::NetworkHandler, types::SeedInfo};
-use crate::types::AppResult;
+use crate::store::{
+    load_or_generate_keypair, save_to_json, PERSISTED_RELAYER_KEYPAIR_FILENAME,
+    PERSISTED_RELAYER_STATS_FILENAME,
+};
+use crate::types::{AppResult, GameId, SystemTimeTick, TeamId, Tick};
 use futures::StreamExt;
 use libp2p::gossipsub::IdentTopic;
-use libp2p::{gossipsub, swarm::SwarmEvent};
+use libp2p::{gossipsub, relay, swarm::SwarmEvent, Multiaddr, PeerId};
+use serde::Serialize;
+use serde_json::json;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 use tokio::select;
-use void::Void;
+
+// A team as last announced to the seed.
+#[derive(Debug, Clone)]
+struct DirectoryEntry {
+    announcement: TeamAnnouncement,
+    last_seen: Tick,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OnlineTeam {
+    pub peer_id: PeerId,
+    pub team_id: TeamId,
+    pub version: u64,
+    pub current_game: Option<GameId>,
+    pub last_seen: Tick,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RelayerStats {
+    pub timestamp: Tick,
+    pub uptime_secs: Tick,
+    pub connected_peers: usize,
+    pub total_connections: u64,
+    pub received_messages: u64,
+    pub relay_reservations: usize,
+    pub relayed_circuits: u64,
+    pub online_teams: Vec<OnlineTeam>,
+    pub games: Vec<GameId>,
+}
 
 pub struct Relayer {
     pub running: bool,
-    network_handler: NetworkHandler,
+    pub network_handler: NetworkHandler,
+    // Shown to peers when they connect.
+    pub motd: Option<String>,
+    directory: HashMap<PeerId, DirectoryEntry>,
+    started_at: Tick,
+    total_connections: u64,
+    received_messages: u64,
+    relay_reservations: HashSet<PeerId>,
+    relayed_circuits: u64,
+}
+
+// Logs are printed as JSON lines, so they can be collected and filtered.
+fn log_event(event: &str, fields: serde_json::Value) {
+    let mut line = json!({
+        "timestamp": Tick::now() as u64,
+        "event": event,
+    });
+    if let (Some(line), serde_json::Value::Object(fields)) = (line.as_object_mut(), fields) {
+        line.extend(fields);
+    }
+    println!("{}", line);
 }
 
 impl Relayer {
-    pub fn new() -> Self {
+    pub fn new(
+        listen_ip: Option<String>,
+        listen_port: Option<u16>,
+        motd: Option<String>,
+    ) -> AppResult<Self> {
+        // Without a listen address, the handler falls back to the default ports.
+        let specific_ip = listen_ip.as_ref().is_some_and(|ip| ip != "0.0.0.0");
+        let listen_addresses: Vec<Multiaddr> = if listen_ip.is_some() || listen_port.is_some() {
+            let ip = listen_ip.unwrap_or("0.0.0.0".to_string());
+            let port = listen_port.unwrap_or(DEFAULT_PORT);
+            vec![
+                format!("/ip4/{ip}/udp/{port}/quic-v1").parse()?,
+                format!("/ip4/{ip}/tcp/{port}").parse()?,
+            ]
+        } else {
+            vec![]
+        };
+
+        // The relayer is the seed, it does not dial one.
+        let mut network_handler = NetworkHandler::with_addresses(
+            load_or_generate_keypair(PERSISTED_RELAYER_KEYPAIR_FILENAME)?,
+            vec![],
+            listen_addresses.clone(),
+            false,
+            true,
+        )
+        .map_err(|e| e.to_string())?;
+
+        // Relay reservations carry the relay addresses, so they must be known
+        // upfront: the listen addresses if they are on a specific IP, else the seed addresses.
+        let external_addresses = if specific_ip {
+            listen_addresses
+        } else {
+            vec![SEED_QUIC_ADDRESS.parse()?, SEED_ADDRESS.parse()?]
+        };
+        for address in external_addresses {
+            network_handler.swarm.add_external_address(address);
+        }
+
+        let mut relayer = Self::with_network_handler(network_handler);
+        relayer.motd = motd;
+        Ok(relayer)
+    }
+
+    pub fn with_network_handler(network_handler: NetworkHandler) -> Self {
         Self {
             running: true,
-            network_handler: NetworkHandler::new(None)
-                .expect("Failed to initialize network handler"),
+            network_handler,
+            motd: None,
+            directory: HashMap::new(),
+            started_at: Tick::now(),
+            total_connections: 0,
+            received_messages: 0,
+            relay_reservations: HashSet::new(),
+            relayed_circuits: 0,
         }
     }
 
     pub async fn run(&mut self) -> AppResult<()> {
-        println!("Starting relayer. Press Ctrl-C to exit.");
+        log_event(
+            "relayer_started",
+            json!({
+                "peer_id": self.network_handler.swarm.local_peer_id().to_string(),
+                "motd": self.motd,
+            }),
+        );
+
+        let mut stats_interval =
+            tokio::time::interval(Duration::from_millis(RELAYER_STATS_INTERVAL as u64));
+        let ctrl_c = tokio::signal::ctrl_c();
+        tokio::pin!(ctrl_c);
+
         while self.running {
             select! {
-                    swarm_event = self.network_handler.swarm.select_next_some() =>  {
-                        let result = self.handle_network_events(swarm_event);
-                        if result.is_err() {
-                            println!("Error handling network event: {:?}", result);
-                        }
+                swarm_event = self.network_handler.swarm.select_next_some() => {
+                    if let Err(e) = self.handle_network_events(swarm_event) {
+                        log_event("error", json!({ "error": e.to_string() }));
+                    }
+                }
+                _ = stats_interval.tick() => {
+                    self.expire_directory(Tick::now());
+                    self.dump_stats();
+                }
+                _ = &mut ctrl_c => {
+                    log_event("shutdown", json!({}));
+                    self.running = false;
                 }
             }
         }
+
+        self.dump_stats();
         Ok(())
     }
 
-    pub fn handle_network_events(
-        &mut self,
-        network_event: SwarmEvent<gossipsub::Event, Void>,
-    ) -> AppResult<()> {
-        println!("Received network event: {:?}", network_event);
+    pub fn handle_network_events(&mut self, network_event: NetworkEvent) -> AppResult<()> {
         match network_event {
-            SwarmEvent::Behaviour(gossipsub::Event::Subscribed { peer_id, topic }) => {
-                if topic == IdentTopic::new(SubscriptionTopic::SEED_INFO).hash() {
-                    println!("Sending info to {}", peer_id);
-                    self.network_handler.send_seed_info(SeedInfo::new(
-                        self.network_handler.swarm.connected_peers().count(),
-                        None,
-                    ))?;
+            SwarmEvent::NewListenAddr { address, .. } => {
+                log_event("listening", json!({ "address": address.to_string() }));
+            }
+            SwarmEvent::ConnectionEstablished {
+                peer_id, endpoint, ..
+            } => {
+                self.total_connections += 1;
+                log_event(
+                    "peer_connected",
+                    json!({
+                        "peer_id": peer_id.to_string(),
+                        "address": endpoint.get_remote_address().to_string(),
+                    }),
+                );
+            }
+            SwarmEvent::ConnectionClosed {
+                peer_id,
+                num_established: 0,
+                ..
+            } => {
+                self.directory.remove(&peer_id);
+                self.relay_reservations.remove(&peer_id);
+                log_event(
+                    "peer_disconnected",
+                    json!({ "peer_id": peer_id.to_string() }),
+                );
+            }
+            SwarmEvent::Behaviour(RebelsBehaviourEvent::Gossipsub(
+                gossipsub::Event::Subscribed { peer_id, topic },
+            )) if topic == IdentTopic::new(SubscriptionTopic::SEED_INFO).hash() => {
+                log_event("seed_info_sent", json!({ "peer_id": peer_id.to_string() }));
+                self.network_handler.send_seed_info(SeedInfo::new(
+                    self.network_handler.swarm.connected_peers().count(),
+                    self.motd.clone(),
+                    self.directory(),
+                ))?;
+            }
+            SwarmEvent::Behaviour(RebelsBehaviourEvent::Gossipsub(gossipsub::Event::Message {
+                message,
+                ..
+            })) => {
+                self.received_messages += 1;
+                if message.topic == IdentTopic::new(SubscriptionTopic::TEAM).hash() {
+                    self.handle_team_message(&message)?;
                 }
             }
+            SwarmEvent::Behaviour(RebelsBehaviourEvent::Relay(event)) => {
+                self.handle_relay_event(event);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_relay_event(&mut self, event: relay::Event) {
+        match event {
+            relay::Event::ReservationReqAccepted {
+                src_peer_id,
+                renewed: false,
+            } => {
+                self.relay_reservations.insert(src_peer_id);
+                log_event(
+                    "relay_reservation",
+                    json!({ "peer_id": src_peer_id.to_string() }),
+                );
+            }
+            relay::Event::ReservationTimedOut { src_peer_id } => {
+                self.relay_reservations.remove(&src_peer_id);
+            }
+            relay::Event::CircuitReqAccepted {
+                src_peer_id,
+                dst_peer_id,
+            } => {
+                self.relayed_circuits += 1;
+                log_event(
+                    "relay_circuit",
+                    json!({
+                        "src_peer_id": src_peer_id.to_string(),
+                        "dst_peer_id": dst_peer_id.to_string(),
+                    }),
+                );
+            }
+            relay::Event::CircuitClosed {
+                src_peer_id,
+                dst_peer_id,
+                error,
+            } => log_event(
+                "relay_circuit_closed",
+                json!({
+                    "src_peer_id": src_peer_id.to_string(),
+                    "dst_peer_id": dst_peer_id.to_string(),
+                    "error": error.map(|e| e.to_string()),
+                }),
+            ),
             _ => {}
         }
+    }
+
+    // Announcements keep the directory of online teams up to date.
+    fn handle_team_message(&mut self, message: &gossipsub::Message) -> AppResult<()> {
+        let (_, data) = split_message(message)?;
+        let envelope = Envelope::from_slice(data).ok_or("Invalid team message")?;
+        if let TeamTopicMessage::Announcement(announcement) =
+            envelope.open::<TeamTopicMessage>(MessageKind::Team)?
+        {
+            if message.source != Some(announcement.peer_id) {
+                return Err("Team announcement does not match its source".into());
+            }
+            let is_new = !self.directory.contains_key(&announcement.peer_id);
+            if is_new {
+                log_event(
+                    "team_online",
+                    json!({
+                        "peer_id": announcement.peer_id.to_string(),
+                        "team_id": announcement.team_id.to_string(),
+                    }),
+                );
+            }
+            self.directory.insert(
+                announcement.peer_id,
+                DirectoryEntry {
+                    announcement,
+                    last_seen: Tick::now(),
+                },
+            );
+        }
         Ok(())
     }
+
+    fn expire_directory(&mut self, now: Tick) {
+        self.directory
+            .retain(|_, entry| now.saturating_sub(entry.last_seen) < DIRECTORY_ENTRY_TTL);
+    }
+
+    // The most recently seen teams.
+    pub fn directory(&self) -> Vec<TeamAnnouncement> {
+        let mut entries: Vec<&DirectoryEntry> = self.directory.values().collect();
+        entries.sort_by_key(|entry| Reverse(entry.last_seen));
+        entries
+            .iter()
+            .take(MAX_DIRECTORY_ENTRIES)
+            .map(|entry| entry.announcement.clone())
+            .collect()
+    }
+
+    pub fn stats(&self) -> RelayerStats {
+        let now = Tick::now();
+        let online_teams: Vec<OnlineTeam> = self
+            .directory
+            .values()
+            .map(|entry| OnlineTeam {
+                peer_id: entry.announcement.peer_id,
+                team_id: entry.announcement.team_id,
+                version: entry.announcement.version,
+                current_game: entry.announcement.current_game,
+                last_seen: entry.last_seen,
+            })
+            .collect();
+        // Both teams of a game announce it.
+        let games: HashSet<GameId> = online_teams
+            .iter()
+            .filter_map(|team| team.current_game)
+            .collect();
+
+        RelayerStats {
+            timestamp: now,
+            uptime_secs: now.saturating_sub(self.started_at).as_secs(),
+            connected_peers: self.network_handler.swarm.connected_peers().count(),
+            total_connections: self.total_connections,
+            received_messages: self.received_messages,
+            relay_reservations: self.relay_reservations.len(),
+            relayed_circuits: self.relayed_circuits,
+            online_teams,
+            games: games.into_iter().collect(),
+        }
+    }
+
+    fn dump_stats(&self) {
+        let stats = self.stats();
+        log_event(
+            "stats",
+            json!({
+                "connected_peers": stats.connected_peers,
+                "relay_reservations": stats.relay_reservations,
+                "online_teams": stats.online_teams.len(),
+                "games": stats.games.len(),
+            }),
+        );
+        if let Err(e) = save_to_json(PERSISTED_RELAYER_STATS_FILENAME, &stats) {
+            log_event("error", json!({ "error": e.to_string() }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Relayer;
+    use crate::network::handler::NetworkHandler;
+    use libp2p::gossipsub::{Message, TopicHash};
+    use libp2p::{identity, PeerId};
+
+    #[tokio::test]
+    async fn test_truncated_team_message() {
+        let handler = NetworkHandler::with_addresses(
+            identity::Keypair::generate_ed25519(),
+            vec![],
+            vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()],
+            false,
+            true,
+        )
+        .unwrap();
+        let mut relayer = Relayer::with_network_handler(handler);
+
+        let mut message = Message {
+            source: Some(PeerId::random()),
+            data: vec![0; 5],
+            sequence_number: None,
+            topic: TopicHash::from_raw("team"),
+        };
+        assert!(relayer.handle_team_message(&message).is_err());
+        // Just the timestamp.
+        message.data = vec![0; 16];
+        assert!(relayer.handle_team_message(&message).is_err());
+        assert!(relayer.directory.is_empty());
+    }
 }
@@ -0,0 +1,501 @@
+use crate::engine::game::Game;
+use crate::engine::tactic::{DefenseTactic, OffenseTactic};
+use crate::engine::types::{GameStats, TeamInGame};
+use crate::types::{AppResult, GameId, PlayerId, PlayerMap, TeamId, Tick, HOURS};
+use crate::world::constants::DEFAULT_PLANET_ID;
+use crate::world::planet::Planet;
+use crate::world::types::TeamLocation;
+use crate::world::world::World;
+use rand::seq::IteratorRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+use std::collections::HashMap;
+use strum::IntoEnumIterator;
+
+// Runs games between two teams without the UI and in no time,
+// so that many games can be played to check the engine balance.
+pub struct Simulator {
+    seed: u64,
+    home_team_in_game: TeamInGame,
+    away_team_in_game: TeamInGame,
+    planet: Planet,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PlayerSimulationReport {
+    pub player_id: PlayerId,
+    pub name: String,
+    pub games_played: u32,
+    pub minutes: f32,
+    pub points: f32,
+    pub rebounds: f32,
+    pub assists: f32,
+    pub steals: f32,
+    pub blocks: f32,
+    pub turnovers: f32,
+    pub fouls: f32,
+    pub plus_minus: f32,
+    pub field_goal_percentage: f32,
+    pub three_point_percentage: f32,
+    pub free_throw_percentage: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TeamSimulationReport {
+    pub team_id: TeamId,
+    pub name: String,
+    pub wins: u32,
+    pub win_rate: f32,
+    pub points: f32,
+    pub points_allowed: f32,
+    pub rebounds: f32,
+    pub offensive_rebounds: f32,
+    pub assists: f32,
+    pub steals: f32,
+    pub blocks: f32,
+    pub turnovers: f32,
+    pub fouls: f32,
+    pub attempted_2pt: f32,
+    pub made_2pt: f32,
+    pub attempted_3pt: f32,
+    pub made_3pt: f32,
+    pub attempted_ft: f32,
+    pub made_ft: f32,
+    pub players: Vec<PlayerSimulationReport>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SimulationReport {
+    pub seed: u64,
+    pub games: u32,
+    pub overtime_games: u32,
+    pub home_team: TeamSimulationReport,
+    pub away_team: TeamSimulationReport,
+}
+
+// Sums of the player stats over all simulated games.
+#[derive(Debug, Clone, Default)]
+struct StatsTotals {
+    games_played: u32,
+    seconds_played: u32,
+    points: u32,
+    offensive_rebounds: u32,
+    defensive_rebounds: u32,
+    assists: u32,
+    steals: u32,
+    blocks: u32,
+    turnovers: u32,
+    fouls: u32,
+    plus_minus: i32,
+    attempted_2pt: u32,
+    made_2pt: u32,
+    attempted_3pt: u32,
+    made_3pt: u32,
+    attempted_ft: u32,
+    made_ft: u32,
+}
+
+impl StatsTotals {
+    fn add(&mut self, stats: &GameStats) {
+        if stats.seconds_played > 0 {
+            self.games_played += 1;
+        }
+        self.seconds_played += stats.seconds_played as u32;
+        self.points += stats.points as u32;
+        self.offensive_rebounds += stats.offensive_rebounds as u32;
+        self.defensive_rebounds += stats.defensive_rebounds as u32;
+        self.assists += stats.assists as u32;
+        self.steals += stats.steals as u32;
+        self.blocks += stats.blocks as u32;
+        self.turnovers += stats.turnovers as u32;
+        self.fouls += (stats.fouls + stats.technical_fouls) as u32;
+        self.plus_minus += stats.plus_minus as i32;
+        self.attempted_2pt += stats.attempted_2pt as u32;
+        self.made_2pt += stats.made_2pt as u32;
+        self.attempted_3pt += stats.attempted_3pt as u32;
+        self.made_3pt += stats.made_3pt as u32;
+        self.attempted_ft += stats.attempted_ft as u32;
+        self.made_ft += stats.made_ft as u32;
+    }
+}
+
+fn per_game(value: u32, games: u32) -> f32 {
+    if games == 0 {
+        return 0.0;
+    }
+    value as f32 / games as f32
+}
+
+fn percentage(made: u32, attempted: u32) -> f32 {
+    if attempted == 0 {
+        return 0.0;
+    }
+    100.0 * made as f32 / attempted as f32
+}
+
+impl Simulator {
+    pub fn new(
+        seed: u64,
+        home_team_in_game: TeamInGame,
+        away_team_in_game: TeamInGame,
+        planet: Planet,
+    ) -> Self {
+        Self {
+            seed,
+            home_team_in_game,
+            away_team_in_game,
+            planet,
+        }
+    }
+
+    // Load the two teams from the saved world.
+    pub fn from_saved_world(
+        seed: u64,
+        home_team_id: TeamId,
+        away_team_id: TeamId,
+    ) -> AppResult<Self> {
+        let world = World::load()?;
+        let home_team = world.get_team_or_err(home_team_id)?;
+        world.get_team_or_err(away_team_id)?;
+        let planet_id = match home_team.current_location {
+            TeamLocation::OnPlanet { planet_id } => planet_id,
+            TeamLocation::Travelling { .. } => home_team.home_planet,
+        };
+        let planet = world.get_planet_or_err(planet_id)?.clone();
+
+        let home_team_in_game =
+            TeamInGame::from_team_id(home_team_id, &world.teams, &world.players)
+                .ok_or("Failed to load home team players")?;
+        let away_team_in_game =
+            TeamInGame::from_team_id(away_team_id, &world.teams, &world.players)
+                .ok_or("Failed to load away team players")?;
+
+        Ok(Self::new(
+            seed,
+            home_team_in_game,
+            away_team_in_game,
+            planet,
+        ))
+    }
+
+    // Generate two random teams from the seed.
+    pub fn from_seed(seed: u64) -> AppResult<Self> {
+        let mut world = World::new(Some(seed));
+        let rng = &mut ChaCha8Rng::seed_from_u64(seed);
+        let planet_id = *DEFAULT_PLANET_ID;
+        let home_team_id = world.generate_random_team(
+            rng,
+            planet_id,
+            "Home Rebels".to_string(),
+            "Home ship".to_string(),
+        )?;
+        let away_team_id = world.generate_random_team(
+            rng,
+            planet_id,
+            "Away Rebels".to_string(),
+            "Away ship".to_string(),
+        )?;
+        let planet = world.get_planet_or_err(planet_id)?.clone();
+
+        let home_team_in_game =
+            TeamInGame::from_team_id(home_team_id, &world.teams, &world.players)
+                .ok_or("Failed to generate home team")?;
+        let away_team_in_game =
+            TeamInGame::from_team_id(away_team_id, &world.teams, &world.players)
+                .ok_or("Failed to generate away team")?;
+
+        Ok(Self::new(
+            seed,
+            Self::with_seeded_ids(home_team_in_game, rng),
+            Self::with_seeded_ids(away_team_in_game, rng),
+            planet,
+        ))
+    }
+
+    // Generated teams get random ids and tactics, which would change the bench order
+    // and the game flow from run to run. We replace them using the seeded rng.
+    fn with_seeded_ids(team_in_game: TeamInGame, rng: &mut ChaCha8Rng) -> TeamInGame {
+        let team_id = TeamId::from_u128(rng.gen());
+        let ids = team_in_game
+            .initial_positions
+            .iter()
+            .map(|id| (*id, PlayerId::from_u128(rng.gen())))
+            .collect::<HashMap<PlayerId, PlayerId>>();
+
+        let mut players = PlayerMap::new();
+        for (id, player) in team_in_game.players.iter() {
+            let mut player = player.clone();
+            player.id = ids[id];
+            player.team = Some(team_id);
+            players.insert(player.id, player);
+        }
+
+        TeamInGame {
+            team_id,
+            initial_positions: team_in_game
+                .initial_positions
+                .iter()
+                .map(|id| ids[id])
+                .collect(),
+            players,
+            stats: team_in_game
+                .stats
+                .iter()
+                .map(|(id, stats)| (ids[id], stats.clone()))
+                .collect(),
+            offense_tactic: OffenseTactic::iter().choose(rng).unwrap_or_default(),
+            defense_tactic: DefenseTactic::iter().choose(rng).unwrap_or_default(),
+            ..team_in_game
+        }
+    }
+
+    pub fn play_game(&self, game_id: GameId, starting_at: Tick) -> Game {
+        let mut game = Game::new(
+            game_id,
+            self.home_team_in_game.clone(),
+            self.away_team_in_game.clone(),
+            starting_at,
+            &self.planet,
+        );
+        while !game.has_ended() {
+            game.tick();
+        }
+        game
+    }
+
+    pub fn run(&self, number_of_games: u32) -> SimulationReport {
+        // Game ids and starting ticks are drawn from the seed, so that each game
+        // gets its own deterministic rng seed (see Game::get_rng_seed).
+        let rng = &mut ChaCha8Rng::seed_from_u64(self.seed);
+
+        let mut home_wins = 0;
+        let mut away_wins = 0;
+        let mut overtime_games = 0;
+        let mut home_points_allowed = 0;
+        let mut away_points_allowed = 0;
+        let mut home_totals: HashMap<PlayerId, StatsTotals> = HashMap::new();
+        let mut away_totals: HashMap<PlayerId, StatsTotals> = HashMap::new();
+
+        for idx in 0..number_of_games {
+            let game_id = GameId::from_u128(rng.gen());
+            let game = self.play_game(game_id, idx as Tick * HOURS);
+
+            let (home_score, away_score) = game.get_score();
+            if home_score > away_score {
+                home_wins += 1;
+            } else {
+                away_wins += 1;
+            }
+            if game.timer.overtimes() > 0 {
+                overtime_games += 1;
+            }
+            home_points_allowed += away_score as u32;
+            away_points_allowed += home_score as u32;
+
+            for (id, stats) in game.home_team_in_game.stats.iter() {
+                home_totals.entry(*id).or_default().add(stats);
+            }
+            for (id, stats) in game.away_team_in_game.stats.iter() {
+                away_totals.entry(*id).or_default().add(stats);
+            }
+        }
+
+        SimulationReport {
+            seed: self.seed,
+            games: number_of_games,
+            overtime_games,
+            home_team: Self::team_report(
+                &self.home_team_in_game,
+                &home_totals,
+                home_wins,
+                home_points_allowed,
+                number_of_games,
+            ),
+            away_team: Self::team_report(
+                &self.away_team_in_game,
+                &away_totals,
+                away_wins,
+                away_points_allowed,
+                number_of_games,
+            ),
+        }
+    }
+
+    fn team_report(
+        team_in_game: &TeamInGame,
+        totals: &HashMap<PlayerId, StatsTotals>,
+        wins: u32,
+        points_allowed: u32,
+        games: u32,
+    ) -> TeamSimulationReport {
+        let mut team_totals = StatsTotals::default();
+        let mut players = vec![];
+        for player_id in team_in_game.initial_positions.iter() {
+            let player = &team_in_game.players[player_id];
+            let player_totals = totals.get(player_id).cloned().unwrap_or_default();
+
+            team_totals.points += player_totals.points;
+            team_totals.offensive_rebounds += player_totals.offensive_rebounds;
+            team_totals.defensive_rebounds += player_totals.defensive_rebounds;
+            team_totals.assists += player_totals.assists;
+            team_totals.steals += player_totals.steals;
+            team_totals.blocks += player_totals.blocks;
+            team_totals.turnovers += player_totals.turnovers;
+            team_totals.fouls += player_totals.fouls;
+            team_totals.attempted_2pt += player_totals.attempted_2pt;
+            team_totals.made_2pt += player_totals.made_2pt;
+            team_totals.attempted_3pt += player_totals.attempted_3pt;
+            team_totals.made_3pt += player_totals.made_3pt;
+            team_totals.attempted_ft += player_totals.attempted_ft;
+            team_totals.made_ft += player_totals.made_ft;
+
+            // Player averages are computed over the games the player actually played.
+            let played = player_totals.games_played;
+            players.push(PlayerSimulationReport {
+                player_id: *player_id,
+                name: format!("{} {}", player.info.first_name, player.info.last_name),
+                games_played: played,
+                minutes: per_game(player_totals.seconds_played, played) / 60.0,
+                points: per_game(player_totals.points, played),
+                rebounds: per_game(
+                    player_totals.offensive_rebounds + player_totals.defensive_rebounds,
+                    played,
+                ),
+                assists: per_game(player_totals.assists, played),
+                steals: per_game(player_totals.steals, played),
+                blocks: per_game(player_totals.blocks, played),
+                turnovers: per_game(player_totals.turnovers, played),
+                fouls: per_game(player_totals.fouls, played),
+                plus_minus: if played > 0 {
+                    player_totals.plus_minus as f32 / played as f32
+                } else {
+                    0.0
+                },
+                field_goal_percentage: percentage(
+                    player_totals.made_2pt + player_totals.made_3pt,
+                    player_totals.attempted_2pt + player_totals.attempted_3pt,
+                ),
+                three_point_percentage: percentage(
+                    player_totals.made_3pt,
+                    player_totals.attempted_3pt,
+                ),
+                free_throw_percentage: percentage(
+                    player_totals.made_ft,
+                    player_totals.attempted_ft,
+                ),
+            });
+        }
+
+        TeamSimulationReport {
+            team_id: team_in_game.team_id,
+            name: team_in_game.name.clone(),
+            wins,
+            win_rate: percentage(wins, games),
+            points: per_game(team_totals.points, games),
+            points_allowed: per_game(points_allowed, games),
+            rebounds: per_game(
+                team_totals.offensive_rebounds + team_totals.defensive_rebounds,
+                games,
+            ),
+            offensive_rebounds: per_game(team_totals.offensive_rebounds, games),
+            assists: per_game(team_totals.assists, games),
+            steals: per_game(team_totals.steals, games),
+            blocks: per_game(team_totals.blocks, games),
+            turnovers: per_game(team_totals.turnovers, games),
+            fouls: per_game(team_totals.fouls, games),
+            attempted_2pt: per_game(team_totals.attempted_2pt, games),
+            made_2pt: per_game(team_totals.made_2pt, games),
+            attempted_3pt: per_game(team_totals.attempted_3pt, games),
+            made_3pt: per_game(team_totals.made_3pt, games),
+            attempted_ft: per_game(team_totals.attempted_ft, games),
+            made_ft: per_game(team_totals.made_ft, games),
+            players,
+        }
+    }
+}
+
+impl SimulationReport {
+    pub fn to_table(&self) -> String {
+        let mut lines = vec![
+            format!(
+                "Simulated {} games with seed {} ({} went to overtime)",
+                self.games, self.seed, self.overtime_games
+            ),
+            String::new(),
+        ];
+        lines.push(format!(
+            "{:<24} {:>5} {:>6} {:>6} {:>6} {:>11} {:>11} {:>11} {:>5} {:>5} {:>5} {:>5} {:>5} {:>5}",
+            "Team", "W", "Win%", "PTS", "OPP", "2PT", "3PT", "FT", "REB", "AST", "STL", "BLK", "TOV", "PF"
+        ));
+        for team in [&self.home_team, &self.away_team] {
+            lines.push(format!(
+                "{:<24} {:>5} {:>6.1} {:>6.1} {:>6.1} {:>11} {:>11} {:>11} {:>5.1} {:>5.1} {:>5.1} {:>5.1} {:>5.1} {:>5.1}",
+                team.name,
+                team.wins,
+                team.win_rate,
+                team.points,
+                team.points_allowed,
+                format!("{:.1}/{:.1}", team.made_2pt, team.attempted_2pt),
+                format!("{:.1}/{:.1}", team.made_3pt, team.attempted_3pt),
+                format!("{:.1}/{:.1}", team.made_ft, team.attempted_ft),
+                team.rebounds,
+                team.assists,
+                team.steals,
+                team.blocks,
+                team.turnovers,
+                team.fouls,
+            ));
+        }
+
+        for team in [&self.home_team, &self.away_team] {
+            lines.push(String::new());
+            lines.push(team.name.clone());
+            lines.push(format!(
+                "{:<24} {:>4} {:>5} {:>5} {:>5} {:>5} {:>5} {:>5} {:>5} {:>5} {:>6} {:>5} {:>5} {:>5}",
+                "Player", "G", "MIN", "PTS", "REB", "AST", "STL", "BLK", "TOV", "PF", "+/-", "FG%", "3P%", "FT%"
+            ));
+            for player in team.players.iter() {
+                lines.push(format!(
+                    "{:<24} {:>4} {:>5.1} {:>5.1} {:>5.1} {:>5.1} {:>5.1} {:>5.1} {:>5.1} {:>5.1} {:>+6.1} {:>5.1} {:>5.1} {:>5.1}",
+                    player.name,
+                    player.games_played,
+                    player.minutes,
+                    player.points,
+                    player.rebounds,
+                    player.assists,
+                    player.steals,
+                    player.blocks,
+                    player.turnovers,
+                    player.fouls,
+                    player.plus_minus,
+                    player.field_goal_percentage,
+                    player.three_point_percentage,
+                    player.free_throw_percentage,
+                ));
+            }
+        }
+
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Simulator;
+
+    #[test]
+    fn test_simulation_is_deterministic() {
+        let simulator = Simulator::from_seed(7).unwrap();
+        let report = simulator.run(2);
+        assert_eq!(report.games, 2);
+        assert_eq!(report.home_team.wins + report.away_team.wins, 2);
+        assert!(report.home_team.points > 0.0);
+
+        let other = Simulator::from_seed(7).unwrap().run(2);
+        assert_eq!(
+            serde_json::to_string(&report).unwrap(),
+            serde_json::to_string(&other).unwrap()
+        );
+    }
+}
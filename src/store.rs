@@ -1,12 +1,24 @@
 use crate::{types::AppResult, world::world::World};
 use directories;
 use include_dir::{include_dir, Dir};
+use libp2p::identity::Keypair;
 use serde::{Deserialize, Serialize};
-use std::{error::Error, fs::File, path::PathBuf};
+use std::{
+    error::Error,
+    fs::File,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
 
 pub static ASSETS_DIR: Dir = include_dir!("$CARGO_MANIFEST_DIR/assets/");
 pub static PERSISTED_WORLD_FILENAME: &str = "world.json";
 pub static PERSISTED_GAMES_PREFIX: &str = "game_";
+pub static PERSISTED_KEYPAIR_FILENAME: &str = "keypair.pb";
+pub static PERSISTED_RELAYER_KEYPAIR_FILENAME: &str = "relayer_keypair.pb";
+pub static PERSISTED_RELAYER_STATS_FILENAME: &str = "relayer_stats.json";
+pub static PERSISTED_CHAT_FILENAME: &str = "chat.json";
+pub static PERSISTED_GHOSTS_FILENAME: &str = "ghosts.json";
+pub static PERSISTED_GAME_RESULTS_FILENAME: &str = "game_results.json";
 
 fn store_path(filename: &str) -> Result<PathBuf, Box<dyn Error>> {
     let dirs = directories::ProjectDirs::from("org", "frittura", "rebels")
@@ -49,6 +61,68 @@ pub fn load_from_json<T: for<'a> Deserialize<'a>>(filename: &str) -> AppResult<T
     Ok(data)
 }
 
+// Private keys are readable by the owner only.
+fn write_private_file(path: &Path, data: &[u8]) -> AppResult<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // The mode only applies to new files.
+        if path.exists() {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    options.open(path)?.write_all(data)?;
+    Ok(())
+}
+
+// The keypair is stored in its protobuf encoding, like other libp2p tools do.
+pub fn save_keypair(filename: &str, keypair: &Keypair) -> AppResult<()> {
+    write_private_file(&store_path(filename)?, &keypair.to_protobuf_encoding()?)
+}
+
+pub fn load_keypair(filename: &str) -> AppResult<Keypair> {
+    let data = std::fs::read(store_path(filename)?)?;
+    Ok(Keypair::from_protobuf_encoding(&data)?)
+}
+
+// Returns None if no keypair was ever stored. Any other failure is an error,
+// as replacing the keypair would change our PeerId.
+fn load_existing_keypair(filename: &str) -> AppResult<Option<Keypair>> {
+    match std::fs::read(store_path(filename)?) {
+        Ok(data) => Ok(Some(Keypair::from_protobuf_encoding(&data)?)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Reuse the stored keypair, so that our PeerId survives restarts.
+pub fn load_or_generate_keypair(filename: &str) -> AppResult<Keypair> {
+    if let Some(keypair) = load_existing_keypair(filename)? {
+        return Ok(keypair);
+    }
+    let keypair = Keypair::generate_ed25519();
+    save_keypair(filename, &keypair)?;
+    Ok(keypair)
+}
+
+// Replaces the stored keypair with a new one. The old one is kept as a backup.
+pub fn rotate_keypair(filename: &str) -> AppResult<Keypair> {
+    if let Some(old_keypair) = load_existing_keypair(filename)? {
+        save_keypair(&format!("{}.back", filename), &old_keypair)?;
+    }
+    let keypair = Keypair::generate_ed25519();
+    save_keypair(filename, &keypair)?;
+    Ok(keypair)
+}
+
+pub fn export_keypair(filename: &str, path: &Path) -> AppResult<()> {
+    let keypair = load_keypair(filename)?;
+    write_private_file(path, &keypair.to_protobuf_encoding()?)
+}
+
 pub fn reset() -> AppResult<()> {
     let dirs = directories::ProjectDirs::from("org", "frittura", "rebels")
         .ok_or("Failed to get directories")?;
@@ -102,4 +176,43 @@ mod tests {
         let result = super::save_to_json("test", &world);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_keypair_is_persisted() {
+        let filename = "test_keypair.pb";
+        let keypair = super::load_or_generate_keypair(filename).unwrap();
+        let reloaded = super::load_or_generate_keypair(filename).unwrap();
+        assert_eq!(keypair.public(), reloaded.public());
+
+        let rotated = super::rotate_keypair(filename).unwrap();
+        assert_ne!(keypair.public(), rotated.public());
+        assert_eq!(
+            super::load_keypair(filename).unwrap().public(),
+            rotated.public()
+        );
+
+        // Do not leave test keys in the user store.
+        for filename in [filename, "test_keypair.pb.back"] {
+            std::fs::remove_file(super::store_path(filename).unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_unreadable_keypair_is_not_replaced() {
+        let filename = "test_corrupt_keypair.pb";
+        let path = super::store_path(filename).unwrap();
+        std::fs::write(&path, b"not a keypair").unwrap();
+        assert!(super::load_or_generate_keypair(filename).is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"not a keypair");
+        std::fs::remove_file(&path).unwrap();
+
+        super::load_or_generate_keypair(filename).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
 }
@@ -39,6 +39,7 @@ pub type TeamId = uuid::Uuid;
 pub type PlanetId = uuid::Uuid;
 pub type GameId = uuid::Uuid;
 pub type EntityId = uuid::Uuid;
+pub type ChallengeId = uuid::Uuid;
 
 pub type AppResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 pub type AppCallback = Box<dyn Fn(&mut App) -> AppResult<Option<String>>>;
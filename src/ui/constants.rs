@@ -20,6 +20,7 @@ impl UiKey {
     pub const TRAINING_FOCUS: KeyCode = KeyCode::Char('t');
     pub const AUTO_ASSIGN: KeyCode = KeyCode::Char('a');
     pub const SET_OFFENSE_TACTIC: KeyCode = KeyCode::Char('o');
+    pub const SET_DEFENSE_TACTIC: KeyCode = KeyCode::Char('f');
     pub const CYCLE_FILTER: KeyCode = KeyCode::Char('=');
     pub const HIRE_FIRE: KeyCode = KeyCode::Char('s');
     pub const LOCK_PLAYER: KeyCode = KeyCode::Char('l');
@@ -29,6 +30,10 @@ impl UiKey {
     pub const SET_PILOT: KeyCode = KeyCode::Char('e');
     pub const PITCH_VIEW: KeyCode = KeyCode::Char('v');
     pub const TRAVEL: KeyCode = KeyCode::Char('t');
+    pub const CALL_TIMEOUT: KeyCode = KeyCode::Char('x');
+    pub const NEXT_PLAYER_OUT: KeyCode = KeyCode::Char('n');
+    pub const NEXT_PLAYER_IN: KeyCode = KeyCode::Char('b');
+    pub const SUBSTITUTE: KeyCode = KeyCode::Char('s');
 }
 pub trait PrintableKeyCode {
     fn to_string(&self) -> String;
@@ -9,7 +9,9 @@ use super::{
     utils::img_to_lines,
     widgets::{default_block, selectable_list, DOWN_ARROW_SPAN, SWITCH_ARROW_SPAN, UP_ARROW_SPAN},
 };
-use crate::engine::constants::MAX_TIREDNESS;
+use crate::engine::command::GameCommandKind;
+use crate::engine::constants::{MAX_TIMEOUTS, MAX_TIREDNESS};
+use crate::engine::tactic::{DefenseTactic, OffenseTactic};
 use crate::types::AppResult;
 use crate::world::planet::PlanetType;
 use crate::{
@@ -21,7 +23,7 @@ use crate::{
     },
     image::pitch::{set_shot_pixels, PitchStyle, PITCH_WIDTH},
     image::player::{PLAYER_IMAGE_HEIGHT, PLAYER_IMAGE_WIDTH},
-    types::GameId,
+    types::{GameId, PlayerId, SortablePlayerMap},
     ui::constants::{PrintableKeyCode, UiKey},
     world::{
         player::Player,
@@ -54,6 +56,16 @@ enum PitchViewFilter {
     Fourth,
 }
 
+// Own team state in the selected game, used to issue coaching commands.
+#[derive(Debug, Default)]
+struct CoachingState {
+    offense_tactic: OffenseTactic,
+    defense_tactic: DefenseTactic,
+    timeouts_left: u8,
+    playing: Vec<(PlayerId, String)>,
+    bench: Vec<(PlayerId, String)>,
+}
+
 #[derive(Debug, Default)]
 pub struct GamePanel {
     pub index: usize,
@@ -64,6 +76,9 @@ pub struct GamePanel {
     debug_mode: bool,
     action_results: Vec<ActionOutput>,
     tick: usize,
+    coaching: Option<CoachingState>,
+    sub_out_index: usize,
+    sub_in_index: usize,
     callback_registry: Rc<RefCell<CallbackRegistry>>,
     gif_map: Rc<RefCell<GifMap>>,
 }
@@ -87,6 +102,62 @@ impl GamePanel {
         world.get_game(self.games[self.index].clone())
     }
 
+    fn coaching_state(game: &Game, world: &World) -> Option<CoachingState> {
+        if game.has_ended() {
+            return None;
+        }
+        let team = if game.home_team_in_game.team_id == world.own_team_id {
+            &game.home_team_in_game
+        } else if game.away_team_in_game.team_id == world.own_team_id {
+            &game.away_team_in_game
+        } else {
+            return None;
+        };
+
+        // Show tactics including changes that are still waiting to be applied.
+        let mut offense_tactic = team.offense_tactic;
+        let mut defense_tactic = team.defense_tactic;
+        for command in game
+            .commands
+            .iter()
+            .filter(|command| command.team_id == team.team_id && !command.applied)
+        {
+            match command.kind {
+                GameCommandKind::SetOffenseTactic { tactic } => offense_tactic = tactic,
+                GameCommandKind::SetDefenseTactic { tactic } => defense_tactic = tactic,
+                _ => {}
+            }
+        }
+
+        let (playing, bench): (Vec<&Player>, Vec<&Player>) = team
+            .players
+            .by_position(&team.stats)
+            .into_iter()
+            .partition(|player| team.stats[&player.id].is_playing());
+        let available = |player: &&Player| {
+            let stats = &team.stats[&player.id];
+            !stats.is_knocked_out() && !stats.is_fouled_out()
+        };
+        let name = |player: &Player| {
+            (
+                player.id,
+                format!(
+                    "{}.{}",
+                    player.info.first_name.chars().next().unwrap_or_default(),
+                    player.info.last_name
+                ),
+            )
+        };
+
+        Some(CoachingState {
+            offense_tactic,
+            defense_tactic,
+            timeouts_left: MAX_TIMEOUTS.saturating_sub(team.timeouts_used),
+            playing: playing.into_iter().map(name).collect(),
+            bench: bench.into_iter().filter(available).map(name).collect(),
+        })
+    }
+
     fn build_top_panel(&mut self, frame: &mut Frame, world: &World, area: Rect) -> AppResult<()> {
         // Split into left and right panels
         let split = Layout::default()
@@ -523,6 +594,8 @@ impl GamePanel {
         let mut made_2pt_total = 0;
         let mut attempted_3pt_total = 0;
         let mut made_3pt_total = 0;
+        let mut attempted_ft_total = 0;
+        let mut made_ft_total = 0;
         let mut assists_total = 0;
         let mut turnovers_total = 0;
         let mut defensive_rebounds_total = 0;
@@ -539,6 +612,8 @@ impl GamePanel {
             made_2pt_total += player_data.made_2pt as u16;
             attempted_3pt_total += player_data.attempted_3pt as u16;
             made_3pt_total += player_data.made_3pt as u16;
+            attempted_ft_total += player_data.attempted_ft as u16;
+            made_ft_total += player_data.made_ft as u16;
             assists_total += player_data.assists as u16;
             turnovers_total += player_data.turnovers as u16;
             defensive_rebounds_total += player_data.defensive_rebounds as u16;
@@ -557,6 +632,7 @@ impl GamePanel {
                 Span::raw(format!("Tds: {}", player_data.tiredness))
             } else {
                 let style = match player_data.tiredness {
+                    _ if player_data.is_fouled_out() => Style::default().fg(Color::DarkGray),
                     x if x < MAX_TIREDNESS / 4.0 => Style::default().fg(Color::White),
                     x if x < MAX_TIREDNESS / 2.0 => Style::default().fg(Color::Yellow),
                     x if x < MAX_TIREDNESS => Style::default().fg(Color::Red),
@@ -588,6 +664,10 @@ impl GamePanel {
                     "{:>2}/{:<2}",
                     players_data[&player.id].made_3pt, players_data[&player.id].attempted_3pt
                 )),
+                Cell::from(format!(
+                    "{:>2}/{:<2}",
+                    players_data[&player.id].made_ft, players_data[&player.id].attempted_ft
+                )),
                 Cell::from(format!(
                     "{:>3}/{:<2}",
                     players_data[&player.id].assists, players_data[&player.id].turnovers
@@ -612,6 +692,7 @@ impl GamePanel {
             Cell::from(format!("{:^3}", points_total)),
             Cell::from(format!("{:>2}/{:<2}", made_2pt_total, attempted_2pt_total)),
             Cell::from(format!("{:>2}/{:<2}", made_3pt_total, attempted_3pt_total)),
+            Cell::from(format!("{:>2}/{:<2}", made_ft_total, attempted_ft_total)),
             Cell::from(format!("{:>3}/{:<2}", assists_total, turnovers_total)),
             Cell::from(format!(
                 "{:>3}/{:<3}",
@@ -634,6 +715,7 @@ impl GamePanel {
                 Constraint::Length(4),
                 Constraint::Length(6),
                 Constraint::Length(5),
+                Constraint::Length(5),
                 Constraint::Length(6),
                 Constraint::Length(7),
                 Constraint::Length(3),
@@ -652,6 +734,7 @@ impl GamePanel {
             "Pts",
             " 2pt ",
             " 3pt ",
+            " FT  ",
             "Ast/TO",
             "DRb/ORb",
             "Stl",
@@ -667,6 +750,7 @@ impl GamePanel {
             "Pts",
             " 2pt ",
             " 3pt ",
+            " FT  ",
             "Ast/TO",
             "DRb/ORb",
             "Stl",
@@ -695,6 +779,7 @@ impl GamePanel {
             Constraint::Length(3), //points
             Constraint::Length(6), //2pt
             Constraint::Length(5), //3pt
+            Constraint::Length(5), //free throws
             Constraint::Length(6), //assists/turnovers
             Constraint::Length(7), //defensive rebounds/offensive rebounds
             Constraint::Length(3), //steals
@@ -748,10 +833,8 @@ impl GamePanel {
                 starting_in_seconds / 60,
                 starting_in_seconds % 60
             )));
-        } else if timer.has_ended() {
-            timer_lines.push(Line::from(
-                Timer::from(timer.period().next().start()).format(),
-            ));
+        } else if game.has_ended() && self.commentary_index == 0 {
+            timer_lines.push(Line::from(format!("{} 00:00", timer.period().previous())));
         } else if timer.is_break() {
             timer_lines.push(Line::from(
                 Timer::from(timer.period().next().start()).format(),
@@ -808,7 +891,9 @@ impl Screen for GamePanel {
             if self.commentary_index == 0 {
                 self.action_results = game.action_results.clone();
             }
+            self.coaching = Self::coaching_state(game, world);
         } else {
+            self.coaching = None;
             self.set_index(0);
         }
         Ok(())
@@ -885,12 +970,53 @@ impl Screen for GamePanel {
             }
             _ => {}
         };
+
+        if let Some(coaching) = self.coaching.as_ref() {
+            match key_event.code {
+                UiKey::SET_OFFENSE_TACTIC => {
+                    return Some(UiCallbackPreset::SendGameCommand {
+                        kind: GameCommandKind::SetOffenseTactic {
+                            tactic: coaching.offense_tactic.next(),
+                        },
+                    });
+                }
+                UiKey::SET_DEFENSE_TACTIC => {
+                    return Some(UiCallbackPreset::SendGameCommand {
+                        kind: GameCommandKind::SetDefenseTactic {
+                            tactic: coaching.defense_tactic.next(),
+                        },
+                    });
+                }
+                UiKey::CALL_TIMEOUT => {
+                    return Some(UiCallbackPreset::SendGameCommand {
+                        kind: GameCommandKind::Timeout,
+                    });
+                }
+                UiKey::NEXT_PLAYER_OUT => {
+                    self.sub_out_index = (self.sub_out_index + 1) % coaching.playing.len().max(1);
+                }
+                UiKey::NEXT_PLAYER_IN => {
+                    self.sub_in_index = (self.sub_in_index + 1) % coaching.bench.len().max(1);
+                }
+                UiKey::SUBSTITUTE => {
+                    let player_out = coaching.playing.get(self.sub_out_index)?.0;
+                    let player_in = coaching.bench.get(self.sub_in_index)?.0;
+                    return Some(UiCallbackPreset::SendGameCommand {
+                        kind: GameCommandKind::Substitution {
+                            player_in,
+                            player_out,
+                        },
+                    });
+                }
+                _ => {}
+            }
+        }
         None
     }
 
     fn footer_spans(&self) -> Vec<Span> {
         let next_view = if self.pitch_view { "Score" } else { "Pitch" };
-        vec![
+        let mut spans = vec![
             Span::styled(
                 " Enter ",
                 Style::default().bg(Color::Gray).fg(Color::DarkGray),
@@ -915,7 +1041,60 @@ impl Screen for GamePanel {
                 format!(" Filter: {:<6} ", self.pitch_view_filter),
                 Style::default().fg(Color::DarkGray),
             ),
-        ]
+        ];
+
+        if let Some(coaching) = self.coaching.as_ref() {
+            let player_name = |players: &Vec<(PlayerId, String)>, index: usize| {
+                players
+                    .get(index)
+                    .map(|(_, name)| name.clone())
+                    .unwrap_or("-".to_string())
+            };
+            spans.extend(vec![
+                Span::styled(
+                    format!(" {} ", UiKey::SET_OFFENSE_TACTIC.to_string()),
+                    Style::default().bg(Color::Gray).fg(Color::DarkGray),
+                ),
+                Span::styled(
+                    format!(" {} ", coaching.offense_tactic),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::styled(
+                    format!(" {} ", UiKey::SET_DEFENSE_TACTIC.to_string()),
+                    Style::default().bg(Color::Gray).fg(Color::DarkGray),
+                ),
+                Span::styled(
+                    format!(" {} ", coaching.defense_tactic),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::styled(
+                    format!(" {} ", UiKey::CALL_TIMEOUT.to_string()),
+                    Style::default().bg(Color::Gray).fg(Color::DarkGray),
+                ),
+                Span::styled(
+                    format!(" Timeout ({} left) ", coaching.timeouts_left),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::styled(
+                    format!(
+                        " {}/{}/{} ",
+                        UiKey::NEXT_PLAYER_OUT.to_string(),
+                        UiKey::NEXT_PLAYER_IN.to_string(),
+                        UiKey::SUBSTITUTE.to_string()
+                    ),
+                    Style::default().bg(Color::Gray).fg(Color::DarkGray),
+                ),
+                Span::styled(
+                    format!(
+                        " Sub {} for {} ",
+                        player_name(&coaching.bench, self.sub_in_index),
+                        player_name(&coaching.playing, self.sub_out_index)
+                    ),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]);
+        }
+        spans
     }
 }
 
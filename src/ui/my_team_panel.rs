@@ -9,7 +9,7 @@ use super::{
 };
 use crate::{
     image::spaceship::SPACESHIP_IMAGE_HEIGHT,
-    types::{SystemTimeTick, TeamId},
+    types::{SystemTimeTick, TeamId, HOURS},
     world::{
         constants::{BASE_BONUS, BONUS_PER_SKILL},
         role::CrewRole,
@@ -66,9 +66,17 @@ impl MyTeamPanel {
     ) -> AppResult<()> {
         let team = world.get_own_team().unwrap();
 
-        let header_cells = [" Name", "Training", "Current", "Best", "Role", "Crew bonus"]
-            .iter()
-            .map(|h| ClickableCell::from(*h).style(UiStyle::HEADER));
+        let header_cells = [
+            " Name",
+            "Training",
+            "Current",
+            "Best",
+            "Role",
+            "Crew bonus",
+            "Status",
+        ]
+        .iter()
+        .map(|h| ClickableCell::from(*h).style(UiStyle::HEADER));
         let header = ClickableRow::new(header_cells);
         let rows = self
             .players
@@ -119,6 +127,18 @@ impl MyTeamPanel {
                     _ => Span::raw(""),
                 };
 
+                let status = match player.injury {
+                    Some(injury) => {
+                        let time_left =
+                            injury.time_to_recovery(world.tiredness_recovery_bonus(team)?);
+                        Span::styled(
+                            format!("{} {}h", injury, (time_left / HOURS).max(1)),
+                            UiStyle::ERROR,
+                        )
+                    }
+                    None => Span::styled("Healthy", UiStyle::OK),
+                };
+
                 let cells = [
                     ClickableCell::from(format!(
                         " {} {}",
@@ -133,6 +153,7 @@ impl MyTeamPanel {
                     )),
                     ClickableCell::from(player.info.crew_role.to_string()),
                     ClickableCell::from(bonus_string),
+                    ClickableCell::from(status),
                 ];
                 Ok(ClickableRow::new(cells))
             })
@@ -148,6 +169,7 @@ impl MyTeamPanel {
                 Constraint::Length(12),
                 Constraint::Length(12),
                 Constraint::Length(18),
+                Constraint::Length(30),
             ]);
 
         frame.render_stateful_widget(
@@ -226,7 +248,8 @@ impl Screen for MyTeamPanel {
                 Constraint::Length(6),
                 Constraint::Length(3),  //margin
                 Constraint::Length(32), //auto-assign
-                Constraint::Length(32), //tactic
+                Constraint::Length(32), //offense tactic
+                Constraint::Length(32), //defense tactic
                 Constraint::Min(0),
             ])
             .split(table_bottom[1].inner(&Margin {
@@ -275,6 +298,19 @@ impl Screen for MyTeamPanel {
         );
         frame.render_widget(offense_tactic_button, position_button_splits[7]);
 
+        let defense_tactic_button = Button::new(
+            format!(
+                "{}: {}",
+                UiKey::SET_DEFENSE_TACTIC.to_string(),
+                team.game_defense_tactic
+            ),
+            UiCallbackPreset::SetTeamDefenseTactic {
+                tactic: team.game_defense_tactic.next(),
+            },
+            Rc::clone(&self.callback_registry),
+        );
+        frame.render_widget(defense_tactic_button, position_button_splits[8]);
+
         let button_splits = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([
@@ -451,9 +487,18 @@ impl Screen for MyTeamPanel {
         }
 
         for game in world.past_games.values() {
+            let overtimes = match game.overtimes {
+                0 => "".to_string(),
+                1 => " (OT)".to_string(),
+                n => format!(" ({}OT)", n),
+            };
             lines.push(Line::from(format!(
-                " {:>12} {:>3}-{:<3} {:<}",
-                game.home_team_name, game.home_score, game.away_score, game.away_team_name,
+                " {:>12} {:>3}-{:<3} {:<}{}",
+                game.home_team_name,
+                game.home_score,
+                game.away_score,
+                game.away_team_name,
+                overtimes
             )));
         }
         frame.render_widget(
@@ -493,6 +538,10 @@ impl Screen for MyTeamPanel {
                 return Some(UiCallbackPreset::SetNextTeamOffenseTactic);
             }
 
+            UiKey::SET_DEFENSE_TACTIC => {
+                return Some(UiCallbackPreset::SetNextTeamDefenseTactic);
+            }
+
             UiKey::HIRE_FIRE => {
                 return Some(UiCallbackPreset::ReleasePlayer { player_id });
             }
@@ -7,8 +7,10 @@ use super::{
     utils::input_from_key_event,
     widgets::default_block,
 };
-use crate::network::types::Challenge;
-use crate::types::{AppResult, SystemTimeTick, TeamId, Tick};
+use crate::network::chat::{ChatAuthor, ChatHistory, ChatMessage, Conversation};
+use crate::network::types::{Challenge, PeerTransport};
+use crate::network::wire::WIRE_VERSION;
+use crate::types::{AppResult, ChallengeId, SystemTimeTick, TeamId, Tick};
 use crate::ui::constants::{PrintableKeyCode, UiKey};
 use crate::world::world::World;
 use core::fmt::Debug;
@@ -26,7 +28,7 @@ use ratatui::{
     Frame,
 };
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 use strum_macros::Display;
 use tui_textarea::{CursorMove, TextArea};
@@ -47,7 +49,18 @@ pub struct SwarmPanel {
     textarea: TextArea<'static>,
     connected_peers: Vec<PeerId>,
     peer_to_team_id: HashMap<PeerId, TeamId>,
-    peer_to_challenge: HashMap<PeerId, Challenge>,
+    // Wire version of the last message received from each peer.
+    peer_versions: HashMap<PeerId, u16>,
+    // Peers found through mDNS.
+    lan_peers: HashSet<PeerId>,
+    peer_transports: HashMap<PeerId, PeerTransport>,
+    // Names of the peer teams, to resolve /commands targets.
+    team_names: HashMap<TeamId, String>,
+    // Snapshots of peer teams that can be challenged while offline.
+    ghost_teams: HashMap<TeamId, String>,
+    chat: ChatHistory,
+    conversation: Conversation,
+    challenges: HashMap<ChallengeId, Challenge>,
     callback_registry: Rc<RefCell<CallbackRegistry>>,
 }
 
@@ -56,7 +69,6 @@ impl SwarmPanel {
         let mut events = HashMap::new();
         events.insert(EventTopic::Log, vec![]);
         events.insert(EventTopic::Challenges, vec![]);
-        events.insert(EventTopic::Chat, vec![]);
         Self {
             callback_registry,
             events,
@@ -68,8 +80,138 @@ impl SwarmPanel {
         self.events.get_mut(&EventTopic::Log).unwrap().push(event);
     }
 
+    // Messages on the global chat, without a peer they are our own.
     pub fn push_chat_event(&mut self, event: SwarmPanelEvent) {
-        self.events.get_mut(&EventTopic::Chat).unwrap().push(event);
+        let author = event.peer_id.map_or(ChatAuthor::Own, ChatAuthor::Peer);
+        self.chat.push(
+            Conversation::Global,
+            ChatMessage {
+                timestamp: event.timestamp,
+                author,
+                text: event.text,
+            },
+        );
+    }
+
+    pub fn push_direct_message(&mut self, peer_id: PeerId, author: ChatAuthor, text: String) {
+        self.chat.push(
+            Conversation::Peer(peer_id),
+            ChatMessage {
+                timestamp: Tick::now(),
+                author,
+                text,
+            },
+        );
+    }
+
+    fn push_system_message(&mut self, text: String) {
+        self.chat.push(
+            self.conversation,
+            ChatMessage {
+                timestamp: Tick::now(),
+                author: ChatAuthor::System,
+                text,
+            },
+        );
+    }
+
+    pub fn chat(&self) -> &ChatHistory {
+        &self.chat
+    }
+
+    pub fn set_chat_history(&mut self, chat: ChatHistory) {
+        self.chat = chat;
+    }
+
+    pub fn save_chat_history(&mut self) -> AppResult<()> {
+        self.chat.save()
+    }
+
+    pub fn unread_count(&self) -> usize {
+        self.chat.total_unread()
+    }
+
+    pub fn peer_version(&self, peer_id: &PeerId) -> Option<u16> {
+        self.peer_versions.get(peer_id).copied()
+    }
+
+    fn short_id(peer_id: &PeerId) -> String {
+        peer_id.to_base58().chars().take(6).collect()
+    }
+
+    fn peer_name(&self, peer_id: &PeerId) -> String {
+        self.peer_to_team_id
+            .get(peer_id)
+            .and_then(|team_id| self.team_names.get(team_id))
+            .cloned()
+            .unwrap_or_else(|| Self::short_id(peer_id))
+    }
+
+    // Targets are team names, which can contain spaces, or peer id prefixes.
+    // Returns the peer and how many words of the input were used.
+    fn resolve_target(&self, words: &[&str]) -> Option<(PeerId, usize)> {
+        for len in (1..=words.len()).rev() {
+            let target = words[..len].join(" ").to_lowercase();
+            if let Some((peer_id, _)) = self.peer_to_team_id.iter().find(|(_, team_id)| {
+                self.team_names
+                    .get(team_id)
+                    .is_some_and(|name| name.to_lowercase() == target)
+            }) {
+                return Some((*peer_id, len));
+            }
+        }
+
+        let prefix = words.first()?;
+        let mut matches = self
+            .peer_to_team_id
+            .keys()
+            .chain(self.lan_peers.iter())
+            .chain(self.peer_versions.keys())
+            .filter(|peer_id| peer_id.to_base58().starts_with(prefix))
+            .collect::<HashSet<_>>()
+            .into_iter();
+        match (matches.next(), matches.next()) {
+            (Some(peer_id), None) => Some((*peer_id, 1)),
+            _ => None,
+        }
+    }
+
+    // Chat commands answer in the chat, so show it.
+    fn resolve_or_report(&mut self, words: &[&str]) -> Option<(PeerId, usize)> {
+        self.set_current_topic(EventTopic::Chat);
+        let target = self.resolve_target(words);
+        if target.is_none() {
+            self.push_system_message(format!("Unknown team or peer: {}", words.join(" ")));
+        }
+        target
+    }
+
+    fn whois(&self, peer_id: &PeerId) -> String {
+        let mut info = vec![format!("Peer {}", peer_id)];
+        if let Some(team_id) = self.peer_to_team_id.get(peer_id) {
+            info.push(format!("Team {} ({})", self.peer_name(peer_id), team_id));
+        }
+        info.push(
+            if self.connected_peers.contains(peer_id) {
+                "Online"
+            } else {
+                "Offline"
+            }
+            .to_string(),
+        );
+        if let Some(version) = self.peer_versions.get(peer_id) {
+            info.push(format!("Wire version {}", version));
+        }
+        if let Some(transport) = self.peer_transports.get(peer_id) {
+            info.push(format!("Connected over {}", transport));
+        }
+        if self.lan_peers.contains(peer_id) {
+            info.push("On the LAN".to_string());
+        }
+        if self.chat.is_muted(peer_id) {
+            info.push("Muted".to_string());
+        }
+        info.join(", ")
     }
 
     pub fn add_peer_id(&mut self, peer_id: PeerId, team_id: TeamId) {
@@ -93,19 +235,54 @@ impl SwarmPanel {
     pub fn remove_peer_id(&mut self, peer_id: &PeerId) {
         // self.peer_to_team_id.remove(peer_id);
         self.connected_peers.retain(|id| id != peer_id);
-        self.remove_challenge(peer_id);
+        self.challenges
+            .retain(|_, challenge| challenge.home_peer_id != *peer_id);
+    }
+
+    // Returns true if the version changed.
+    pub fn set_peer_version(&mut self, peer_id: PeerId, version: u16) -> bool {
+        self.peer_versions.insert(peer_id, version) != Some(version)
+    }
+
+    // Returns true if the transport changed.
+    pub fn set_peer_transport(&mut self, peer_id: PeerId, transport: PeerTransport) -> bool {
+        self.peer_transports.insert(peer_id, transport) != Some(transport)
+    }
+
+    pub fn remove_peer_transport(&mut self, peer_id: &PeerId) {
+        self.peer_transports.remove(peer_id);
+    }
+
+    pub fn peer_transport(&self, peer_id: &PeerId) -> Option<PeerTransport> {
+        self.peer_transports.get(peer_id).copied()
+    }
+
+    pub fn set_ghost_teams(&mut self, ghost_teams: HashMap<TeamId, String>) {
+        self.ghost_teams = ghost_teams;
     }
 
-    pub fn add_challenge(&mut self, peer_id: PeerId, challenge: Challenge) {
-        self.peer_to_challenge.insert(peer_id, challenge);
+    pub fn add_lan_peer(&mut self, peer_id: PeerId) {
+        self.lan_peers.insert(peer_id);
     }
 
-    pub fn remove_challenge(&mut self, peer_id: &PeerId) {
-        self.peer_to_challenge.remove(peer_id);
+    pub fn remove_lan_peer(&mut self, peer_id: &PeerId) {
+        self.lan_peers.remove(peer_id);
+    }
+
+    pub fn is_lan_peer(&self, peer_id: &PeerId) -> bool {
+        self.lan_peers.contains(peer_id)
+    }
+
+    pub fn add_challenge(&mut self, challenge: Challenge) {
+        self.challenges.insert(challenge.id, challenge);
+    }
+
+    pub fn remove_challenge(&mut self, challenge_id: &ChallengeId) {
+        self.challenges.remove(challenge_id);
     }
 
     pub fn remove_all_challenges(&mut self) {
-        self.peer_to_challenge.clear();
+        self.challenges.clear();
     }
 
     fn build_left_panel(&mut self, frame: &mut Frame, world: &World, area: Rect) {
@@ -120,8 +297,14 @@ impl SwarmPanel {
             ])
             .split(area);
 
+        let unread = self.chat.total_unread();
+        let chat_label = if unread > 0 {
+            format!("Chat ({})", unread)
+        } else {
+            "Chat".to_string()
+        };
         let mut chat_button = Button::new(
-            "Chat".to_string(),
+            chat_label,
             UiCallbackPreset::SetSwarmPanelTopic {
                 topic: EventTopic::Chat,
             },
@@ -162,16 +345,66 @@ impl SwarmPanel {
         let mut items: Vec<ListItem> = vec![];
 
         for (peer_id, team_id) in self.peer_to_team_id.iter() {
-            let team = world.get_team_or_err(*team_id);
-            if team.is_ok() {
+            if let Ok(team) = world.get_team_or_err(*team_id) {
                 let style = if self.connected_peers.contains(peer_id) {
                     UiStyle::NETWORK
                 } else {
                     UiStyle::DISCONNECTED
                 };
+                let mut spans = vec![Span::styled(team.name.clone(), style)];
+                if self.lan_peers.contains(peer_id) {
+                    spans.push(Span::styled(" LAN", UiStyle::OK));
+                }
+                match self.peer_transports.get(peer_id) {
+                    Some(PeerTransport::Relayed) => {
+                        spans.push(Span::styled(" relay", UiStyle::WARNING))
+                    }
+                    Some(transport) => spans.push(Span::styled(
+                        format!(" {}", transport),
+                        UiStyle::DISCONNECTED,
+                    )),
+                    None => {}
+                }
+                let unread = self.chat.unread(Conversation::Peer(*peer_id));
+                if unread > 0 {
+                    spans.push(Span::styled(format!(" ({})", unread), UiStyle::WARNING));
+                }
+                if let Some(version) = self.peer_versions.get(peer_id) {
+                    if *version != WIRE_VERSION {
+                        spans.push(Span::styled(format!(" v{}", version), UiStyle::HIGHLIGHT));
+                    }
+                }
+                items.push(ListItem::new(Line::from(spans)));
+            }
+        }
+
+        // LAN peers show up as soon as they are discovered, before we get their team.
+        for peer_id in self.lan_peers.iter() {
+            let is_incompatible = self
+                .peer_versions
+                .get(peer_id)
+                .is_some_and(|version| *version != WIRE_VERSION);
+            if !self.peer_to_team_id.contains_key(peer_id) && !is_incompatible {
                 items.push(ListItem::new(Span::styled(
-                    team.unwrap().name.clone(),
-                    style,
+                    format!(
+                        "{} LAN",
+                        peer_id.to_base58().chars().take(6).collect::<String>()
+                    ),
+                    UiStyle::DISCONNECTED,
+                )));
+            }
+        }
+
+        // Peers we cannot talk to never get a team, show them by id.
+        for (peer_id, version) in self.peer_versions.iter() {
+            if *version != WIRE_VERSION && !self.peer_to_team_id.contains_key(peer_id) {
+                items.push(ListItem::new(Span::styled(
+                    format!(
+                        "{} v{}",
+                        peer_id.to_base58().chars().take(6).collect::<String>(),
+                        version
+                    ),
+                    UiStyle::ERROR,
                 )));
             }
         }
@@ -189,7 +422,7 @@ impl SwarmPanel {
     }
 
     fn build_challenge_list(&mut self, frame: &mut Frame, area: Rect) {
-        let mut constraints = [Constraint::Length(3)].repeat(self.peer_to_challenge.len());
+        let mut constraints = [Constraint::Length(3)].repeat(self.challenges.len());
         constraints.push(Constraint::Min(0));
         let split = Layout::default()
             .direction(Direction::Vertical)
@@ -198,7 +431,8 @@ impl SwarmPanel {
 
         // let mut items = vec![];
 
-        for (idx, (peer_id, challenge)) in self.peer_to_challenge.iter().enumerate() {
+        for (idx, challenge) in self.challenges.values().enumerate() {
+            let peer_id = challenge.home_peer_id;
             let line_split = Layout::default()
                 .direction(Direction::Horizontal)
                 .constraints([
@@ -242,6 +476,16 @@ impl SwarmPanel {
                 )
                 .set_box_style(UiStyle::ERROR);
                 frame.render_widget(decline_button, line_split[2]);
+                frame.render_widget(
+                    Paragraph::new(format!(
+                        "Expires in {}",
+                        challenge.expires_at.saturating_sub(Tick::now()).formatted()
+                    )),
+                    line_split[3].inner(&Margin {
+                        horizontal: 1,
+                        vertical: 1,
+                    }),
+                );
             }
         }
 
@@ -269,14 +513,18 @@ impl SwarmPanel {
             );
             return;
         }
+        if self.current_topic == EventTopic::Chat {
+            self.build_chat(frame, world, split[0]);
+            return;
+        }
+
         let mut items = vec![];
         for event in self.events.get(&self.current_topic).unwrap().iter().rev() {
             match event.peer_id {
                 Some(peer_id) => {
                     let from = if let Some(team_id) = self.peer_to_team_id.get(&peer_id) {
-                        let team = world.get_team_or_err(*team_id);
-                        if team.is_ok() {
-                            team.unwrap().name.clone()
+                        if let Ok(team) = world.get_team_or_err(*team_id) {
+                            team.name.clone()
                         } else {
                             "Unknown".to_string()
                         }
@@ -294,17 +542,12 @@ impl SwarmPanel {
                     ]));
                 }
                 None => {
-                    let own_message = if self.current_topic == EventTopic::Log {
-                        "System"
-                    } else {
-                        "You"
-                    };
                     items.push(Line::from(vec![
                         Span::styled(
                             format!("[{}] ", event.timestamp.formatted_as_time()),
                             UiStyle::HIGHLIGHT,
                         ),
-                        Span::styled(format!("{own_message}: "), UiStyle::OWN_TEAM),
+                        Span::styled("System: ", UiStyle::OWN_TEAM),
                         Span::raw(event.text.clone()),
                     ]));
                 }
@@ -319,6 +562,47 @@ impl SwarmPanel {
         );
     }
 
+    fn build_chat(&mut self, frame: &mut Frame, world: &World, area: Rect) {
+        // Rendering the conversation is reading it.
+        self.chat.mark_read(self.conversation);
+
+        let mut items = vec![];
+        for message in self.chat.messages(self.conversation).iter().rev() {
+            let (from, style) = match message.author {
+                ChatAuthor::Own => ("You".to_string(), UiStyle::OWN_TEAM),
+                ChatAuthor::System => ("System".to_string(), UiStyle::WARNING),
+                ChatAuthor::Peer(peer_id) => {
+                    let from = self
+                        .peer_to_team_id
+                        .get(&peer_id)
+                        .and_then(|team_id| world.get_team(*team_id))
+                        .map(|team| team.name.clone())
+                        .unwrap_or_else(|| Self::short_id(&peer_id));
+                    (from, UiStyle::NETWORK)
+                }
+            };
+            items.push(Line::from(vec![
+                Span::styled(
+                    format!("[{}] ", message.timestamp.formatted_as_time()),
+                    UiStyle::HIGHLIGHT,
+                ),
+                Span::styled(format!("{}: ", from), style),
+                Span::raw(message.text.clone()),
+            ]));
+        }
+
+        let title = match self.conversation {
+            Conversation::Global => "Chat".to_string(),
+            Conversation::Peer(peer_id) => format!("Chat with {}", self.peer_name(&peer_id)),
+        };
+        frame.render_widget(
+            Paragraph::new(items)
+                .wrap(Wrap { trim: true })
+                .block(default_block().title(title)),
+            area,
+        );
+    }
+
     pub fn set_current_topic(&mut self, topic: EventTopic) {
         self.current_topic = topic;
     }
@@ -329,7 +613,13 @@ impl Screen for SwarmPanel {
         "Swarm"
     }
 
-    fn update(&mut self, _world: &World) -> AppResult<()> {
+    fn update(&mut self, world: &World) -> AppResult<()> {
+        self.team_names = self
+            .peer_to_team_id
+            .values()
+            .filter_map(|team_id| world.get_team(*team_id))
+            .map(|team| (team.id, team.name.clone()))
+            .collect();
         Ok(())
     }
 
@@ -370,12 +660,10 @@ impl Screen for SwarmPanel {
 
                 match command {
                     "/dial" => {
-                        let next = split_input.skip(1).next();
-                        let address = if next.is_some() {
-                            next.unwrap().to_string()
-                        } else {
-                            "seed".to_string()
-                        };
+                        let address = split_input
+                            .clone()
+                            .nth(1)
+                            .map_or("seed".to_string(), |next| next.to_string());
 
                         return Some(UiCallbackPreset::Dial { address });
                     }
@@ -383,8 +671,99 @@ impl Screen for SwarmPanel {
                         return Some(UiCallbackPreset::Sync);
                     }
                     "/clear" => {
-                        self.events.clear();
+                        if self.current_topic == EventTopic::Chat {
+                            self.chat.clear(self.conversation);
+                        } else if let Some(events) = self.events.get_mut(&self.current_topic) {
+                            events.clear();
+                        }
+                    }
+                    "/all" => {
+                        self.conversation = Conversation::Global;
+                        self.set_current_topic(EventTopic::Chat);
+                    }
+                    "/msg" => {
+                        let words: Vec<&str> = split_input.skip(1).collect();
+                        let (peer_id, used) = self.resolve_or_report(&words)?;
+                        self.conversation = Conversation::Peer(peer_id);
+                        let message = words[used..].join(" ");
+                        if !message.is_empty() {
+                            self.push_direct_message(peer_id, ChatAuthor::Own, message.clone());
+                            return Some(UiCallbackPreset::SendDirectMessage { peer_id, message });
+                        }
+                    }
+                    "/challenge" => {
+                        let words: Vec<&str> = split_input.skip(1).collect();
+                        let (peer_id, _) = self.resolve_or_report(&words)?;
+                        match self.peer_to_team_id.get(&peer_id) {
+                            Some(team_id) => {
+                                return Some(UiCallbackPreset::ChallengeTeam { team_id: *team_id })
+                            }
+                            None => self.push_system_message(format!(
+                                "{} has no team",
+                                Self::short_id(&peer_id)
+                            )),
+                        }
+                    }
+                    "/ghost" => {
+                        let name = split_input.skip(1).collect::<Vec<&str>>().join(" ");
+                        if name.is_empty() {
+                            let mut names: Vec<&String> = self.ghost_teams.values().collect();
+                            names.sort();
+                            let text = if names.is_empty() {
+                                "No ghost teams".to_string()
+                            } else {
+                                format!(
+                                    "Ghost teams: {}",
+                                    names
+                                        .iter()
+                                        .map(|name| name.as_str())
+                                        .collect::<Vec<&str>>()
+                                        .join(", ")
+                                )
+                            };
+                            self.push_system_message(text);
+                        } else {
+                            match self
+                                .ghost_teams
+                                .iter()
+                                .find(|(_, ghost)| ghost.eq_ignore_ascii_case(&name))
+                            {
+                                Some((team_id, _)) => {
+                                    return Some(UiCallbackPreset::ChallengeGhost {
+                                        team_id: *team_id,
+                                    })
+                                }
+                                None => self.push_system_message(format!("No ghost team {}", name)),
+                            }
+                        }
+                    }
+                    "/whois" => {
+                        let words: Vec<&str> = split_input.skip(1).collect();
+                        let (peer_id, _) = self.resolve_or_report(&words)?;
+                        self.push_system_message(self.whois(&peer_id));
+                    }
+                    "/mute" => {
+                        let words: Vec<&str> = split_input.skip(1).collect();
+                        let (peer_id, _) = self.resolve_or_report(&words)?;
+                        let text = if self.chat.toggle_mute(peer_id) {
+                            format!("Muted {}", self.peer_name(&peer_id))
+                        } else {
+                            format!("Unmuted {}", self.peer_name(&peer_id))
+                        };
+                        self.push_system_message(text);
                     }
+                    "/identity" => match split_input.clone().nth(1) {
+                        Some("rotate") => return Some(UiCallbackPreset::RotateIdentity),
+                        Some("export") => {
+                            let path = split_input
+                                .clone()
+                                .nth(2)
+                                .unwrap_or("rebels_identity.pb")
+                                .to_string();
+                            return Some(UiCallbackPreset::ExportIdentity { path });
+                        }
+                        _ => return Some(UiCallbackPreset::ShowIdentity),
+                    },
 
                     "/help" => {
                         // self.push_log_event(SwarmPanelEvent {
@@ -405,20 +784,29 @@ impl Screen for SwarmPanel {
                         self.push_log_event(SwarmPanelEvent {
                             timestamp: Tick::now(),
                             peer_id: None,
-                            text: "/Commands:\n/dial <Option<ip_address>>\n/sync\n/clear"
+                            text: "/Commands:\n/dial <Option<ip_address|multiaddr>>\n/sync\n/clear\n/identity <Option<rotate|export <path>>>\n/msg <team|peer> <Option<message>>\n/all\n/challenge <team|peer>\n/ghost <Option<team>>\n/whois <team|peer>\n/mute <team|peer>"
                                 .to_string(),
                         });
                     }
-                    _ => {
-                        self.push_chat_event(SwarmPanelEvent {
-                            timestamp: Tick::now(),
-                            peer_id: None,
-                            text: lines[0].clone(),
-                        });
-                        return Some(UiCallbackPreset::SendMessage {
-                            message: lines[0].clone(),
-                        });
-                    }
+                    _ => match self.conversation {
+                        Conversation::Global => {
+                            self.push_chat_event(SwarmPanelEvent {
+                                timestamp: Tick::now(),
+                                peer_id: None,
+                                text: lines[0].clone(),
+                            });
+                            return Some(UiCallbackPreset::SendMessage {
+                                message: lines[0].clone(),
+                            });
+                        }
+                        Conversation::Peer(peer_id) => {
+                            self.push_direct_message(peer_id, ChatAuthor::Own, lines[0].clone());
+                            return Some(UiCallbackPreset::SendDirectMessage {
+                                peer_id,
+                                message: lines[0].clone(),
+                            });
+                        }
+                    },
                 }
             }
             _ => {
@@ -428,7 +816,7 @@ impl Screen for SwarmPanel {
         None
     }
 
-    fn footer_spans(&self) -> Vec<Span> {
+    fn footer_spans(&self) -> Vec<Span<'_>> {
         vec![
             Span::styled(
                 format!(" {} ", UiKey::CYCLE_FILTER.to_string()),
@@ -445,7 +833,7 @@ impl SplitPanel for SwarmPanel {
     }
 
     fn max_index(&self) -> usize {
-        self.peer_to_challenge.len()
+        self.challenges.len()
     }
 
     fn set_index(&mut self, index: usize) {
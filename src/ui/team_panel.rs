@@ -10,6 +10,7 @@ use super::{
     widgets::{default_block, selectable_list},
 };
 use crate::image::spaceship::{SPACESHIP_IMAGE_HEIGHT, SPACESHIP_IMAGE_WIDTH};
+use crate::network::ladder::LadderEntry;
 use crate::types::{AppResult, SystemTimeTick};
 use crate::world::position::MAX_POSITION;
 use crate::world::team::Team;
@@ -33,7 +34,7 @@ use ratatui::{
     prelude::Rect,
     style::{Color, Style},
     text::Span,
-    widgets::{Borders, Paragraph},
+    widgets::{Borders, Cell, Paragraph, Row, Table},
     Frame,
 };
 use std::vec;
@@ -48,6 +49,7 @@ pub enum TeamFilter {
     All,
     OpenToChallenge,
     Peers,
+    Ladder,
 }
 
 impl TeamFilter {
@@ -55,7 +57,8 @@ impl TeamFilter {
         match self {
             TeamFilter::All => TeamFilter::OpenToChallenge,
             TeamFilter::OpenToChallenge => TeamFilter::Peers,
-            TeamFilter::Peers => TeamFilter::All,
+            TeamFilter::Peers => TeamFilter::Ladder,
+            TeamFilter::Ladder => TeamFilter::All,
         }
     }
 
@@ -64,6 +67,8 @@ impl TeamFilter {
             TeamFilter::All => true,
             TeamFilter::OpenToChallenge => team.can_challenge_team(own_team).is_ok(),
             TeamFilter::Peers => team.peer_id.is_some(),
+            // Ladder teams come from game results, not from the world.
+            TeamFilter::Ladder => false,
         }
     }
 
@@ -72,6 +77,7 @@ impl TeamFilter {
             TeamFilter::All => "All".to_string(),
             TeamFilter::OpenToChallenge => "Open to challenge".to_string(),
             TeamFilter::Peers => "From swarm".to_string(),
+            TeamFilter::Ladder => "Ladder".to_string(),
         }
     }
 }
@@ -87,6 +93,8 @@ pub struct TeamListPanel {
     filter: TeamFilter,
     update_filter: bool,
     current_team_players_length: usize,
+    // Network teams ranked by the signed results of their games.
+    ladder: Vec<LadderEntry>,
     tick: usize,
     callback_registry: Rc<RefCell<CallbackRegistry>>,
     gif_map: Rc<RefCell<GifMap>>,
@@ -126,6 +134,13 @@ impl TeamListPanel {
         self.update_filter = true;
     }
 
+    pub fn set_ladder(&mut self, ladder: Vec<LadderEntry>) {
+        self.ladder = ladder;
+        if self.filter == TeamFilter::Ladder {
+            self.update_filter = true;
+        }
+    }
+
     pub fn reset_filter(&mut self) {
         self.set_filter(TeamFilter::All);
     }
@@ -137,6 +152,7 @@ impl TeamListPanel {
                 Constraint::Length(3),
                 Constraint::Length(3),
                 Constraint::Length(3),
+                Constraint::Length(3),
                 Constraint::Min(1),
             ])
             .split(area);
@@ -164,17 +180,49 @@ impl TeamListPanel {
             },
             Rc::clone(&self.callback_registry),
         );
+        let mut filter_ladder_button = Button::new(
+            format!("Filter: {}", TeamFilter::Ladder.to_string()),
+            UiCallbackPreset::SetTeamPanelFilter {
+                filter: TeamFilter::Ladder,
+            },
+            Rc::clone(&self.callback_registry),
+        );
         match self.filter {
             TeamFilter::All => filter_all_button.disable(None),
             TeamFilter::OpenToChallenge => filter_challenge_button.disable(None),
             TeamFilter::Peers => filter_peers_button.disable(None),
+            TeamFilter::Ladder => filter_ladder_button.disable(None),
         }
 
         frame.render_widget(filter_all_button, split[0]);
         frame.render_widget(filter_challenge_button, split[1]);
         frame.render_widget(filter_peers_button, split[2]);
+        frame.render_widget(filter_ladder_button, split[3]);
 
-        if self.teams.len() > 0 {
+        if self.filter == TeamFilter::Ladder && !self.ladder.is_empty() {
+            let mut options = vec![];
+            for (rank, entry) in self.ladder.iter().enumerate() {
+                let style = if entry.team_id == world.own_team_id {
+                    UiStyle::OWN_TEAM
+                } else {
+                    UiStyle::NETWORK
+                };
+                let text = format!(
+                    "{:>3}. {:<14} {:>4.0}",
+                    rank + 1,
+                    entry.team_name,
+                    entry.rating
+                );
+                options.push((text, style));
+            }
+            let list = selectable_list(options, &self.callback_registry);
+
+            frame.render_stateful_widget(
+                list.block(default_block().title("Ladder ↓/↑")),
+                split[4],
+                &mut ClickableListState::default().with_selected(Some(self.index)),
+            );
+        } else if self.filter != TeamFilter::Ladder && self.teams.len() > 0 {
             let mut options = vec![];
             for &team_id in self.teams.iter() {
                 let team = world.get_team(team_id);
@@ -195,15 +243,71 @@ impl TeamListPanel {
 
             frame.render_stateful_widget(
                 list.block(default_block().title("Teams ↓/↑")),
-                split[3],
+                split[4],
                 &mut ClickableListState::default().with_selected(Some(self.index)),
             );
         } else {
-            frame.render_widget(default_block().title("Teams"), split[3]);
+            frame.render_widget(default_block().title("Teams"), split[4]);
+        }
+    }
+
+    fn build_ladder_table(&self, frame: &mut Frame, area: Rect) {
+        if self.ladder.is_empty() {
+            frame.render_widget(
+                Paragraph::new(" No signed game results yet!").block(default_block()),
+                area,
+            );
+            return;
         }
+
+        let rows = self.ladder.iter().enumerate().map(|(rank, entry)| {
+            let streak = match entry.streak {
+                s if s > 0 => format!("W{}", s),
+                s if s < 0 => format!("L{}", -s),
+                _ => "-".to_string(),
+            };
+            let row = Row::new(vec![
+                Cell::from(format!("{:>4}", rank + 1)),
+                Cell::from(entry.team_name.clone()),
+                Cell::from(format!("{:>6.0}", entry.rating)),
+                Cell::from(format!("{:>5}", entry.games)),
+                Cell::from(format!("{:>3}-{:<3}", entry.wins, entry.losses)),
+                Cell::from(format!("{:^6}", streak)),
+                Cell::from(format!("{:^4}", entry.best_streak)),
+            ]);
+            if rank == self.index {
+                row.style(UiStyle::SELECTED)
+            } else {
+                row
+            }
+        });
+        let header = Row::new(vec![
+            "Rank", "Team", "Rating", "Games", "  W-L  ", "Streak", "Best",
+        ])
+        .style(UiStyle::HEADER)
+        .height(1);
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(4),
+                Constraint::Min(16),
+                Constraint::Length(6),
+                Constraint::Length(5),
+                Constraint::Length(7),
+                Constraint::Length(6),
+                Constraint::Length(4),
+            ],
+        )
+        .header(header)
+        .block(default_block().title("Galaxy ladder"));
+        frame.render_widget(table, area);
     }
 
     fn build_right_panel(&mut self, frame: &mut Frame, world: &World, area: Rect) -> AppResult<()> {
+        if self.filter == TeamFilter::Ladder {
+            self.build_ladder_table(frame, area);
+            return Ok(());
+        }
         if self.index >= self.teams.len() {
             return Ok(());
         }
@@ -526,15 +630,18 @@ impl Screen for TeamListPanel {
         }
 
         if self.update_filter {
-            self.teams = self
-                .all_teams
-                .iter()
-                .filter(|&&team_id| {
-                    let team = world.get_team_or_err(team_id).unwrap();
-                    self.filter.rule(team, world.get_own_team().unwrap())
-                })
-                .map(|&player_id| player_id)
-                .collect();
+            self.teams = if self.filter == TeamFilter::Ladder {
+                self.ladder.iter().map(|entry| entry.team_id).collect()
+            } else {
+                self.all_teams
+                    .iter()
+                    .filter(|&&team_id| {
+                        let team = world.get_team_or_err(team_id).unwrap();
+                        self.filter.rule(team, world.get_own_team().unwrap())
+                    })
+                    .map(|&player_id| player_id)
+                    .collect()
+            };
             self.update_filter = false;
         }
 
@@ -543,12 +650,11 @@ impl Screen for TeamListPanel {
         }
         if self.index < self.teams.len() {
             self.selected_team_id = self.teams[self.index];
-            let players = world
-                .get_team_or_err(self.selected_team_id)?
-                .player_ids
-                .clone();
-            if self.player_index < players.len() {
-                self.selected_player_id = players[self.player_index];
+            // Ladder teams may not be in our world.
+            if let Some(team) = world.get_team(self.selected_team_id) {
+                if self.player_index < team.player_ids.len() {
+                    self.selected_player_id = team.player_ids[self.player_index];
+                }
             }
         }
         Ok(())
@@ -591,7 +697,7 @@ impl Screen for TeamListPanel {
                 self.set_filter(self.filter.next());
                 self.set_index(0);
             }
-            KeyCode::Enter => {
+            KeyCode::Enter if self.filter != TeamFilter::Ladder => {
                 let player_id = self.selected_player_id.clone();
                 return Some(UiCallbackPreset::GoToPlayer { player_id });
             }
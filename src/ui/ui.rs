@@ -293,6 +293,7 @@ impl Ui {
                 self.player_panel.update(world)?;
                 self.game_panel.update(world)?;
                 self.galaxy_panel.update(world)?;
+                self.swarm_panel.update(world)?;
             }
         }
 
@@ -340,8 +341,15 @@ impl Ui {
                     .split(tab_main_split[0]);
 
                 for idx in 0..self.ui_tabs.len() {
+                    // Unread chat messages are shown on the Swarm tab.
+                    let unread = self.swarm_panel.unread_count();
+                    let label = if self.ui_tabs[idx] == UiTab::Swarm && unread > 0 {
+                        format!("{} ({})", self.ui_tabs[idx], unread)
+                    } else {
+                        self.ui_tabs[idx].to_string()
+                    };
                     let mut button = Button::no_box(
-                        format!("{:^}", self.ui_tabs[idx].to_string()),
+                        format!("{:^}", label),
                         UiCallbackPreset::SetUiTab {
                             ui_tab: self.ui_tabs[idx],
                         },
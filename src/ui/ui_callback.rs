@@ -6,12 +6,21 @@ use super::{
     team_panel::TeamFilter,
     traits::{Screen, SplitPanel},
     ui::{UiState, UiTab},
+    utils::SwarmPanelEvent,
 };
 use crate::{
     app::App,
-    engine::{tactic::OffenseTactic, types::TeamInGame},
+    engine::{
+        command::GameCommandKind,
+        tactic::{DefenseTactic, OffenseTactic},
+        types::TeamInGame,
+    },
     image::color_map::{ColorMap, ColorPreset},
-    network::{constants::DEFAULT_PORT, types::Challenge},
+    network::{
+        constants::DEFAULT_PORT, protocol::PeerRequest, types::Challenge,
+        wire::DIRECT_MESSAGE_WIRE_VERSION,
+    },
+    store::{export_keypair, rotate_keypair, PERSISTED_KEYPAIR_FILENAME},
     types::{
         AppCallback, AppResult, GameId, IdSystem, PlanetId, PlayerId, SystemTimeTick, TeamId, Tick,
         SECONDS,
@@ -25,9 +34,10 @@ use crate::{
     },
 };
 use crossterm::event::{MouseEvent, MouseEventKind};
+use libp2p::PeerId;
 use rand::Rng;
 use ratatui::layout::Rect;
-use std::collections::HashMap;
+use std::{collections::HashMap, path::Path};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum UiCallbackPreset {
@@ -62,6 +72,9 @@ pub enum UiCallbackPreset {
     ChallengeTeam {
         team_id: TeamId,
     },
+    ChallengeGhost {
+        team_id: TeamId,
+    },
     AcceptChallenge {
         challenge: Challenge,
     },
@@ -76,6 +89,10 @@ pub enum UiCallbackPreset {
         tactic: OffenseTactic,
     },
     SetNextTeamOffenseTactic,
+    SetTeamDefenseTactic {
+        tactic: DefenseTactic,
+    },
+    SetNextTeamDefenseTactic,
     NextUiTab,
     PreviousUiTab,
     SetUiTab {
@@ -141,6 +158,18 @@ pub enum UiCallbackPreset {
     SendMessage {
         message: String,
     },
+    SendDirectMessage {
+        peer_id: PeerId,
+        message: String,
+    },
+    SendGameCommand {
+        kind: GameCommandKind,
+    },
+    ShowIdentity,
+    RotateIdentity,
+    ExportIdentity {
+        path: String,
+    },
 }
 
 impl UiCallbackPreset {
@@ -360,42 +389,7 @@ impl UiCallbackPreset {
                     .send_new_challenge(&app.world, peer_id)?;
                 return Ok(Some("Challenge sent".to_string()));
             }
-            let (home_team_in_game, away_team_in_game) = match rand::thread_rng().gen_range(0..=1) {
-                0 => (
-                    TeamInGame::from_team_id(own_team_id, &app.world.teams, &app.world.players)
-                        .ok_or(format!("Own team {:?} not found", own_team_id))?,
-                    TeamInGame::from_team_id(team_id, &app.world.teams, &app.world.players)
-                        .ok_or(format!("Team {:?} not found", team_id))?,
-                ),
-
-                _ => (
-                    TeamInGame::from_team_id(team_id, &app.world.teams, &app.world.players)
-                        .ok_or(format!("Team {:?} not found", team_id))?,
-                    TeamInGame::from_team_id(own_team_id, &app.world.teams, &app.world.players)
-                        .ok_or(format!("Own team {:?} not found", own_team_id))?,
-                ),
-            };
-
-            let game_id = GameId::new();
-            app.world.generate_game(
-                game_id,
-                home_team_in_game,
-                away_team_in_game,
-                Tick::now() + 30 * SECONDS,
-            )?;
-
-            app.ui.game_panel.update(&app.world)?;
-
-            let index = app
-                .ui
-                .game_panel
-                .games
-                .iter()
-                .position(|&x| x == game_id)
-                .ok_or::<String>(format!("Game {:?} not found", game_id).into())?;
-
-            app.ui.game_panel.set_index(index);
-            app.ui.switch_to(super::ui::UiTab::Game);
+            Self::start_local_game(app, team_id)?;
             // if let Some(network_handler) = app.network_handler.as_mut() {
             //     network_handler.decline_all_challenges()?;
             //     app.ui.swarm_panel.remove_all_challenges();
@@ -404,6 +398,81 @@ impl UiCallbackPreset {
         })
     }
 
+    // Ghosts play locally with the snapshot of an offline team.
+    fn challenge_ghost(team_id: TeamId) -> AppCallback {
+        Box::new(move |app: &mut App| {
+            if !app.world.has_own_team() {
+                return Err("No own team".into());
+            }
+
+            let ghost = app.ghosts.get(&team_id).ok_or("Ghost team not found")?;
+            let peer_id = ghost.peer_id().ok_or("Ghost team has no peer")?;
+            if app
+                .network_handler
+                .as_ref()
+                .is_some_and(|handler| handler.swarm.is_connected(&peer_id))
+            {
+                return Err("Team is online, challenge it directly".into());
+            }
+
+            // The snapshot may have been taken during a game it never finished.
+            let mut network_team = ghost.network_team.clone();
+            network_team.team.current_game = None;
+            let own_team = app.world.get_team_or_err(app.world.own_team_id)?;
+            own_team.can_challenge_team(&network_team.team)?;
+
+            app.world.add_network_team(network_team)?;
+            let mut team = app.world.get_team_or_err(team_id)?.clone();
+            team.current_game = None;
+            app.world.teams.insert(team.id, team);
+
+            let game_id = Self::start_local_game(app, team_id)?;
+            app.ghosts.start_game(game_id, peer_id);
+            Ok(Some("Playing against ghost".to_string()))
+        })
+    }
+
+    fn start_local_game(app: &mut App, team_id: TeamId) -> AppResult<GameId> {
+        let own_team_id = app.world.own_team_id;
+        let (home_team_in_game, away_team_in_game) = match rand::thread_rng().gen_range(0..=1) {
+            0 => (
+                TeamInGame::from_team_id(own_team_id, &app.world.teams, &app.world.players)
+                    .ok_or(format!("Own team {:?} not found", own_team_id))?,
+                TeamInGame::from_team_id(team_id, &app.world.teams, &app.world.players)
+                    .ok_or(format!("Team {:?} not found", team_id))?,
+            ),
+
+            _ => (
+                TeamInGame::from_team_id(team_id, &app.world.teams, &app.world.players)
+                    .ok_or(format!("Team {:?} not found", team_id))?,
+                TeamInGame::from_team_id(own_team_id, &app.world.teams, &app.world.players)
+                    .ok_or(format!("Own team {:?} not found", own_team_id))?,
+            ),
+        };
+
+        let game_id = GameId::new();
+        app.world.generate_game(
+            game_id,
+            home_team_in_game,
+            away_team_in_game,
+            Tick::now() + 30 * SECONDS,
+        )?;
+
+        app.ui.game_panel.update(&app.world)?;
+
+        let index = app
+            .ui
+            .game_panel
+            .games
+            .iter()
+            .position(|&x| x == game_id)
+            .ok_or::<String>(format!("Game {:?} not found", game_id).into())?;
+
+        app.ui.game_panel.set_index(index);
+        app.ui.switch_to(super::ui::UiTab::Game);
+        Ok(game_id)
+    }
+
     fn next_ui_tab() -> AppCallback {
         Box::new(move |app: &mut App| {
             app.ui.next_tab();
@@ -592,19 +661,18 @@ impl UiCallbackPreset {
 
     fn dial(address: String) -> AppCallback {
         Box::new(move |app: &mut App| {
-            let multiaddr = match address.clone() {
-                x if x == "seed".to_string() => {
-                    app.network_handler.as_ref().unwrap().seed_address.clone()
-                }
-                _ => format!("/ip4/{address}/tcp/{DEFAULT_PORT}")
-                    .as_str()
-                    .parse()?,
-            };
-            app.network_handler
-                .as_mut()
-                .unwrap()
-                .dial(multiaddr)
-                .map_err(|e| e.to_string())?;
+            let network_handler = app.network_handler.as_mut().unwrap();
+            if address == "seed" {
+                network_handler.dial_seed()?;
+            } else {
+                // Peers listen on random ports, a bare ip is dialed at the seed port.
+                let multiaddr = if address.starts_with('/') {
+                    address.parse()?
+                } else {
+                    format!("/ip4/{address}/tcp/{DEFAULT_PORT}").parse()?
+                };
+                network_handler.dial(multiaddr).map_err(|e| e.to_string())?;
+            }
             app.world.dirty_network = true;
             Ok(None)
         })
@@ -628,6 +696,93 @@ impl UiCallbackPreset {
         })
     }
 
+    fn send_direct_message(peer_id: PeerId, message: String) -> AppCallback {
+        Box::new(move |app: &mut App| {
+            if let Some(version) = app.ui.swarm_panel.peer_version(&peer_id) {
+                if version < DIRECT_MESSAGE_WIRE_VERSION {
+                    return Err("Peer is on an older version without direct messages".into());
+                }
+            }
+            app.network_handler
+                .as_mut()
+                .ok_or("Network is disabled")?
+                .send_request(
+                    peer_id,
+                    PeerRequest::Chat {
+                        text: message.clone(),
+                    },
+                )?;
+
+            Ok(None)
+        })
+    }
+
+    fn send_game_command(kind: GameCommandKind) -> AppCallback {
+        Box::new(move |app: &mut App| {
+            let command = app.world.own_game_command(kind)?;
+            app.world.add_game_command(command.clone())?;
+
+            // Network games are simulated by both peers, which need the same commands.
+            let game = app.world.get_game_or_err(command.game_id)?;
+            if game.home_team_in_game.peer_id.is_some() && game.away_team_in_game.peer_id.is_some()
+            {
+                if let Some(network_handler) = app.network_handler.as_mut() {
+                    network_handler.send_game_command(&command)?;
+                }
+            }
+
+            Ok(Some(format!("{}.", kind)))
+        })
+    }
+
+    fn show_identity() -> AppCallback {
+        Box::new(move |app: &mut App| {
+            let peer_id = app
+                .network_handler
+                .as_ref()
+                .ok_or("Network is not running".to_string())?
+                .swarm
+                .local_peer_id();
+            app.ui.swarm_panel.push_log_event(SwarmPanelEvent {
+                timestamp: Tick::now(),
+                peer_id: None,
+                text: format!("Our peer id is {}", peer_id),
+            });
+            Ok(None)
+        })
+    }
+
+    fn rotate_identity() -> AppCallback {
+        Box::new(move |app: &mut App| {
+            // The result of the game must be signed with the identity that played it.
+            if app.game_results.is_playing() {
+                return Err("Cannot rotate identity during a network game".into());
+            }
+            // Peers would not recognize us when answering the handshake.
+            if app
+                .network_handler
+                .as_ref()
+                .is_some_and(|handler| !handler.challenges.is_empty())
+            {
+                return Err("Cannot rotate identity during a challenge".into());
+            }
+            rotate_keypair(PERSISTED_KEYPAIR_FILENAME)?;
+            // Dropping the handler closes the swarm, the app restarts it with the new identity.
+            app.network_handler = None;
+            app.ui.swarm_panel.remove_all_challenges();
+            Ok(Some(
+                "Identity rotated. Peers will see us with a new peer id.".to_string(),
+            ))
+        })
+    }
+
+    fn export_identity(path: String) -> AppCallback {
+        Box::new(move |_app: &mut App| {
+            export_keypair(PERSISTED_KEYPAIR_FILENAME, Path::new(&path))?;
+            Ok(Some(format!("Identity exported to {}", path)))
+        })
+    }
+
     pub fn call(&self, app: &mut App) -> AppResult<Option<String>> {
         match self {
             UiCallbackPreset::None => Ok(None),
@@ -682,14 +837,35 @@ impl UiCallbackPreset {
                 app.world.dirty_network = true;
                 Ok(None)
             }
+            UiCallbackPreset::SetTeamDefenseTactic { tactic } => {
+                let own_team = app.world.get_own_team()?;
+                let mut team = own_team.clone();
+                team.game_defense_tactic = tactic.clone();
+                app.world.teams.insert(team.id, team);
+                app.world.dirty = true;
+                app.world.dirty_ui = true;
+                app.world.dirty_network = true;
+                Ok(None)
+            }
+            UiCallbackPreset::SetNextTeamDefenseTactic => {
+                let own_team = app.world.get_own_team()?;
+                let mut team = own_team.clone();
+                team.game_defense_tactic = team.game_defense_tactic.next();
+                app.world.teams.insert(team.id, team);
+                app.world.dirty = true;
+                app.world.dirty_ui = true;
+                app.world.dirty_network = true;
+                Ok(None)
+            }
             UiCallbackPreset::ChallengeTeam { team_id } => Self::challenge_team(*team_id)(app),
+            UiCallbackPreset::ChallengeGhost { team_id } => Self::challenge_ghost(*team_id)(app),
             UiCallbackPreset::AcceptChallenge { challenge } => {
                 app.network_handler
                     .as_mut()
                     .unwrap()
-                    .accept_challenge(&&app.world, challenge.clone())?;
+                    .accept_challenge(&app.world, challenge.id)?;
 
-                app.ui.swarm_panel.remove_challenge(&challenge.home_peer_id);
+                app.ui.swarm_panel.remove_challenge(&challenge.id);
 
                 // app.network_handler
                 //     .as_mut()
@@ -702,8 +878,8 @@ impl UiCallbackPreset {
                 app.network_handler
                     .as_mut()
                     .unwrap()
-                    .decline_challenge(challenge.clone())?;
-                app.ui.swarm_panel.remove_challenge(&challenge.home_peer_id);
+                    .decline_challenge(challenge.id)?;
+                app.ui.swarm_panel.remove_challenge(&challenge.id);
                 Ok(None)
             }
             UiCallbackPreset::NextUiTab => Self::next_ui_tab()(app),
@@ -808,6 +984,13 @@ impl UiCallbackPreset {
             UiCallbackPreset::Dial { address } => Self::dial(address.clone())(app),
             UiCallbackPreset::Sync => Self::sync()(app),
             UiCallbackPreset::SendMessage { message } => Self::send(message.clone())(app),
+            UiCallbackPreset::SendDirectMessage { peer_id, message } => {
+                Self::send_direct_message(*peer_id, message.clone())(app)
+            }
+            UiCallbackPreset::SendGameCommand { kind } => Self::send_game_command(*kind)(app),
+            UiCallbackPreset::ShowIdentity => Self::show_identity()(app),
+            UiCallbackPreset::RotateIdentity => Self::rotate_identity()(app),
+            UiCallbackPreset::ExportIdentity { path } => Self::export_identity(path.clone())(app),
         }
     }
 }
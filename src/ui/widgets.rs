@@ -6,7 +6,7 @@ use super::{
     ui_callback::CallbackRegistry,
 };
 use crate::{
-    engine::constants::MAX_TIREDNESS,
+    engine::constants::{MAX_MORALE, MAX_TIREDNESS},
     image::{player::PLAYER_IMAGE_WIDTH, spaceship::SPACESHIP_IMAGE_WIDTH},
     types::{AU, HOURS},
     world::{
@@ -194,7 +194,7 @@ pub fn render_player_description(
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Length(2),  //margin
-            Constraint::Length(3),  //header
+            Constraint::Length(4),  //header
             Constraint::Length(1),  //margin
             Constraint::Length(20), //stats
         ])
@@ -239,6 +239,12 @@ pub fn render_player_description(
         x if x < MAX_TIREDNESS => UiStyle::ERROR,
         _ => UiStyle::DEFAULT.bg(Color::DarkGray),
     };
+    let (morale_string, morale_style) = match player.morale {
+        x if x < MAX_MORALE / 4.0 => ("Miserable", UiStyle::ERROR),
+        x if x < MAX_MORALE / 2.0 => ("Unhappy", UiStyle::WARNING),
+        x if x < MAX_MORALE * 3.0 / 4.0 => ("Content", UiStyle::DEFAULT),
+        _ => ("Happy", UiStyle::OK),
+    };
     let player_info = Paragraph::new(vec![
         Line::from(format!(
             "Reputation {} Style {}",
@@ -249,6 +255,10 @@ pub fn render_player_description(
             Span::raw("Energy ".to_string()),
             Span::styled(format!("{}", energy_string), energy_style),
         ]),
+        Line::from(vec![
+            Span::raw("Morale ".to_string()),
+            Span::styled(morale_string, morale_style),
+        ]),
         Line::from(format!(
             "{} yo, {} cm, {} kg, {}",
             player.info.age.floor() as u8,
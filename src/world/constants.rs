@@ -9,6 +9,23 @@ pub const REPUTATION_PER_EXPERIENCE: f32 = 0.00005;
 pub const REPUTATION_DECREASE_PER_LONG_TICK: f32 = 0.1;
 pub const AGE_INCREASE_PER_LONG_TICK: f32 = 0.025;
 
+pub struct MoraleChange;
+impl MoraleChange {
+    pub const WIN: f32 = 1.0;
+    pub const LOSS: f32 = 1.0;
+    pub const PER_STREAK_GAME: f32 = 0.25;
+    pub const PLAYED: f32 = 0.5;
+    pub const BENCHED: f32 = 0.75;
+    pub const PER_REPUTATION: f32 = 0.1;
+}
+pub const MAX_MORALE_STREAK: i8 = 5;
+pub const MIN_SECONDS_PLAYED_FOR_MORALE: u16 = 20 * 60;
+pub const MIN_MORALE_TO_STAY: f32 = 4.0;
+
+// Playing past exhaustion causes lasting harm.
+pub const EXHAUSTION_SKILL_LOSS: f32 = 0.5;
+pub const MIN_EXHAUSTION_RETIREMENT_AGE: f32 = 28.0;
+
 pub const INCOME_PER_ATTENDEE_HOME: u32 = 12;
 pub const INCOME_PER_ATTENDEE_AWAY: u32 = 12;
 
@@ -41,8 +58,8 @@ pub const BASE_GAME_START_DELAY: Tick = 10 * SECONDS;
 static GALAXY_ROOT_STR: &str = "71a43700-0000-0000-0000-000000000000";
 static DEFAULT_PLANET_STR: &str = "71a43700-0000-0000-0002-000000000000";
 static SOL_STR: &str = "71a43700-0000-0000-0001-000000000000";
-pub const GALAXY_ROOT_ID: Lazy<PlanetId> =
+pub static GALAXY_ROOT_ID: Lazy<PlanetId> =
     Lazy::new(|| PlanetId::try_parse(GALAXY_ROOT_STR).unwrap());
-pub const DEFAULT_PLANET_ID: Lazy<PlanetId> =
+pub static DEFAULT_PLANET_ID: Lazy<PlanetId> =
     Lazy::new(|| PlanetId::try_parse(DEFAULT_PLANET_STR).unwrap());
-pub const SOL_ID: Lazy<PlanetId> = Lazy::new(|| PlanetId::try_parse(SOL_STR).unwrap());
+pub static SOL_ID: Lazy<PlanetId> = Lazy::new(|| PlanetId::try_parse(SOL_STR).unwrap());
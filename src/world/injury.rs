@@ -0,0 +1,139 @@
+use crate::types::{Tick, DAYS};
+use rand::seq::SliceRandom;
+use rand_chacha::ChaCha8Rng;
+use rand_distr::{Distribution, WeightedIndex};
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, Serialize_repr, Deserialize_repr, PartialEq, PartialOrd)]
+#[repr(u8)]
+pub enum InjuryKind {
+    Bruise,
+    BrokenNose,
+    AnkleSprain,
+    Concussion,
+    HamstringStrain,
+    KneeSprain,
+}
+
+impl InjuryKind {
+    fn recovery_factor(&self) -> f32 {
+        match self {
+            Self::Bruise => 0.5,
+            Self::BrokenNose => 1.0,
+            Self::AnkleSprain => 1.0,
+            Self::Concussion => 1.5,
+            Self::HamstringStrain => 1.5,
+            Self::KneeSprain => 2.0,
+        }
+    }
+}
+
+impl Display for InjuryKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bruise => write!(f, "bruise"),
+            Self::BrokenNose => write!(f, "broken nose"),
+            Self::AnkleSprain => write!(f, "ankle sprain"),
+            Self::Concussion => write!(f, "concussion"),
+            Self::HamstringStrain => write!(f, "hamstring strain"),
+            Self::KneeSprain => write!(f, "knee sprain"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize_repr, Deserialize_repr, PartialEq, PartialOrd)]
+#[repr(u8)]
+pub enum InjurySeverity {
+    Minor,
+    Moderate,
+    Severe,
+}
+
+impl InjurySeverity {
+    fn recovery_time(&self) -> Tick {
+        match self {
+            Self::Minor => DAYS,
+            Self::Moderate => 3 * DAYS,
+            Self::Severe => 7 * DAYS,
+        }
+    }
+}
+
+impl Display for InjurySeverity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Minor => write!(f, "Minor"),
+            Self::Moderate => write!(f, "Moderate"),
+            Self::Severe => write!(f, "Severe"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, PartialOrd)]
+pub struct Injury {
+    pub kind: InjuryKind,
+    pub severity: InjurySeverity,
+    // Time left before the player is healed, at the base recovery rate.
+    pub recovery_left: Tick,
+}
+
+impl Injury {
+    pub fn new(kind: InjuryKind, severity: InjurySeverity) -> Self {
+        Self {
+            kind,
+            severity,
+            recovery_left: (severity.recovery_time() as f32 * kind.recovery_factor()) as Tick,
+        }
+    }
+
+    pub fn random(rng: &mut ChaCha8Rng, kinds: &[InjuryKind]) -> Option<Self> {
+        let kind = *kinds.choose(rng)?;
+        let severity = match WeightedIndex::new([6, 3, 1]).ok()?.sample(rng) {
+            0 => InjurySeverity::Minor,
+            1 => InjurySeverity::Moderate,
+            _ => InjurySeverity::Severe,
+        };
+        Some(Self::new(kind, severity))
+    }
+
+    // The recovery bonus (from the ship's doctor) speeds up healing.
+    // Returns true once the player is healed.
+    pub fn recover(&mut self, elapsed: Tick, bonus: f32) -> bool {
+        let recovered = (elapsed as f32 * bonus) as Tick;
+        self.recovery_left = self.recovery_left.saturating_sub(recovered);
+        self.recovery_left == 0
+    }
+
+    pub fn time_to_recovery(&self, bonus: f32) -> Tick {
+        if bonus <= 0.0 {
+            return self.recovery_left;
+        }
+        (self.recovery_left as f32 / bonus) as Tick
+    }
+}
+
+impl Display for Injury {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.severity, self.kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Injury, InjuryKind, InjurySeverity};
+    use crate::types::DAYS;
+
+    #[test]
+    fn test_doctor_shortens_recovery() {
+        let injury = Injury::new(InjuryKind::AnkleSprain, InjurySeverity::Moderate);
+        assert_eq!(injury.recovery_left, 3 * DAYS);
+
+        let mut without_doctor = injury;
+        let mut with_doctor = injury;
+        assert!(!without_doctor.recover(2 * DAYS, 0.5));
+        assert!(with_doctor.recover(2 * DAYS, 2.0));
+        assert!(without_doctor.time_to_recovery(0.5) > with_doctor.time_to_recovery(2.0));
+    }
+}
@@ -1,4 +1,5 @@
 pub mod constants;
+pub mod injury;
 pub mod jersey;
 pub mod planet;
 pub mod player;
@@ -1,5 +1,10 @@
 use super::{
-    constants::{COST_PER_VALUE, EXPERIENCE_PER_SKILL_MULTIPLIER, REPUTATION_PER_EXPERIENCE},
+    constants::{
+        MoraleChange, COST_PER_VALUE, EXHAUSTION_SKILL_LOSS, EXPERIENCE_PER_SKILL_MULTIPLIER,
+        MAX_MORALE_STREAK, MIN_EXHAUSTION_RETIREMENT_AGE, MIN_MORALE_TO_STAY,
+        MIN_SECONDS_PLAYED_FOR_MORALE, REPUTATION_PER_EXPERIENCE,
+    },
+    injury::Injury,
     jersey::Jersey,
     planet::Planet,
     position::{GamePosition, PlayingStyle, MAX_POSITION},
@@ -9,7 +14,10 @@ use super::{
     utils::PLAYER_DATA,
 };
 use crate::{
-    engine::{constants::MAX_TIREDNESS, types::GameStats},
+    engine::{
+        constants::{DEFAULT_MORALE, MAX_MORALE, MAX_TIREDNESS},
+        types::GameStats,
+    },
     image::{player::PlayerImage, types::Gif},
     types::{PlanetId, PlayerId, TeamId},
     world::{
@@ -49,6 +57,8 @@ pub struct Player {
     pub previous_skills: [Skill; 20], // This is for displaying purposes to show the skills that were recently modified
     pub training_focus: Option<TrainingFocus>,
     pub tiredness: f32,
+    pub morale: f32,
+    pub injury: Option<Injury>,
 }
 
 impl Serialize for Player {
@@ -57,7 +67,7 @@ impl Serialize for Player {
         // and serialize them in a vector which is then deserialized
         // into the corresponding fields
         let compact_skills = self.current_skill_array().to_vec();
-        let mut state = serializer.serialize_struct("Player", 14)?;
+        let mut state = serializer.serialize_struct("Player", 16)?;
         state.serialize_field("id", &self.id)?;
         state.serialize_field("peer_id", &self.peer_id)?;
         state.serialize_field("version", &self.version)?;
@@ -72,6 +82,8 @@ impl Serialize for Player {
         state.serialize_field("training_focus", &self.training_focus)?;
         state.serialize_field("tiredness", &self.tiredness)?;
         state.serialize_field("compact_skills", &compact_skills)?;
+        state.serialize_field("morale", &self.morale)?;
+        state.serialize_field("injury", &self.injury)?;
         state.end()
     }
 }
@@ -96,6 +108,8 @@ impl<'de> Deserialize<'de> for Player {
             TrainingFocus,
             Tiredness,
             CompactSkills,
+            Morale,
+            Injury,
         }
 
         impl<'de> Deserialize<'de> for Field {
@@ -128,6 +142,8 @@ impl<'de> Deserialize<'de> for Player {
                             "training_focus" => Ok(Field::TrainingFocus),
                             "tiredness" => Ok(Field::Tiredness),
                             "compact_skills" => Ok(Field::CompactSkills),
+                            "morale" => Ok(Field::Morale),
+                            "injury" => Ok(Field::Injury),
                             _ => Err(serde::de::Error::unknown_field(value, FIELDS)),
                         }
                     }
@@ -192,6 +208,9 @@ impl<'de> Deserialize<'de> for Player {
                 let compact_skills: Vec<Skill> = seq
                     .next_element()?
                     .ok_or_else(|| serde::de::Error::invalid_length(13, &self))?;
+                // Morale was added later, so older data might not have it.
+                let morale = seq.next_element()?.unwrap_or(DEFAULT_MORALE);
+                let injury = seq.next_element()?.unwrap_or_default();
 
                 let mut player = Player {
                     id,
@@ -212,6 +231,8 @@ impl<'de> Deserialize<'de> for Player {
                     previous_skills,
                     training_focus,
                     tiredness,
+                    morale,
+                    injury,
                 };
 
                 player.athleticism = Athleticism {
@@ -266,6 +287,8 @@ impl<'de> Deserialize<'de> for Player {
                 let mut training_focus = None;
                 let mut tiredness = None;
                 let mut compact_skills: Option<Vec<Skill>> = None;
+                let mut morale = None;
+                let mut injury = None;
 
                 while let Some(key) = map.next_key()? {
                     match key {
@@ -353,6 +376,18 @@ impl<'de> Deserialize<'de> for Player {
                             }
                             compact_skills = Some(map.next_value()?);
                         }
+                        Field::Morale => {
+                            if morale.is_some() {
+                                return Err(serde::de::Error::duplicate_field("morale"));
+                            }
+                            morale = Some(map.next_value()?);
+                        }
+                        Field::Injury => {
+                            if injury.is_some() {
+                                return Err(serde::de::Error::duplicate_field("injury"));
+                            }
+                            injury = Some(map.next_value()?);
+                        }
                     }
                 }
 
@@ -378,6 +413,8 @@ impl<'de> Deserialize<'de> for Player {
                     tiredness.ok_or_else(|| serde::de::Error::missing_field("tiredness"))?;
                 let compact_skills = compact_skills
                     .ok_or_else(|| serde::de::Error::missing_field("compact_skills"))?;
+                let morale = morale.unwrap_or(DEFAULT_MORALE);
+                let injury = injury.unwrap_or_default();
 
                 let mut player = Player {
                     id,
@@ -398,6 +435,8 @@ impl<'de> Deserialize<'de> for Player {
                     previous_skills,
                     training_focus,
                     tiredness,
+                    morale,
+                    injury,
                 };
 
                 player.athleticism = Athleticism {
@@ -450,6 +489,8 @@ impl<'de> Deserialize<'de> for Player {
             "training_focus",
             "tiredness",
             "compact_skills",
+            "morale",
+            "injury",
         ];
         deserializer.deserialize_struct("Player", FIELDS, PlayerVisitor)
     }
@@ -464,6 +505,40 @@ impl Player {
             .unwrap()
     }
 
+    // Inverse of current_skill_array.
+    pub fn set_skill_array(&mut self, skills: [Skill; 20]) {
+        self.athleticism = Athleticism {
+            quickness: skills[0],
+            vertical: skills[1],
+            strength: skills[2],
+            stamina: skills[3],
+        };
+        self.offense = Offense {
+            dunk: skills[4],
+            close_range: skills[5],
+            medium_range: skills[6],
+            long_range: skills[7],
+        };
+        self.defense = Defense {
+            steal: skills[8],
+            block: skills[9],
+            perimeter_defense: skills[10],
+            interior_defense: skills[11],
+        };
+        self.technical = Technical {
+            passing: skills[12],
+            ball_handling: skills[13],
+            post_moves: skills[14],
+            rebounding: skills[15],
+        };
+        self.mental = Mental {
+            vision: skills[16],
+            positioning: skills[17],
+            off_ball_movement: skills[18],
+            charisma: skills[19],
+        };
+    }
+
     fn player_value(&self) -> u32 {
         let value = self.total_skills() as f32;
         let age_diff = self.info.age - 36.0;
@@ -534,6 +609,8 @@ impl Player {
             previous_skills: [Skill::default(); 20],
             training_focus: None,
             tiredness: 0.0,
+            morale: DEFAULT_MORALE,
+            injury: None,
         };
 
         player
@@ -742,6 +819,9 @@ impl Player {
 
     pub fn apply_end_of_game_logic(&mut self, stats: &GameStats) {
         self.version += 1;
+        if stats.injury.is_some() {
+            self.injury = stats.injury;
+        }
         if stats.is_knocked_out() {
             self.tiredness = MAX_TIREDNESS;
             return;
@@ -779,6 +859,72 @@ impl Player {
             }
             self.modify_skill(idx, increment);
         }
+
+        // Playing past exhaustion permanently damages the body.
+        if self.is_exhausted() {
+            for idx in 0..4 {
+                self.modify_skill(idx, -EXHAUSTION_SKILL_LOSS);
+            }
+        }
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.tiredness >= MAX_TIREDNESS
+    }
+
+    // Older players might decide to quit after being pushed past exhaustion.
+    // Crew members are needed on the ship and stay.
+    pub fn retires_after_exhaustion(&self, rng: &mut ChaCha8Rng) -> bool {
+        if !self.is_exhausted() || self.info.crew_role != CrewRole::Mozzo {
+            return false;
+        }
+        let chance = ((self.info.age - MIN_EXHAUSTION_RETIREMENT_AGE) / 20.0).clamp(0.0, 0.5);
+        rng.gen_bool(chance as f64)
+    }
+
+    // Morale carries across games. Playing time, results and the team reputation
+    // move it, while a charismatic captain amplifies good news and softens bad ones.
+    pub fn apply_morale_update(
+        &mut self,
+        stats: &GameStats,
+        won: bool,
+        team_streak: i8,
+        captain_bonus: f32,
+        team_reputation: f32,
+    ) {
+        let mut change = if won {
+            MoraleChange::WIN
+        } else {
+            -MoraleChange::LOSS
+        };
+        change += MoraleChange::PER_STREAK_GAME
+            * team_streak.clamp(-MAX_MORALE_STREAK, MAX_MORALE_STREAK) as f32;
+        change += match stats.seconds_played {
+            0 => -MoraleChange::BENCHED,
+            x if x >= MIN_SECONDS_PLAYED_FOR_MORALE => MoraleChange::PLAYED,
+            _ => 0.0,
+        };
+        // Stars are unhappy in teams with less reputation than themselves.
+        change += (MoraleChange::PER_REPUTATION * (team_reputation - self.reputation))
+            .clamp(-MoraleChange::PLAYED, MoraleChange::PLAYED);
+
+        if change > 0.0 {
+            change *= captain_bonus;
+        } else if captain_bonus > 0.0 {
+            change /= captain_bonus;
+        }
+        self.morale = (self.morale + change).clamp(0.0, MAX_MORALE);
+    }
+
+    pub fn is_injured(&self) -> bool {
+        self.injury.is_some()
+    }
+
+    pub fn wants_to_leave(&self, rng: &mut ChaCha8Rng) -> bool {
+        if self.morale >= MIN_MORALE_TO_STAY {
+            return false;
+        }
+        rng.gen_bool(((MIN_MORALE_TO_STAY - self.morale) / MIN_MORALE_TO_STAY / 2.0) as f64)
     }
 }
 
@@ -817,7 +963,7 @@ impl InfoStats {
         let pronouns = if population == Population::Polpett {
             Pronoun::They
         } else {
-            Pronoun::random()
+            Pronoun::random(rng)
         };
         let idx = population as usize;
         let first_name = match pronouns {
@@ -852,3 +998,30 @@ impl InfoStats {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Player;
+    use crate::engine::{constants::MAX_TIREDNESS, types::GameStats};
+    use crate::types::{IdSystem, PlayerId};
+    use crate::world::planet::Planet;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn test_knocked_out_player_ends_game_exhausted() {
+        let rng = &mut ChaCha8Rng::seed_from_u64(0);
+        let mut player = Player::random(rng, PlayerId::new(), None, &Planet::default(), 0.0);
+        let skills = player.current_skill_array();
+        let stats = GameStats {
+            tiredness: 10.0,
+            experience_at_position: [100, 0, 0, 0, 0],
+            knocked_out_by: Some(PlayerId::new()),
+            ..Default::default()
+        };
+
+        player.apply_end_of_game_logic(&stats);
+        assert_eq!(player.tiredness, MAX_TIREDNESS);
+        assert_eq!(player.current_skill_array(), skills);
+    }
+}
@@ -8,7 +8,7 @@ use crate::{
 use super::constants::{
     BASE_FUEL_CONSUMPTION, BASE_SPEED, BASE_TANK_CAPACITY, MIN_PLAYERS_PER_TEAM,
 };
-use rand::{seq::IteratorRandom, SeedableRng};
+use rand::seq::IteratorRandom;
 use rand_chacha::ChaCha8Rng;
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
@@ -259,8 +259,7 @@ impl Spaceship {
         }
     }
 
-    pub fn random(name: String, color_map: ColorMap) -> Self {
-        let rng = &mut ChaCha8Rng::from_entropy();
+    pub fn random(name: String, color_map: ColorMap, rng: &mut ChaCha8Rng) -> Self {
         let style = SpaceshipStyle::iter().choose(rng).unwrap();
         let hull = Hull::iter()
             .filter(|h| h.style() == style)
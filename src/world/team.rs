@@ -14,6 +14,7 @@ use crate::{
 };
 use itertools::Itertools;
 use libp2p::PeerId;
+use rand_chacha::ChaCha8Rng;
 use serde::{Deserialize, Serialize};
 use std::cmp::min;
 
@@ -43,10 +44,13 @@ pub struct Team {
     pub current_game: Option<GameId>,
     pub game_offense_tactic: OffenseTactic,
     pub game_defense_tactic: DefenseTactic,
+    // Positive for consecutive wins, negative for consecutive losses.
+    #[serde(default)]
+    pub streak: i8,
 }
 
 impl Team {
-    pub fn random(id: TeamId, home_planet: PlanetId, name: String) -> Self {
+    pub fn random(id: TeamId, home_planet: PlanetId, name: String, rng: &mut ChaCha8Rng) -> Self {
         let jersey = Jersey::random();
         let ship_name = format!("{}shipp", name);
         let ship_color = jersey.color;
@@ -58,7 +62,7 @@ impl Team {
             current_location: TeamLocation::OnPlanet {
                 planet_id: home_planet,
             },
-            spaceship: Spaceship::random(ship_name, ship_color),
+            spaceship: Spaceship::random(ship_name, ship_color, rng),
             game_offense_tactic: OffenseTactic::random(),
             game_defense_tactic: DefenseTactic::random(),
             ..Default::default()
@@ -97,6 +101,15 @@ impl Team {
         Ok(())
     }
 
+    pub fn streak_after_game(&self, won: bool) -> i8 {
+        match (won, self.streak) {
+            (true, x) if x > 0 => x.saturating_add(1),
+            (true, _) => 1,
+            (false, x) if x < 0 => x.saturating_sub(1),
+            (false, _) => -1,
+        }
+    }
+
     pub fn can_release_player(&self, player: &Player) -> AppResult<()> {
         if player.team.is_none() {
             return Err("Player is not in a team".into());
@@ -330,12 +343,28 @@ mod tests {
         types::{IdSystem, TeamId},
         world::{planet::Planet, utils::TEAM_DATA},
     };
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
 
     #[test]
     fn test_team_random() {
         let data = TEAM_DATA.as_ref().unwrap();
         let (name, _) = data.names[0].clone();
-        let team = super::Team::random(TeamId::new(), Planet::default().id, name);
+        let rng = &mut ChaCha8Rng::seed_from_u64(0);
+        let team = super::Team::random(TeamId::new(), Planet::default().id, name, rng);
         println!("{:?}", team);
     }
+
+    #[test]
+    fn test_streak_after_game() {
+        let mut team = super::Team::default();
+        assert_eq!(team.streak_after_game(true), 1);
+        assert_eq!(team.streak_after_game(false), -1);
+        team.streak = 3;
+        assert_eq!(team.streak_after_game(true), 4);
+        assert_eq!(team.streak_after_game(false), -1);
+        team.streak = -2;
+        assert_eq!(team.streak_after_game(false), -3);
+        assert_eq!(team.streak_after_game(true), 1);
+    }
 }
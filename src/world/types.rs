@@ -142,6 +142,7 @@ pub enum PlayerLocation {
 impl PartialEq for PlayerLocation {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
+            (Self::WithTeam, Self::WithTeam) => true,
             (Self::OnPlanet { planet_id: p1 }, Self::OnPlanet { planet_id: p2 }) => p1 == p2,
             _ => false,
         }
@@ -196,8 +197,8 @@ pub enum Pronoun {
 }
 
 impl Pronoun {
-    pub fn random() -> Self {
-        match rand::thread_rng().gen_range(0..=2) {
+    pub fn random(rng: &mut ChaCha8Rng) -> Self {
+        match rng.gen_range(0..=2) {
             0 => Self::He,
             1 => Self::She,
             _ => Self::They,
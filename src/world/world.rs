@@ -9,9 +9,11 @@ use super::spaceship::Spaceship;
 use super::team::Team;
 use super::types::TeamLocation;
 use super::utils::{PLANET_DATA, TEAM_DATA};
-use crate::engine::constants::{MAX_TIREDNESS, RECOVERING_TIREDNESS_PER_SHORT_TICK};
+use crate::engine::command::{GameCommand, GameCommandKind};
+use crate::engine::constants::{ENGINE_VERSION, MAX_TIREDNESS};
 use crate::engine::game::{Game, GameSummary};
 use crate::engine::types::TeamInGame;
+use crate::engine::utils::tiredness_recovery;
 use crate::image::color_map::ColorMap;
 use crate::network::types::{NetworkGame, NetworkTeam};
 use crate::store::{
@@ -135,7 +137,7 @@ impl World {
         ship_name: String,
     ) -> AppResult<TeamId> {
         let team_id = TeamId::new();
-        let mut team = Team::random(team_id, home_planet, team_name);
+        let mut team = Team::random(team_id, home_planet, team_name, rng);
         team.spaceship.name = ship_name;
         let home_planet = team.home_planet.clone();
         let team_base_level = rng.gen_range(0..=5) as f32;
@@ -490,7 +492,40 @@ impl World {
         Ok(())
     }
 
-    pub fn add_network_game(&mut self, network_game: NetworkGame) -> AppResult<()> {
+    // Remove a game that has not started yet, e.g. when the challenge handshake fails after it was generated.
+    pub fn cancel_game(&mut self, game_id: GameId) -> AppResult<()> {
+        let game = self.get_game_or_err(game_id)?;
+        if game.starting_at <= Tick::now() {
+            return Err("Cannot cancel game, it has already started".into());
+        }
+
+        for team_id in [
+            game.home_team_in_game.team_id,
+            game.away_team_in_game.team_id,
+        ] {
+            if let Ok(team) = self.get_team_or_err(team_id) {
+                let mut team = team.clone();
+                if team.current_game == Some(game_id) {
+                    team.current_game = None;
+                }
+                if team.id == self.own_team_id {
+                    self.dirty_network = true;
+                }
+                self.teams.insert(team.id, team);
+            }
+        }
+        self.games.remove(&game_id);
+        self.dirty = true;
+        self.dirty_ui = true;
+        Ok(())
+    }
+
+    pub fn add_network_game(&mut self, network_game: NetworkGame, source: PeerId) -> AppResult<()> {
+        // We already simulate this game, only the commands we missed are of interest.
+        if self.get_game(network_game.id).is_some() {
+            return self.resync_network_game(network_game, source);
+        }
+
         // Check that the game does not involve the own team (otherwise we would have generated it).
         if network_game.home_team_in_game.team_id == self.own_team_id
             || network_game.away_team_in_game.team_id == self.own_team_id
@@ -498,37 +533,164 @@ impl World {
             return Err("Cannot receive game involving own team over the network.".into());
         }
 
-        if network_game.timer.has_ended() {
+        // A different engine would replay the game differently.
+        if network_game.engine_version != ENGINE_VERSION {
+            return Err(format!(
+                "Cannot receive game simulated with engine version {} (ours is {}).",
+                network_game.engine_version, ENGINE_VERSION
+            )
+            .into());
+        }
+
+        let timer = network_game.timer.value;
+        let game = self.replay_network_game(network_game, timer)?;
+
+        if game.has_ended() {
             return Err("Cannot receive game that has ended over the network.".into());
         }
 
-        let db_game = self.get_game(network_game.id);
-        if db_game.is_none() {
-            let mut game = Game::new(
-                network_game.id,
-                network_game.home_team_in_game,
-                network_game.away_team_in_game,
-                network_game.starting_at,
-                self.get_planet_or_err(network_game.location)?,
-            );
+        self.games.insert(game.id, game);
+        self.dirty_ui = true;
+        Ok(())
+    }
 
-            while game.timer.value < network_game.timer.value && !game.timer.has_ended() {
-                game.tick();
+    // Merges the commands of a running game (possibly our own) that we missed.
+    // A peer only vouches for the commands of the team it owns, the others are ignored.
+    pub fn resync_network_game(
+        &mut self,
+        network_game: NetworkGame,
+        source: PeerId,
+    ) -> AppResult<()> {
+        let db_game = self.get_game_or_err(network_game.id)?;
+        let source_team_id = if db_game.home_team_in_game.peer_id == Some(source) {
+            db_game.home_team_in_game.team_id
+        } else if db_game.away_team_in_game.peer_id == Some(source) {
+            db_game.away_team_in_game.team_id
+        } else {
+            return Err("Game was not sent by one of its teams".into());
+        };
+
+        let missing = network_game
+            .commands
+            .into_iter()
+            .filter(|command| {
+                command.game_id == db_game.id
+                    && command.team_id == source_team_id
+                    && !db_game.has_command(command)
+            })
+            .collect::<Vec<GameCommand>>();
+
+        if !missing
+            .iter()
+            .any(|command| db_game.misses_command(command))
+        {
+            for command in missing {
+                self.add_game_command(command)?;
             }
+            return Ok(());
+        }
 
-            self.games.insert(game.id, game);
-            self.dirty_ui = true;
+        // Our copy diverged, replay it from the start with all the commands we know of.
+        let timer = db_game.timer.value;
+        let mut replayed_game = NetworkGame::from_game_id(self, db_game.id)?;
+        replayed_game
+            .commands
+            .extend(missing.into_iter().map(|command| GameCommand {
+                applied: false,
+                ..command
+            }));
+        let game = self.replay_network_game(replayed_game, timer)?;
+        self.games.insert(game.id, game);
+        self.dirty_ui = true;
+        Ok(())
+    }
+
+    // Simulates a network game from its start up to the given timer value.
+    fn replay_network_game(&self, network_game: NetworkGame, timer: u16) -> AppResult<Game> {
+        let mut game = Game::new(
+            network_game.id,
+            network_game.home_team_in_game,
+            network_game.away_team_in_game,
+            network_game.starting_at,
+            self.get_planet_or_err(network_game.location)?,
+        );
+        game.commands = network_game.commands;
+        game.commands
+            .sort_by_key(|c| (c.apply_at, c.team_id, c.sequence));
+
+        while game.timer.value < timer && !game.has_ended() {
+            game.tick();
+        }
+        Ok(game)
+    }
+
+    // Builds a coaching command for the game the own team is currently playing.
+    pub fn own_game_command(&self, kind: GameCommandKind) -> AppResult<GameCommand> {
+        let own_team = self.get_own_team()?;
+        let game_id = own_team
+            .current_game
+            .ok_or("Own team is not playing a game".to_string())?;
+        let game = self.get_game_or_err(game_id)?;
+        if game.has_ended() {
+            return Err("Game has already ended".into());
         }
+        Ok(GameCommand::new(
+            game_id,
+            own_team.id,
+            game.next_command_sequence(own_team.id),
+            game.next_command_timer(),
+            kind,
+        ))
+    }
 
+    pub fn add_game_command(&mut self, command: GameCommand) -> AppResult<()> {
+        let game = self
+            .games
+            .get_mut(&command.game_id)
+            .ok_or("Cannot find game for command".to_string())?;
+        game.add_command(command)?;
+        self.dirty_ui = true;
         Ok(())
     }
 
+    pub fn add_network_game_command(
+        &mut self,
+        command: GameCommand,
+        source: PeerId,
+    ) -> AppResult<()> {
+        let game = self.get_game_or_err(command.game_id)?;
+        // Only the peer owning a team can coach it.
+        let team_in_game = if game.home_team_in_game.team_id == command.team_id {
+            &game.home_team_in_game
+        } else if game.away_team_in_game.team_id == command.team_id {
+            &game.away_team_in_game
+        } else {
+            return Err("Command is from a team not in the game".into());
+        };
+        if team_in_game.peer_id != Some(source) {
+            return Err("Command was not sent by the team owner".into());
+        }
+        self.add_game_command(command)
+    }
+
     pub fn add_network_team(&mut self, network_team: NetworkTeam) -> AppResult<()> {
         let NetworkTeam { team, players } = network_team;
         if team.peer_id.is_none() {
             return Err("Cannot receive team without peer_id over the network.".into());
         }
         let db_team = self.get_team(team.id);
+        // Ids are bound to the peer that first sent them, our own teams and
+        // players have no peer_id and can never be overwritten.
+        if db_team.is_some() && db_team.unwrap().peer_id != team.peer_id {
+            return Err(format!("Team {} belongs to another peer", team.id).into());
+        }
+        for player in players.iter() {
+            if let Some(db_player) = self.get_player(player.id) {
+                if db_player.peer_id != team.peer_id {
+                    return Err(format!("Player {} belongs to another peer", player.id).into());
+                }
+            }
+        }
         if db_team.is_none() || db_team.unwrap().version < team.version {
             // Remove team from previous planet
             if db_team.is_some() {
@@ -675,7 +837,7 @@ impl World {
         if current_timestamp >= self.last_tick_short_interval + TickInterval::SHORT {
             if self.games.len() > 0 {
                 self.tick_games(current_timestamp)?;
-                self.cleanup_games()?;
+                messages.append(&mut self.cleanup_games()?);
             }
 
             if !is_simulating && self.games.len() < AUTO_GENERATE_GAMES_NUMBER {
@@ -706,6 +868,7 @@ impl World {
                 self.tick_skill_improvements_reset()?;
             }
             self.tick_player_aging();
+            messages.append(&mut self.tick_player_morale()?);
             self.modify_players_reputation();
             self.modify_teams_reputation()?;
             self.last_tick_long_interval += TickInterval::LONG;
@@ -714,14 +877,30 @@ impl World {
         Ok(messages)
     }
 
-    fn cleanup_games(&mut self) -> AppResult<()> {
+    fn cleanup_games(&mut self) -> AppResult<Vec<String>> {
+        let mut retiring = vec![];
         for (_, game) in self.games.iter() {
-            if game.timer.has_ended() {
-                for team in [&game.home_team_in_game, &game.away_team_in_game] {
+            if game.has_ended() {
+                // Seeded by the game, so that retirements do not depend on when we clean up.
+                let rng = &mut ChaCha8Rng::from_seed(game.get_rng_seed());
+                let score = game.get_score();
+                for (team, won) in [
+                    (&game.home_team_in_game, score.0 > score.1),
+                    (&game.away_team_in_game, score.1 > score.0),
+                ] {
                     //we do not apply end of game logic to peer teams
                     if team.peer_id.is_some() && team.team_id != self.own_team_id {
                         continue;
                     }
+                    let (team_streak, captain_bonus, team_reputation) =
+                        match self.get_team(team.team_id) {
+                            Some(db_team) => (
+                                db_team.streak_after_game(won),
+                                self.team_reputation_bonus(db_team)?,
+                                db_team.reputation,
+                            ),
+                            None => (0, BASE_BONUS, team.reputation),
+                        };
                     for player in team.players.values() {
                         let mut player = player.clone();
                         let stats = team
@@ -729,6 +908,18 @@ impl World {
                             .get(&player.id)
                             .ok_or(format!("Player {:?} not found in team stats", player.id))?;
                         player.apply_end_of_game_logic(stats);
+                        // Only our own team loses players, others decide for their own roster.
+                        if team.team_id == self.own_team_id && player.retires_after_exhaustion(rng)
+                        {
+                            retiring.push(player.id);
+                        }
+                        player.apply_morale_update(
+                            stats,
+                            won,
+                            team_streak,
+                            captain_bonus,
+                            team_reputation,
+                        );
                         self.players.insert(player.id, player);
                     }
                 }
@@ -752,21 +943,10 @@ impl World {
                 let home_team_income = 100 + game.attendance * INCOME_PER_ATTENDEE_HOME;
                 let away_team_income = 100 + game.attendance * INCOME_PER_ATTENDEE_AWAY;
                 // Winner team gets reputation bonus
-                let score = game.get_score();
-                let home_team_reputation = if score.0 > score.1 {
-                    0.5
-                } else if score.0 < score.1 {
-                    -0.25
+                let (home_team_reputation, away_team_reputation) = if score.0 > score.1 {
+                    (0.5, -0.25)
                 } else {
-                    0.2
-                };
-
-                let away_team_reputation = if score.0 < score.1 {
-                    0.5
-                } else if score.0 > score.1 {
-                    -0.25
-                } else {
-                    0.2
+                    (-0.25, 0.5)
                 };
 
                 // Set playing teams current game to None
@@ -775,6 +955,7 @@ impl World {
                     home_team.current_game = None;
                     home_team.balance += home_team_income;
                     home_team.reputation = (home_team.reputation + home_team_reputation).bound();
+                    home_team.streak = home_team.streak_after_game(score.0 > score.1);
                     self.teams.insert(home_team.id, home_team.clone());
                 }
 
@@ -783,6 +964,7 @@ impl World {
                     away_team.current_game = None;
                     away_team.balance += away_team_income;
                     away_team.reputation = (away_team.reputation + away_team_reputation).bound();
+                    away_team.streak = away_team.streak_after_game(score.1 > score.0);
                     self.teams.insert(away_team.id, away_team.clone());
                 }
 
@@ -790,8 +972,28 @@ impl World {
                 self.dirty_ui = true;
             }
         }
-        self.games.retain(|_, game| !game.timer.has_ended());
-        Ok(())
+        self.games.retain(|_, game| !game.has_ended());
+
+        let mut messages = vec![];
+        for player_id in retiring {
+            let player = self.get_player_or_err(player_id)?;
+            let team = match player.team {
+                Some(team_id) => self.get_team_or_err(team_id)?,
+                None => continue,
+            };
+            if team.can_release_player(player).is_err() {
+                continue;
+            }
+            if team.id == self.own_team_id {
+                messages.push(format!(
+                    "{} {} pushed past exhaustion and retired",
+                    player.info.first_name, player.info.last_name
+                ));
+            }
+            self.release_player_from_team(player_id)?;
+            self.players.remove(&player_id);
+        }
+        Ok(messages)
     }
 
     fn tick_games(&mut self, current_timestamp: Tick) -> AppResult<()> {
@@ -799,7 +1001,7 @@ impl World {
         //         the idea is that the game is completely determined at the beginning,
         //         so we can similuate it through.
         for (_, game) in self.games.iter_mut() {
-            if current_timestamp >= game.starting_at && !game.timer.has_ended() {
+            if current_timestamp >= game.starting_at && !game.has_ended() {
                 game.tick();
             }
         }
@@ -853,12 +1055,20 @@ impl World {
                 let db_player = self
                     .get_player(*player_id)
                     .ok_or(format!("Player {:?} not found", player_id))?;
-                if db_player.tiredness > 0.0 && db_player.tiredness <= MAX_TIREDNESS {
+                if (db_player.tiredness > 0.0 && db_player.tiredness <= MAX_TIREDNESS)
+                    || db_player.is_injured()
+                {
                     let mut player = db_player.clone();
                     // Recovery outside of games is slower by a factor TICK_SHORT_INTERVAL/TICK_MEDIUM_INTERVAL
-                    // so that it takes 1 minute * 10 * 100 ~ 18 hours to recover from 100% tiredness.
-                    player.tiredness =
-                        (player.tiredness - bonus * RECOVERING_TIREDNESS_PER_SHORT_TICK).max(0.0);
+                    // and depends on how tired the player is, so that it takes ~39 hours
+                    // to recover from 100% tiredness without a doctor bonus.
+                    player.tiredness = tiredness_recovery(player.tiredness, bonus);
+                    // The doctor also speeds up the healing of injuries.
+                    if let Some(injury) = player.injury.as_mut() {
+                        if injury.recover(TickInterval::MEDIUM, bonus) {
+                            player.injury = None;
+                        }
+                    }
                     self.players.insert(player.id, player);
                 }
             }
@@ -894,6 +1104,40 @@ impl World {
         }
     }
 
+    // Players of the own team with very low morale might decide to leave.
+    fn tick_player_morale(&mut self) -> AppResult<Vec<String>> {
+        if !self.has_own_team() {
+            return Ok(vec![]);
+        }
+        // Seeded by the tick, so that replaying the same world gives the same departures.
+        let rng = &mut ChaCha8Rng::seed_from_u64(
+            self.seed.wrapping_add(self.last_tick_long_interval as u64),
+        );
+        let own_team = self.get_own_team()?;
+        let leaving = own_team
+            .player_ids
+            .iter()
+            .filter_map(|id| self.get_player(*id))
+            .filter(|player| player.wants_to_leave(rng))
+            .map(|player| player.id)
+            .collect::<Vec<PlayerId>>();
+
+        let mut messages = vec![];
+        for player_id in leaving {
+            let player = self.get_player_or_err(player_id)?;
+            if self.get_own_team()?.can_release_player(player).is_err() {
+                continue;
+            }
+            let message = format!(
+                "{} {} has left the team due to low morale",
+                player.info.first_name, player.info.last_name
+            );
+            self.release_player_from_team(player_id)?;
+            messages.push(message);
+        }
+        Ok(messages)
+    }
+
     pub fn spaceship_speed_bonus(&self, team: &Team) -> AppResult<f32> {
         let role_fitness = if let Some(pilot_id) = team.crew_roles.pilot {
             let pilot = self.get_player_or_err(pilot_id)?;
@@ -1167,7 +1411,11 @@ impl World {
 #[cfg(test)]
 mod test {
     use super::World;
+    use crate::engine::command::{GameCommand, GameCommandKind};
+    use crate::engine::game::Game;
+    use crate::network::types::NetworkGame;
     use crate::world::constants::BASE_DISTANCES;
+    use libp2p::PeerId;
     use rand::{Rng, SeedableRng};
     use rand_chacha::ChaCha8Rng;
 
@@ -1187,6 +1435,61 @@ mod test {
         assert_eq!(v1, v2);
     }
 
+    #[test]
+    fn test_network_game_resyncs_on_missed_command() {
+        let mut world = World::new(Some(0));
+        let mut game = Game::test_game(&mut world);
+        let home_peer = PeerId::random();
+        let away_peer = PeerId::random();
+        game.home_team_in_game.peer_id = Some(home_peer);
+        game.away_team_in_game.peer_id = Some(away_peer);
+        let game_id = game.id;
+        let home_id = game.home_team_in_game.team_id;
+        world.games.insert(game_id, game);
+        let mut network_game = NetworkGame::from_game_id(&world, game_id).unwrap();
+
+        let mut other_world = World::new(Some(1));
+        other_world
+            .add_network_game(network_game.clone(), home_peer)
+            .unwrap();
+
+        // One peer gets the command in time, the other never hears about it.
+        let command = GameCommand::new(game_id, home_id, 0, 50, GameCommandKind::Timeout);
+        let mut synced_world = World::new(Some(2));
+        synced_world
+            .add_network_game(network_game.clone(), home_peer)
+            .unwrap();
+        synced_world.add_game_command(command.clone()).unwrap();
+        for w in [&mut synced_world, &mut other_world] {
+            let game = w.games.get_mut(&game_id).unwrap();
+            while game.timer.value < 100 {
+                game.tick();
+            }
+        }
+        assert!(other_world.add_game_command(command.clone()).is_err());
+
+        // The next game update carries the command, but only its owner can vouch for it.
+        network_game.commands.push(command);
+        other_world
+            .add_network_game(network_game.clone(), away_peer)
+            .unwrap();
+        assert!(other_world.get_game(game_id).unwrap().commands.is_empty());
+        assert!(other_world
+            .add_network_game(network_game.clone(), PeerId::random())
+            .is_err());
+
+        // The copy is then replayed with the missed command.
+        other_world
+            .add_network_game(network_game, home_peer)
+            .unwrap();
+        let game = synced_world.get_game(game_id).unwrap();
+        let other_game = other_world.get_game(game_id).unwrap();
+        assert_eq!(other_game.timer.value, game.timer.value);
+        assert_eq!(other_game.commands, game.commands);
+        assert_eq!(other_game.action_results.len(), game.action_results.len());
+        assert_eq!(other_game.get_score(), game.get_score());
+    }
+
     #[test]
     fn test_distance_between_planets() {
         let world = World::new(None);